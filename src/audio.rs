@@ -0,0 +1,235 @@
+//! The input's audio track, decoded into a loudness envelope: the RMS
+//! level of every interval between stored frames. `--audio-drive` uses it
+//! for music-video style outputs, either keeping only the loud moments on
+//! the time axis (`select`) or sweeping through the columns faster on loud
+//! passages and lingering on quiet ones (`order`).
+
+use crate::os_path;
+use ffmpeg_next as ffmpeg;
+use ffmpeg_next::format::Sample;
+use ffmpeg_next::media::Type;
+use ffmpeg_next::util::frame::audio::Audio;
+use std::path::Path;
+
+/// What `--audio-drive` does with the envelope.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AudioDrive {
+    /// Keep the loudest fraction of the source frames, in time order.
+    Select(f64),
+    /// Advance through the columns by cumulative loudness.
+    Order,
+}
+
+impl AudioDrive {
+    /// Fraction of the frames `select` keeps unless given.
+    const DEFAULT_SELECT: f64 = 0.5;
+
+    /// Parses `select`, `select:<fraction>` or `order`.
+    pub fn parse(text: &str) -> Result<AudioDrive, String> {
+        match text.split_once(':') {
+            None if text == "select" => Ok(AudioDrive::Select(AudioDrive::DEFAULT_SELECT)),
+            None if text == "order" => Ok(AudioDrive::Order),
+            Some(("select", fraction)) => match fraction.parse::<f64>() {
+                Ok(fraction) if fraction > 0.0 && fraction <= 1.0 => {
+                    Ok(AudioDrive::Select(fraction))
+                }
+                _ => Err(format!(
+                    "Invalid --audio-drive select fraction: {} (expected more than 0, up to 1)",
+                    fraction
+                )),
+            },
+            _ => Err(format!(
+                "Invalid --audio-drive: {} (expected select, select:<fraction> or order)",
+                text
+            )),
+        }
+    }
+}
+
+/// RMS level of the audio, full scale 1, in intervals from its start.
+pub struct Envelope {
+    interval: f64,
+    levels: Vec<f64>,
+    /// Decoder of the track, for messages.
+    pub codec: String,
+}
+
+impl Envelope {
+    /// Decodes the best audio track of `path` into levels over `interval`
+    /// seconds each.
+    pub fn analyze(path: &Path, interval: f64) -> Result<Envelope, Box<dyn std::error::Error>> {
+        let mut ictx = os_path::open_input(path, None)?;
+        let stream = ictx
+            .streams()
+            .best(Type::Audio)
+            .ok_or_else(|| format!("{} has no audio track", path.display()))?;
+        let stream_index = stream.index();
+        let mut decoder = ffmpeg::codec::context::Context::from_parameters(stream.parameters())?
+            .decoder()
+            .audio()?;
+        let codec = decoder
+            .codec()
+            .map_or_else(|| "unknown".to_string(), |codec| codec.name().to_string());
+
+        // Sums of squared samples and their counts, per interval
+        let mut bins: Vec<(f64, usize)> = Vec::new();
+        let mut position = 0usize;
+        let mut receive = |decoder: &mut ffmpeg::decoder::Audio| {
+            let mut frame = Audio::empty();
+            while decoder.receive_frame(&mut frame).is_ok() {
+                let rate = f64::from(frame.rate().max(1));
+                let bin = (position as f64 / rate / interval) as usize;
+                if bins.len() <= bin {
+                    bins.resize(bin + 1, (0.0, 0));
+                }
+                let (sum, count) = sum_of_squares(&frame);
+                bins[bin].0 += sum;
+                bins[bin].1 += count;
+                position += frame.samples();
+            }
+        };
+        for (stream, packet) in ictx.packets() {
+            if stream.index() == stream_index {
+                // A corrupt packet costs a few milliseconds of envelope
+                if decoder.send_packet(&packet).is_ok() {
+                    receive(&mut decoder);
+                }
+            }
+        }
+        decoder.send_eof()?;
+        receive(&mut decoder);
+
+        let levels = bins
+            .iter()
+            .map(|&(sum, count)| (sum / count.max(1) as f64).sqrt())
+            .collect();
+        Ok(Envelope {
+            interval,
+            levels,
+            codec,
+        })
+    }
+
+    /// Seconds of audio analyzed.
+    pub fn duration(&self) -> f64 {
+        self.levels.len() as f64 * self.interval
+    }
+
+    /// Level of the interval at `time` seconds, silence past the end.
+    pub fn level_at(&self, time: f64) -> f64 {
+        let bin = (time.max(0.0) / self.interval) as usize;
+        self.levels.get(bin).copied().unwrap_or(0.0)
+    }
+
+    /// Level at each stored frame, by its timestamp or, without one, its
+    /// place at `interval` spacing.
+    pub fn frame_levels(&self, times: &[Option<f64>], interval: f64) -> Vec<f64> {
+        times
+            .iter()
+            .enumerate()
+            .map(|(index, time)| self.level_at(time.unwrap_or(index as f64 * interval)))
+            .collect()
+    }
+
+    /// Mean level in dBFS, for messages.
+    pub fn mean_dbfs(&self) -> f64 {
+        let mean = self.levels.iter().sum::<f64>() / self.levels.len().max(1) as f64;
+        20.0 * mean.max(1e-6).log10()
+    }
+}
+
+/// Which of the frames at `levels` `select` keeps: the loudest `fraction`
+/// of them, ties included.
+pub fn select(levels: &[f64], fraction: f64) -> Vec<bool> {
+    let mut sorted = levels.to_vec();
+    sorted.sort_by(|a, b| b.total_cmp(a));
+    let count = ((levels.len() as f64 * fraction).ceil() as usize).clamp(1, levels.len().max(1));
+    let threshold = sorted.get(count - 1).copied().unwrap_or(0.0);
+    levels.iter().map(|&level| level >= threshold).collect()
+}
+
+/// `columns` resequenced by loudness: output frame `k` of `columns.len()`
+/// takes the level of its share of `levels` and advances through `columns`
+/// in proportion, so the sweep rushes on loud passages and holds on quiet
+/// ones. `None` when the audio is silent.
+pub fn order(columns: &[usize], levels: &[f64]) -> Option<Vec<usize>> {
+    let slots = columns.len();
+    if levels.is_empty() {
+        return None;
+    }
+    let slot_levels: Vec<f64> = (0..slots)
+        .map(|slot| {
+            let start = slot * levels.len() / slots;
+            let end = ((slot + 1) * levels.len() / slots).max(start + 1);
+            let share = &levels[start.min(levels.len() - 1)..end.min(levels.len())];
+            share.iter().sum::<f64>() / share.len().max(1) as f64
+        })
+        .collect();
+    let total: f64 = slot_levels.iter().sum();
+    if total <= 0.0 {
+        return None;
+    }
+    let mut reached = 0.0;
+    let mut ordered = Vec::with_capacity(slots);
+    for level in slot_levels {
+        let position = (reached / total * (slots - 1) as f64).round() as usize;
+        ordered.push(columns[position.min(slots - 1)]);
+        reached += level;
+    }
+    Some(ordered)
+}
+
+/// Sum of the squares of every sample of `frame`, full scale 1, and how
+/// many samples there are.
+fn sum_of_squares(frame: &Audio) -> (f64, usize) {
+    if frame.is_planar() {
+        return match frame.format() {
+            Sample::U8(_) => planar(frame, |v: u8| (f64::from(v) - 128.0) / 128.0),
+            Sample::I16(_) => planar(frame, |v: i16| f64::from(v) / 32768.0),
+            Sample::I32(_) => planar(frame, |v: i32| f64::from(v) / 2147483648.0),
+            Sample::F32(_) => planar(frame, |v: f32| f64::from(v)),
+            Sample::F64(_) => planar(frame, |v: f64| v),
+            // No decoder produces planar 64-bit integers
+            Sample::I64(_) | Sample::None => (0.0, 0),
+        };
+    }
+    let (size, value): (usize, fn(&[u8]) -> f64) = match frame.format() {
+        Sample::U8(_) => (1, |b| (f64::from(b[0]) - 128.0) / 128.0),
+        Sample::I16(_) => (2, |b| f64::from(i16::from_ne_bytes([b[0], b[1]])) / 32768.0),
+        Sample::I32(_) => (4, |b| {
+            f64::from(i32::from_ne_bytes([b[0], b[1], b[2], b[3]])) / 2147483648.0
+        }),
+        Sample::I64(_) => (8, |b| {
+            b.try_into().map_or(0.0, |b| i64::from_ne_bytes(b) as f64) / 9223372036854775808.0
+        }),
+        Sample::F32(_) => (4, |b| {
+            f64::from(f32::from_ne_bytes([b[0], b[1], b[2], b[3]]))
+        }),
+        Sample::F64(_) => (8, |b| b.try_into().map_or(0.0, f64::from_ne_bytes)),
+        Sample::None => return (0.0, 0),
+    };
+    let data = frame.data(0);
+    let count = (frame.samples() * usize::from(frame.channels())).min(data.len() / size);
+    let sum = data
+        .chunks_exact(size)
+        .take(count)
+        .map(|bytes| value(bytes).powi(2))
+        .sum();
+    (sum, count)
+}
+
+/// [`sum_of_squares`] of a frame with a plane per channel.
+fn planar<T: ffmpeg::util::frame::audio::Sample + Copy>(
+    frame: &Audio,
+    value: impl Fn(T) -> f64,
+) -> (f64, usize) {
+    let mut sum = 0.0;
+    let mut count = 0;
+    for plane in 0..frame.planes() {
+        for &sample in frame.plane::<T>(plane) {
+            sum += value(sample).powi(2);
+            count += 1;
+        }
+    }
+    (sum, count)
+}
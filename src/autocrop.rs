@@ -0,0 +1,100 @@
+//! `--autocrop`: finds letterbox and pillarbox bars in the source and crops
+//! them before transposing, since black source columns become long runs of
+//! black output frames and black rows a band across every one. Like
+//! FFmpeg's cropdetect, a line is part of a bar while its mean luma stays
+//! under a limit; frames sampled across the input are checked and the crop
+//! keeps whatever any of them shows, so a dark scene doesn't eat the
+//! picture.
+
+use crate::frame_store::FrameFormat;
+
+/// Frames checked, spread evenly over the input.
+const SAMPLES: usize = 32;
+/// Mean luma (of 255) under which a row or column counts as black, as
+/// cropdetect's default.
+const LIMIT: u64 = 24;
+
+/// The part of the frame that is kept.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Crop {
+    pub left: usize,
+    pub top: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Crop {
+    /// The packed `frame` of `format`, `width` pixels wide, cut down to
+    /// the crop.
+    pub fn apply(&self, frame: &[u8], format: FrameFormat, width: usize) -> Vec<u8> {
+        let bpp = format.bytes_per_pixel();
+        let row_bytes = self.width * bpp;
+        let mut cropped = Vec::with_capacity(row_bytes * self.height);
+        for y in self.top..self.top + self.height {
+            let start = (y * width + self.left) * bpp;
+            cropped.extend_from_slice(&frame[start..start + row_bytes]);
+        }
+        cropped
+    }
+}
+
+/// Finds the bars around frames of `width × height`. Returns `None` when
+/// there are none, or when every sampled frame is black.
+pub fn detect(
+    frames: &[Vec<u8>],
+    format: FrameFormat,
+    width: usize,
+    height: usize,
+) -> Option<Crop> {
+    let count = frames.len().min(SAMPLES);
+    // Extent of the picture over all samples: left, top, right and bottom
+    let mut extent: Option<(usize, usize, usize, usize)> = None;
+    for sample in 0..count {
+        let frame = &frames[sample * frames.len() / count];
+        let luma = format.luma(frame);
+        let bright_row = |y: usize| {
+            let sum: u64 = luma[y * width..(y + 1) * width]
+                .iter()
+                .map(|&l| u64::from(l))
+                .sum();
+            sum > LIMIT * width as u64
+        };
+        let bright_column = |x: usize| {
+            let sum: u64 = (0..height).map(|y| u64::from(luma[y * width + x])).sum();
+            sum > LIMIT * height as u64
+        };
+        let (Some(top), Some(left)) = (
+            (0..height).find(|&y| bright_row(y)),
+            (0..width).find(|&x| bright_column(x)),
+        ) else {
+            continue;
+        };
+        let bottom = (0..height).rev().find(|&y| bright_row(y)).unwrap_or(top);
+        let right = (0..width).rev().find(|&x| bright_column(x)).unwrap_or(left);
+        extent = Some(match extent {
+            Some((l, t, r, b)) => (l.min(left), t.min(top), r.max(right), b.max(bottom)),
+            None => (left, top, right, bottom),
+        });
+    }
+    let (left, top, right, bottom) = extent?;
+    // Even sizes for 4:2:0 encoding, growing into the bars rather than
+    // cutting into the picture
+    let even = |start: usize, end: usize, size: usize| {
+        let length = end + 1 - start;
+        match length % 2 {
+            0 => (start, length),
+            _ if end + 1 < size => (start, length + 1),
+            _ if start > 0 => (start - 1, length + 1),
+            _ => (start, length),
+        }
+    };
+    let (left, crop_width) = even(left, right, width);
+    let (top, crop_height) = even(top, bottom, height);
+    let crop = Crop {
+        left,
+        top,
+        width: crop_width,
+        height: crop_height,
+    };
+    (crop.width != width || crop.height != height).then_some(crop)
+}
@@ -0,0 +1,289 @@
+//! `video-transpose-gui`: a window around the library for people who'd
+//! rather not use a terminal. Pick an input, an output and the encoder
+//! settings, then watch the progress and a preview of the output frames.
+//! The run is the same as the command line one; its progress comes back
+//! over a `--progress-socket` the window listens to.
+
+use eframe::egui;
+use ffmpeg_next as ffmpeg;
+use std::env;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use video_transpose::cli::Options;
+use video_transpose::run;
+use video_transpose::stats::RunStats;
+
+/// `--target` choices offered, with their labels.
+const TARGETS: [(Option<&str>, &str); 5] = [
+    (None, "H.264 (default)"),
+    (Some("web"), "Web (H.264, fast start)"),
+    (Some("editing"), "Editing (intra-only H.264)"),
+    (Some("analysis"), "Analysis"),
+    (Some("archive"), "Archive (lossless FFV1)"),
+];
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ffmpeg::init()?;
+    eframe::run_native(
+        "video-transpose",
+        eframe::NativeOptions::default(),
+        Box::new(|_| Ok(Box::new(TransposeApp::default()))),
+    )?;
+    Ok(())
+}
+
+/// What the running job has reported so far.
+#[derive(Default)]
+struct JobState {
+    phase: String,
+    frame: u64,
+    total: Option<u64>,
+    /// Preview not yet turned into a texture.
+    preview: Option<egui::ColorImage>,
+    /// Output path on success, the error otherwise.
+    result: Option<Result<String, String>>,
+}
+
+#[derive(Default)]
+struct TransposeApp {
+    input: String,
+    output: String,
+    target: Option<&'static str>,
+    job: Option<Arc<Mutex<JobState>>>,
+    preview: Option<egui::TextureHandle>,
+    /// Why the last job couldn't start.
+    error: Option<String>,
+}
+
+impl TransposeApp {
+    fn running(&self) -> bool {
+        self.job
+            .as_ref()
+            .is_some_and(|job| job.lock().unwrap().result.is_none())
+    }
+
+    fn start(&mut self, ctx: &egui::Context) {
+        let socket =
+            env::temp_dir().join(format!("video-transpose-gui-{}.sock", std::process::id()));
+        let mut args: Vec<OsString> = vec![
+            "video-transpose".into(),
+            self.input.clone().into(),
+            self.output.clone().into(),
+            "--overwrite".into(),
+            "--no-progress".into(),
+            "--progress-socket".into(),
+            socket.clone().into(),
+        ];
+        if let Some(target) = self.target {
+            args.extend(["--target".into(), target.into()]);
+        }
+        let options = match Options::parse_layered(&args) {
+            Ok(options) => options,
+            Err(error) => {
+                self.error = Some(error);
+                return;
+            }
+        };
+
+        let job = Arc::new(Mutex::new(JobState::default()));
+        self.job = Some(Arc::clone(&job));
+        self.preview = None;
+        self.error = None;
+
+        let (state, repaint) = (Arc::clone(&job), ctx.clone());
+        thread::spawn(move || {
+            let mut stats = RunStats::default();
+            let result = run(&options, &mut stats)
+                .map(|_| options.output.display().to_string())
+                .map_err(|error| error.to_string());
+            state.lock().unwrap().result = Some(result);
+            repaint.request_repaint();
+        });
+        let repaint = ctx.clone();
+        thread::spawn(move || watch(&socket, &job, &repaint));
+    }
+}
+
+impl eframe::App for TransposeApp {
+    fn ui(&mut self, ui: &mut egui::Ui, _frame: &mut eframe::Frame) {
+        egui::CentralPanel::default().show(ui, |ui| {
+            let running = self.running();
+            ui.add_enabled_ui(!running, |ui| self.settings(ui));
+            ui.add_space(8.0);
+
+            if ui
+                .add_enabled(
+                    !running && !self.input.is_empty() && !self.output.is_empty(),
+                    egui::Button::new("Transpose"),
+                )
+                .clicked()
+            {
+                self.start(ui.ctx());
+            }
+            if let Some(error) = &self.error {
+                ui.colored_label(ui.visuals().error_fg_color, error);
+            }
+            if let Some(job) = self.job.clone() {
+                self.status(ui, &mut job.lock().unwrap());
+            }
+        });
+    }
+}
+
+impl TransposeApp {
+    fn settings(&mut self, ui: &mut egui::Ui) {
+        egui::Grid::new("settings").num_columns(3).show(ui, |ui| {
+            ui.label("Input video");
+            ui.text_edit_singleline(&mut self.input);
+            if ui.button("Browse…").clicked() {
+                if let Some(path) = rfd::FileDialog::new().pick_file() {
+                    self.input = path.display().to_string();
+                    if self.output.is_empty() {
+                        self.output = default_output(&path).display().to_string();
+                    }
+                }
+            }
+            ui.end_row();
+
+            ui.label("Output video");
+            ui.text_edit_singleline(&mut self.output);
+            if ui.button("Browse…").clicked() {
+                let dialog = rfd::FileDialog::new()
+                    .add_filter("Video", &["mp4", "mov", "mkv", "nut", "mxf"]);
+                if let Some(path) = dialog.save_file() {
+                    self.output = path.display().to_string();
+                }
+            }
+            ui.end_row();
+
+            ui.label("Encoding");
+            let selected = TARGETS
+                .iter()
+                .find(|(target, _)| *target == self.target)
+                .map_or("", |(_, label)| label);
+            egui::ComboBox::from_id_salt("target")
+                .selected_text(selected)
+                .show_ui(ui, |ui| {
+                    for (target, label) in TARGETS {
+                        ui.selectable_value(&mut self.target, target, label);
+                    }
+                });
+            ui.end_row();
+        });
+        // Dropping a file on the window picks it as the input
+        let dropped = ui.ctx().input(|input| {
+            input
+                .raw
+                .dropped_files
+                .first()
+                .map(|file| file.path().to_path_buf())
+                .filter(|path| !path.as_os_str().is_empty())
+        });
+        if let Some(path) = dropped {
+            self.input = path.display().to_string();
+            self.output = default_output(&path).display().to_string();
+        }
+    }
+
+    fn status(&mut self, ui: &mut egui::Ui, job: &mut JobState) {
+        if let Some(image) = job.preview.take() {
+            self.preview = Some(ui.ctx().load_texture(
+                "preview",
+                image,
+                egui::TextureOptions::LINEAR,
+            ));
+        }
+        match &job.result {
+            None => {
+                let phase = match job.phase.as_str() {
+                    "" => "Starting",
+                    "decode" => "Decoding",
+                    _ => "Transposing and encoding",
+                };
+                let text = format!("{}: frame {}", phase, job.frame);
+                let bar = match job.total {
+                    Some(total) if total > 0 => {
+                        egui::ProgressBar::new(job.frame as f32 / total as f32).text(text)
+                    }
+                    _ => egui::ProgressBar::new(0.0).animate(true).text(text),
+                };
+                ui.add(bar);
+            }
+            Some(Ok(output)) => {
+                ui.label(format!("Done: {}", output));
+            }
+            Some(Err(error)) => {
+                ui.colored_label(ui.visuals().error_fg_color, error);
+            }
+        }
+        if let Some(texture) = &self.preview {
+            ui.add(egui::Image::new(texture).shrink_to_fit());
+        }
+    }
+}
+
+/// `<input>_transposed.mp4` next to the input.
+fn default_output(input: &Path) -> PathBuf {
+    let stem = input.file_stem().unwrap_or_default().to_string_lossy();
+    input.with_file_name(format!("{}_transposed.mp4", stem))
+}
+
+/// Follows the job's progress socket until the run ends.
+#[cfg(unix)]
+fn watch(socket: &Path, job: &Mutex<JobState>, repaint: &egui::Context) {
+    use std::io::{BufRead, BufReader, Read};
+    use std::os::unix::net::UnixStream;
+    use std::time::Duration;
+
+    // The run binds the socket once it starts
+    let stream = loop {
+        if let Ok(stream) = UnixStream::connect(socket) {
+            break stream;
+        }
+        if job.lock().unwrap().result.is_some() {
+            return;
+        }
+        thread::sleep(Duration::from_millis(50));
+    };
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    while matches!(reader.read_line(&mut line), Ok(n) if n > 0) {
+        let event: serde_json::Value = serde_json::from_str(&line).unwrap_or_default();
+        line.clear();
+        let mut state = job.lock().unwrap();
+        match event["event"].as_str() {
+            Some("phase") => {
+                state.phase = event["phase"].as_str().unwrap_or_default().to_string();
+                state.frame = 0;
+                state.total = event["total"].as_u64();
+            }
+            Some("progress") => {
+                state.frame = event["frame"].as_u64().unwrap_or_default();
+            }
+            Some("preview") => {
+                let mut jpeg = vec![0; event["bytes"].as_u64().unwrap_or_default() as usize];
+                drop(state);
+                if reader.read_exact(&mut jpeg).is_err() {
+                    return;
+                }
+                if let Ok(image) =
+                    image::load_from_memory_with_format(&jpeg, image::ImageFormat::Jpeg)
+                {
+                    let rgba = image.to_rgba8();
+                    let size = [rgba.width() as usize, rgba.height() as usize];
+                    job.lock().unwrap().preview =
+                        Some(egui::ColorImage::from_rgba_unmultiplied(size, &rgba));
+                }
+            }
+            Some("done") => return,
+            _ => {}
+        }
+        repaint.request_repaint();
+    }
+}
+
+/// Without Unix sockets the window only shows how the run ended.
+#[cfg(not(unix))]
+fn watch(_socket: &Path, _job: &Mutex<JobState>, _repaint: &egui::Context) {}
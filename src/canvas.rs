@@ -0,0 +1,140 @@
+//! `--canvas WxH`: places the transposed frames on a canvas of a standard
+//! size, since their natural size (frames × height) rarely is one players
+//! and platforms like. `--fit contain` scales them to fit inside it with
+//! black bars (letterbox or pillarbox), `--fit cover` scales them to fill
+//! it and crops what overhangs, evenly on both sides.
+
+use crate::frame_store::FrameFormat;
+use crate::scaler::Scaler;
+use ffmpeg_next as ffmpeg;
+use ffmpeg_next::software::scaling::context::Context;
+use ffmpeg_next::util::frame::video::Video;
+
+/// How frames are fitted to the canvas.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Fit {
+    /// The whole frame, with bars on two sides.
+    Contain,
+    /// The whole canvas, cropping the frame.
+    Cover,
+}
+
+impl Fit {
+    pub fn parse(text: &str) -> Result<Fit, String> {
+        match text {
+            "contain" => Ok(Fit::Contain),
+            "cover" => Ok(Fit::Cover),
+            _ => Err(format!(
+                "Invalid --fit: {} (expected contain or cover)",
+                text
+            )),
+        }
+    }
+}
+
+/// Size and fit of the canvas.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Canvas {
+    pub width: usize,
+    pub height: usize,
+    pub fit: Fit,
+}
+
+impl Canvas {
+    /// Parses `WxH`; both must be even for 4:2:0 encoding.
+    pub fn parse(text: &str, fit: Fit) -> Result<Canvas, String> {
+        let invalid = || format!("Invalid --canvas: {} (expected WxH, e.g. 1920x1080)", text);
+        let (width, height) = text.split_once('x').ok_or_else(invalid)?;
+        let width: usize = width.parse().map_err(|_| invalid())?;
+        let height: usize = height.parse().map_err(|_| invalid())?;
+        if width < 2 || height < 2 || !width.is_multiple_of(2) || !height.is_multiple_of(2) {
+            return Err(format!(
+                "Invalid --canvas: {} (width and height must be even, at least 2)",
+                text
+            ));
+        }
+        Ok(Canvas { width, height, fit })
+    }
+
+    /// Prepares fitting frames of `width × height` to the canvas.
+    pub fn fitter(
+        &self,
+        width: usize,
+        height: usize,
+        format: FrameFormat,
+        scaler: Scaler,
+    ) -> Result<Fitter, ffmpeg::Error> {
+        let factor = match self.fit {
+            Fit::Contain => {
+                (self.width as f64 / width as f64).min(self.height as f64 / height as f64)
+            }
+            Fit::Cover => {
+                (self.width as f64 / width as f64).max(self.height as f64 / height as f64)
+            }
+        };
+        let size = |size: usize, canvas: usize| {
+            let scaled = ((size as f64 * factor).round() as usize).max(1);
+            match self.fit {
+                Fit::Contain => scaled.min(canvas),
+                Fit::Cover => scaled.max(canvas),
+            }
+        };
+        let scaled = (size(width, self.width), size(height, self.height));
+        let context = Context::get(
+            format.pixel(),
+            width as u32,
+            height as u32,
+            format.pixel(),
+            scaled.0 as u32,
+            scaled.1 as u32,
+            scaler.flags(),
+        )?;
+        Ok(Fitter {
+            context,
+            format,
+            frame: (width, height),
+            scaled,
+            canvas: (self.width, self.height),
+        })
+    }
+}
+
+/// Scales frames of one size and places them on the canvas.
+pub struct Fitter {
+    context: Context,
+    format: FrameFormat,
+    frame: (usize, usize),
+    scaled: (usize, usize),
+    canvas: (usize, usize),
+}
+
+impl Fitter {
+    /// The packed frame `data` on the canvas, centered.
+    pub fn apply(&mut self, data: &[u8]) -> Result<Vec<u8>, ffmpeg::Error> {
+        let bpp = self.format.bytes_per_pixel();
+        let input =
+            crate::encode::packed_frame(data, self.format.pixel(), bpp, self.frame.0, self.frame.1);
+        let mut scaled = Video::empty();
+        self.context.run(&input, &mut scaled)?;
+        let stride = scaled.stride(0);
+        let plane = scaled.data(0);
+
+        let (canvas_width, canvas_height) = self.canvas;
+        let (scaled_width, scaled_height) = self.scaled;
+        let mut canvas = vec![0u8; canvas_width * canvas_height * bpp];
+        // Bars when the scaled frame is smaller, a crop when it is larger
+        let offset = |canvas: usize, scaled: usize| match canvas > scaled {
+            true => ((canvas - scaled) / 2, 0),
+            false => (0, (scaled - canvas) / 2),
+        };
+        let (left, crop_left) = offset(canvas_width, scaled_width);
+        let (top, crop_top) = offset(canvas_height, scaled_height);
+        let row_bytes = scaled_width.min(canvas_width) * bpp;
+        for y in 0..scaled_height.min(canvas_height) {
+            let source = (crop_top + y) * stride + crop_left * bpp;
+            let target = ((top + y) * canvas_width + left) * bpp;
+            canvas[target..target + row_bytes].copy_from_slice(&plane[source..source + row_bytes]);
+        }
+        Ok(canvas)
+    }
+}
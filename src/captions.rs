@@ -0,0 +1,27 @@
+//! A53 closed captions. Broadcast sources carry them as side data on the
+//! decoded frames, timed to those frames; after the transpose no frame
+//! shows what they caption, so outputs go without them. `--keep-cc` carries
+//! them over instead: output frame n gets the captions of source frame n,
+//! which keeps them on the same playback times.
+
+use ffmpeg_next::util::frame::side_data::Type as SideDataType;
+use ffmpeg_next::util::frame::video::Video;
+
+/// The A53 caption payload of a decoded frame, if it has one.
+pub fn extract(frame: &Video) -> Option<Vec<u8>> {
+    frame
+        .side_data(SideDataType::A53CC)
+        .map(|side_data| side_data.data().to_vec())
+}
+
+/// Attaches an A53 caption payload to a frame about to be encoded. H.264
+/// and MPEG-2 encoders write it into the bitstream.
+pub fn attach(frame: &mut Video, payload: &[u8]) {
+    if let Some(mut side_data) = frame.new_side_data(SideDataType::A53CC, payload.len()) {
+        unsafe {
+            let data =
+                std::slice::from_raw_parts_mut((*side_data.as_mut_ptr()).data, payload.len());
+            data.copy_from_slice(payload);
+        }
+    }
+}
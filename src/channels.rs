@@ -0,0 +1,73 @@
+/// Which channels `--split-channels` writes as separate grayscale outputs.
+#[derive(Clone, Copy, PartialEq)]
+pub enum ChannelSet {
+    Rgb,
+    Yuv,
+}
+
+impl ChannelSet {
+    pub fn parse(text: &str) -> Result<ChannelSet, String> {
+        match text {
+            "rgb" => Ok(ChannelSet::Rgb),
+            "yuv" => Ok(ChannelSet::Yuv),
+            _ => Err(format!(
+                "Invalid --split-channels value: {} (expected rgb or yuv)",
+                text
+            )),
+        }
+    }
+
+    pub fn channels(self) -> [Channel; 3] {
+        match self {
+            ChannelSet::Rgb => [Channel::R, Channel::G, Channel::B],
+            ChannelSet::Yuv => [Channel::Y, Channel::U, Channel::V],
+        }
+    }
+}
+
+/// A single channel of the transposed frames, encoded as grayscale.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Channel {
+    R,
+    G,
+    B,
+    Y,
+    U,
+    V,
+}
+
+impl Channel {
+    /// File name suffix for the channel's output, e.g. `_R`.
+    pub fn suffix(self) -> &'static str {
+        match self {
+            Channel::R => "_R",
+            Channel::G => "_G",
+            Channel::B => "_B",
+            Channel::Y => "_Y",
+            Channel::U => "_U",
+            Channel::V => "_V",
+        }
+    }
+
+    /// Extracts the channel from a packed RGB24 buffer as one byte per pixel.
+    /// Y, U and V use full-range BT.601, so U and V are centered on 128.
+    pub fn extract(self, rgb: &[u8]) -> Vec<u8> {
+        let convert = |pixel: &[u8]| -> u8 {
+            let (r, g, b) = (
+                i32::from(pixel[0]),
+                i32::from(pixel[1]),
+                i32::from(pixel[2]),
+            );
+            let value = match self {
+                Channel::R => r,
+                Channel::G => g,
+                Channel::B => b,
+                Channel::Y => (77 * r + 150 * g + 29 * b) >> 8,
+                Channel::U => ((-43 * r - 85 * g + 128 * b) >> 8) + 128,
+                Channel::V => ((128 * r - 107 * g - 21 * b) >> 8) + 128,
+            };
+            value.clamp(0, 255) as u8
+        };
+        rgb.chunks_exact(3).map(convert).collect()
+    }
+}
@@ -0,0 +1,93 @@
+//! `--checksum-verify`: checks the input against a known SHA-256 digest
+//! before a long run, so a truncated or corrupted transfer fails in seconds
+//! instead of after hours of transposing.
+
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+/// Where the expected digest comes from.
+pub enum Expected {
+    /// A digest given on the command line, `sha256:<hex>` or bare hex.
+    Digest(String),
+    /// The `<input>.sha256` file next to the input, as written by sha256sum.
+    Sidecar,
+}
+
+impl Expected {
+    pub fn parse(text: &str) -> Result<Expected, String> {
+        if text == "sidecar" {
+            return Ok(Expected::Sidecar);
+        }
+        parse_digest(text).map(Expected::Digest)
+    }
+
+    fn resolve(&self, input: &Path) -> Result<String, String> {
+        match self {
+            Expected::Digest(digest) => Ok(digest.clone()),
+            Expected::Sidecar => {
+                let path = sidecar_path(input);
+                let text = fs::read_to_string(&path)
+                    .map_err(|e| format!("Cannot read checksum {}: {}", path.display(), e))?;
+                // sha256sum format: "<hex>  <file name>"
+                parse_digest(text.split_whitespace().next().unwrap_or_default())
+                    .map_err(|e| format!("{} in {}", e, path.display()))
+            }
+        }
+    }
+}
+
+fn parse_digest(text: &str) -> Result<String, String> {
+    let hex = text.strip_prefix("sha256:").unwrap_or(text);
+    if hex.len() != 64 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!(
+            "Invalid checksum: {} (expected a SHA-256 digest in hex or sidecar)",
+            text
+        ));
+    }
+    Ok(hex.to_ascii_lowercase())
+}
+
+fn sidecar_path(input: &Path) -> PathBuf {
+    let mut path = input.as_os_str().to_owned();
+    path.push(".sha256");
+    PathBuf::from(path)
+}
+
+/// Hashes `input` and fails unless it matches `expected`.
+pub fn verify(input: &Path, expected: &Expected) -> Result<(), String> {
+    let expected = expected.resolve(input)?;
+    status!("  Verifying input checksum...");
+    let actual =
+        sha256_file(input).map_err(|e| format!("Cannot hash {}: {}", input.display(), e))?;
+    if actual != expected {
+        return Err(format!(
+            "Checksum mismatch for {}: expected sha256:{}, got sha256:{}. The input may be \
+             truncated or corrupted.",
+            input.display(),
+            expected,
+            actual
+        ));
+    }
+    status!("  Checksum OK (sha256:{})", actual);
+    Ok(())
+}
+
+fn sha256_file(path: &Path) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 1 << 20];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect())
+}
@@ -0,0 +1,198 @@
+//! `--chroma-loc` and `--chroma-filter`: how color is subsampled for 4:2:0
+//! and 4:2:2 outputs. Transposed frames are made of fine horizontal streaks,
+//! one per source row, and swscale's default conversion, chroma averaged
+//! from pixel pairs and sited to the left, can leave color fringes along
+//! their edges. These pick where the chroma samples sit (written to the
+//! stream too, so players upsample from the same place) and how they are
+//! filtered down from full resolution.
+
+use ffmpeg_next as ffmpeg;
+use ffmpeg_next::format::Pixel;
+use ffmpeg_next::software::scaling::flag::Flags;
+use ffmpeg_next::util::chroma::Location;
+use ffmpeg_next::util::frame::video::Video;
+use std::os::raw::{c_int, c_uint};
+use std::ptr;
+
+/// Gaussian blur (in chroma samples) of `--chroma-filter blur`.
+const CHROMA_BLUR: f32 = 1.0;
+
+/// Where chroma samples sit relative to the luma samples they cover.
+#[derive(Clone, Copy, PartialEq)]
+pub enum ChromaLoc {
+    /// MPEG-2, H.264 and H.265's default for 4:2:0.
+    Left,
+    /// MPEG-1 and JPEG.
+    Center,
+    /// BT.2020 and DCI 4:2:0.
+    TopLeft,
+    Top,
+    BottomLeft,
+    Bottom,
+}
+
+impl ChromaLoc {
+    pub fn parse(text: &str) -> Result<ChromaLoc, String> {
+        match text {
+            "left" => Ok(ChromaLoc::Left),
+            "center" => Ok(ChromaLoc::Center),
+            "topleft" => Ok(ChromaLoc::TopLeft),
+            "top" => Ok(ChromaLoc::Top),
+            "bottomleft" => Ok(ChromaLoc::BottomLeft),
+            "bottom" => Ok(ChromaLoc::Bottom),
+            _ => Err(format!(
+                "Invalid --chroma-loc: {} (expected left, center, topleft, top, bottomleft or \
+                 bottom)",
+                text
+            )),
+        }
+    }
+
+    /// The location tag written to the stream.
+    pub fn location(self) -> Location {
+        match self {
+            ChromaLoc::Left => Location::Left,
+            ChromaLoc::Center => Location::Center,
+            ChromaLoc::TopLeft => Location::TopLeft,
+            ChromaLoc::Top => Location::Top,
+            ChromaLoc::BottomLeft => Location::BottomLeft,
+            ChromaLoc::Bottom => Location::Bottom,
+        }
+    }
+
+    /// Horizontal and vertical chroma position in 1/256 luma samples, as
+    /// swscale takes them.
+    fn position(self) -> (c_int, c_int) {
+        match self {
+            ChromaLoc::Left => (0, 128),
+            ChromaLoc::Center => (128, 128),
+            ChromaLoc::TopLeft => (0, 0),
+            ChromaLoc::Top => (128, 0),
+            ChromaLoc::BottomLeft => (0, 256),
+            ChromaLoc::Bottom => (128, 256),
+        }
+    }
+}
+
+/// How chroma is filtered down to the subsampled planes.
+#[derive(Clone, Copy, PartialEq)]
+pub enum ChromaFilter {
+    /// swscale's default: chroma of pixel pairs averaged on input.
+    Fast,
+    /// Chroma taken at full resolution and filtered down with `--scaler`.
+    Full,
+    /// Like `Full`, after a slight blur of the chroma against aliasing.
+    Blur,
+}
+
+impl ChromaFilter {
+    pub fn parse(text: &str) -> Result<ChromaFilter, String> {
+        match text {
+            "fast" => Ok(ChromaFilter::Fast),
+            "full" => Ok(ChromaFilter::Full),
+            "blur" => Ok(ChromaFilter::Blur),
+            _ => Err(format!(
+                "Invalid --chroma-filter: {} (expected fast, full or blur)",
+                text
+            )),
+        }
+    }
+
+    fn flags(self) -> Flags {
+        match self {
+            ChromaFilter::Fast => Flags::empty(),
+            ChromaFilter::Full | ChromaFilter::Blur => {
+                Flags::FULL_CHR_H_INP | Flags::FULL_CHR_H_INT | Flags::ACCURATE_RND
+            }
+        }
+    }
+}
+
+/// A swscale context converting packed RGB or gray frames to YUV with the
+/// chroma sited and filtered as asked, which `Context::get` can't set up.
+pub struct ChromaScaler {
+    context: *mut ffmpeg::ffi::SwsContext,
+    output: (Pixel, u32, u32),
+    input_height: u32,
+}
+
+impl ChromaScaler {
+    pub fn new(
+        (input_format, input_width, input_height): (Pixel, u32, u32),
+        (output_format, output_width, output_height): (Pixel, u32, u32),
+        flags: Flags,
+        location: Option<ChromaLoc>,
+        filter: ChromaFilter,
+        full_range: bool,
+    ) -> Result<Self, ffmpeg::Error> {
+        unsafe {
+            let context = ffmpeg::ffi::sws_alloc_context();
+            if context.is_null() {
+                return Err(ffmpeg::Error::Other {
+                    errno: ffmpeg::util::error::ENOMEM,
+                });
+            }
+            (*context).src_w = input_width as c_int;
+            (*context).src_h = input_height as c_int;
+            (*context).src_format = ffmpeg::ffi::AVPixelFormat::from(input_format) as c_int;
+            (*context).dst_w = output_width as c_int;
+            (*context).dst_h = output_height as c_int;
+            (*context).dst_format = ffmpeg::ffi::AVPixelFormat::from(output_format) as c_int;
+            (*context).flags = (flags | filter.flags()).bits() as c_uint;
+            // RGB and gray input is full range
+            (*context).src_range = 1;
+            (*context).dst_range = c_int::from(full_range);
+            if let Some(location) = location {
+                let (horizontal, vertical) = location.position();
+                (*context).dst_h_chr_pos = horizontal;
+                (*context).dst_v_chr_pos = vertical;
+            }
+
+            let source_filter = match filter {
+                ChromaFilter::Blur => {
+                    ffmpeg::ffi::sws_getDefaultFilter(0.0, CHROMA_BLUR, 0.0, 0.0, 0.0, 0.0, 0)
+                }
+                _ => ptr::null_mut(),
+            };
+            let result = ffmpeg::ffi::sws_init_context(context, source_filter, ptr::null_mut());
+            // The filter is only read while the context is set up
+            if !source_filter.is_null() {
+                ffmpeg::ffi::sws_freeFilter(source_filter);
+            }
+            let scaler = ChromaScaler {
+                context,
+                output: (output_format, output_width, output_height),
+                input_height,
+            };
+            if result < 0 {
+                return Err(ffmpeg::Error::from(result));
+            }
+            Ok(scaler)
+        }
+    }
+
+    pub fn run(&mut self, input: &Video, output: &mut Video) -> Result<(), ffmpeg::Error> {
+        let (format, width, height) = self.output;
+        unsafe {
+            if output.is_empty() {
+                output.alloc(format, width, height);
+            }
+            ffmpeg::ffi::sws_scale(
+                self.context,
+                (*input.as_ptr()).data.as_ptr() as *const *const _,
+                (*input.as_ptr()).linesize.as_ptr() as *const _,
+                0,
+                self.input_height as c_int,
+                (*output.as_mut_ptr()).data.as_ptr(),
+                (*output.as_mut_ptr()).linesize.as_ptr() as *mut _,
+            );
+        }
+        Ok(())
+    }
+}
+
+impl Drop for ChromaScaler {
+    fn drop(&mut self) {
+        unsafe { ffmpeg::ffi::sws_freeContext(self.context) };
+    }
+}
@@ -0,0 +1,1399 @@
+use crate::audio::AudioDrive;
+use crate::canvas::{Canvas, Fit};
+use crate::channels::ChannelSet;
+use crate::checksum::Expected;
+use crate::chroma::{ChromaFilter, ChromaLoc};
+use crate::conform;
+use crate::encoder_chain;
+use crate::env_options;
+use crate::heatmap::{AutoRoi, HeatmapStat};
+use crate::index::IndexMode;
+use crate::ivtc::Ivtc;
+use crate::memory::parse_size;
+use crate::mosaic::Mosaic;
+use crate::multi_track::Track;
+use crate::notify::NotifyOn;
+use crate::ops::Rearrangement;
+use crate::order::{ColumnRange, FrameOrder};
+use crate::output_spec::{OutputSpec, Scale};
+use crate::packaging::DEFAULT_SEGMENT_SECONDS;
+use crate::pix_fmt::PixFmt;
+use crate::presets;
+use crate::raw_input::RawFormat;
+use crate::resample::Resample;
+use crate::retry::RetryPolicy;
+use crate::scaler::Scaler;
+use crate::scenes;
+use crate::side_by_side::Layout;
+use crate::smooth::SmoothFilter;
+use crate::sonify::Sonify;
+use crate::source::SizeChange;
+use crate::target::Target;
+use crate::throttle::{parse_cpu_list, Priority};
+use crate::watermark::Position;
+use crate::windows::{TimeWindow, WindowLayout};
+use crate::worker::QueueUrl;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+/// Command-line options for a transpose run.
+pub struct Options {
+    /// Input path, kept as the OS gave it so any file name opens.
+    pub input: PathBuf,
+    /// Output path, or `-` to write raw frames to stdout, kept as the OS
+    /// gave it like the input.
+    pub output: PathBuf,
+    /// Discard the output frames instead of writing them anywhere.
+    pub null_sink: bool,
+    /// Read the input as headerless raw frames of this layout.
+    pub raw_format: Option<RawFormat>,
+    /// FFmpeg demuxer or capture device for the input, instead of probing.
+    pub input_format: Option<String>,
+    /// `--io-buffer-size`: how far ahead of the demuxer the input is read;
+    /// network inputs read ahead by default.
+    pub io_buffer_size: Option<usize>,
+    /// `--input-retries`: reconnects after network input errors.
+    pub input_retries: RetryPolicy,
+    /// What to do when the input's frame size changes mid-stream.
+    pub on_size_change: SizeChange,
+    /// Repeat frames over gaps in the timestamps so columns map linearly to time.
+    pub fill_dropped_frames: bool,
+    /// Rebuild the time axis at uniform timestamp steps.
+    pub resample: Option<Resample>,
+    /// File keeping the decoded frames between runs.
+    pub cache_cube: Option<PathBuf>,
+    /// Digest the input must match before the run starts.
+    pub checksum: Option<Expected>,
+    /// Upper bound for the in-memory frame store, in bytes.
+    pub max_memory: Option<u64>,
+    /// Carry the source's A53 closed captions over to the outputs.
+    pub keep_cc: bool,
+    /// Encode on a thread of its own per output, with at most this many
+    /// frames queued for it.
+    pub inflight_frames: Option<usize>,
+    /// Threads decoding keyframe ranges of a file input in parallel.
+    pub decode_threads: usize,
+    /// Inverse telecine: always, never, or where 3:2 pulldown is detected.
+    pub ivtc: Ivtc,
+    /// Abort if the input has more frames than this.
+    pub frame_limit: Option<u64>,
+    /// Abort if the input is longer than this many seconds.
+    pub duration_limit: Option<f64>,
+    /// Only transpose these stretches of the input.
+    pub windows: Option<Vec<TimeWindow>>,
+    pub windows_layout: WindowLayout,
+    /// Factor the decoded frames are scaled by, at most 1.
+    pub scale: f64,
+    /// Keep one decoded frame out of this many.
+    pub frame_step: usize,
+    /// Blend, average and scale in linear light.
+    pub linear_light: bool,
+    /// Apply the `--scale`/`--frame-step` that fits `--max-memory` instead
+    /// of failing.
+    pub auto_fit: bool,
+    /// Extra outputs encoded from the same transpose pass.
+    pub also_encode: Vec<OutputSpec>,
+    /// Streams of a `--multi-track` output, in the order they're stored.
+    pub multi_track: Option<Vec<Track>>,
+    /// Target segment length for HLS (.m3u8) and DASH (.mpd) outputs.
+    pub segment_seconds: f64,
+    /// Where to write a JSON summary of the run.
+    pub summary_json: Option<PathBuf>,
+    /// Where to POST the JSON summary when the run ends.
+    pub notify_url: Option<String>,
+    pub notify_on: NotifyOn,
+    pub overwrite: bool,
+    /// Open the finished output in a player.
+    pub play: bool,
+    /// Embed per-frame source column mapping (SEI + timecode track).
+    pub embed_mapping: bool,
+    /// Where to write the CSV/JSON frame mapping sidecar.
+    pub sidecar: Option<PathBuf>,
+    /// Where to write the OTIO/EDL edit list lining the original up with
+    /// the output.
+    pub conform: Option<PathBuf>,
+    /// Where to write the per-output index of each pixel's source.
+    pub index: Option<IndexMode>,
+    pub frame_order: FrameOrder,
+    /// Maximum number of frames per output file; longer outputs are split.
+    pub split_output: Option<usize>,
+    /// Maximum output width in pixels (source frames) per output file.
+    pub split_width: Option<usize>,
+    /// Move --split-width boundaries onto detected scene cuts.
+    pub align_scenes: bool,
+    pub scene_threshold: f64,
+    /// Write each channel as its own grayscale output.
+    pub split_channels: Option<ChannelSet>,
+    /// Source columns to turn into output frames.
+    pub columns: Option<ColumnRange>,
+    /// Keep one source column out of this many as an output frame.
+    pub column_step: usize,
+    /// Selected columns skipped before the first kept one.
+    pub column_offset: usize,
+    /// Choose the columns automatically from temporal activity.
+    pub auto_roi: Option<AutoRoi>,
+    /// Crop black bars found in the source before transposing.
+    pub autocrop: bool,
+    /// Pick or order the frames by the loudness of the input's audio.
+    pub audio_drive: Option<AudioDrive>,
+    /// Tile the transposes of a grid of source regions into one output.
+    pub mosaic: Option<Mosaic>,
+    /// `--ops`: axis swaps and reversals run instead of the X-T transpose.
+    pub ops: Option<Rearrangement>,
+    /// Play the output forward and then backward.
+    pub boomerang: bool,
+    /// Number of blended frames inserted between adjacent output frames.
+    pub interpolate: usize,
+    /// Radius in frames of the temporal filter applied before transposing.
+    pub temporal_smooth: usize,
+    pub smooth_filter: SmoothFilter,
+    /// Filter for pixel format conversion and output scaling.
+    pub scaler: Scaler,
+    /// Where to write a PNG heatmap of per-pixel temporal activity.
+    pub heatmap: Option<PathBuf>,
+    pub heatmap_stat: HeatmapStat,
+    /// Drop output frames whose source column's mean temporal variance is
+    /// under this.
+    pub skip_static: Option<f64>,
+    /// Where to write the source columns `--skip-static` dropped.
+    pub skip_static_log: Option<PathBuf>,
+    /// Width in pixels of the fade applied at the left and right edges.
+    pub edge_fade: usize,
+    /// Seconds of title card explaining the axis mapping, shown first.
+    pub legend: Option<f64>,
+    /// Standard frame size the transposed frames are fitted to.
+    pub canvas: Option<Canvas>,
+    /// Show the retimed source next to the transposed frames.
+    pub with_original: Option<Layout>,
+    /// Image composited onto every output frame.
+    pub watermark: Option<PathBuf>,
+    pub watermark_position: Position,
+    /// Opacity the watermark's own alpha is scaled by, 0 to 1.
+    pub watermark_opacity: f64,
+    /// `--sonify`: play a row of the output frames as an audio track.
+    pub sonify: Option<Sonify>,
+    /// Seed for randomized options such as `--frame-order shuffle`.
+    pub seed: Option<u64>,
+    /// Guarantee output pixels are input pixels re-indexed: lossless FFV1,
+    /// point sampling, and nothing that computes new pixel values.
+    pub exact: bool,
+    /// `--validate`: decode each output once written and fail the run
+    /// unless it holds what was encoded.
+    pub validate: bool,
+    /// Encoder settings bundle for what the output is for.
+    pub target: Option<Target>,
+    /// Pixel format of color outputs, overriding the target's.
+    pub pix_fmt: Option<PixFmt>,
+    /// Where subsampled chroma is sited, and tagged as.
+    pub chroma_loc: Option<ChromaLoc>,
+    pub chroma_filter: ChromaFilter,
+    /// `--encoder`: encoders to try for color outputs, in order; empty for
+    /// FFmpeg's default H.264 encoder.
+    pub encoders: Vec<String>,
+    /// Maximum consecutive B-frames in H.264 outputs.
+    pub bframes: usize,
+    /// Encode every frame as a keyframe, for frame-accurate scrubbing.
+    pub intra_only: bool,
+    /// x264 rate control lookahead in frames.
+    pub lookahead: Option<u32>,
+    /// x264 tuning, e.g. `film` or `grain`, or `transpose` for settings
+    /// suited to transposed frames.
+    pub tune: Option<String>,
+    /// CSV of per-frame encoded sizes; also prints bitrate statistics.
+    pub encode_stats: Option<PathBuf>,
+    /// Produce byte-identical output across runs: single-threaded encoding,
+    /// no creation timestamps, fixed seed.
+    pub deterministic: bool,
+    /// Directory for staging files instead of the system temp directory.
+    pub temp_dir: Option<PathBuf>,
+    /// Leave the run's temp files in place for debugging.
+    pub keep_temp: bool,
+    /// `--upload-retries`: retries of failed uploads to cloud storage.
+    pub upload_retries: RetryPolicy,
+    /// Scheduling priority; `--nice` is `--priority low`.
+    pub priority: Priority,
+    /// `--affinity`: CPUs the run's threads are pinned to.
+    pub affinity: Option<Vec<usize>>,
+    /// Maximum frames per second for each stage.
+    pub throttle: Option<f64>,
+    /// Hide progress bars, e.g. when logging to a file.
+    pub no_progress: bool,
+    pub no_color: bool,
+    /// Unix socket streaming progress events and previews to frontends.
+    pub progress_socket: Option<PathBuf>,
+    /// `--save-preset`: the name to save this run's options under, and the
+    /// options (everything but the input and output paths).
+    pub save_preset: Option<(String, Vec<String>)>,
+}
+
+/// Options that take no value, which `VIDEO_TRANSPOSE_*` variables turn
+/// on and off.
+pub(crate) const SWITCHES: &[&str] = &[
+    "--keep-cc",
+    "--linear-light",
+    "--auto-fit",
+    "--null-sink",
+    "--raw-input",
+    "--fill-dropped-frames",
+    "--overwrite",
+    "--no-clobber",
+    "--play",
+    "--embed-mapping",
+    "--align-scenes",
+    "--autocrop",
+    "--boomerang",
+    "--deterministic",
+    "--exact",
+    "--validate",
+    "--intra-only",
+    "--keep-temp",
+    "--nice",
+    "--no-progress",
+    "--no-color",
+];
+
+/// Short spellings of options, with their long names.
+const ALIASES: &[(&str, &str)] = &[
+    ("-o", "--output"),
+    ("-f", "--input-format"),
+    ("-y", "--overwrite"),
+    ("-n", "--no-clobber"),
+];
+
+/// Options that rule each other out: one given on the command line takes
+/// the other out of the layers under it.
+const OPPOSITES: &[(&str, &str)] = &[("--overwrite", "--no-clobber"), ("--output", "--null-sink")];
+
+/// The long name of `flag`.
+fn long_name(flag: &str) -> &str {
+    ALIASES
+        .iter()
+        .find(|(short, _)| *short == flag)
+        .map_or(flag, |(_, long)| long)
+}
+
+/// The switch `--no-<switch>` turns off in the layers, for switches that
+/// have no opposite of their own.
+fn negated(flag: &str) -> Option<String> {
+    let switch = format!("--{}", flag.strip_prefix("--no-")?);
+    (SWITCHES.contains(&switch.as_str()) && !SWITCHES.contains(&flag)).then_some(switch)
+}
+
+/// Splits `args` into options, by long name and with their value, and
+/// positional arguments, which have no name.
+fn split_options(args: &[OsString]) -> Vec<(Option<String>, Vec<OsString>)> {
+    let mut split = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let flag = match arg.to_str() {
+            Some(text) if text.starts_with('-') && text.len() > 1 => text,
+            _ => {
+                split.push((None, vec![arg.clone()]));
+                continue;
+            }
+        };
+        let (flag, inline) = match flag.split_once('=') {
+            Some((flag, _)) if flag.starts_with("--") => (flag, true),
+            _ => (flag, false),
+        };
+        let flag = long_name(flag);
+        let mut option = vec![arg.clone()];
+        if !inline && !SWITCHES.contains(&flag) && negated(flag).is_none() {
+            option.extend(iter.next().cloned());
+        }
+        split.push((Some(flag.to_string()), option));
+    }
+    split
+}
+
+/// Takes the options that `args` give again, rule out or turn off out of
+/// `layers`, so the command line replaces the layers rather than adding
+/// to or conflicting with them. An output path among `args` replaces a
+/// layered `--output` or `--null-sink`.
+fn unlayered(layers: &[OsString], args: &[OsString]) -> Vec<OsString> {
+    let given = split_options(args);
+    let mut replaced = Vec::new();
+    for (flag, _) in &given {
+        let Some(flag) = flag else { continue };
+        replaced.push(flag.clone());
+        replaced.extend(negated(flag));
+        for (one, other) in OPPOSITES {
+            if flag == one {
+                replaced.push(other.to_string());
+            } else if flag == other {
+                replaced.push(one.to_string());
+            }
+        }
+    }
+    if given.iter().filter(|(flag, _)| flag.is_none()).count() >= 2 {
+        replaced.extend(["--output".to_string(), "--null-sink".to_string()]);
+    }
+    split_options(layers)
+        .into_iter()
+        .filter(|(flag, _)| !flag.as_ref().is_some_and(|flag| replaced.contains(flag)))
+        .flat_map(|(_, option)| option)
+        .collect()
+}
+
+impl Options {
+    /// Parses the command line `args`, program name first.
+    pub fn parse(args: &[OsString]) -> Result<Options, String> {
+        Options::parse_with(args, Vec::new())
+    }
+
+    /// Parses `args` like [`Options::parse`], on top of the `default`
+    /// preset and the `VIDEO_TRANSPOSE_*` environment variables, as the
+    /// command-line tool does.
+    pub fn parse_layered(args: &[OsString]) -> Result<Options, String> {
+        let mut layers = presets::defaults()?;
+        layers.extend(env_options::options()?);
+        Options::parse_with(args, layers)
+    }
+
+    /// Parses `args` with `layers` of options before them, which options
+    /// in `args` override. Only `args` are saved with `--save-preset`.
+    fn parse_with(args: &[OsString], layers: Vec<OsString>) -> Result<Options, String> {
+        let (args, save_preset) = presets::expand(args)?;
+        let (layers, _) = presets::expand(&layers)?;
+        let layers = unlayered(&layers, args.get(1..).unwrap_or_default());
+        let layered = layers.len();
+        let args: Vec<OsString> = args
+            .iter()
+            .take(1)
+            .cloned()
+            .chain(layers)
+            .chain(args.iter().skip(1).cloned())
+            .collect();
+        let mut positional = Vec::new();
+        // Where the positional arguments were, to leave them out of presets
+        let mut positional_at = Vec::new();
+        let mut output = None;
+        let mut null_sink = false;
+        let mut max_memory = None;
+        let mut decode_threads = 1;
+        let mut ivtc = Ivtc::Auto;
+        let mut inflight_frames = None;
+        let mut keep_cc = false;
+        let mut frame_limit = None;
+        let mut duration_limit = None;
+        let mut windows = None;
+        let mut windows_layout = None;
+        let mut scale = 1.0;
+        let mut frame_step = 1;
+        let mut linear_light = false;
+        let mut auto_fit = false;
+        let mut raw_input = false;
+        let mut input_pixfmt = None;
+        let mut input_size = None;
+        let mut input_fps = None;
+        let mut input_format = None;
+        let mut io_buffer_size = None;
+        let mut input_retries = RetryPolicy::DEFAULT;
+        let mut on_size_change = SizeChange::Abort;
+        let mut fill_dropped_frames = false;
+        let mut resample = None;
+        let mut cache_cube = None;
+        let mut checksum = None;
+        let mut summary_json = None;
+        let mut notify_url = None;
+        let mut notify_on = NotifyOn::Always;
+        let mut also_encode = Vec::new();
+        let mut multi_track = None;
+        let mut segment_seconds = DEFAULT_SEGMENT_SECONDS;
+        let mut overwrite = false;
+        let mut no_clobber = false;
+        let mut play = false;
+        let mut embed_mapping = false;
+        let mut sidecar = None;
+        let mut conform = None;
+        let mut index = None;
+        let mut frame_order = FrameOrder::Natural;
+        let mut seed = None;
+        let mut deterministic = false;
+        let mut exact = false;
+        let mut validate = false;
+        let mut target = None;
+        let mut pix_fmt = None;
+        let mut chroma_loc = None;
+        let mut chroma_filter = ChromaFilter::Fast;
+        let mut bframes = None;
+        let mut intra_only = false;
+        let mut lookahead = None;
+        let mut tune = None;
+        let mut encoders = Vec::new();
+        let mut encode_stats = None;
+        let mut columns = None;
+        let mut auto_roi = None;
+        let mut autocrop = false;
+        let mut audio_drive = None;
+        let mut mosaic = None;
+        let mut ops = None;
+        let mut column_step = 1;
+        let mut column_offset = 0;
+        let mut split_channels = None;
+        let mut split_output = None;
+        let mut split_width = None;
+        let mut align_scenes = false;
+        let mut scene_threshold = scenes::DEFAULT_THRESHOLD;
+        let mut boomerang = false;
+        let mut interpolate = 0;
+        let mut temporal_smooth = 0;
+        let mut smooth_filter = SmoothFilter::Mean;
+        let mut scaler = None;
+        let mut edge_fade = 0;
+        let mut legend = None;
+        let mut canvas = None;
+        let mut fit = None;
+        let mut with_original = None;
+        let mut watermark = None;
+        let mut watermark_position = None;
+        let mut watermark_opacity = None;
+        let mut sonify = None;
+        let mut heatmap = None;
+        let mut heatmap_stat = HeatmapStat::Variance;
+        let mut skip_static = None;
+        let mut skip_static_log = None;
+        let mut temp_dir = None;
+        let mut keep_temp = false;
+        let mut upload_retries = RetryPolicy::DEFAULT;
+        let mut priority = Priority::Normal;
+        let mut affinity = None;
+        let mut throttle = None;
+        let mut no_progress = false;
+        let mut no_color = false;
+        let mut progress_socket = None;
+
+        let mut iter = args.iter().enumerate().skip(1);
+        while let Some((at, arg)) = iter.next() {
+            // Options must be Unicode, paths are kept in whatever encoding
+            // the file system uses
+            let Some(text) = arg.to_str() else {
+                positional.push(arg.clone());
+                positional_at.push(at);
+                continue;
+            };
+            let (flag, inline_value) = match text.split_once('=') {
+                Some((flag, value)) if text.starts_with("--") => (flag, Some(value.to_string())),
+                _ => (text, None),
+            };
+            let mut value = |name: &str| -> Result<String, String> {
+                match inline_value.clone() {
+                    Some(value) => Ok(value),
+                    None => iter
+                        .next()
+                        .ok_or_else(|| format!("Missing value for {}", name))?
+                        .1
+                        .clone()
+                        .into_string()
+                        .map_err(|_| format!("Value for {} is not valid Unicode", name)),
+                }
+            };
+
+            match flag {
+                "--max-memory" => max_memory = Some(parse_size(&value(flag)?)?),
+                "--keep-cc" => keep_cc = true,
+                "--inflight-frames" => inflight_frames = Some(parse_number(flag, &value(flag)?)?),
+                "--decode-threads" => decode_threads = parse_number(flag, &value(flag)?)?,
+                "--ivtc" => ivtc = Ivtc::parse(&value(flag)?)?,
+                "--frame-limit" => frame_limit = Some(parse_number(flag, &value(flag)?)?),
+                "--duration-limit" => duration_limit = Some(parse_number(flag, &value(flag)?)?),
+                "--windows" => windows = Some(crate::windows::parse(&value(flag)?)?),
+                "--windows-layout" => windows_layout = Some(WindowLayout::parse(&value(flag)?)?),
+                "--scale" => scale = parse_number(flag, &value(flag)?)?,
+                "--frame-step" => frame_step = parse_number(flag, &value(flag)?)?,
+                "--linear-light" => linear_light = true,
+                "--auto-fit" => auto_fit = true,
+                "-o" | "--output" => output = Some(path_value(flag, &inline_value, &mut iter)?),
+                "--null-sink" => null_sink = true,
+                "--raw-input" => raw_input = true,
+                "--input-fps" => input_fps = Some(value(flag)?),
+                "--input-pixfmt" => input_pixfmt = Some(value(flag)?),
+                "--input-size" => input_size = Some(value(flag)?),
+                "-f" | "--input-format" => input_format = Some(value(flag)?),
+                "--io-buffer-size" => io_buffer_size = Some(parse_size(&value(flag)?)? as usize),
+                "--input-retries" => input_retries = RetryPolicy::parse(flag, &value(flag)?)?,
+                "--on-size-change" => on_size_change = SizeChange::parse(&value(flag)?)?,
+                "--fill-dropped-frames" => fill_dropped_frames = true,
+                "--resample" => resample = Some(Resample::parse(&value(flag)?)?),
+                "--cache-cube" => cache_cube = Some(path_value(flag, &inline_value, &mut iter)?),
+                "--checksum-verify" => checksum = Some(Expected::parse(&value(flag)?)?),
+                "--segment-time" => segment_seconds = parse_number(flag, &value(flag)?)?,
+                "--summary-json" => {
+                    summary_json = Some(path_value(flag, &inline_value, &mut iter)?)
+                }
+                "--notify-url" => notify_url = Some(value(flag)?),
+                "--notify-on" => notify_on = NotifyOn::parse(&value(flag)?)?,
+                "--also-encode" => also_encode.push(OutputSpec::parse(&value(flag)?)?),
+                "--multi-track" => multi_track = Some(Track::parse_list(&value(flag)?)?),
+                "-y" | "--overwrite" => overwrite = true,
+                "-n" | "--no-clobber" => no_clobber = true,
+                "--play" => play = true,
+                "--embed-mapping" => embed_mapping = true,
+                "--sidecar" => sidecar = Some(path_value(flag, &inline_value, &mut iter)?),
+                "--conform" => conform = Some(path_value(flag, &inline_value, &mut iter)?),
+                "--index" => index = Some(IndexMode::parse(&value(flag)?)?),
+                "--frame-order" => frame_order = FrameOrder::parse(&value(flag)?)?,
+                "--columns" => columns = Some(ColumnRange::parse(&value(flag)?)?),
+                "--auto-roi" => auto_roi = Some(AutoRoi::parse(&value(flag)?)?),
+                "--autocrop" => autocrop = true,
+                "--audio-drive" => audio_drive = Some(AudioDrive::parse(&value(flag)?)?),
+                "--column-step" => column_step = parse_number(flag, &value(flag)?)?,
+                "--column-offset" => column_offset = parse_number(flag, &value(flag)?)?,
+                "--mosaic" => mosaic = Some(Mosaic::parse(&value(flag)?)?),
+                "--ops" => ops = Some(Rearrangement::parse(&value(flag)?)?),
+                "--split-output" => split_output = Some(parse_number(flag, &value(flag)?)?),
+                "--split-width" => split_width = Some(parse_number(flag, &value(flag)?)?),
+                "--align-scenes" => align_scenes = true,
+                "--scene-threshold" => scene_threshold = parse_number(flag, &value(flag)?)?,
+                "--split-channels" => split_channels = Some(ChannelSet::parse(&value(flag)?)?),
+                "--boomerang" => boomerang = true,
+                "--interpolate" => interpolate = parse_number(flag, &value(flag)?)?,
+                "--temporal-smooth" => temporal_smooth = parse_number(flag, &value(flag)?)?,
+                "--smooth-filter" => smooth_filter = SmoothFilter::parse(&value(flag)?)?,
+                "--scaler" => scaler = Some(Scaler::parse(&value(flag)?)?),
+                "--edge-fade" => edge_fade = parse_number(flag, &value(flag)?)?,
+                "--legend" => legend = Some(parse_number(flag, &value(flag)?)?),
+                "--canvas" => canvas = Some(value(flag)?),
+                "--with-original" => with_original = Some(Layout::parse(&value(flag)?)?),
+                "--fit" => fit = Some(Fit::parse(&value(flag)?)?),
+                "--watermark" => watermark = Some(path_value(flag, &inline_value, &mut iter)?),
+                "--position" => watermark_position = Some(Position::parse(&value(flag)?)?),
+                "--opacity" => watermark_opacity = Some(parse_number(flag, &value(flag)?)?),
+                "--sonify" => sonify = Some(Sonify::parse(&value(flag)?)?),
+                "--heatmap" => heatmap = Some(path_value(flag, &inline_value, &mut iter)?),
+                "--heatmap-stat" => heatmap_stat = HeatmapStat::parse(&value(flag)?)?,
+                "--skip-static" => skip_static = Some(parse_number(flag, &value(flag)?)?),
+                "--skip-static-log" => {
+                    skip_static_log = Some(path_value(flag, &inline_value, &mut iter)?)
+                }
+                "--seed" => seed = Some(parse_number(flag, &value(flag)?)?),
+                "--deterministic" => deterministic = true,
+                "--exact" => exact = true,
+                "--validate" => validate = true,
+                "--pix-fmt" => pix_fmt = Some(PixFmt::parse(&value(flag)?)?),
+                "--chroma-loc" => chroma_loc = Some(ChromaLoc::parse(&value(flag)?)?),
+                "--chroma-filter" => chroma_filter = ChromaFilter::parse(&value(flag)?)?,
+                "--target" => target = Some(Target::parse(&value(flag)?)?),
+                "--bframes" => bframes = Some(parse_number(flag, &value(flag)?)?),
+                "--intra-only" => intra_only = true,
+                "--lookahead" => lookahead = Some(parse_number(flag, &value(flag)?)?),
+                "--tune" => tune = Some(value(flag)?),
+                "--encoder" => encoders = encoder_chain::parse(&value(flag)?)?,
+                "--encode-stats" => {
+                    encode_stats = Some(path_value(flag, &inline_value, &mut iter)?)
+                }
+                "--temp-dir" => temp_dir = Some(path_value(flag, &inline_value, &mut iter)?),
+                "--keep-temp" => keep_temp = true,
+                "--upload-retries" => upload_retries = RetryPolicy::parse(flag, &value(flag)?)?,
+                "--nice" => priority = Priority::Low,
+                "--priority" => priority = Priority::parse(&value(flag)?)?,
+                "--affinity" => affinity = Some(parse_cpu_list(&value(flag)?)?),
+                "--throttle" => throttle = Some(parse_number(flag, &value(flag)?)?),
+                "--no-progress" => no_progress = true,
+                "--no-color" => no_color = true,
+                "--progress-socket" => {
+                    progress_socket = Some(path_value(flag, &inline_value, &mut iter)?)
+                }
+                // Only takes the switch out of the layers
+                _ if negated(flag).is_some() => {}
+                // A stray variable shouldn't fail every run
+                _ if flag.starts_with('-') && flag.len() > 1 && at <= layered => {
+                    status!(
+                        "  Warning: ignoring {}, set in the environment or the default preset, \
+                         which is not an option",
+                        flag
+                    );
+                }
+                _ if flag.starts_with('-') && flag.len() > 1 => {
+                    return Err(format!("Unknown option: {}", flag));
+                }
+                _ => {
+                    positional.push(arg.clone());
+                    positional_at.push(at);
+                }
+            }
+        }
+
+        if segment_seconds <= 0.0 || !segment_seconds.is_finite() {
+            return Err(format!("Invalid segment time: {}", segment_seconds));
+        }
+        if duration_limit.is_some_and(|seconds: f64| seconds <= 0.0 || !seconds.is_finite()) {
+            return Err("--duration-limit must be a positive number of seconds".to_string());
+        }
+        if !(scale > 0.0 && scale <= 1.0) {
+            return Err(format!("Invalid --scale: {} (expected 0 to 1)", scale));
+        }
+        if inflight_frames == Some(0) {
+            return Err("--inflight-frames must be at least 1".to_string());
+        }
+        if decode_threads == 0 {
+            return Err("--decode-threads must be at least 1".to_string());
+        }
+        if frame_step == 0 {
+            return Err("--frame-step must be at least 1".to_string());
+        }
+        if column_step == 0 {
+            return Err("--column-step must be at least 1".to_string());
+        }
+        if column_offset >= column_step {
+            return Err(format!(
+                "--column-offset must be less than --column-step ({})",
+                column_step
+            ));
+        }
+        if legend.is_some_and(|seconds: f64| seconds <= 0.0 || !seconds.is_finite()) {
+            return Err("--legend must be a positive number of seconds".to_string());
+        }
+        let canvas = match (canvas, fit) {
+            (Some(size), fit) => Some(Canvas::parse(&size, fit.unwrap_or(Fit::Contain))?),
+            (None, Some(_)) => return Err("--fit requires --canvas".to_string()),
+            (None, None) => None,
+        };
+        if canvas.is_some() && index.is_some() {
+            return Err("--canvas can't be combined with --index".to_string());
+        }
+        if with_original.is_some() && index.is_some() {
+            return Err("--with-original can't be combined with --index".to_string());
+        }
+        if watermark.is_none() && (watermark_position.is_some() || watermark_opacity.is_some()) {
+            return Err("--position and --opacity require --watermark".to_string());
+        }
+        if watermark_opacity.is_some_and(|opacity: f64| !(0.0..=1.0).contains(&opacity)) {
+            return Err("--opacity must be between 0 and 1".to_string());
+        }
+        if throttle.is_some_and(|fps: f64| fps <= 0.0 || !fps.is_finite()) {
+            return Err("--throttle must be a positive frame rate".to_string());
+        }
+        if split_output == Some(0) {
+            return Err("--split-output must be at least 1".to_string());
+        }
+        if split_width.is_some_and(|width| width < 2) {
+            return Err("--split-width must be at least 2".to_string());
+        }
+        if !(0.0..=1.0).contains(&scene_threshold) {
+            return Err(format!(
+                "Invalid scene threshold: {} (expected 0 to 1)",
+                scene_threshold
+            ));
+        }
+        if auto_roi.is_some() && matches!(frame_order, FrameOrder::File(_)) {
+            return Err("--auto-roi can't be combined with --frame-order file:".to_string());
+        }
+        if mosaic.is_some() {
+            // The regions are laid out before decoding, --auto-roi picks after
+            if auto_roi.is_some() {
+                return Err("--mosaic can't be combined with --auto-roi".to_string());
+            }
+            if index.is_some() {
+                return Err("--mosaic can't be combined with --index".to_string());
+            }
+        }
+        if autocrop {
+            // These name source columns, which the crop renumbers
+            let conflicts = [
+                (columns.is_some(), "--columns"),
+                (auto_roi.is_some(), "--auto-roi"),
+                (mosaic.is_some(), "--mosaic"),
+                (index.is_some(), "--index"),
+                (
+                    matches!(frame_order, FrameOrder::File(_)),
+                    "--frame-order file:",
+                ),
+            ];
+            if let Some((_, conflict)) = conflicts.iter().find(|(conflicting, _)| *conflicting) {
+                return Err(format!("--autocrop can't be combined with {}", conflict));
+            }
+        }
+        if skip_static.is_some_and(|threshold: f64| threshold < 0.0 || !threshold.is_finite()) {
+            return Err("--skip-static must be a variance of 0 or more".to_string());
+        }
+        if skip_static_log.is_some() && skip_static.is_none() {
+            return Err("--skip-static-log requires --skip-static".to_string());
+        }
+        if skip_static.is_some() && mosaic.is_some() {
+            return Err("--skip-static can't be combined with --mosaic".to_string());
+        }
+        if ops.is_some() {
+            // These pick, lay out or label output frames as source columns,
+            // which they needn't be after --ops
+            let conflicts = [
+                (columns.is_some(), "--columns"),
+                (auto_roi.is_some(), "--auto-roi"),
+                (mosaic.is_some(), "--mosaic"),
+                (windows.is_some(), "--windows"),
+                (align_scenes, "--align-scenes"),
+                (split_width.is_some(), "--split-width"),
+                (embed_mapping, "--embed-mapping"),
+                (sidecar.is_some(), "--sidecar"),
+                (conform.is_some(), "--conform"),
+                (index.is_some(), "--index"),
+                (legend.is_some(), "--legend"),
+                (skip_static.is_some(), "--skip-static"),
+                (exact, "--exact"),
+            ];
+            if let Some((_, conflict)) = conflicts.iter().find(|(conflicting, _)| *conflicting) {
+                return Err(format!(
+                    "--ops can't be combined with {}, which assumes the X-T transpose",
+                    conflict
+                ));
+            }
+        }
+        // The raw layout flags imply --raw-input, which needs all of them but the rate
+        let raw_input = raw_input || input_pixfmt.is_some() || input_size.is_some();
+        let raw_format = match (input_pixfmt, input_size) {
+            (Some(pixfmt), Some(size)) => {
+                Some(RawFormat::parse(&pixfmt, &size, input_fps.as_deref())?)
+            }
+            _ if raw_input => {
+                return Err("--raw-input requires --input-size and --input-pixfmt".to_string())
+            }
+            _ if input_fps.is_some() => return Err("--input-fps requires --raw-input".to_string()),
+            _ => None,
+        };
+        if raw_format.is_some() && input_format.is_some() {
+            return Err("--input-format can't be combined with --raw-input".to_string());
+        }
+        if raw_format.is_some() && io_buffer_size.is_some() {
+            return Err("--io-buffer-size can't be combined with --raw-input".to_string());
+        }
+        if io_buffer_size == Some(0) {
+            return Err("--io-buffer-size must be greater than zero".to_string());
+        }
+        if intra_only && bframes.is_some_and(|bframes| bframes > 0) {
+            return Err("--intra-only can't be combined with --bframes".to_string());
+        }
+        if exact {
+            // Each of these computes new pixel values
+            let conflicts = [
+                (interpolate > 0, "--interpolate"),
+                (temporal_smooth > 0, "--temporal-smooth"),
+                (edge_fade > 0, "--edge-fade"),
+                (legend.is_some(), "--legend"),
+                (watermark.is_some(), "--watermark"),
+                (canvas.is_some(), "--canvas"),
+                (with_original.is_some(), "--with-original"),
+                (mosaic.is_some(), "--mosaic"),
+                (scale < 1.0, "--scale"),
+                (resample == Some(Resample::Blend), "--resample blend"),
+                (
+                    on_size_change == SizeChange::Scale,
+                    "--on-size-change scale",
+                ),
+                (
+                    split_channels == Some(ChannelSet::Yuv),
+                    "--split-channels yuv",
+                ),
+                (
+                    scaler.is_some_and(|scaler| scaler != Scaler::Point),
+                    "a --scaler other than point",
+                ),
+                (
+                    pix_fmt.is_some_and(|pix_fmt| pix_fmt != PixFmt::Rgb),
+                    "a --pix-fmt other than rgb",
+                ),
+                (
+                    target.is_some_and(|target| target != Target::Archive),
+                    "a --target other than archive",
+                ),
+                (
+                    also_encode
+                        .iter()
+                        .any(|spec| !matches!(spec.scale, Scale::Factor(1.0))),
+                    "scaled --also-encode outputs",
+                ),
+            ];
+            if let Some((_, conflict)) = conflicts.iter().find(|(conflicting, _)| *conflicting) {
+                return Err(format!(
+                    "--exact can't be combined with {}, which changes pixel values",
+                    conflict
+                ));
+            }
+            if frame_step > 1 || auto_fit || ivtc == Ivtc::On {
+                return Err(
+                    "--exact can't be combined with --frame-step, --auto-fit or --ivtc on, which \
+                     drop frames"
+                        .to_string(),
+                );
+            }
+            target = Some(Target::Archive);
+        }
+        // Lossless and RGB outputs have their one encoder
+        if !encoders.is_empty() {
+            if target.is_some_and(Target::lossless) {
+                return Err("--encoder can't be combined with lossless FFV1 outputs".to_string());
+            }
+            if pix_fmt == Some(PixFmt::Rgb) {
+                return Err(
+                    "--encoder can't be combined with --pix-fmt rgb (libx264rgb)".to_string(),
+                );
+            }
+        }
+        let scaler = scaler.unwrap_or(if exact {
+            Scaler::Point
+        } else {
+            Scaler::Bilinear
+        });
+        // The target fills in what wasn't given explicitly
+        let intra_only =
+            intra_only || (bframes.is_none() && target.is_some_and(Target::intra_only));
+        let bframes = match (bframes, target) {
+            (Some(bframes), _) => bframes,
+            (None, Some(target)) if !intra_only => target.bframes(),
+            _ => 0,
+        };
+        if overwrite && no_clobber {
+            return Err("--overwrite and --no-clobber are mutually exclusive".to_string());
+        }
+        // The output is the last positional argument unless given with -o,
+        // and not needed at all with --null-sink
+        if output.is_none() && !null_sink && positional.len() == 2 {
+            output = positional.pop().map(PathBuf::from);
+        }
+        if positional.len() != 1 {
+            return Err("Expected an input and an output path".to_string());
+        }
+        let output = output.unwrap_or_default();
+        let input = PathBuf::from(positional.pop().unwrap());
+        // Options are Unicode, anything else was a path
+        let save_preset = save_preset.map(|name| {
+            let options = args
+                .iter()
+                .enumerate()
+                .skip(1 + layered)
+                .filter(|(at, _)| !positional_at.contains(at))
+                .map(|(_, arg)| arg.to_string_lossy().into_owned())
+                .collect();
+            (name, options)
+        });
+        if (null_sink || output == Path::new("-")) && !also_encode.is_empty() {
+            return Err("--also-encode needs a file output".to_string());
+        }
+        if (null_sink || output == Path::new("-")) && play {
+            return Err("--play needs a file output".to_string());
+        }
+        if (null_sink || output == Path::new("-")) && sonify.is_some() {
+            return Err("--sonify needs a file output".to_string());
+        }
+        if let Some(path) = &conform {
+            conform::Format::of(path)?;
+            if null_sink || output == Path::new("-") {
+                return Err("--conform needs a file output".to_string());
+            }
+            // The original is laid out over whole output frames, one time
+            // range for all of them
+            let conflicts = [
+                (mosaic.is_some(), "--mosaic"),
+                (windows.is_some(), "--windows"),
+                (split_width.is_some(), "--split-width"),
+            ];
+            if let Some((_, conflict)) = conflicts.iter().find(|(conflicting, _)| *conflicting) {
+                return Err(format!("--conform can't be combined with {}", conflict));
+            }
+        }
+        if multi_track.is_some() {
+            if null_sink || output == Path::new("-") {
+                return Err("--multi-track needs a file output".to_string());
+            }
+            // The tracks are whole transposes of the cube, written to one file
+            let conflicts = [
+                (ops.is_some(), "--ops"),
+                (mosaic.is_some(), "--mosaic"),
+                (windows.is_some(), "--windows"),
+                (!also_encode.is_empty(), "--also-encode"),
+                (split_output.is_some(), "--split-output"),
+                (split_width.is_some(), "--split-width"),
+                (split_channels.is_some(), "--split-channels"),
+                (legend.is_some(), "--legend"),
+                (interpolate > 0, "--interpolate"),
+                (edge_fade > 0, "--edge-fade"),
+                (canvas.is_some(), "--canvas"),
+                (with_original.is_some(), "--with-original"),
+                (watermark.is_some(), "--watermark"),
+                (sonify.is_some(), "--sonify"),
+                (index.is_some(), "--index"),
+                (embed_mapping, "--embed-mapping"),
+                (keep_cc, "--keep-cc"),
+                (inflight_frames.is_some(), "--inflight-frames"),
+                (validate, "--validate"),
+                (encode_stats.is_some(), "--encode-stats"),
+            ];
+            if let Some((_, conflict)) = conflicts.iter().find(|(conflicting, _)| *conflicting) {
+                return Err(format!("--multi-track can't be combined with {}", conflict));
+            }
+        }
+        if (null_sink || output == Path::new("-")) && index.is_some() {
+            return Err("--index needs a file output".to_string());
+        }
+        if (null_sink || output == Path::new("-")) && validate {
+            return Err("--validate needs a file output".to_string());
+        }
+        if audio_drive.is_some() {
+            // The audio is read in a second pass over the input
+            let generated = input
+                .to_str()
+                .is_some_and(|input| input.starts_with("generate:"));
+            if generated || raw_input || input.as_os_str() == "-" {
+                return Err(
+                    "--audio-drive needs an input file or URL with an audio track".to_string(),
+                );
+            }
+        }
+        if let Some(AudioDrive::Select(_)) = audio_drive {
+            // These count the source frames as decoded
+            let conflicts = [
+                (windows.is_some(), "--windows"),
+                (index.is_some(), "--index"),
+                (keep_cc, "--keep-cc"),
+            ];
+            if let Some((_, conflict)) = conflicts.iter().find(|(conflicting, _)| *conflicting) {
+                return Err(format!(
+                    "--audio-drive select can't be combined with {}",
+                    conflict
+                ));
+            }
+        }
+        if windows_layout.is_some() && windows.is_none() {
+            return Err("--windows-layout needs --windows".to_string());
+        }
+        let windows_layout = windows_layout.unwrap_or(WindowLayout::Stack);
+        if windows.is_some() {
+            // Both would fill the gaps between the windows with frames
+            if resample.is_some() {
+                return Err("--windows and --resample can't be combined".to_string());
+            }
+            // The cache holds the frames of one selection only
+            if cache_cube.is_some() {
+                return Err("--windows and --cache-cube can't be combined".to_string());
+            }
+        }
+        if (null_sink || output == Path::new("-")) && windows_layout == WindowLayout::Separate {
+            return Err("--windows-layout separate needs a file output".to_string());
+        }
+
+        Ok(Options {
+            input,
+            output,
+            null_sink,
+            raw_format,
+            input_format,
+            io_buffer_size,
+            input_retries,
+            on_size_change,
+            fill_dropped_frames,
+            resample,
+            cache_cube,
+            checksum,
+            max_memory,
+            decode_threads,
+            ivtc,
+            inflight_frames,
+            keep_cc,
+            frame_limit,
+            duration_limit,
+            windows,
+            windows_layout,
+            scale,
+            frame_step,
+            linear_light,
+            auto_fit,
+            also_encode,
+            multi_track,
+            segment_seconds,
+            summary_json,
+            notify_url,
+            notify_on,
+            overwrite,
+            play,
+            embed_mapping,
+            sidecar,
+            conform,
+            index,
+            frame_order,
+            split_output,
+            split_width,
+            split_channels,
+            align_scenes,
+            scene_threshold,
+            columns,
+            column_step,
+            column_offset,
+            auto_roi,
+            autocrop,
+            audio_drive,
+            mosaic,
+            ops,
+            boomerang,
+            interpolate,
+            temporal_smooth,
+            smooth_filter,
+            scaler,
+            edge_fade,
+            legend,
+            canvas,
+            with_original,
+            watermark,
+            watermark_position: watermark_position.unwrap_or(Position::BottomRight),
+            watermark_opacity: watermark_opacity.unwrap_or(1.0),
+            sonify,
+            heatmap,
+            heatmap_stat,
+            skip_static,
+            skip_static_log,
+            seed,
+            deterministic,
+            exact,
+            validate,
+            target,
+            pix_fmt,
+            chroma_loc,
+            chroma_filter,
+            bframes,
+            intra_only,
+            lookahead,
+            tune,
+            encoders,
+            encode_stats,
+            temp_dir,
+            keep_temp,
+            upload_retries,
+            priority,
+            affinity,
+            throttle,
+            no_progress,
+            no_color,
+            progress_socket,
+            save_preset,
+        })
+    }
+}
+
+/// Options for `video-transpose worker`.
+pub struct WorkerOptions {
+    pub queue: QueueUrl,
+    /// Number of jobs run at the same time.
+    pub concurrency: usize,
+    /// Address to serve Prometheus metrics on.
+    pub metrics_addr: Option<String>,
+    /// Memory shared by the jobs running at the same time, in bytes.
+    pub max_memory: Option<u64>,
+}
+
+impl WorkerOptions {
+    /// Parses the arguments following `worker`.
+    pub fn parse(args: &[String]) -> Result<WorkerOptions, String> {
+        let mut queue = None;
+        let mut concurrency = 1;
+        let mut metrics_addr = None;
+        let mut max_memory = None;
+
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            let (flag, inline_value) = match arg.split_once('=') {
+                Some((flag, value)) if arg.starts_with("--") => (flag, Some(value.to_string())),
+                _ => (arg.as_str(), None),
+            };
+            let mut value = |name: &str| -> Result<String, String> {
+                inline_value
+                    .clone()
+                    .or_else(|| iter.next().cloned())
+                    .ok_or_else(|| format!("Missing value for {}", name))
+            };
+
+            match flag {
+                "--queue" => queue = Some(QueueUrl::parse(&value(flag)?)?),
+                "--concurrency" => concurrency = parse_number(flag, &value(flag)?)?,
+                "--metrics-addr" => metrics_addr = Some(value(flag)?),
+                "--max-memory" => max_memory = Some(parse_size(&value(flag)?)?),
+                _ => return Err(format!("Unknown worker option: {}", arg)),
+            }
+        }
+
+        if concurrency == 0 {
+            return Err("--concurrency must be at least 1".to_string());
+        }
+        Ok(WorkerOptions {
+            queue: queue.ok_or("worker requires --queue redis://host[:port][/list]")?,
+            concurrency,
+            metrics_addr,
+            max_memory,
+        })
+    }
+}
+
+/// Options for `video-transpose clean-tmp`.
+pub struct CleanTmpOptions {
+    pub temp_dir: Option<String>,
+}
+
+impl CleanTmpOptions {
+    /// Parses the arguments following `clean-tmp`.
+    pub fn parse(args: &[String]) -> Result<CleanTmpOptions, String> {
+        let mut temp_dir = None;
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            match arg.split_once('=') {
+                Some(("--temp-dir", value)) => temp_dir = Some(value.to_string()),
+                _ if arg == "--temp-dir" => {
+                    temp_dir = Some(iter.next().cloned().ok_or("Missing value for --temp-dir")?)
+                }
+                _ => return Err(format!("Unknown clean-tmp option: {}", arg)),
+            }
+        }
+        Ok(CleanTmpOptions { temp_dir })
+    }
+}
+
+/// Options for `video-transpose self-update`.
+pub struct SelfUpdateOptions {
+    /// Only report whether a newer release exists.
+    pub check: bool,
+    /// GitHub repository (`owner/name`) the releases are published in.
+    pub repo: String,
+}
+
+impl SelfUpdateOptions {
+    /// Parses the arguments following `self-update`.
+    pub fn parse(args: &[String]) -> Result<SelfUpdateOptions, String> {
+        let mut check = false;
+        let mut repo = crate::self_update::DEFAULT_REPOSITORY.to_string();
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            match arg.split_once('=') {
+                Some(("--repo", value)) => repo = value.to_string(),
+                _ if arg == "--repo" => {
+                    repo = iter.next().cloned().ok_or("Missing value for --repo")?
+                }
+                _ if arg == "--check" => check = true,
+                _ => return Err(format!("Unknown self-update option: {}", arg)),
+            }
+        }
+        if repo.split('/').filter(|part| !part.is_empty()).count() != 2 {
+            return Err(format!("Invalid --repo: {} (expected owner/name)", repo));
+        }
+        Ok(SelfUpdateOptions { check, repo })
+    }
+}
+
+/// Options for `video-transpose compare-stack`.
+pub struct CompareOptions {
+    /// Transpose options of the top and bottom input, with their paths.
+    pub inputs: [Options; 2],
+    pub output: PathBuf,
+}
+
+impl CompareOptions {
+    /// Parses the arguments following `compare-stack`: transpose options
+    /// applying to both inputs, then the two inputs and the output.
+    pub fn parse(args: &[OsString]) -> Result<CompareOptions, String> {
+        let [options @ .., top, bottom, output] = args else {
+            return Err("compare-stack expects two inputs and an output path".to_string());
+        };
+        let output = PathBuf::from(output);
+        // Both transposes hand their frames over instead of writing files
+        let parse_input = |input: &OsString| {
+            let mut args: Vec<OsString> = vec![
+                "compare-stack".into(),
+                "--null-sink".into(),
+                "--no-progress".into(),
+            ];
+            args.extend(options.iter().cloned());
+            args.push(input.clone());
+            Options::parse_layered(&args)
+        };
+        Ok(CompareOptions {
+            inputs: [parse_input(top)?, parse_input(bottom)?],
+            output,
+        })
+    }
+}
+
+/// The value of a path-valued option, in whatever encoding the file system
+/// uses, unlike the Unicode values of other options.
+fn path_value<'a>(
+    flag: &str,
+    inline_value: &Option<String>,
+    iter: &mut impl Iterator<Item = (usize, &'a OsString)>,
+) -> Result<PathBuf, String> {
+    match inline_value {
+        Some(value) => Ok(PathBuf::from(value)),
+        None => iter
+            .next()
+            .map(|(_, value)| PathBuf::from(value))
+            .ok_or_else(|| format!("Missing value for {}", flag)),
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(flag: &str, text: &str) -> Result<T, String> {
+    text.parse()
+        .map_err(|_| format!("Invalid value for {}: {}", flag, text))
+}
+
+pub fn usage(program: &str) -> String {
+    format!(
+        "Usage: {} [options] <input_video|-|generate:WxH[:n]> <output_video|->\n       \
+         {} worker --queue redis://host[:port][/list] [--concurrency <n>] [--max-memory <size>] [--metrics-addr <host:port>]\n       \
+         {} clean-tmp [--temp-dir <dir>]\n       \
+         {} compare-stack [options] <input_a> <input_b> <output_video>\n       \
+         {} doctor\n       \
+         {} self-update [--check] [--repo owner/name]\n\
+         \n\
+         Options:\n  \
+           -o, --output <path>   Output path (instead of the last argument); - writes raw frames to stdout\n  \
+           --null-sink           Decode and transpose but discard the frames (no output path needed)\n  \
+           --raw-input           Read the input as headerless raw frames (.yuv, .rgb, Bayer dumps)\n  \
+           --input-pixfmt <fmt>  Pixel format of the raw input, e.g. yuv420p, rgb24 or bayer_rggb8\n  \
+           --input-size <WxH>    Frame size of the raw input\n  \
+           --input-fps <rate>    Frame rate of the raw input, e.g. 30000/1001 or 29.97 (default 25)\n  \
+           -f, --input-format <name> Read the input with this FFmpeg demuxer or device, e.g. v4l2 or lavfi\n  \
+           --io-buffer-size <s>  Read the input up to <s> ahead on a thread (e.g. 64M; 32M for URLs by default)\n  \
+           --input-retries <r>   Reconnect after URL input errors: N[:initial[:max]] seconds (default 3:1:30)\n  \
+           --on-size-change <a>  When the frame size changes mid-stream: abort (default) or scale to the first size\n  \
+           --fill-dropped-frames Repeat frames over timestamp gaps so each output column is one frame interval\n  \
+           --resample <mode>     Sample frames at uniform timestamp steps: nearest or blend (for VFR input)\n  \
+           --cache-cube <path>   Keep the decoded frames in <path> and reuse them on later runs of the same input\n  \
+           --checksum-verify <d> Check the input's SHA-256 first: sha256:<hex>, <hex> or sidecar (<input>.sha256)\n  \
+           --max-memory <size>   Abort if the frame store would exceed <size> (e.g. 8G, 512M)\n  \
+           --decode-threads <n>  Decode keyframe ranges of long file inputs in n threads (default 1)\n  \
+           --ivtc <mode>         Inverse telecine 3:2 pulldown: on, off or auto (warn if found, the default)\n  \
+           --frame-limit <n>     Abort if the input has more than n frames\n  \
+           --duration-limit <s>  Abort if the input is longer than s seconds\n  \
+           --windows <list>      Only transpose these time windows, side by side, e.g. 0-5s,30-35s,60-65s\n  \
+           --windows-layout <l>  stack (one output, the default) or separate (one output per window)\n  \
+           --scale <f>           Scale decoded frames by f (0 to 1) to shrink the frame store\n  \
+           --frame-step <n>      Keep every n-th decoded frame\n  \
+           --linear-light        Scale, blend and average in linear light, using the input's transfer function\n  \
+           --auto-fit            Pick --scale/--frame-step to fit --max-memory instead of aborting\n  \
+           --also-encode <spec>  Also write path[:scale=half|<f>|WxH][:crf=<n>] (repeatable)\n  \
+           --multi-track <t,..>  Write xt, yt and/or original as video streams of one .mkv\n  \
+           --exact               Fail rather than interpolate or subsample: lossless FFV1, point scaling\n  \
+           --validate            Decode each output once written and fail unless frames, size and duration match\n  \
+           --target <use>        Encoder settings for web, archive (lossless FFV1), analysis or editing\n  \
+           --pix-fmt <fmt>       Pixel format of color outputs: yuv420p (default), yuv422p, yuv444p or rgb\n  \
+           --chroma-loc <loc>    Site 4:2:0/4:2:2 chroma at left, center, topleft, top, bottomleft or bottom\n  \
+           --chroma-filter <f>   Chroma subsampling: fast (default), full (full-resolution chroma) or blur\n  \
+           --bframes <n>         Allow up to n consecutive B-frames in H.264 outputs (default 0)\n  \
+           --intra-only          Encode every frame as a keyframe for frame-accurate scrubbing\n  \
+           --lookahead <n>       x264 rate control lookahead in frames\n  \
+           --inflight-frames <n> Encode on a separate thread with at most n frames queued per output\n  \
+           --tune <name>         transpose (tuned for transposed frames) or an x264 tuning such as film or grain\n  \
+           --encoder <a,b,...>   Encoders to try for color outputs in order, e.g. h264_nvenc,libx264,mpeg4\n  \
+           --encode-stats <csv>  Print bitrate and frame size statistics, and write each frame's size to <csv>\n  \
+           --segment-time <s>    Segment length for .m3u8 (HLS) and .mpd (DASH) outputs (default 6)\n  \
+           --summary-json <path> Write a JSON summary of the run (timings, sizes)\n  \
+           --notify-url <url>    POST the JSON summary to <url> when the run ends\n  \
+           --notify-on <when>    When to notify: failure, success or always (default)\n  \
+           -y, --overwrite       Replace the output file if it already exists\n  \
+           -n, --no-clobber      Never replace an existing output file (the default)\n  \
+           --play                Open the output in mpv, ffplay or the default player when the run succeeds\n  \
+           --keep-cc             Carry the input's closed captions over (dropped by default)\n  \
+           --embed-mapping       Record each output frame's source column in SEI and a timecode track\n  \
+           --sidecar <path>      Write the frame mapping and run parameters as CSV (.csv) or JSON\n  \
+           --conform <path>      Write an edit list lining the original up with the output (.otio or .edl)\n  \
+           --index <where>       Index each output pixel's source frame and column: sidecar or embed (.mkv)\n  \
+           --frame-order <order> Order of output frames: natural, reverse, shuffle or file:<path>\n  \
+           --columns <a..b>      Only encode output frames for source columns a (inclusive) to b\n  \
+           --auto-roi <m>        motion[:n]: encode the n most active adjacent columns (default 1/4)\n  \
+           --autocrop            Crop black letterbox/pillarbox bars from the source before transposing\n  \
+           --audio-drive <m>     select[:f] keeps the loudest fraction f of frames, order sweeps by loudness\n  \
+           --column-step <n>     Only encode every n-th of the selected source columns (no blurring, unlike --scale)\n  \
+           --column-offset <k>   Start --column-step at the k-th selected column (default 0)\n  \
+           --mosaic <CxR>        Tile the transposes of a C×R grid of source regions into one output\n  \
+           --ops <a,b,...>       Rearrange axes: xt_transpose, yt_transpose, xy_transpose, reverse_time/x/y\n  \
+           --split-output <n>    Write at most n frames per file as <name>_partNNN with a manifest\n  \
+           --split-width <px>    Split outputs wider than px (default: the codec limit) as <name>_wNNN\n  \
+           --align-scenes        Start --split-width pieces on scene cuts where possible\n  \
+           --scene-threshold <t> Histogram change (0-1) that counts as a scene cut (default 0.4)\n  \
+           --split-channels <c>  Write rgb or yuv channels as separate grayscale _R/_G/_B or _Y/_U/_V files\n  \
+           --boomerang           Append the frames in reverse so the output loops back and forth\n  \
+           --interpolate <n>     Insert n blended frames between adjacent output frames\n  \
+           --temporal-smooth <k> Filter each pixel over 2k+1 frames before transposing\n  \
+           --smooth-filter <f>   Temporal filter: mean (default) or median\n  \
+           --scaler <filter>     Conversion and scaling filter: bilinear (default), bicubic, lanczos or point\n  \
+           --edge-fade <px>      Fade the left/right px columns to black instead of hard edges\n  \
+           --legend <seconds>    Start with a title card explaining the axis mapping and source\n  \
+           --canvas <WxH>        Fit the output frames to a canvas of this size, e.g. 1920x1080\n  \
+           --fit <mode>          Canvas fit: contain (default, black bars) or cover (crop)\n  \
+           --with-original <p>   Show the retimed source left of or above (top) the transposed frames\n  \
+           --watermark <png>     Composite a logo onto every output frame\n  \
+           --position <p>        Watermark position: tl, tr, bl, br (default) or center\n  \
+           --opacity <0-1>       Watermark opacity, times the image's own alpha (default 1)\n  \
+           --sonify <m>:<y>      Experimental: play row y of the frames as audio, as a wave or by synth\n  \
+           --heatmap <png>       Write a heatmap of per-pixel activity over time\n  \
+           --heatmap-stat <s>    Heatmap statistic: variance (default) or motion\n  \
+           --skip-static <v>     Drop output frames of source columns with mean temporal variance under v\n  \
+           --skip-static-log <p> Write the source columns --skip-static dropped as JSON\n  \
+           --seed <n>            Seed for --frame-order shuffle\n  \
+           --deterministic       Byte-identical output for identical input (single-threaded encode)\n  \
+           --preset <name>       Insert the options saved under name (options after it override them)\n  \
+           --save-preset <name>  Save this run's options, without input and output, under name\n  \
+           --no-<switch>         Turn off a switch set by VIDEO_TRANSPOSE_* or the default preset\n  \
+           --temp-dir <dir>      Directory for staging files (default: the system temp directory)\n  \
+           --keep-temp           Keep the run's temp files instead of deleting them\n  \
+           --upload-retries <r>  Retry failed cloud uploads: N[:initial[:max]] seconds (default 3:1:30)\n  \
+           --priority <p>        CPU priority: low (niceness +10, same as --nice) or normal (default)\n  \
+           --affinity <cpus>     Pin the run's threads to CPUs such as 0-7 or 0,2,4-6 (Linux)\n  \
+           --throttle <fps>      Process at most fps frames per second in each stage\n  \
+           --no-progress         Print plain status lines instead of progress bars\n  \
+           --no-color            Draw progress bars without ANSI colors (also set by NO_COLOR)\n  \
+           --progress-socket <p> Stream progress and preview JPEGs as JSON lines on Unix socket <p>",
+        program, program, program, program, program, program
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(layers: &[&str], args: &[&str]) -> Result<Options, String> {
+        let args: Vec<OsString> = ["video-transpose"]
+            .iter()
+            .chain(args)
+            .map(OsString::from)
+            .collect();
+        Options::parse_with(&args, layers.iter().map(OsString::from).collect())
+    }
+
+    #[test]
+    fn output_path_replaces_layered_output() {
+        let options = parse(&["--output=layered.mp4"], &["in.mp4", "out.mp4"]).unwrap();
+        assert_eq!(options.input, Path::new("in.mp4"));
+        assert_eq!(options.output, Path::new("out.mp4"));
+        let options = parse(&["--null-sink"], &["in.mp4", "out.mp4"]).unwrap();
+        assert!(!options.null_sink);
+        let options = parse(&["--output=layered.mp4"], &["in.mp4"]).unwrap();
+        assert_eq!(options.output, Path::new("layered.mp4"));
+    }
+
+    #[test]
+    fn switches_replace_their_layered_opposites() {
+        let options = parse(&["--overwrite"], &["-n", "in.mp4", "out.mp4"]).unwrap();
+        assert!(!options.overwrite);
+        let options = parse(&["--exact"], &["--no-exact", "in.mp4", "out.mp4"]).unwrap();
+        assert!(!options.exact);
+        assert!(parse(&[], &["-y", "-n", "in.mp4", "out.mp4"]).is_err());
+    }
+
+    #[test]
+    fn values_replace_layered_values() {
+        let options = parse(
+            &["--frame-step=3", "--also-encode=a.webm"],
+            &[
+                "--frame-step",
+                "2",
+                "--also-encode",
+                "b.webm",
+                "in.mp4",
+                "out.mp4",
+            ],
+        )
+        .unwrap();
+        assert_eq!(options.frame_step, 2);
+        assert_eq!(options.also_encode.len(), 1);
+    }
+
+    #[test]
+    fn unknown_layered_options_are_ignored() {
+        assert!(parse(&["--no-such-option=1"], &["in.mp4", "out.mp4"]).is_ok());
+        assert!(parse(&[], &["--no-such-option=1", "in.mp4", "out.mp4"]).is_err());
+    }
+}
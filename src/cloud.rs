@@ -0,0 +1,437 @@
+//! Uploading finished outputs to S3-compatible object storage.
+//!
+//! `s3://bucket/key` goes to AWS (or `AWS_ENDPOINT_URL`), `gs://bucket/key`
+//! to Google Cloud Storage through its S3-compatible XML API using HMAC
+//! keys. Credentials come from `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY`
+//! and optionally `AWS_SESSION_TOKEN`; the region from `AWS_REGION`.
+//! Requests are retried as `--upload-retries` says.
+
+use std::path::Path;
+
+/// A parsed `s3://` or `gs://` object URL.
+pub struct ObjectUrl {
+    pub scheme: String,
+    pub bucket: String,
+    pub key: String,
+}
+
+impl ObjectUrl {
+    /// Parses an output `path` given as an object URL, like [`parse`]; URLs
+    /// are always Unicode.
+    ///
+    /// [`parse`]: ObjectUrl::parse
+    pub fn from_path(path: &Path) -> Option<Result<ObjectUrl, String>> {
+        ObjectUrl::parse(path.to_str()?)
+    }
+
+    /// Parses `path` as an object URL, or returns `None` for local paths.
+    pub fn parse(path: &str) -> Option<Result<ObjectUrl, String>> {
+        let (scheme, rest) = path.split_once("://")?;
+        if scheme != "s3" && scheme != "gs" {
+            return None;
+        }
+        Some(match rest.split_once('/') {
+            Some((bucket, key)) if !bucket.is_empty() && !key.is_empty() => Ok(ObjectUrl {
+                scheme: scheme.to_string(),
+                bucket: bucket.to_string(),
+                key: key.to_string(),
+            }),
+            _ => Err(format!(
+                "Invalid object URL: {} (expected {}://bucket/key)",
+                path, scheme
+            )),
+        })
+    }
+
+    /// File name of the object, used for the local staging file.
+    pub fn file_name(&self) -> &str {
+        self.key.rsplit('/').next().unwrap_or(&self.key)
+    }
+}
+
+impl std::fmt::Display for ObjectUrl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}://{}/{}", self.scheme, self.bucket, self.key)
+    }
+}
+
+#[cfg(not(feature = "cloud"))]
+pub fn exists(url: &ObjectUrl) -> Result<bool, String> {
+    Err(format!(
+        "Cannot write to {}: video-transpose was built without the `cloud` feature",
+        url
+    ))
+}
+
+#[cfg(not(feature = "cloud"))]
+pub fn upload(
+    _local: &std::path::Path,
+    url: &ObjectUrl,
+    _retries: &crate::retry::RetryPolicy,
+) -> Result<(), String> {
+    exists(url).map(|_| ())
+}
+
+#[cfg(feature = "cloud")]
+pub use client::{exists, upload};
+
+#[cfg(feature = "cloud")]
+mod client {
+    use super::ObjectUrl;
+    use crate::retry::RetryPolicy;
+    use hmac::{Hmac, Mac};
+    use sha2::{Digest, Sha256};
+    use std::env;
+    use std::fs::File;
+    use std::io::Read;
+    use std::path::Path;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    /// Files larger than this are sent as a multipart upload in parts of this size.
+    const PART_SIZE: usize = 16 << 20;
+
+    struct Credentials {
+        access_key: String,
+        secret_key: String,
+        session_token: Option<String>,
+        region: String,
+        /// Custom endpoint, addressed path-style (`endpoint/bucket/key`).
+        endpoint: Option<String>,
+    }
+
+    impl Credentials {
+        fn from_env(url: &ObjectUrl) -> Result<Credentials, String> {
+            let var = |name: &str| env::var(name).ok().filter(|v| !v.is_empty());
+            let access_key = var("AWS_ACCESS_KEY_ID").ok_or("AWS_ACCESS_KEY_ID is not set")?;
+            let secret_key =
+                var("AWS_SECRET_ACCESS_KEY").ok_or("AWS_SECRET_ACCESS_KEY is not set")?;
+            let (region, endpoint) = if url.scheme == "gs" {
+                (
+                    "auto".to_string(),
+                    Some("https://storage.googleapis.com".to_string()),
+                )
+            } else {
+                (
+                    var("AWS_REGION")
+                        .or_else(|| var("AWS_DEFAULT_REGION"))
+                        .unwrap_or_else(|| "us-east-1".to_string()),
+                    var("AWS_ENDPOINT_URL"),
+                )
+            };
+            Ok(Credentials {
+                access_key,
+                secret_key,
+                session_token: var("AWS_SESSION_TOKEN"),
+                region,
+                endpoint,
+            })
+        }
+    }
+
+    struct Request<'a> {
+        method: &'a str,
+        query: Vec<(&'a str, String)>,
+        body: &'a [u8],
+    }
+
+    /// Checks whether the object already exists.
+    pub fn exists(url: &ObjectUrl) -> Result<bool, String> {
+        let credentials = Credentials::from_env(url)?;
+        let request = Request {
+            method: "HEAD",
+            query: Vec::new(),
+            body: &[],
+        };
+        match send(&credentials, url, &request).map_err(|e| *e) {
+            Ok(_) => Ok(true),
+            Err(ureq::Error::Status(404, _)) => Ok(false),
+            Err(e) => Err(format!("Cannot check {}: {}", url, e)),
+        }
+    }
+
+    /// Uploads `local` to `url`, in parts for large files, retrying each
+    /// request as `retries` says.
+    pub fn upload(local: &Path, url: &ObjectUrl, retries: &RetryPolicy) -> Result<(), String> {
+        let credentials = Credentials::from_env(url)?;
+        let mut file = File::open(local).map_err(|e| e.to_string())?;
+        let size = file.metadata().map_err(|e| e.to_string())?.len() as usize;
+
+        if size <= PART_SIZE {
+            let mut body = Vec::with_capacity(size);
+            file.read_to_end(&mut body).map_err(|e| e.to_string())?;
+            let request = Request {
+                method: "PUT",
+                query: Vec::new(),
+                body: &body,
+            };
+            with_retries(url, retries, || send(&credentials, url, &request))?;
+            return Ok(());
+        }
+
+        let create = Request {
+            method: "POST",
+            query: vec![("uploads", String::new())],
+            body: &[],
+        };
+        let response = with_retries(url, retries, || send(&credentials, url, &create))?;
+        let body = response.into_string().map_err(|e| e.to_string())?;
+        let upload_id = xml_value(&body, "UploadId")
+            .ok_or_else(|| format!("No UploadId in response from {}", url))?;
+
+        let result = upload_parts(&credentials, url, retries, &mut file, &upload_id);
+        if result.is_err() {
+            let abort = Request {
+                method: "DELETE",
+                query: vec![("uploadId", upload_id.clone())],
+                body: &[],
+            };
+            let _ = send(&credentials, url, &abort);
+        }
+        result
+    }
+
+    fn upload_parts(
+        credentials: &Credentials,
+        url: &ObjectUrl,
+        retries: &RetryPolicy,
+        file: &mut File,
+        upload_id: &str,
+    ) -> Result<(), String> {
+        let mut etags = Vec::new();
+        let mut buffer = vec![0u8; PART_SIZE];
+        loop {
+            let mut filled = 0;
+            while filled < PART_SIZE {
+                match file
+                    .read(&mut buffer[filled..])
+                    .map_err(|e| e.to_string())?
+                {
+                    0 => break,
+                    n => filled += n,
+                }
+            }
+            if filled == 0 {
+                break;
+            }
+
+            let part_number = etags.len() + 1;
+            let request = Request {
+                method: "PUT",
+                query: vec![
+                    ("partNumber", part_number.to_string()),
+                    ("uploadId", upload_id.to_string()),
+                ],
+                body: &buffer[..filled],
+            };
+            let response = with_retries(url, retries, || send(credentials, url, &request))?;
+            let etag = response
+                .header("ETag")
+                .ok_or_else(|| format!("No ETag for part {} of {}", part_number, url))?
+                .to_string();
+            etags.push(etag);
+        }
+
+        let mut complete = String::from("<CompleteMultipartUpload>");
+        for (index, etag) in etags.iter().enumerate() {
+            complete.push_str(&format!(
+                "<Part><PartNumber>{}</PartNumber><ETag>{}</ETag></Part>",
+                index + 1,
+                etag
+            ));
+        }
+        complete.push_str("</CompleteMultipartUpload>");
+        let request = Request {
+            method: "POST",
+            query: vec![("uploadId", upload_id.to_string())],
+            body: complete.as_bytes(),
+        };
+        let response = with_retries(url, retries, || send(credentials, url, &request))?;
+
+        // Completion can fail with a 200 response carrying an error document
+        let body = response.into_string().map_err(|e| e.to_string())?;
+        if body.contains("<Error>") {
+            return Err(format!("Completing upload to {} failed: {}", url, body));
+        }
+        Ok(())
+    }
+
+    /// Sends a request with `attempt`, retrying server and connection
+    /// errors; client errors (4xx) won't go away by themselves.
+    fn with_retries<T>(
+        url: &ObjectUrl,
+        retries: &RetryPolicy,
+        attempt: impl FnMut() -> Result<T, Box<ureq::Error>>,
+    ) -> Result<T, String> {
+        let retryable = |e: &Box<_>| !matches!(**e, ureq::Error::Status(code, _) if code < 500);
+        match retries.run("Upload", attempt, retryable).map_err(|e| *e) {
+            Ok(value) => Ok(value),
+            Err(ureq::Error::Status(code, response)) if code < 500 => {
+                let body = response.into_string().unwrap_or_default();
+                Err(format!("Upload to {} failed ({}): {}", url, code, body))
+            }
+            Err(e) => Err(format!("Upload to {} failed: {}", url, e)),
+        }
+    }
+
+    /// Sends a SigV4-signed request for the object.
+    fn send(
+        credentials: &Credentials,
+        url: &ObjectUrl,
+        request: &Request,
+    ) -> Result<ureq::Response, Box<ureq::Error>> {
+        let key_path: String = url
+            .key
+            .split('/')
+            .map(uri_encode)
+            .collect::<Vec<_>>()
+            .join("/");
+        let (base, host, path) = match &credentials.endpoint {
+            Some(endpoint) => {
+                let host = endpoint.split("://").nth(1).unwrap_or(endpoint);
+                (
+                    endpoint.trim_end_matches('/').to_string(),
+                    host.trim_end_matches('/').to_string(),
+                    format!("/{}/{}", uri_encode(&url.bucket), key_path),
+                )
+            }
+            None => {
+                let host = format!("{}.s3.{}.amazonaws.com", url.bucket, credentials.region);
+                (format!("https://{}", host), host, format!("/{}", key_path))
+            }
+        };
+
+        let mut query: Vec<(String, String)> = request
+            .query
+            .iter()
+            .map(|(k, v)| (uri_encode(k), uri_encode(v)))
+            .collect();
+        query.sort();
+        let query = query
+            .iter()
+            .map(|(k, v)| format!("{}={}", k, v))
+            .collect::<Vec<_>>()
+            .join("&");
+
+        let (date, time) = utc_timestamp();
+        let amz_date = format!("{}T{}Z", date, time);
+        let payload_hash = hex(&Sha256::digest(request.body));
+
+        let mut headers = vec![
+            ("host", host),
+            ("x-amz-content-sha256", payload_hash.clone()),
+            ("x-amz-date", amz_date.clone()),
+        ];
+        if let Some(token) = &credentials.session_token {
+            headers.push(("x-amz-security-token", token.clone()));
+        }
+        let signed_headers = headers
+            .iter()
+            .map(|(name, _)| *name)
+            .collect::<Vec<_>>()
+            .join(";");
+        let canonical_headers: String = headers
+            .iter()
+            .map(|(name, value)| format!("{}:{}\n", name, value.trim()))
+            .collect();
+
+        let canonical_request = format!(
+            "{}\n{}\n{}\n{}\n{}\n{}",
+            request.method, path, query, canonical_headers, signed_headers, payload_hash
+        );
+        let scope = format!("{}/{}/s3/aws4_request", date, credentials.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date,
+            scope,
+            hex(&Sha256::digest(canonical_request.as_bytes()))
+        );
+
+        let mut key = hmac(
+            format!("AWS4{}", credentials.secret_key).as_bytes(),
+            date.as_bytes(),
+        );
+        for part in [credentials.region.as_str(), "s3", "aws4_request"] {
+            key = hmac(&key, part.as_bytes());
+        }
+        let signature = hex(&hmac(&key, string_to_sign.as_bytes()));
+        let authorization = format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            credentials.access_key, scope, signed_headers, signature
+        );
+
+        let target = if query.is_empty() {
+            format!("{}{}", base, path)
+        } else {
+            format!("{}{}?{}", base, path, query)
+        };
+        let mut call = ureq::request(request.method, &target).set("Authorization", &authorization);
+        for (name, value) in &headers {
+            if *name != "host" {
+                call = call.set(name, value);
+            }
+        }
+        call.send_bytes(request.body).map_err(Box::new)
+    }
+
+    fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key length");
+        mac.update(data);
+        mac.finalize().into_bytes().to_vec()
+    }
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    /// Percent-encodes everything except unreserved characters (RFC 3986).
+    fn uri_encode(text: &str) -> String {
+        let mut out = String::new();
+        for byte in text.bytes() {
+            match byte {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                    out.push(byte as char)
+                }
+                _ => out.push_str(&format!("%{:02X}", byte)),
+            }
+        }
+        out
+    }
+
+    fn xml_value(xml: &str, tag: &str) -> Option<String> {
+        let open = format!("<{}>", tag);
+        let start = xml.find(&open)? + open.len();
+        let end = xml[start..].find(&format!("</{}>", tag))? + start;
+        Some(xml[start..end].to_string())
+    }
+
+    /// Current UTC date (`YYYYMMDD`) and time (`HHMMSS`).
+    fn utc_timestamp() -> (String, String) {
+        let seconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let days = (seconds / 86_400) as i64;
+        let of_day = seconds % 86_400;
+
+        // Days since the epoch to a civil date (Howard Hinnant's algorithm)
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let doe = z.rem_euclid(146_097);
+        let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + i64::from(month <= 2);
+
+        (
+            format!("{:04}{:02}{:02}", year, month, day),
+            format!(
+                "{:02}{:02}{:02}",
+                of_day / 3600,
+                of_day % 3600 / 60,
+                of_day % 60
+            ),
+        )
+    }
+}
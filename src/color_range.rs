@@ -0,0 +1,89 @@
+//! Full ("JPEG") range YUV. MJPEG decoders and many phone recordings still
+//! report the deprecated `yuvj*` pixel formats, which swscale warns about
+//! and which don't say the range anywhere else. Frames in those formats are
+//! relabelled as their regular twin with the range set on the frame, and
+//! the scalers are told the range explicitly instead of guessing it from
+//! the format.
+
+use ffmpeg_next as ffmpeg;
+use ffmpeg_next::format::Pixel;
+use ffmpeg_next::software::scaling::context::Context;
+use ffmpeg_next::util::color::Range;
+use ffmpeg_next::util::frame::video::Video;
+use std::os::raw::c_int;
+use std::ptr;
+
+/// The regular pixel format for a deprecated full range one, and whether
+/// `format` is full range by definition.
+pub fn normalize(format: Pixel) -> (Pixel, bool) {
+    match format {
+        Pixel::YUVJ420P => (Pixel::YUV420P, true),
+        Pixel::YUVJ422P => (Pixel::YUV422P, true),
+        Pixel::YUVJ444P => (Pixel::YUV444P, true),
+        Pixel::YUVJ440P => (Pixel::YUV440P, true),
+        Pixel::YUVJ411P => (Pixel::YUV411P, true),
+        _ => (format, false),
+    }
+}
+
+/// Relabels a frame in a `yuvj*` format as its regular format with full
+/// range. The samples are the same, only the labelling changes.
+pub fn normalize_frame(frame: &mut Video) {
+    let (format, full_range) = normalize(frame.format());
+    if full_range {
+        frame.set_format(format);
+        frame.set_color_range(Range::JPEG);
+    }
+}
+
+/// Whether frames labelled `range` (or a `yuvj*` format) are full range.
+pub fn is_full(format: Pixel, range: Range) -> bool {
+    normalize(format).1 || range == Range::JPEG
+}
+
+/// Sets the YUV ranges `scaler` converts from and to, keeping its
+/// colorspace coefficients. RGB and grayscale sides keep the full range
+/// swscale gives them.
+pub fn set_ranges(scaler: &mut Context, input_full: bool, output_full: bool) {
+    let input_full = input_full || !is_yuv(scaler.input().format);
+    let output_full = output_full || !is_yuv(scaler.output().format);
+    unsafe {
+        let context = scaler.as_mut_ptr();
+        let mut inv_table = ptr::null_mut();
+        let mut table = ptr::null_mut();
+        let (mut input_range, mut output_range) = (0, 0);
+        let (mut brightness, mut contrast, mut saturation) = (0, 0, 0);
+        let read = ffmpeg::ffi::sws_getColorspaceDetails(
+            context,
+            &mut inv_table,
+            &mut input_range,
+            &mut table,
+            &mut output_range,
+            &mut brightness,
+            &mut contrast,
+            &mut saturation,
+        );
+        if read < 0 {
+            return;
+        }
+        ffmpeg::ffi::sws_setColorspaceDetails(
+            context,
+            inv_table,
+            c_int::from(input_full),
+            table,
+            c_int::from(output_full),
+            brightness,
+            contrast,
+            saturation,
+        );
+    }
+}
+
+fn is_yuv(format: Pixel) -> bool {
+    unsafe {
+        let descriptor = ffmpeg::ffi::av_pix_fmt_desc_get(format.into());
+        !descriptor.is_null()
+            && (*descriptor).nb_components >= 3
+            && (*descriptor).flags & ffmpeg::ffi::AV_PIX_FMT_FLAG_RGB as u64 == 0
+    }
+}
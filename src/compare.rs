@@ -0,0 +1,197 @@
+//! `video-transpose compare-stack a.mp4 b.mp4 out.mp4`: transposes two
+//! inputs and stacks the results, `a` on top of `b`, each under a label
+//! with its file name, for comparing camera settings or processing
+//! side by side. Both inputs have to share their frame size and rate; the
+//! transposes run together and their frames are stacked as they come.
+
+use crate::cli::{CompareOptions, Options};
+use crate::encode::{EncodeTarget, VideoWriter};
+use crate::frame_store::FrameFormat;
+use crate::legend;
+use crate::output_file::PartialOutput;
+use crate::pipeline::run_with_callback;
+use crate::sink::{FrameSink, OutputFrame};
+use crate::source;
+use crate::stats::{format_rate, RunStats};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+/// Height of the label band above each transpose.
+const LABEL_HEIGHT: usize = 36;
+/// Why a transpose stopped when the stacking did.
+const STOPPED: &str = "compare-stack stopped";
+
+/// A transposed frame of one input, as handed to the callback.
+struct Transposed {
+    width: usize,
+    height: usize,
+    format: FrameFormat,
+    data: Vec<u8>,
+}
+
+pub fn run(options: &CompareOptions) -> Result<(), Box<dyn std::error::Error>> {
+    let [top, bottom] = &options.inputs;
+    let file = PartialOutput::new(&options.output)?;
+    if file.exists()? && !top.overwrite {
+        return Err(format!(
+            "Output file {} already exists (use -y/--overwrite to replace it)",
+            options.output.display()
+        )
+        .into());
+    }
+
+    // Rows and columns only line up between inputs of the same geometry
+    let (_, top_info) = source::open(top)?;
+    let (_, bottom_info) = source::open(bottom)?;
+    let geometry = |info: &source::InputInfo| {
+        format!(
+            "{}×{} at {} fps",
+            info.width,
+            info.height,
+            format_rate(info.fps)
+        )
+    };
+    if (top_info.width, top_info.height, top_info.fps)
+        != (bottom_info.width, bottom_info.height, bottom_info.fps)
+    {
+        return Err(format!(
+            "compare-stack needs inputs of the same frame size and rate: {} is {}, {} is {}",
+            top.input.display(),
+            geometry(&top_info),
+            bottom.input.display(),
+            geometry(&bottom_info)
+        )
+        .into());
+    }
+    status!(
+        "Comparing {} (top) with {} (bottom), {}",
+        top.input.display(),
+        bottom.input.display(),
+        geometry(&top_info)
+    );
+
+    thread::scope(|scope| -> Result<(), Box<dyn std::error::Error>> {
+        let mut receivers = Vec::new();
+        let mut runs = Vec::new();
+        for input in &options.inputs {
+            let (sender, receiver) = mpsc::sync_channel::<Transposed>(2);
+            receivers.push(receiver);
+            runs.push(scope.spawn(move || {
+                let mut stats = RunStats::default();
+                run_with_callback(input, &mut stats, &mut |frame| {
+                    let transposed = Transposed {
+                        width: frame.width,
+                        height: frame.height,
+                        format: frame.format,
+                        data: frame.data.to_vec(),
+                    };
+                    sender.send(transposed).map_err(|_| STOPPED.into())
+                })
+                .map(|_| ())
+                .map_err(|error| error.to_string())
+            }));
+        }
+
+        let stacked = stack_frames(&receivers, &file, &options.inputs, &top_info);
+        // Unblocks a transpose still sending when stacking stopped early
+        drop(receivers);
+        let mut results = Vec::new();
+        for (run, input) in runs.into_iter().zip(&options.inputs) {
+            let result = run.join().map_err(|_| "Transpose thread panicked")?;
+            results.push(result.map_err(|error| (input.input.display().to_string(), error)));
+        }
+        stacked?;
+        // A transpose that was cut off only reports why the other stopped
+        let failed = results.iter().filter_map(|result| result.as_ref().err());
+        match failed.clone().find(|(_, error)| error != STOPPED) {
+            Some((input, error)) => Err(format!("{}: {}", input, error).into()),
+            None if failed.count() > 0 => {
+                Err("The inputs transposed to different numbers of frames".into())
+            }
+            None => Ok(()),
+        }
+    })?;
+
+    file.commit()?;
+    status!("\n✓ Comparison saved to: {}", options.output.display());
+    Ok(())
+}
+
+/// Stacks the frames arriving from both transposes and encodes them into
+/// `file`, with the encoder settings of the top input.
+fn stack_frames(
+    receivers: &[Receiver<Transposed>],
+    file: &PartialOutput,
+    inputs: &[Options; 2],
+    info: &source::InputInfo,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut writer: Option<VideoWriter> = None;
+    let mut label_bands = Vec::new();
+    // Ends when both are done, or one failed and its error comes from the join
+    while let (Ok(top), Ok(bottom)) = (receivers[0].recv(), receivers[1].recv()) {
+        if top.format != bottom.format || top.height != bottom.height {
+            return Err("compare-stack inputs were transposed to different formats".into());
+        }
+        let (format, height) = (top.format, top.height);
+        let width = top.width.max(bottom.width);
+        let stacked_height = 2 * (LABEL_HEIGHT + height);
+
+        let writer = match &mut writer {
+            Some(writer) => writer,
+            None => {
+                let target = EncodeTarget {
+                    width,
+                    height: stacked_height,
+                    crf: None,
+                    channel: None,
+                    full_range: info.full_range,
+                    sequence: None,
+                    index: None,
+                    audio: false,
+                };
+                label_bands = inputs
+                    .iter()
+                    .zip(["A", "B"])
+                    .map(|(input, letter)| {
+                        let name = input.input.file_name().unwrap_or_default();
+                        let label = format!("{}: {}", letter, name.to_string_lossy());
+                        legend::render(&[label], width, LABEL_HEIGHT, format)
+                    })
+                    .collect();
+                writer.insert(VideoWriter::open(
+                    file,
+                    width,
+                    stacked_height,
+                    format,
+                    &target,
+                    info.fps,
+                    &inputs[0],
+                )?)
+            }
+        };
+
+        let bpp = format.bytes_per_pixel();
+        let mut data = Vec::with_capacity(width * stacked_height * bpp);
+        for (band, half) in label_bands.iter().zip([&top, &bottom]) {
+            data.extend_from_slice(band);
+            // The shorter input's frames are padded with black on the right
+            for row in half.data.chunks_exact(half.width * bpp) {
+                data.extend_from_slice(row);
+                data.resize(data.len() + (width - half.width) * bpp, 0);
+            }
+        }
+        writer.write(&OutputFrame {
+            width,
+            height: stacked_height,
+            format,
+            data: &data,
+            mapping: "",
+            captions: None,
+            audio: None,
+        })?;
+    }
+    match writer {
+        Some(mut writer) => writer.finish(),
+        None => Err("The inputs gave no frames to compare".into()),
+    }
+}
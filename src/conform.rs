@@ -0,0 +1,269 @@
+//! `--conform <path>`: an edit list lining the original up with the output,
+//! so an editor can conform the transposed media against it in an NLE. The
+//! transpose has no frame-for-frame counterpart in the source (every output
+//! frame spans the whole capture), so the original is retimed the way
+//! `--with-original` shows it: its time range stretched or squeezed over
+//! the transposed frames, after any `--legend` card.
+//!
+//! - `.otio` writes an OpenTimelineIO timeline: the original on the lower
+//!   video track with a linear time warp, the output files on the upper
+//!   one, and the source column of every output frame in its metadata.
+//! - `.edl` writes a CMX 3600 EDL of the original on the output's record
+//!   timeline, with an M2 speed change. EDLs hold a single track, so the
+//!   output itself goes on the track above it from `00:00:00:00`.
+
+use serde_json::{json, Value};
+use std::path::Path;
+
+/// Kind of edit list, by the file extension.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Otio,
+    Edl,
+}
+
+impl Format {
+    pub fn of(path: &Path) -> Result<Format, String> {
+        let extension = path.extension().and_then(|extension| extension.to_str());
+        match extension.map(str::to_ascii_lowercase).as_deref() {
+            Some("otio") => Ok(Format::Otio),
+            Some("edl") => Ok(Format::Edl),
+            _ => Err(format!(
+                "Invalid --conform: {} (expected a .otio or .edl path)",
+                path.display()
+            )),
+        }
+    }
+}
+
+/// The run's timing, from source frames to output files.
+pub struct Conform<'a> {
+    pub input: &'a str,
+    /// Output files in order and the frames each holds.
+    pub parts: &'a [(String, usize)],
+    pub fps: (i32, i32),
+    /// `--frame-step`: source frames per decoded frame kept.
+    pub frame_step: usize,
+    /// Output frame index → source column.
+    pub columns: &'a [usize],
+    /// Decoded frame index → seconds from the stream start.
+    pub times: &'a [Option<f64>],
+    /// `--legend` frames before the first transposed frame.
+    pub legend_frames: usize,
+}
+
+impl Conform<'_> {
+    /// The edit list in `format`.
+    pub fn render(&self, format: Format) -> serde_json::Result<String> {
+        match format {
+            Format::Otio => serde_json::to_string_pretty(&self.to_otio()),
+            Format::Edl => Ok(self.to_edl()),
+        }
+    }
+
+    fn rate(&self) -> f64 {
+        f64::from(self.fps.0) / f64::from(self.fps.1.max(1))
+    }
+
+    /// First source frame of the capture and how many source frames it
+    /// spans, at the input's frame rate.
+    fn source_range(&self) -> (usize, usize) {
+        let rate = self.rate();
+        let first = self.times.first().copied().flatten();
+        let last = self.times.last().copied().flatten();
+        match (first, last) {
+            (Some(first), Some(last)) => (
+                (first * rate).round() as usize,
+                ((last - first) * rate).round() as usize + self.frame_step,
+            ),
+            _ => (0, self.times.len() * self.frame_step),
+        }
+    }
+
+    /// Transposed frames the original is retimed over.
+    fn retimed_frames(&self) -> usize {
+        self.columns.len().max(1)
+    }
+
+    pub fn to_otio(&self) -> Value {
+        let rate = self.rate();
+        let (source_start, source_frames) = self.source_range();
+        let output_frames: usize = self.parts.iter().map(|(_, frames)| frames).sum();
+
+        let mut original = Vec::new();
+        if self.legend_frames > 0 {
+            original.push(json!({
+                "OTIO_SCHEMA": "Gap.1",
+                "name": "legend",
+                "source_range": range(0, self.legend_frames, rate),
+                "effects": [],
+                "markers": [],
+                "metadata": {},
+            }));
+        }
+        original.push(json!({
+            "OTIO_SCHEMA": "Clip.1",
+            "name": file_name(self.input),
+            "source_range": range(source_start, self.retimed_frames(), rate),
+            "media_reference": reference(self.input, range(source_start, source_frames, rate)),
+            "effects": [{
+                "OTIO_SCHEMA": "LinearTimeWarp.1",
+                "name": "retime",
+                "effect_name": "LinearTimeWarp",
+                "time_scalar": source_frames as f64 / self.retimed_frames() as f64,
+                "metadata": {},
+            }],
+            "markers": [],
+            "metadata": {},
+        }));
+        let transposed: Vec<Value> = self
+            .parts
+            .iter()
+            .map(|(path, frames)| {
+                json!({
+                    "OTIO_SCHEMA": "Clip.1",
+                    "name": file_name(path),
+                    "source_range": range(0, *frames, rate),
+                    "media_reference": reference(path, range(0, *frames, rate)),
+                    "effects": [],
+                    "markers": [],
+                    "metadata": {},
+                })
+            })
+            .collect();
+        let track = |name: &str, children: Vec<Value>| {
+            json!({
+                "OTIO_SCHEMA": "Track.1",
+                "name": name,
+                "kind": "Video",
+                "source_range": null,
+                "children": children,
+                "effects": [],
+                "markers": [],
+                "metadata": {},
+            })
+        };
+
+        json!({
+            "OTIO_SCHEMA": "Timeline.1",
+            "name": file_name(self.parts.first().map_or(self.input, |(path, _)| path)),
+            "global_start_time": time(0, rate),
+            "tracks": {
+                "OTIO_SCHEMA": "Stack.1",
+                "name": "tracks",
+                "source_range": null,
+                "children": [track("Original", original), track("Transposed", transposed)],
+                "effects": [],
+                "markers": [],
+                "metadata": {},
+            },
+            "metadata": {
+                "video_transpose": {
+                    "tool": concat!("video-transpose ", env!("CARGO_PKG_VERSION")),
+                    "axis_mapping": {
+                        "output_frame": "source_x",
+                        "output_x": "source_t",
+                        "output_y": "source_y",
+                    },
+                    "output_frames": output_frames,
+                    "legend_frames": self.legend_frames,
+                    "frame_step": self.frame_step,
+                    "source_first_frame": source_start,
+                    "source_frames": source_frames,
+                    "source_columns": self.columns,
+                    "source_times": self.times,
+                },
+            },
+        })
+    }
+
+    pub fn to_edl(&self) -> String {
+        // Timecode counts whole frames per second; 29.97 runs as 30
+        // non-drop, like most conform EDLs
+        let base = (self.rate().round() as usize).max(1);
+        let timecode = |frames: usize| {
+            format!(
+                "{:02}:{:02}:{:02}:{:02}",
+                frames / base / 3600,
+                frames / base / 60 % 60,
+                frames / base % 60,
+                frames % base
+            )
+        };
+        let (source_start, source_frames) = self.source_range();
+        let record_start = self.legend_frames;
+        let record_end = record_start + self.retimed_frames();
+        let speed = self.rate() * source_frames as f64 / self.retimed_frames() as f64;
+        let title = file_name(self.parts.first().map_or(self.input, |(path, _)| path));
+
+        let mut edl = format!("TITLE: {}\nFCM: NON-DROP FRAME\n\n", title);
+        let mut event = 1;
+        if self.legend_frames > 0 {
+            edl.push_str(&format!(
+                "{:03}  {:<9}V     C        {} {} {} {}\n",
+                event,
+                "BL",
+                timecode(0),
+                timecode(self.legend_frames),
+                timecode(0),
+                timecode(record_start)
+            ));
+            edl.push_str("* COMMENT: --legend title card\n\n");
+            event += 1;
+        }
+        edl.push_str(&format!(
+            "{:03}  {:<9}V     C        {} {} {} {}\n",
+            event,
+            "AX",
+            timecode(source_start),
+            timecode(source_start + source_frames),
+            timecode(record_start),
+            timecode(record_end)
+        ));
+        edl.push_str(&format!(
+            "M2   {:<9}{:05.1}{:>27}\n",
+            "AX",
+            speed,
+            timecode(source_start)
+        ));
+        edl.push_str(&format!("* FROM CLIP NAME: {}\n", file_name(self.input)));
+        edl.push_str(&format!("* SOURCE FILE: {}\n", self.input));
+        let mut record = 0;
+        for (path, frames) in self.parts {
+            edl.push_str(&format!(
+                "* COMMENT: TRANSPOSED {} AT {}\n",
+                file_name(path),
+                timecode(record)
+            ));
+            record += frames;
+        }
+        edl
+    }
+}
+
+/// The file name at the end of `path`, which may be an object URL.
+fn file_name(path: &str) -> &str {
+    &path[path.rfind('/').map_or(0, |slash| slash + 1)..]
+}
+
+fn time(frames: usize, rate: f64) -> Value {
+    json!({"OTIO_SCHEMA": "RationalTime.1", "rate": rate, "value": frames as f64})
+}
+
+fn range(start: usize, duration: usize, rate: f64) -> Value {
+    json!({
+        "OTIO_SCHEMA": "TimeRange.1",
+        "start_time": time(start, rate),
+        "duration": time(duration, rate),
+    })
+}
+
+fn reference(path: &str, available: Value) -> Value {
+    json!({
+        "OTIO_SCHEMA": "ExternalReference.1",
+        "name": "",
+        "target_url": path,
+        "available_range": available,
+        "metadata": {},
+    })
+}
@@ -0,0 +1,193 @@
+//! What output containers need beyond the defaults. MP4, MOV, Matroska and
+//! NUT hold the H.264 and FFV1 streams as they are. MXF (OP1a), which
+//! broadcast and archive ingest often insists on, can't carry those
+//! reliably, so MXF outputs are encoded as MPEG-2 4:2:2, as XDCAM HD422
+//! does, and are checked up front against what that pair allows.
+
+use crate::cli::Options;
+use crate::pix_fmt::PixFmt;
+use ffmpeg_next as ffmpeg;
+use ffmpeg_next::format::Pixel;
+use std::path::Path;
+
+/// Largest MPEG-2 width or height.
+pub const MPEG2_MAX_DIMENSION: usize = 16383;
+
+/// Frame rates MPEG-2 can signal; MXF describes all of them.
+const MPEG2_FRAME_RATES: [(i32, i32); 8] = [
+    (24000, 1001),
+    (24, 1),
+    (25, 1),
+    (30000, 1001),
+    (30, 1),
+    (50, 1),
+    (60000, 1001),
+    (60, 1),
+];
+
+/// MPEG-2 quantizer for MXF outputs, near transparent.
+const MPEG2_QSCALE: i32 = 2;
+
+/// Whether `muxer` writes MXF OP1a, encoded as MPEG-2.
+pub fn is_mxf(muxer: &str) -> bool {
+    muxer == "mxf"
+}
+
+/// Checks that an output written by `muxer` can take `options` and frames
+/// `height` pixels high at `fps`.
+pub fn check(
+    path: &Path,
+    muxer: Option<&str>,
+    options: &Options,
+    fps: ffmpeg::Rational,
+    height: usize,
+) -> Result<(), String> {
+    if !muxer.is_some_and(is_mxf) {
+        return Ok(());
+    }
+    let fps = fps.reduce();
+    let supported = MPEG2_FRAME_RATES
+        .iter()
+        .any(|&(numerator, denominator)| fps == ffmpeg::Rational(numerator, denominator));
+    if !supported {
+        return Err(format!(
+            "{} is MXF, written as MPEG-2, which only takes 23.976, 24, 25, 29.97, 30, 50, 59.94 \
+             or 60 fps (the input is {}/{})",
+            path.display(),
+            fps.numerator(),
+            fps.denominator()
+        ));
+    }
+    if height > MPEG2_MAX_DIMENSION {
+        return Err(format!(
+            "{} is MXF, written as MPEG-2, which is at most {} pixels high (the output is {})",
+            path.display(),
+            MPEG2_MAX_DIMENSION,
+            height
+        ));
+    }
+    if let Some(pix_fmt) = options
+        .pix_fmt
+        .filter(|&pix_fmt| !matches!(pix_fmt, PixFmt::Yuv420 | PixFmt::Yuv422))
+    {
+        return Err(format!(
+            "{} is MXF, written as MPEG-2, which can't take --pix-fmt {} (only yuv420p or \
+             yuv422p)",
+            path.display(),
+            pix_fmt.name()
+        ));
+    }
+    if options.sonify.is_some() {
+        return Err(format!(
+            "{} is MXF, which can't take the AAC track of --sonify",
+            path.display()
+        ));
+    }
+    if options.tune.is_some() || options.lookahead.is_some() {
+        status!(
+            "  Warning: --tune and --lookahead are x264 settings, ignored for {}",
+            path.display()
+        );
+    }
+    if options.embed_mapping {
+        status!(
+            "  Warning: {} gets no per-frame mapping, only H.264 outputs carry it",
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+/// Pixel format MXF outputs are encoded in: 4:2:2 unless `--pix-fmt`
+/// asks for 4:2:0.
+pub fn mxf_pixel(options: &Options) -> Pixel {
+    match options.pix_fmt {
+        Some(PixFmt::Yuv420) => Pixel::YUV420P,
+        _ => Pixel::YUV422P,
+    }
+}
+
+/// Constant quality for MPEG-2, as a `global_quality` lambda; the encoder
+/// also needs the `QSCALE` flag to use it.
+pub fn mpeg2_quality() -> i32 {
+    MPEG2_QSCALE * ffmpeg::ffi::FF_QP2LAMBDA
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::OsString;
+
+    fn options(extra: &[&str]) -> Options {
+        let args: Vec<OsString> = ["video-transpose", "in.mp4", "out.mxf"]
+            .iter()
+            .chain(extra)
+            .map(OsString::from)
+            .collect();
+        Options::parse(&args).unwrap()
+    }
+
+    fn check_mxf(options: &Options, fps: (i32, i32), height: usize) -> Result<(), String> {
+        let fps = ffmpeg::Rational(fps.0, fps.1);
+        check(Path::new("out.mxf"), Some("mxf"), options, fps, height)
+    }
+
+    #[test]
+    fn mxf_takes_mpeg2_frame_rates() {
+        let options = options(&[]);
+        for (numerator, denominator) in MPEG2_FRAME_RATES {
+            assert_eq!(check_mxf(&options, (numerator, denominator), 1080), Ok(()));
+        }
+        // Unreduced rates are the same rate
+        assert_eq!(check_mxf(&options, (50, 2), 1080), Ok(()));
+    }
+
+    #[test]
+    fn mxf_rejects_other_frame_rates() {
+        let options = options(&[]);
+        for fps in [(15, 1), (48, 1), (120, 1), (12000, 1001)] {
+            let error = check_mxf(&options, fps, 1080).unwrap_err();
+            assert!(error.contains("fps"), "{}", error);
+        }
+    }
+
+    #[test]
+    fn mxf_rejects_tall_frames() {
+        let options = options(&[]);
+        assert_eq!(check_mxf(&options, (25, 1), MPEG2_MAX_DIMENSION), Ok(()));
+        let error = check_mxf(&options, (25, 1), MPEG2_MAX_DIMENSION + 1).unwrap_err();
+        assert!(error.contains("pixels high"), "{}", error);
+    }
+
+    #[test]
+    fn mxf_takes_only_4_2_x_pix_fmts() {
+        for pix_fmt in ["yuv420p", "yuv422p"] {
+            let options = options(&["--pix-fmt", pix_fmt]);
+            assert_eq!(check_mxf(&options, (25, 1), 1080), Ok(()));
+        }
+        for pix_fmt in ["yuv444p", "rgb"] {
+            let options = options(&["--pix-fmt", pix_fmt]);
+            let error = check_mxf(&options, (25, 1), 1080).unwrap_err();
+            assert!(error.contains("--pix-fmt"), "{}", error);
+        }
+    }
+
+    #[test]
+    fn mxf_rejects_sonify() {
+        let options = options(&["--sonify", "wave:10"]);
+        let error = check_mxf(&options, (25, 1), 1080).unwrap_err();
+        assert!(error.contains("--sonify"), "{}", error);
+    }
+
+    #[test]
+    fn other_containers_pass_through() {
+        // What MXF refuses is fine where H.264 and FFV1 are stored as they are
+        let options = options(&["--pix-fmt", "yuv444p", "--sonify", "wave:10"]);
+        let fps = ffmpeg::Rational(15, 1);
+        let height = MPEG2_MAX_DIMENSION + 1;
+        for (path, muxer) in [("out.nut", Some("nut")), ("out.mkv", Some("matroska"))] {
+            assert_eq!(check(Path::new(path), muxer, &options, fps, height), Ok(()));
+        }
+        assert_eq!(check(Path::new("out"), None, &options, fps, height), Ok(()));
+    }
+}
@@ -0,0 +1,150 @@
+//! `--cache-cube`: keeps the decoded frames in a file so later runs on the
+//! same input (trying other codecs, quality or frame orders) skip decoding.
+//!
+//! The file is a `video-transpose cube 2` line, a JSON header line, then the
+//! frames' packed pixel data back to back. The header records what the
+//! frames were decoded from, their times and the decoded frame behind each
+//! (for `--index`); a cache whose input file, size or decode settings
+//! differ is ignored and rewritten.
+
+use crate::cli::Options;
+use crate::fit::Reduction;
+use crate::frame_store::{FrameFormat, FrameStore};
+use crate::memory::MemoryGuard;
+use crate::source::InputInfo;
+use serde_json::{json, Value};
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+const MAGIC: &str = "video-transpose cube 2";
+
+/// Frames read back from the cache.
+pub struct Cached {
+    pub frames: FrameStore,
+    /// The decoded frame behind each of `frames`.
+    pub source_frames: Vec<Option<u64>>,
+}
+
+pub struct CubeCache {
+    path: PathBuf,
+    /// Identifies the input and decode settings the frames came from.
+    key: Value,
+    frame_bytes: usize,
+}
+
+impl CubeCache {
+    pub fn new(
+        path: &Path,
+        options: &Options,
+        info: &InputInfo,
+        format: FrameFormat,
+        reduction: Reduction,
+    ) -> Self {
+        let metadata = fs::metadata(&options.input).ok();
+        let modified = metadata
+            .as_ref()
+            .and_then(|metadata| metadata.modified().ok())
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .map(|modified| modified.as_nanos().to_string());
+        let key = json!({
+            "input": options.input.to_string_lossy(),
+            "size": metadata.map(|metadata| metadata.len()),
+            "modified": modified,
+            "width": info.width,
+            "height": info.height,
+            "pixel_format": format!("{:?}", info.format),
+            // differs once --ivtc drops the pulldown frames
+            "fps": format!("{}/{}", info.fps.numerator(), info.fps.denominator()),
+            "frame_format": format.name(),
+            "fill_dropped_frames": options.fill_dropped_frames,
+            "scale": reduction.scale,
+            // frames are scaled differently in linear light
+            "linear_light": options.linear_light && reduction.scale < 1.0,
+            "frame_step": reduction.step,
+            // the filter that converted and scaled the decoded frames
+            "scaler": options.scaler.name(),
+        });
+        let (width, height) = reduction.dimensions(info.width, info.height);
+        CubeCache {
+            path: path.to_path_buf(),
+            key,
+            frame_bytes: width * height * format.bytes_per_pixel(),
+        }
+    }
+
+    /// Reads the cached frames and the decoded frame behind each, or `None`
+    /// if there is no cache for this input yet or it was made from
+    /// something else.
+    pub fn load(&self, guard: &MemoryGuard) -> Result<Option<Cached>, Box<dyn std::error::Error>> {
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(_) => return Ok(None),
+        };
+        let mut reader = BufReader::new(file);
+        let mut magic = Vec::new();
+        reader.read_until(b'\n', &mut magic)?;
+        if magic.trim_ascii_end() != MAGIC.as_bytes() {
+            status!(
+                "  {} is not a cube cache, replacing it",
+                self.path.display()
+            );
+            return Ok(None);
+        }
+        let mut header = String::new();
+        reader.read_line(&mut header)?;
+        let header: Value = serde_json::from_str(&header)?;
+        if header["key"] != self.key {
+            status!(
+                "  Cache {} is for another input or settings, decoding again",
+                self.path.display()
+            );
+            return Ok(None);
+        }
+
+        let times: Vec<Option<f64>> = serde_json::from_value(header["times"].clone())?;
+        let source_frames = serde_json::from_value(header["source_frames"].clone())?;
+        guard.check_projected((times.len() * self.frame_bytes) as u64)?;
+        let mut store = FrameStore::default();
+        for time in times {
+            let mut frame = vec![0; self.frame_bytes];
+            reader.read_exact(&mut frame)?;
+            store.push(frame, time);
+        }
+        Ok(Some(Cached {
+            frames: store,
+            source_frames,
+        }))
+    }
+
+    /// Writes `frames`, decoded from `source_frames`, to the cache,
+    /// replacing it atomically.
+    pub fn save(
+        &self,
+        frames: &FrameStore,
+        source_frames: &[Option<u64>],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut partial = self.path.as_os_str().to_owned();
+        partial.push(".part");
+        let partial = PathBuf::from(partial);
+
+        let mut writer = BufWriter::new(File::create(&partial)?);
+        let header = json!({
+            "key": self.key,
+            "times": frames.times,
+            "source_frames": source_frames,
+        });
+        writeln!(writer, "{}", MAGIC)?;
+        writeln!(writer, "{}", header)?;
+        for frame in &frames.frames {
+            writer.write_all(frame)?;
+        }
+        writer
+            .into_inner()
+            .map_err(|e| e.into_error())?
+            .sync_all()?;
+        fs::rename(&partial, &self.path)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,345 @@
+//! `video-transpose doctor`: reports what the linked FFmpeg build can do.
+//! The transpose only finds out an encoder or muxer is missing once it has
+//! decoded the whole input, and distribution builds differ a lot (no
+//! libx264 in Fedora's `ffmpeg-free`, minimal container images without
+//! most muxers). This lists the decoders, encoders, protocols and muxers
+//! of the libraries the binary loads, checks for the ones the tool uses,
+//! and says what to install when something is missing.
+
+use ffmpeg_next as ffmpeg;
+use ffmpeg_next::codec::Id;
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_void};
+use std::ptr;
+
+/// Width lists of names are wrapped at.
+const LINE_WIDTH: usize = 100;
+
+/// Something the tool looks up in FFmpeg.
+enum Component {
+    /// The first encoder registered for a codec, as `encoder::find` picks.
+    Encoder(Id),
+    /// An encoder looked up by name.
+    NamedEncoder(&'static str),
+    Decoder(Id),
+    Muxer(&'static str),
+    Protocol(&'static str),
+}
+
+struct Check {
+    component: Component,
+    label: &'static str,
+    /// The feature that needs it.
+    used_for: &'static str,
+    /// Whether plain transposes fail without it.
+    required: bool,
+    /// What to do about it missing.
+    hint: &'static str,
+}
+
+const LIBX264_HINT: &str = "FFmpeg was built without libx264, which needs --enable-gpl \
+                            --enable-libx264";
+
+const CHECKS: &[Check] = &[
+    Check {
+        component: Component::Encoder(Id::H264),
+        label: "H.264 encoder",
+        used_for: "H.264 outputs (the default)",
+        required: true,
+        hint: LIBX264_HINT,
+    },
+    Check {
+        component: Component::NamedEncoder("libx264rgb"),
+        label: "libx264rgb encoder",
+        used_for: "--pix-fmt rgb",
+        required: false,
+        hint: LIBX264_HINT,
+    },
+    Check {
+        component: Component::Encoder(Id::FFV1),
+        label: "FFV1 encoder",
+        used_for: "16-bit grayscale and --target lossless outputs",
+        required: false,
+        hint: "FFV1 is built into FFmpeg unless it was configured with --disable-encoders",
+    },
+    Check {
+        component: Component::Encoder(Id::MPEG2VIDEO),
+        label: "MPEG-2 encoder",
+        used_for: "MXF outputs",
+        required: false,
+        hint: "MPEG-2 is built into FFmpeg unless it was configured with --disable-encoders",
+    },
+    Check {
+        component: Component::Encoder(Id::PNG),
+        label: "PNG encoder",
+        used_for: "--heatmap",
+        required: false,
+        hint: "PNG needs FFmpeg built with zlib",
+    },
+    Check {
+        component: Component::Encoder(Id::MJPEG),
+        label: "JPEG encoder",
+        used_for: "--progress-socket previews",
+        required: false,
+        hint: "MJPEG is built into FFmpeg unless it was configured with --disable-encoders",
+    },
+    Check {
+        component: Component::Decoder(Id::H264),
+        label: "H.264 decoder",
+        used_for: "H.264 inputs, most camera and phone recordings",
+        required: true,
+        hint: "the H.264 decoder is built into FFmpeg unless it was configured with \
+               --disable-decoders",
+    },
+    Check {
+        component: Component::Decoder(Id::HEVC),
+        label: "HEVC decoder",
+        used_for: "H.265 inputs",
+        required: false,
+        hint: "the HEVC decoder is built into FFmpeg unless it was configured with \
+               --disable-decoders",
+    },
+    Check {
+        component: Component::Muxer("mp4"),
+        label: "MP4 muxer",
+        used_for: ".mp4 outputs",
+        required: true,
+        hint: "the MP4 muxer is built into FFmpeg unless it was configured with --disable-muxers",
+    },
+    Check {
+        component: Component::Muxer("matroska"),
+        label: "Matroska muxer",
+        used_for: ".mkv outputs, 16-bit grayscale and --index embed",
+        required: false,
+        hint: "the Matroska muxer is built into FFmpeg unless it was configured with \
+               --disable-muxers",
+    },
+    Check {
+        component: Component::Muxer("mxf"),
+        label: "MXF muxer",
+        used_for: ".mxf outputs",
+        required: false,
+        hint: "the MXF muxer is built into FFmpeg unless it was configured with --disable-muxers",
+    },
+    Check {
+        component: Component::Muxer("hls"),
+        label: "HLS muxer",
+        used_for: ".m3u8 outputs",
+        required: false,
+        hint: "the HLS muxer is built into FFmpeg unless it was configured with --disable-muxers",
+    },
+    Check {
+        component: Component::Protocol("file"),
+        label: "file protocol",
+        used_for: "reading and writing files",
+        required: true,
+        hint: "FFmpeg was configured with --disable-protocols",
+    },
+    Check {
+        component: Component::Protocol("pipe"),
+        label: "pipe protocol",
+        used_for: "reading the input from stdin (-)",
+        required: false,
+        hint: "FFmpeg was configured with --disable-protocols",
+    },
+];
+
+pub fn run() -> Result<(), Box<dyn std::error::Error>> {
+    let version = unsafe { CStr::from_ptr(ffmpeg::ffi::av_version_info()) };
+    println!(
+        "FFmpeg {} (libavcodec {}, libavformat {}, libavutil {})",
+        version.to_string_lossy(),
+        library_version(ffmpeg::codec::version()),
+        library_version(ffmpeg::format::version()),
+        library_version(ffmpeg::util::version()),
+    );
+    println!("  License: {}", ffmpeg::codec::license());
+    println!("  Configuration: {}", ffmpeg::codec::configuration());
+    if cfg!(feature = "bundled") {
+        println!("  Built from source and linked in statically (the bundled feature).");
+    } else {
+        println!(
+            "  These are the libraries video-transpose loads, which can differ from the ffmpeg \
+             command on PATH."
+        );
+    }
+
+    let codecs = video_codecs();
+    let names = |encoder: bool, hardware: bool| -> Vec<&str> {
+        codecs
+            .iter()
+            .filter(|codec| codec.is_encoder() == encoder && is_hardware(codec) == hardware)
+            .map(|codec| codec.name())
+            .collect()
+    };
+    println!();
+    print_list("Video decoders", &names(false, false));
+    print_list("Hardware video decoders", &names(false, true));
+    print_list("Video encoders", &names(true, false));
+    print_list("Hardware video encoders", &names(true, true));
+    print_list("Input protocols", &protocols(false));
+    print_list("Output protocols", &protocols(true));
+    print_list("Muxers", &muxers());
+
+    println!("\nChecks:");
+    let mut missing_required = 0;
+    let mut hints = Vec::new();
+    for check in CHECKS {
+        match find(&check.component) {
+            Some((name, hardware)) => {
+                println!("  ✓ {} ({}): {}", check.label, name, check.used_for);
+                // Without a software encoder `encoder::find` lands on a
+                // hardware one, which may reject the frames and ignores --crf
+                if hardware && matches!(check.component, Component::Encoder(_)) {
+                    hints.push(format!(
+                        "The {} used is {}, a hardware encoder that may not take video-transpose's \
+                         frames or quality settings; {}",
+                        check.label, name, check.hint
+                    ));
+                }
+            }
+            None if check.required => {
+                missing_required += 1;
+                println!("  ✗ {} missing, needed for {}", check.label, check.used_for);
+                hints.push(format!("{}: {}", check.label, check.hint));
+            }
+            None => {
+                println!(
+                    "  - {} missing, only needed for {}",
+                    check.label, check.used_for
+                );
+                hints.push(format!("{}: {}", check.label, check.hint));
+            }
+        }
+    }
+
+    if hints.is_empty() {
+        println!("\nEverything video-transpose uses is available.");
+        return Ok(());
+    }
+    println!("\nHints:");
+    for hint in &hints {
+        println!("  - {}", hint);
+    }
+    println!("  - {}", install_hint());
+    if !cfg!(feature = "bundled") {
+        println!(
+            "  - Or build video-transpose with --features bundled to link in an FFmpeg of its own"
+        );
+    }
+    match missing_required {
+        0 => Ok(()),
+        count => Err(format!("{} required FFmpeg components are missing", count).into()),
+    }
+}
+
+/// `major.minor.micro` of a packed library version.
+fn library_version(version: u32) -> String {
+    format!(
+        "{}.{}.{}",
+        version >> 16,
+        (version >> 8) & 0xff,
+        version & 0xff
+    )
+}
+
+/// Encoders and decoders for video.
+fn video_codecs() -> Vec<ffmpeg::Codec> {
+    let mut codecs = Vec::new();
+    let mut opaque: *mut c_void = ptr::null_mut();
+    loop {
+        let codec = unsafe { ffmpeg::ffi::av_codec_iterate(&mut opaque) };
+        if codec.is_null() {
+            break;
+        }
+        let codec = unsafe { ffmpeg::Codec::wrap(codec) };
+        if codec.is_video() {
+            codecs.push(codec);
+        }
+    }
+    codecs
+}
+
+/// Whether `codec` runs on (or partly on) dedicated hardware.
+fn is_hardware(codec: &ffmpeg::Codec) -> bool {
+    let capabilities = unsafe { (*codec.as_ptr()).capabilities } as u32;
+    capabilities & (ffmpeg::ffi::AV_CODEC_CAP_HARDWARE | ffmpeg::ffi::AV_CODEC_CAP_HYBRID) != 0
+}
+
+fn protocols(output: bool) -> Vec<&'static str> {
+    let mut names = Vec::new();
+    let mut opaque: *mut c_void = ptr::null_mut();
+    loop {
+        let name = unsafe { ffmpeg::ffi::avio_enum_protocols(&mut opaque, output.into()) };
+        if name.is_null() {
+            break;
+        }
+        names.push(static_str(name));
+    }
+    names
+}
+
+fn muxers() -> Vec<&'static str> {
+    let mut names = Vec::new();
+    let mut opaque: *mut c_void = ptr::null_mut();
+    loop {
+        let muxer = unsafe { ffmpeg::ffi::av_muxer_iterate(&mut opaque) };
+        if muxer.is_null() {
+            break;
+        }
+        names.push(static_str(unsafe { (*muxer).name }));
+    }
+    names
+}
+
+/// A name FFmpeg keeps for the life of the process.
+fn static_str(name: *const c_char) -> &'static str {
+    unsafe { CStr::from_ptr(name) }.to_str().unwrap_or("?")
+}
+
+/// What `component` resolved to: its name, and for encoders whether it
+/// needs hardware.
+fn find(component: &Component) -> Option<(String, bool)> {
+    let codec =
+        |codec: Option<ffmpeg::Codec>| codec.map(|c| (c.name().to_string(), is_hardware(&c)));
+    let listed =
+        |names: Vec<&str>, name: &str| names.contains(&name).then(|| (name.to_string(), false));
+    match *component {
+        Component::Encoder(id) => codec(ffmpeg::encoder::find(id)),
+        Component::NamedEncoder(name) => codec(ffmpeg::encoder::find_by_name(name)),
+        Component::Decoder(id) => codec(ffmpeg::decoder::find(id)),
+        Component::Muxer(name) => listed(muxers(), name),
+        Component::Protocol(name) => listed(protocols(false), name),
+    }
+}
+
+/// Prints `names` under `title`, wrapped.
+fn print_list(title: &str, names: &[&str]) {
+    println!("{} ({}):", title, names.len());
+    let mut line = String::new();
+    for name in names {
+        if !line.is_empty() && line.len() + name.len() + 1 > LINE_WIDTH {
+            println!("   {}", line);
+            line.clear();
+        }
+        line.push(' ');
+        line.push_str(name);
+    }
+    if !line.is_empty() {
+        println!("   {}", line);
+    }
+}
+
+/// Where to get an FFmpeg with everything, for this platform.
+fn install_hint() -> &'static str {
+    if cfg!(target_os = "macos") {
+        "Homebrew's FFmpeg has everything: brew install ffmpeg, then rebuild video-transpose"
+    } else if cfg!(target_os = "windows") {
+        "Use a full GPL build (e.g. gyan.dev or BtbN's ffmpeg-master-latest-win64-gpl-shared) \
+         and point FFMPEG_DIR at it when building video-transpose"
+    } else {
+        "Debian and Ubuntu: sudo apt install ffmpeg libavcodec-extra libavformat-dev \
+         libavcodec-dev libswscale-dev; Fedora's ffmpeg-free has no libx264, swap it for RPM \
+         Fusion's: sudo dnf swap ffmpeg-free ffmpeg --allowerasing; then rebuild video-transpose"
+    }
+}
@@ -0,0 +1,874 @@
+use crate::captions;
+use crate::channels::Channel;
+use crate::chroma::{ChromaFilter, ChromaScaler};
+use crate::cli::Options;
+use crate::color_range;
+use crate::container;
+use crate::encode_queue::EncodeQueue;
+use crate::encode_stats::PacketLog;
+use crate::encoder_chain;
+use crate::frame_store::FrameFormat;
+use crate::index;
+use crate::mapping;
+use crate::muxer::Muxer;
+use crate::output_file::PartialOutput;
+use crate::packaging;
+use crate::pix_fmt::PixFmt;
+use crate::sink::{FrameSink, OutputFrame};
+use crate::sonify::AudioTrack;
+use crate::split::Sequence;
+use crate::stats::{format_rate, format_rational, QueueStats};
+use crate::target::Target;
+use ffmpeg_next as ffmpeg;
+use ffmpeg_next::format::Pixel;
+use ffmpeg_next::software::scaling::context::Context;
+use ffmpeg_next::util::color::Range;
+use ffmpeg_next::util::frame::video::Video;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// `--tune` value selecting [`TRANSPOSE_TUNING`] instead of an x264 tuning.
+const TRANSPOSE_TUNE: &str = "transpose";
+
+/// x264 settings for transposed frames. Each row of a transposed frame is
+/// one source pixel over time, so frames are made of long horizontal
+/// streaks with sharp vertical steps at motion edges. The defaults spend
+/// bits unevenly on them:
+///
+/// - `aq-mode` 3 (auto-variance, biased to dark areas) and a lower strength
+///   keep flat streaks from being starved next to busy ones;
+/// - a low `psy-rd`, without psy-trellis, stops the encoder inventing
+///   texture that reads as shimmer across the streaks;
+/// - a slightly negative `deblock` keeps the vertical steps crisp.
+const TRANSPOSE_TUNING: [(&str, &str); 4] = [
+    ("aq-mode", "3"),
+    ("aq-strength", "0.8"),
+    ("psy-rd", "0.4:0"),
+    ("deblock", "-1:-1"),
+];
+
+/// Size and quality of one encoded output.
+pub struct EncodeTarget {
+    pub width: usize,
+    pub height: usize,
+    pub crf: Option<u32>,
+    /// Encode just this channel, as grayscale.
+    pub channel: Option<Channel>,
+    /// Encode YUV at full (JPEG) range, as the input was.
+    pub full_range: bool,
+    /// Place in a `--split-output` sequence, recorded in the metadata.
+    pub sequence: Option<Sequence>,
+    /// `--index embed`: the index JSON, attached to the file.
+    pub index: Option<String>,
+    /// Mux a `--sonify` audio track alongside the video.
+    pub audio: bool,
+}
+
+/// Encoder and muxer for the transposed frames, fed packed buffers one
+/// output frame at a time: H.264 for RGB24 frames (FFV1 for `--target
+/// archive`), lossless FFV1 for 16-bit grayscale.
+pub struct VideoWriter {
+    scaler: Converter,
+    width: usize,
+    height: usize,
+    frames_sent: i64,
+    embed_mapping: bool,
+    channel: Option<Channel>,
+    format: FrameFormat,
+    encoding: Encoding,
+}
+
+/// Converts packed frames to the encoded format: swscale as `Context::get`
+/// sets it up, or with the `--chroma-loc`/`--chroma-filter` chroma.
+enum Converter {
+    Plain(Context),
+    Chroma(ChromaScaler),
+}
+
+impl Converter {
+    fn run(&mut self, input: &Video, output: &mut Video) -> Result<(), ffmpeg::Error> {
+        match self {
+            Converter::Plain(scaler) => scaler.run(input, output),
+            Converter::Chroma(scaler) => scaler.run(input, output),
+        }
+    }
+}
+
+/// Where converted frames are encoded: right away, or on an encoder thread
+/// with `--inflight-frames`.
+enum Encoding {
+    Inline(FrameEncoder),
+    Queued(EncodeQueue),
+}
+
+/// The encoder and muxer half of a [`VideoWriter`], fed frames already in
+/// the encoded pixel format.
+pub struct FrameEncoder {
+    muxer: Muxer,
+    encoder: ffmpeg::encoder::Video,
+    stream_index: usize,
+    audio: Option<AudioTrack>,
+    fps: f64,
+    /// Packet sizes for `--encode-stats`, and the CSV they're appended to.
+    packet_log: Option<(PacketLog, PathBuf)>,
+    name: String,
+}
+
+impl VideoWriter {
+    /// Opens an encoder for `target`, fed frames of `width × height` in
+    /// `format` that are scaled to the target size if it differs.
+    pub fn open(
+        output: &PartialOutput,
+        width: usize,
+        height: usize,
+        format: FrameFormat,
+        target: &EncodeTarget,
+        fps: ffmpeg::Rational,
+        options: &Options,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        // Setup FFmpeg output
+        let mut muxer = Muxer::new(output.open()?);
+        let stream = VideoStream::add(
+            &mut muxer,
+            output,
+            (width, height),
+            format,
+            target,
+            fps,
+            options,
+        )?;
+        // --sonify: the audio goes in a stream after the video's
+        let audio = match target.audio {
+            true => Some(AudioTrack::open(&mut muxer, options.deterministic)?),
+            false => None,
+        };
+        write_header(
+            &mut muxer,
+            output,
+            (target.sequence, target.index.as_deref()),
+            fps,
+            options,
+        )?;
+
+        // Get the ACTUAL time base that the muxer is using after write_header
+        let stream_time_base = muxer
+            .stream_time_base(stream.index)
+            .ok_or("Stream not found")?;
+
+        status!(
+            "  Stream time base AFTER header: {}",
+            format_rational(stream_time_base)
+        );
+
+        let encoder = FrameEncoder {
+            muxer,
+            encoder: stream.encoder,
+            stream_index: stream.index,
+            audio,
+            fps: f64::from(fps),
+            packet_log: options
+                .encode_stats
+                .as_ref()
+                .map(|path| (PacketLog::default(), PathBuf::from(path))),
+            name: output.final_path().display().to_string(),
+        };
+        let encoding = match options.inflight_frames {
+            Some(capacity) => Encoding::Queued(EncodeQueue::new(encoder, capacity)),
+            None => Encoding::Inline(encoder),
+        };
+        Ok(VideoWriter {
+            scaler: stream.scaler,
+            width,
+            height,
+            frames_sent: 0,
+            embed_mapping: options.embed_mapping,
+            channel: target.channel,
+            format,
+            encoding,
+        })
+    }
+}
+
+/// One video stream of an output: its encoder, and the conversion of
+/// packed frames to the format the encoder takes.
+struct VideoStream {
+    scaler: Converter,
+    encoder: ffmpeg::encoder::Video,
+    index: usize,
+}
+
+impl VideoStream {
+    /// Adds a stream encoding `target` to `muxer`, before its header is
+    /// written, fed frames of `width × height` in `format`.
+    fn add(
+        muxer: &mut Muxer,
+        output: &PartialOutput,
+        (width, height): (usize, usize),
+        format: FrameFormat,
+        target: &EncodeTarget,
+        fps: ffmpeg::Rational,
+        options: &Options,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        // Get format flags before creating encoder
+        let global_header = muxer.global_header();
+
+        // Find H264 encoder, or FFV1 to keep 16-bit samples intact
+        let lossless = options.target.is_some_and(Target::lossless);
+        let mxf = container::is_mxf(output.format());
+        // `None` for color outputs picking from the `--encoder` chain
+        let codec = match format {
+            // MXF ingest expects MPEG-2
+            FrameFormat::Rgb24 if mxf => Some(
+                ffmpeg::encoder::find(ffmpeg::codec::Id::MPEG2VIDEO)
+                    .ok_or("MPEG-2 encoder not found (needed for MXF outputs)")?,
+            ),
+            FrameFormat::Rgb24 if lossless => Some(
+                ffmpeg::encoder::find(ffmpeg::codec::Id::FFV1).ok_or("FFV1 encoder not found")?,
+            ),
+            // x264 built for RGB input, skipping the conversion to YUV
+            FrameFormat::Rgb24 if options.pix_fmt == Some(PixFmt::Rgb) => Some(
+                ffmpeg::encoder::find_by_name("libx264rgb")
+                    .ok_or("libx264rgb encoder not found (needed for --pix-fmt rgb)")?,
+            ),
+            FrameFormat::Rgb24 if !options.encoders.is_empty() => None,
+            FrameFormat::Rgb24 => Some(
+                ffmpeg::encoder::find(ffmpeg::codec::Id::H264).ok_or("H264 encoder not found")?,
+            ),
+            FrameFormat::Gray16 => Some(
+                ffmpeg::encoder::find(ffmpeg::codec::Id::FFV1).ok_or("FFV1 encoder not found")?,
+            ),
+            FrameFormat::RgbF32 => return Err("Float frames can only be written as EXR".into()),
+        };
+        let encoded_format = match (format, options.pix_fmt, options.target) {
+            (FrameFormat::Gray16, _, _) => Pixel::GRAY16LE,
+            // FFV1 stores single channels as they are
+            _ if lossless && target.channel.is_some() => Pixel::GRAY8,
+            _ if mxf => container::mxf_pixel(options),
+            (_, Some(pix_fmt), _) => pix_fmt.pixel(lossless),
+            (_, None, Some(target)) => target.pixel(),
+            _ => Pixel::YUV420P,
+        };
+        // Tag the range explicitly rather than leave players to guess it
+        let yuv = |codec: ffmpeg::Codec| {
+            matches!(
+                codec.id(),
+                ffmpeg::codec::Id::H264 | ffmpeg::codec::Id::MPEG2VIDEO
+            ) && encoded_format != Pixel::RGB24
+        };
+        // Chroma siting only means something for subsampled chroma
+        let subsampled = matches!(encoded_format, Pixel::YUV420P | Pixel::YUV422P);
+        let chroma_options =
+            options.chroma_loc.is_some() || options.chroma_filter != ChromaFilter::Fast;
+        // Segmenters can only cut on keyframes, so put one at every boundary
+        let segmented = packaging::is_segmented(output.format());
+
+        let open_encoder = |codec: ffmpeg::Codec| -> Result<_, Box<dyn std::error::Error>> {
+            // Encoders list the formats they take, builds differ (e.g. 10-bit x264)
+            if let Some(mut supported) = codec.video()?.formats() {
+                if !supported.any(|pixel| pixel == encoded_format) {
+                    return Err(format!(
+                        "The {} encoder can't encode {:?}; pick another --pix-fmt",
+                        codec.name(),
+                        encoded_format
+                    )
+                    .into());
+                }
+            }
+
+            // Create and configure encoder context FIRST
+            let mut encoder = ffmpeg::codec::context::Context::new_with_codec(codec)
+                .encoder()
+                .video()?;
+
+            encoder.set_width(target.width as u32);
+            encoder.set_height(target.height as u32);
+            encoder.set_format(encoded_format);
+            if yuv(codec) {
+                let range = if target.full_range {
+                    Range::JPEG
+                } else {
+                    Range::MPEG
+                };
+                encoder.set_color_range(range);
+            }
+            if let Some(location) = options.chroma_loc.filter(|_| subsampled) {
+                unsafe {
+                    (*encoder.as_mut_ptr()).chroma_sample_location = location.location().into();
+                }
+            }
+
+            // Time base should be inverse of frame rate
+            // For 29.97 fps (30000/1001), time_base should be 1001/30000
+            encoder.set_time_base(ffmpeg::Rational(fps.denominator(), fps.numerator()));
+            encoder.set_frame_rate(Some(fps));
+            encoder.set_max_b_frames(options.bframes);
+
+            if options.intra_only {
+                // Every frame a keyframe (IDR for H.264, so every GOP is closed)
+                encoder.set_gop(1);
+            } else if segmented {
+                let gop = (options.segment_seconds * f64::from(fps)).round().max(1.0);
+                encoder.set_gop(gop as u32);
+            } else if let Some(seconds) = options.target.and_then(Target::gop_seconds) {
+                let gop = (seconds * f64::from(fps)).round().max(1.0);
+                encoder.set_gop(gop as u32);
+            }
+
+            let mut flags = ffmpeg::codec::flag::Flags::empty();
+            if global_header {
+                flags |= ffmpeg::codec::flag::Flags::GLOBAL_HEADER;
+            }
+            // MPEG-2 has no CRF, encode at a fixed quantizer instead
+            if mxf {
+                flags |= ffmpeg::codec::flag::Flags::QSCALE;
+                encoder.set_global_quality(container::mpeg2_quality());
+            }
+            encoder.set_flags(flags);
+
+            // Open encoder
+            let mut encoder_options = ffmpeg::Dictionary::new();
+            // Frame-threaded encoders split work depending on timing, and some
+            // write their version into the stream unless asked to be bitexact
+            if options.deterministic {
+                encoder_options.set("threads", "1");
+                encoder_options.set("flags", "+bitexact");
+            }
+            // H.264 and MPEG-2 encoders write A53 captions found on the frames,
+            // which only get there with --keep-cc
+            if options.keep_cc {
+                encoder_options.set("a53cc", "1");
+            }
+            if format == FrameFormat::Rgb24 && !lossless && !mxf {
+                // Other encoders of a --encoder chain name these differently
+                // or not at all, and may reject x264's values
+                let x264 = codec.name().starts_with("libx264");
+                if !x264 && (options.tune.is_some() || options.lookahead.is_some()) {
+                    status!(
+                        "  Warning: --tune and --lookahead are x264 settings, ignored for {}",
+                        codec.name()
+                    );
+                }
+                if let Some(lookahead) = options.lookahead.filter(|_| x264) {
+                    encoder_options.set("rc-lookahead", &lookahead.to_string());
+                }
+                match options.tune.as_deref().filter(|_| x264) {
+                    Some(TRANSPOSE_TUNE) => {
+                        for (key, value) in TRANSPOSE_TUNING {
+                            encoder_options.set(key, value);
+                        }
+                    }
+                    Some(tune) => encoder_options.set("tune", tune),
+                    None => {}
+                }
+                if options.embed_mapping {
+                    // libx264 only writes per-frame SEI side data when asked to
+                    encoder_options.set("udu_sei", "1");
+                }
+                let crf = target.crf.or_else(|| options.target.and_then(Target::crf));
+                match (crf, quality_option(codec.name())) {
+                    (Some(crf), Some(option)) => encoder_options.set(option, &crf.to_string()),
+                    (Some(_), None) => status!(
+                        "  Warning: {} has no constant-quality setting, the CRF is ignored",
+                        codec.name()
+                    ),
+                    (None, _) => {}
+                }
+            }
+            Ok(open_with(encoder, codec, encoder_options)?)
+        };
+        let (codec, encoder) = match codec {
+            Some(codec) => (codec, open_encoder(codec)?),
+            None => encoder_chain::open(&options.encoders, open_encoder)?,
+        };
+        let yuv = yuv(codec);
+        let encoder_time_base = encoder.time_base();
+
+        // NOW add stream and copy parameters
+        let mut ostream = muxer.add_stream(codec, encoder_time_base)?;
+        let stream_index = ostream.index();
+
+        // Copy encoder parameters to stream
+        ostream.set_parameters(&encoder);
+        if options.embed_mapping {
+            ostream.set_metadata(mapping::stream_metadata());
+        }
+        ostream.set_time_base(ffmpeg::Rational(fps.denominator(), fps.numerator()));
+        ostream.set_avg_frame_rate(fps);
+
+        status!("  Input FPS: {} fps", format_rate(fps));
+        status!(
+            "  Encoder time base: {}",
+            format_rational(encoder_time_base)
+        );
+        status!(
+            "  Stream time base before header: {}",
+            format_rational(ostream.time_base())
+        );
+
+        // Create scaler
+        let input_format = match target.channel {
+            Some(_) => Pixel::GRAY8,
+            None => format.pixel(),
+        };
+        let scaler = if subsampled && chroma_options {
+            Converter::Chroma(ChromaScaler::new(
+                (input_format, width as u32, height as u32),
+                (encoded_format, target.width as u32, target.height as u32),
+                options.scaler.flags(),
+                options.chroma_loc,
+                options.chroma_filter,
+                yuv && target.full_range,
+            )?)
+        } else {
+            let mut scaler = Context::get(
+                input_format,
+                width as u32,
+                height as u32,
+                encoded_format,
+                target.width as u32,
+                target.height as u32,
+                options.scaler.flags(),
+            )?;
+            color_range::set_ranges(&mut scaler, false, yuv && target.full_range);
+            Converter::Plain(scaler)
+        };
+
+        Ok(VideoStream {
+            scaler,
+            encoder,
+            index: stream_index,
+        })
+    }
+}
+
+/// Sets the muxer options and the container metadata of an output's place
+/// in a `--split-output` sequence, attaches its `--index embed` JSON and
+/// writes the header, once every stream is added.
+/// The option `codec` takes a constant-quality level in, as x264 takes
+/// its CRF; hardware encoders have their own on a similar 0-51 scale.
+fn quality_option(codec: &str) -> Option<&'static str> {
+    match codec {
+        "libx264" | "libx264rgb" | "libx265" | "libvpx-vp9" | "libaom-av1" | "libsvtav1" => {
+            Some("crf")
+        }
+        _ if codec.ends_with("_nvenc") => Some("cq"),
+        _ if codec.ends_with("_qsv") => Some("global_quality"),
+        _ if codec.ends_with("_vaapi") => Some("qp"),
+        _ => None,
+    }
+}
+
+/// Opens `encoder` as `codec` with `options`, warning about the options
+/// the encoder didn't take, which FFmpeg otherwise drops silently.
+fn open_with(
+    mut encoder: ffmpeg::encoder::video::Video,
+    codec: ffmpeg::Codec,
+    options: ffmpeg::Dictionary,
+) -> Result<ffmpeg::encoder::video::Encoder, ffmpeg::Error> {
+    let (opened, ignored) = unsafe {
+        let mut options = options.disown();
+        let opened = ffmpeg::ffi::avcodec_open2(encoder.as_mut_ptr(), codec.as_ptr(), &mut options);
+        (opened, ffmpeg::Dictionary::own(options))
+    };
+    if opened < 0 {
+        return Err(ffmpeg::Error::from(opened));
+    }
+    let ignored: Vec<&str> = ignored.iter().map(|(key, _)| key).collect();
+    if !ignored.is_empty() {
+        status!(
+            "  Warning: {} ignored the options {}",
+            codec.name(),
+            ignored.join(", ")
+        );
+    }
+    Ok(ffmpeg::encoder::video::Encoder(encoder))
+}
+
+fn write_header(
+    muxer: &mut Muxer,
+    output: &PartialOutput,
+    (sequence, index): (Option<Sequence>, Option<&str>),
+    fps: ffmpeg::Rational,
+    options: &Options,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // Write header - this may change the time base!
+    let mut muxer_options = ffmpeg::Dictionary::new();
+    // Keeps muxers from writing creation_time and the library version
+    if options.deterministic {
+        muxer_options.set("fflags", "+bitexact");
+    }
+    if options.embed_mapping {
+        for (key, value) in mapping::muxer_options(&muxer.format()).iter() {
+            muxer_options.set(key, value);
+        }
+    }
+    let mut movflags = Vec::new();
+    // Moves the index in front of the media once the file is written
+    if options.target.is_some_and(Target::faststart) {
+        movflags.push("+faststart");
+    }
+    if let Some(sequence) = sequence {
+        let mut metadata = ffmpeg::Dictionary::new();
+        for (key, value) in sequence.metadata(f64::from(fps)) {
+            metadata.set(key, &value);
+        }
+        muxer.set_metadata(metadata);
+        // MP4 only keeps tags it knows unless told otherwise
+        movflags.push("+use_metadata_tags");
+    }
+    if !movflags.is_empty() && matches!(output.format(), "mp4" | "mov") {
+        muxer_options.set("movflags", &movflags.concat());
+    }
+    if packaging::is_segmented(output.format()) {
+        let packaging_options = packaging::muxer_options(
+            output.format(),
+            output.final_path(),
+            output.segment_dir(),
+            options.segment_seconds,
+        );
+        for (key, value) in packaging_options.iter() {
+            muxer_options.set(key, value);
+        }
+    }
+    if let Some(index) = index {
+        muxer.add_attachment(
+            index::ATTACHMENT_NAME,
+            index::ATTACHMENT_MIME_TYPE,
+            index.as_bytes(),
+        )?;
+    }
+    muxer.write_header(muxer_options)?;
+    Ok(())
+}
+
+impl FrameEncoder {
+    /// Encodes one frame, and the audio played while it's shown, and writes
+    /// the packets the encoders have ready.
+    pub fn send(&mut self, frame: &Video, audio: &[f32]) -> Result<(), Box<dyn std::error::Error>> {
+        self.encoder.send_frame(frame)?;
+        if let Some(track) = &mut self.audio {
+            track.send(&mut self.muxer, audio)?;
+        }
+        self.receive_and_write_packets()
+    }
+
+    /// Flushes the encoder and writes the trailer.
+    pub fn finish(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        self.encoder.send_eof()?;
+        self.receive_and_write_packets()?;
+        if let Some(track) = &mut self.audio {
+            track.finish(&mut self.muxer)?;
+        }
+        self.muxer.finish()?;
+        if let Some((log, csv)) = &self.packet_log {
+            log.print_summary(self.fps);
+            log.append_csv(csv, &self.name)?;
+        }
+        Ok(())
+    }
+
+    /// Name of the output, for messages.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn receive_and_write_packets(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let mut encoded_packet = ffmpeg::Packet::empty();
+
+        while self.encoder.receive_packet(&mut encoded_packet).is_ok() {
+            if let Some((log, _)) = &mut self.packet_log {
+                log.record(
+                    encoded_packet.pts().unwrap_or_default(),
+                    encoded_packet.size(),
+                    encoded_packet.is_key(),
+                );
+            }
+            self.muxer.write(&mut encoded_packet, self.stream_index)?;
+        }
+        Ok(())
+    }
+}
+
+impl FrameSink for VideoWriter {
+    /// Encodes one packed frame of `width × height` pixels. Its mapping is
+    /// embedded as SEI when `--embed-mapping` is on.
+    fn write(&mut self, frame: &OutputFrame) -> Result<(), Box<dyn std::error::Error>> {
+        let data = frame.data;
+        // Create frame from transposed data
+        let input_frame = match self.channel {
+            Some(channel) => {
+                let plane = channel.extract(data);
+                packed_frame(&plane, Pixel::GRAY8, 1, self.width, self.height)
+            }
+            None => packed_frame(
+                data,
+                self.format.pixel(),
+                self.format.bytes_per_pixel(),
+                self.width,
+                self.height,
+            ),
+        };
+
+        // Convert to the encoded format (YUV420P unless --target picks
+        // another), or scale GRAY16LE
+        let mut yuv_frame = Video::empty();
+        self.scaler.run(&input_frame, &mut yuv_frame)?;
+
+        // Set PTS in encoder time base
+        yuv_frame.set_pts(Some(self.frames_sent));
+        self.frames_sent += 1;
+        if self.embed_mapping {
+            mapping::attach_sei(&mut yuv_frame, frame.mapping);
+        }
+        if let Some(payload) = frame.captions {
+            captions::attach(&mut yuv_frame, payload);
+        }
+
+        // Encode frame
+        let audio = frame.audio.unwrap_or_default();
+        match &mut self.encoding {
+            Encoding::Inline(encoder) => encoder.send(&yuv_frame, audio),
+            Encoding::Queued(queue) => queue.send(yuv_frame, audio.to_vec()),
+        }
+    }
+
+    fn finish(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        match &mut self.encoding {
+            Encoding::Inline(encoder) => encoder.finish(),
+            Encoding::Queued(queue) => queue.finish(),
+        }
+    }
+
+    fn queue_stats(&self) -> Option<QueueStats> {
+        match &self.encoding {
+            Encoding::Inline(_) => None,
+            Encoding::Queued(queue) => Some(queue.stats()),
+        }
+    }
+}
+
+/// `--multi-track`: video streams of different sizes encoded into one
+/// output, fed a frame of one of them at a time. Callers interleave the
+/// streams' frames in time so the muxer doesn't have to buffer them.
+pub struct MultiTrackWriter {
+    muxer: Muxer,
+    tracks: Vec<Track>,
+}
+
+/// One stream of a [`MultiTrackWriter`].
+struct Track {
+    stream: VideoStream,
+    width: usize,
+    height: usize,
+    frames_sent: i64,
+}
+
+impl MultiTrackWriter {
+    /// Opens a stream per track, each fed frames of its `width × height`
+    /// in `format` and titled `title`, encoded at `crf` and, for YUV, at
+    /// full range if `full_range`.
+    pub fn open(
+        output: &PartialOutput,
+        tracks: &[(usize, usize, &str)],
+        format: FrameFormat,
+        (crf, full_range): (Option<u32>, bool),
+        fps: ffmpeg::Rational,
+        options: &Options,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut muxer = Muxer::new(output.open()?);
+        let mut opened = Vec::new();
+        for &(width, height, title) in tracks {
+            let target = EncodeTarget {
+                width,
+                height,
+                crf,
+                channel: None,
+                full_range,
+                sequence: None,
+                index: None,
+                audio: false,
+            };
+            let stream = VideoStream::add(
+                &mut muxer,
+                output,
+                (width, height),
+                format,
+                &target,
+                fps,
+                options,
+            )?;
+            let mut metadata = ffmpeg::Dictionary::new();
+            metadata.set("title", title);
+            muxer.set_stream_metadata(stream.index, metadata);
+            opened.push(Track {
+                stream,
+                width,
+                height,
+                frames_sent: 0,
+            });
+        }
+        write_header(&mut muxer, output, (None, None), fps, options)?;
+        Ok(MultiTrackWriter {
+            muxer,
+            tracks: opened,
+        })
+    }
+
+    /// Encodes the next packed frame of track `track` (in the order given
+    /// to [`MultiTrackWriter::open`]) in `format`.
+    pub fn write(
+        &mut self,
+        track: usize,
+        data: &[u8],
+        format: FrameFormat,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let track = &mut self.tracks[track];
+        let input = packed_frame(
+            data,
+            format.pixel(),
+            format.bytes_per_pixel(),
+            track.width,
+            track.height,
+        );
+        let mut converted = Video::empty();
+        track.stream.scaler.run(&input, &mut converted)?;
+        converted.set_pts(Some(track.frames_sent));
+        track.frames_sent += 1;
+        track.stream.encoder.send_frame(&converted)?;
+        track.write_packets(&mut self.muxer)
+    }
+
+    /// Flushes every encoder and writes the trailer.
+    pub fn finish(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        for track in &mut self.tracks {
+            track.stream.encoder.send_eof()?;
+            track.write_packets(&mut self.muxer)?;
+        }
+        self.muxer.finish()?;
+        Ok(())
+    }
+}
+
+impl Track {
+    fn write_packets(&mut self, muxer: &mut Muxer) -> Result<(), Box<dyn std::error::Error>> {
+        let mut packet = ffmpeg::Packet::empty();
+        while self.stream.encoder.receive_packet(&mut packet).is_ok() {
+            muxer.write(&mut packet, self.stream.index)?;
+        }
+        Ok(())
+    }
+}
+
+/// Copies a packed single-plane buffer (RGB24, GRAY8 or GRAY16LE) into a frame,
+/// respecting its line stride.
+pub(crate) fn packed_frame(
+    data: &[u8],
+    format: Pixel,
+    bytes_per_pixel: usize,
+    width: usize,
+    height: usize,
+) -> Video {
+    let mut frame = Video::new(format, width as u32, height as u32);
+
+    // Get the stride (linesize) for the frame
+    let linesize = frame.stride(0);
+    let frame_data = frame.data_mut(0);
+    let row_bytes = width * bytes_per_pixel;
+
+    // Copy row by row, respecting the stride
+    for y in 0..height {
+        let src_start = y * row_bytes;
+        let dst_start = y * linesize;
+
+        frame_data[dst_start..dst_start + row_bytes]
+            .copy_from_slice(&data[src_start..src_start + row_bytes]);
+    }
+    frame
+}
+
+/// Whether `muxer` can store the FFV1 video used for 16-bit grayscale.
+pub fn supports_ffv1(muxer: &str) -> bool {
+    matches!(muxer, "matroska" | "nut" | "avi")
+}
+
+/// Writes a packed RGB24 image as a PNG file using FFmpeg's PNG encoder.
+pub fn write_png(
+    path: &Path,
+    rgb: &[u8],
+    width: usize,
+    height: usize,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let codec = ffmpeg::encoder::find(ffmpeg::codec::Id::PNG).ok_or("PNG encoder not found")?;
+    let mut encoder = ffmpeg::codec::context::Context::new_with_codec(codec)
+        .encoder()
+        .video()?;
+    encoder.set_width(width as u32);
+    encoder.set_height(height as u32);
+    encoder.set_format(Pixel::RGB24);
+    encoder.set_time_base(ffmpeg::Rational(1, 1));
+    let mut encoder = encoder.open_as(codec)?;
+
+    encoder.send_frame(&packed_frame(rgb, Pixel::RGB24, 3, width, height))?;
+    encoder.send_eof()?;
+
+    // A PNG packet is a complete file
+    let mut packet = ffmpeg::Packet::empty();
+    encoder.receive_packet(&mut packet)?;
+    fs::write(path, packet.data().ok_or("PNG encoder produced no data")?)?;
+    Ok(())
+}
+
+/// Encodes packed `format` samples of `width × height` pixels as a JPEG no
+/// larger than `max_width × max_height`, keeping the aspect ratio.
+pub fn jpeg_thumbnail(
+    data: &[u8],
+    format: FrameFormat,
+    width: usize,
+    height: usize,
+    (max_width, max_height): (usize, usize),
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let factor = (max_width as f64 / width as f64)
+        .min(max_height as f64 / height as f64)
+        .min(1.0);
+    // 4:2:0 JPEG wants even dimensions
+    let thumb_width = ((width as f64 * factor) as usize / 2 * 2).max(2);
+    let thumb_height = ((height as f64 * factor) as usize / 2 * 2).max(2);
+
+    let frame = packed_frame(
+        data,
+        format.pixel(),
+        format.bytes_per_pixel(),
+        width,
+        height,
+    );
+    let mut scaler = Context::get(
+        format.pixel(),
+        width as u32,
+        height as u32,
+        Pixel::YUVJ420P,
+        thumb_width as u32,
+        thumb_height as u32,
+        ffmpeg::software::scaling::flag::Flags::AREA,
+    )?;
+    let mut scaled = Video::empty();
+    scaler.run(&frame, &mut scaled)?;
+
+    let codec = ffmpeg::encoder::find(ffmpeg::codec::Id::MJPEG).ok_or("JPEG encoder not found")?;
+    let mut encoder = ffmpeg::codec::context::Context::new_with_codec(codec)
+        .encoder()
+        .video()?;
+    encoder.set_width(thumb_width as u32);
+    encoder.set_height(thumb_height as u32);
+    encoder.set_format(Pixel::YUVJ420P);
+    encoder.set_time_base(ffmpeg::Rational(1, 1));
+    let mut encoder = encoder.open_as(codec)?;
+
+    encoder.send_frame(&scaled)?;
+    encoder.send_eof()?;
+    let mut packet = ffmpeg::Packet::empty();
+    encoder.receive_packet(&mut packet)?;
+    Ok(packet
+        .data()
+        .ok_or("JPEG encoder produced no data")?
+        .to_vec())
+}
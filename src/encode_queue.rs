@@ -0,0 +1,111 @@
+//! `--inflight-frames`: encoding on a thread of its own, so transposing
+//! the next frames overlaps with encoding the last ones. Frames converted to
+//! the encoded pixel format, with their `--sonify` samples, wait in a
+//! bounded queue; when the encoder falls behind, the transpose blocks on the
+//! full queue rather than buffering without limit.
+
+use crate::encode::FrameEncoder;
+use crate::stats::QueueStats;
+use ffmpeg_next::util::frame::video::Video;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, SyncSender, TrySendError};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Instant;
+
+pub struct EncodeQueue {
+    sender: Option<SyncSender<(Video, Vec<f32>)>>,
+    thread: Option<JoinHandle<Result<(), String>>>,
+    /// Frames sent but not yet taken by the encoder thread.
+    depth: Arc<AtomicUsize>,
+    stats: QueueStats,
+}
+
+impl EncodeQueue {
+    /// Starts a thread encoding with `encoder`, holding at most `capacity`
+    /// frames waiting for it.
+    pub fn new(mut encoder: FrameEncoder, capacity: usize) -> Self {
+        let (sender, receiver) = mpsc::sync_channel::<(Video, Vec<f32>)>(capacity);
+        let depth = Arc::new(AtomicUsize::new(0));
+        let stats = QueueStats {
+            name: encoder.name().to_string(),
+            capacity,
+            ..QueueStats::default()
+        };
+        let taken = Arc::clone(&depth);
+        let thread = thread::spawn(move || {
+            let result = (|| -> Result<(), Box<dyn std::error::Error>> {
+                for (frame, audio) in receiver {
+                    taken.fetch_sub(1, Ordering::Relaxed);
+                    encoder.send(&frame, &audio)?;
+                }
+                encoder.finish()
+            })();
+            result.map_err(|error| format!("Encoding {} failed: {}", encoder.name(), error))
+        });
+        EncodeQueue {
+            sender: Some(sender),
+            thread: Some(thread),
+            depth,
+            stats,
+        }
+    }
+
+    /// Queues a frame and its audio, waiting while the queue is full.
+    pub fn send(
+        &mut self,
+        frame: Video,
+        audio: Vec<f32>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let sender = self
+            .sender
+            .as_ref()
+            .ok_or("Encoder queue already finished")?;
+        self.depth.fetch_add(1, Ordering::Relaxed);
+        let sent = match sender.try_send((frame, audio)) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(queued)) => {
+                let start = Instant::now();
+                let sent = sender.send(queued).map_err(|_| ());
+                self.stats.blocked += start.elapsed();
+                sent
+            }
+            Err(TrySendError::Disconnected(_)) => Err(()),
+        };
+        if sent.is_err() {
+            // The encoder thread only hangs up when it fails
+            self.join()?;
+            return Err("Encoder thread stopped early".into());
+        }
+        let depth = self.depth.load(Ordering::Relaxed).min(self.stats.capacity);
+        self.stats.frames += 1;
+        self.stats.total_depth += depth as u64;
+        self.stats.max_depth = self.stats.max_depth.max(depth);
+        Ok(())
+    }
+
+    /// Waits for the queued frames to be encoded and the output finished.
+    pub fn finish(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        self.join()
+    }
+
+    pub fn stats(&self) -> QueueStats {
+        self.stats.clone()
+    }
+
+    /// Closes the queue and waits for the encoder thread, returning its
+    /// error if it failed.
+    fn join(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        self.sender = None;
+        match self.thread.take() {
+            Some(thread) => Ok(thread.join().map_err(|_| "Encoder thread panicked")??),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for EncodeQueue {
+    fn drop(&mut self) {
+        let _ = self.join();
+    }
+}
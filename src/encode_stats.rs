@@ -0,0 +1,81 @@
+use crate::memory::format_bytes;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// Size of one encoded output frame.
+struct FramePacket {
+    frame: i64,
+    bytes: usize,
+    keyframe: bool,
+}
+
+/// Per-frame packet sizes of one encoded output, reported when it's
+/// finished. Transposed content has unusual statistics (every frame is a
+/// slow pan over the source's time axis), so these help pick a CRF.
+#[derive(Default)]
+pub struct PacketLog {
+    packets: Vec<FramePacket>,
+}
+
+impl PacketLog {
+    /// Records a packet of output frame `frame` (its PTS in the encoder's
+    /// 1/fps time base).
+    pub fn record(&mut self, frame: i64, bytes: usize, keyframe: bool) {
+        self.packets.push(FramePacket {
+            frame,
+            bytes,
+            keyframe,
+        });
+    }
+
+    /// Prints the average bitrate and frame size distribution.
+    pub fn print_summary(&self, fps: f64) {
+        let mut sizes: Vec<usize> = self.packets.iter().map(|packet| packet.bytes).collect();
+        if sizes.is_empty() {
+            return;
+        }
+        sizes.sort_unstable();
+        let total: usize = sizes.iter().sum();
+        let seconds = sizes.len() as f64 / fps;
+        let percentile = |p: f64| sizes[((sizes.len() - 1) as f64 * p).round() as usize] as u64;
+        let keyframes = self.packets.iter().filter(|packet| packet.keyframe).count();
+        status!(
+            "  Encoded {} in {} frames: {:.0} kbit/s, {} keyframes",
+            format_bytes(total as u64),
+            sizes.len(),
+            total as f64 * 8.0 / seconds / 1000.0,
+            keyframes
+        );
+        status!(
+            "  Frame sizes: min {}, median {}, p95 {}, max {}",
+            format_bytes(sizes[0] as u64),
+            format_bytes(percentile(0.5)),
+            format_bytes(percentile(0.95)),
+            format_bytes(sizes[sizes.len() - 1] as u64)
+        );
+    }
+
+    /// Appends one `output,frame,bytes,keyframe` row per frame, in frame
+    /// order, to the CSV at `path` (started with [`create_csv`]).
+    pub fn append_csv(&self, path: &Path, output: &str) -> std::io::Result<()> {
+        let mut packets: Vec<&FramePacket> = self.packets.iter().collect();
+        packets.sort_by_key(|packet| packet.frame);
+        let mut csv = BufWriter::new(OpenOptions::new().append(true).open(path)?);
+        for packet in packets {
+            writeln!(
+                csv,
+                "{},{},{},{}",
+                output, packet.frame, packet.bytes, packet.keyframe as u8
+            )?;
+        }
+        csv.flush()
+    }
+}
+
+/// Creates the `--encode-stats` CSV with its header row; every output
+/// appends its frames when it finishes.
+pub fn create_csv(path: &Path) -> std::io::Result<()> {
+    let mut csv = File::create(path)?;
+    writeln!(csv, "output,frame,bytes,keyframe")
+}
@@ -0,0 +1,56 @@
+//! `--encoder a,b,c`: the encoders to try for color outputs, in order,
+//! instead of whatever FFmpeg registered first for H.264. Batch and worker
+//! runs go to machines with different FFmpeg builds and GPUs, so a chain
+//! like `h264_nvenc,libx264,mpeg4` uses NVENC where there is one and still
+//! encodes where there isn't. An encoder is skipped when the build lacks it,
+//! it can't take the output's pixel format, or it fails to open (e.g. a
+//! hardware encoder without its device); the one used is logged.
+
+use ffmpeg_next as ffmpeg;
+
+/// Parses a comma-separated list of encoder names.
+pub fn parse(text: &str) -> Result<Vec<String>, String> {
+    let names: Vec<String> = text
+        .split(',')
+        .map(|name| name.trim().to_string())
+        .collect();
+    if names.iter().any(String::is_empty) {
+        return Err(format!(
+            "Invalid --encoder: {} (expected encoder names separated by commas, e.g. \
+             h264_nvenc,libx264)",
+            text
+        ));
+    }
+    Ok(names)
+}
+
+/// Opens the first encoder of `names` that `open` succeeds with, logging
+/// the ones skipped and the one used.
+pub fn open<T>(
+    names: &[String],
+    mut open: impl FnMut(ffmpeg::Codec) -> Result<T, Box<dyn std::error::Error>>,
+) -> Result<(ffmpeg::Codec, T), Box<dyn std::error::Error>> {
+    let mut skipped = Vec::new();
+    for name in names {
+        let attempt = match ffmpeg::encoder::find_by_name(name) {
+            None => Err("not in this FFmpeg build".into()),
+            Some(codec) if !codec.is_video() => Err("not a video encoder".into()),
+            Some(codec) => open(codec).map(|opened| (codec, opened)),
+        };
+        match attempt {
+            Ok((codec, opened)) => {
+                status!("  Encoder: {}", codec.name());
+                return Ok((codec, opened));
+            }
+            Err(err) => {
+                status!("  Encoder {} unavailable: {}", name, err);
+                skipped.push(format!("{} ({})", name, err));
+            }
+        }
+    }
+    Err(format!(
+        "No encoder of --encoder could be used: {}",
+        skipped.join(", ")
+    )
+    .into())
+}
@@ -0,0 +1,51 @@
+//! `VIDEO_TRANSPOSE_*` environment variables: every option of a run can
+//! be set as a variable named after it, e.g. `VIDEO_TRANSPOSE_CRF=18` for
+//! `--crf 18` or `VIDEO_TRANSPOSE_NO_PROGRESS=1` for `--no-progress`, so
+//! containers are configured without wrapper scripts. Options on the
+//! command line override the variables, which override the `default`
+//! preset of the config file: a switch on the command line takes its
+//! opposite out (`--no-clobber` a variable's `--overwrite`), and
+//! `--no-<switch>` turns a variable's switch off. Variables that name no
+//! option are ignored with a warning.
+
+use crate::cli::SWITCHES;
+use std::env;
+use std::ffi::OsString;
+
+const PREFIX: &str = "VIDEO_TRANSPOSE_";
+
+/// The options set in the environment, as command-line arguments. Empty
+/// variables are ignored; switches take `1`, `true`, `yes` or `on` to be
+/// set and `0`, `false`, `no` or `off` not to be.
+pub fn options() -> Result<Vec<OsString>, String> {
+    let mut variables: Vec<(String, OsString)> = env::vars_os()
+        .filter_map(|(name, value)| Some((name.into_string().ok()?, value)))
+        .filter(|(name, value)| name.starts_with(PREFIX) && !value.is_empty())
+        .collect();
+    variables.sort();
+    let mut options = Vec::new();
+    for (name, value) in variables {
+        let flag = format!(
+            "--{}",
+            name[PREFIX.len()..].to_lowercase().replace('_', "-")
+        );
+        let value = value
+            .into_string()
+            .map_err(|_| format!("{} is not valid Unicode", name))?;
+        if !SWITCHES.contains(&flag.as_str()) {
+            options.push(format!("{}={}", flag, value).into());
+            continue;
+        }
+        match value.to_lowercase().as_str() {
+            "1" | "true" | "yes" | "on" => options.push(flag.into()),
+            "0" | "false" | "no" | "off" => {}
+            _ => {
+                return Err(format!(
+                    "Invalid {}: {} (expected 1, true, yes or on, or 0, false, no or off)",
+                    name, value
+                ))
+            }
+        }
+    }
+    Ok(options)
+}
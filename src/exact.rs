@@ -0,0 +1,199 @@
+//! `--exact`: a guarantee that every output pixel is an input pixel moved
+//! to a new place, never interpolated, color converted or subsampled.
+//! Options that compute new pixel values are refused when parsing; this
+//! checks what is only known once the input is open, and proves the result:
+//! every output frame is hashed, as transposed and again as decoded back
+//! from the written file, and compared with a hash of the source column it
+//! should show.
+
+use crate::channels::Channel;
+use crate::frame_store::FrameFormat;
+use crate::os_path;
+use crate::pipeline;
+use crate::probe;
+use crate::sink::{FrameSink, OutputFrame};
+use ffmpeg_next as ffmpeg;
+use ffmpeg_next::format::Pixel;
+use ffmpeg_next::software::scaling::{context::Context, flag::Flags};
+use ffmpeg_next::util::frame::video::Video;
+use sha2::{Digest, Sha256};
+use std::path::Path;
+
+type Hash = [u8; 32];
+
+/// Fails unless frames of `input` are stored as `format` without changing
+/// a sample: RGB is only reordered, grayscale only replicated or byte
+/// swapped.
+pub fn check_input(input: Pixel, format: FrameFormat) -> Result<(), String> {
+    let exact = match format {
+        FrameFormat::Rgb24 => matches!(
+            input,
+            Pixel::RGB24 | Pixel::BGR24 | Pixel::GBRP | Pixel::GRAY8
+        ),
+        FrameFormat::Gray16 => matches!(input, Pixel::GRAY16LE | Pixel::GRAY16BE),
+        FrameFormat::RgbF32 => {
+            return Err("--exact can't write EXR sequences: samples would become floats".into())
+        }
+    };
+    if exact {
+        return Ok(());
+    }
+    Err(format!(
+        "--exact needs 8-bit RGB or grayscale input (or 16-bit grayscale with lossless outputs); \
+         {:?} can't be stored as {} without converting its samples",
+        input,
+        format.name()
+    ))
+}
+
+/// Hashes source column `x` of `frames` in the layout of a transposed
+/// frame: row by row, frame by frame. Read straight from the frame store,
+/// independently of the transpose. With `channel`, only that byte of each
+/// RGB pixel, as a split channel output holds.
+pub fn column_hash(
+    frames: &[Vec<u8>],
+    format: FrameFormat,
+    width: usize,
+    height: usize,
+    x: usize,
+    channel: Option<Channel>,
+) -> Hash {
+    let bpp = format.bytes_per_pixel();
+    let bytes = match channel {
+        Some(Channel::R) => 0..1,
+        Some(Channel::G) => 1..2,
+        Some(Channel::B) => 2..3,
+        _ => 0..bpp,
+    };
+    let mut hasher = Sha256::new();
+    for y in 0..height {
+        let offset = (y * width + x) * bpp;
+        for frame in frames {
+            hasher.update(&frame[offset + bytes.start..offset + bytes.end]);
+        }
+    }
+    hasher.finalize().into()
+}
+
+/// Compares output frames, in order, with the hashes of the source columns
+/// they should show.
+pub struct HashCheck {
+    name: String,
+    expected: Vec<Hash>,
+    checked: usize,
+    mismatched: Vec<usize>,
+}
+
+impl HashCheck {
+    pub fn new(name: &str, expected: Vec<Hash>) -> HashCheck {
+        HashCheck {
+            name: name.to_string(),
+            expected,
+            checked: 0,
+            mismatched: Vec::new(),
+        }
+    }
+
+    fn check(&mut self, data: &[u8]) {
+        let actual: Hash = Sha256::digest(data).into();
+        if self.expected.get(self.checked) != Some(&actual) {
+            self.mismatched.push(self.checked);
+        }
+        self.checked += 1;
+    }
+
+    /// Fails unless every expected frame arrived and matched.
+    pub fn result(&self) -> Result<(), String> {
+        if self.checked != self.expected.len() {
+            return Err(format!(
+                "--exact check failed: {} has {} frames, expected {}",
+                self.name,
+                self.checked,
+                self.expected.len()
+            ));
+        }
+        if let Some(first) = self.mismatched.first() {
+            return Err(format!(
+                "--exact check failed: {} of {} frames of {} don't match their source column \
+                 (first: frame {})",
+                self.mismatched.len(),
+                self.checked,
+                self.name,
+                first
+            ));
+        }
+        status!(
+            "  Exact: all {} frames of {} match their source columns",
+            self.checked,
+            self.name
+        );
+        Ok(())
+    }
+}
+
+/// Checks the transposed frames as they are handed to the outputs.
+impl FrameSink for HashCheck {
+    fn write(&mut self, frame: &OutputFrame) -> Result<(), Box<dyn std::error::Error>> {
+        self.check(frame.data);
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(self.result()?)
+    }
+}
+
+/// Decodes the video at `path` and checks every frame, converted to packed
+/// `pixel` samples of `bytes_per_pixel` bytes, against `check`.
+pub fn verify_file(
+    path: &Path,
+    pixel: Pixel,
+    bytes_per_pixel: usize,
+    check: &mut HashCheck,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut ictx = os_path::open_input(path, None)?;
+    let stream_index = probe::video_stream(&ictx, path)?;
+    let parameters = ictx
+        .stream(stream_index)
+        .ok_or("No video stream found")?
+        .parameters();
+    let mut decoder = ffmpeg::codec::context::Context::from_parameters(parameters)?
+        .decoder()
+        .video()?;
+    let mut scaler = None;
+    let mut receive = |decoder: &mut ffmpeg::decoder::Video| -> Result<(), ffmpeg::Error> {
+        let mut decoded = Video::empty();
+        while decoder.receive_frame(&mut decoded).is_ok() {
+            if decoded.format() != pixel {
+                if scaler.is_none() {
+                    scaler = Some(Context::get(
+                        decoded.format(),
+                        decoded.width(),
+                        decoded.height(),
+                        pixel,
+                        decoded.width(),
+                        decoded.height(),
+                        Flags::POINT,
+                    )?);
+                }
+                let mut converted = Video::empty();
+                if let Some(scaler) = &mut scaler {
+                    scaler.run(&decoded, &mut converted)?;
+                }
+                decoded = converted;
+            }
+            let row_bytes = decoded.width() as usize * bytes_per_pixel;
+            check.check(&pipeline::copy_plane(&decoded, 0, row_bytes));
+        }
+        Ok(())
+    };
+    for (stream, packet) in ictx.packets() {
+        if stream.index() == stream_index {
+            decoder.send_packet(&packet)?;
+            receive(&mut decoder)?;
+        }
+    }
+    decoder.send_eof()?;
+    receive(&mut decoder)?;
+    Ok(())
+}
@@ -0,0 +1,215 @@
+//! OpenEXR image sequence output for float frames, written with the `exr`
+//! crate when built with the `exr` feature.
+//!
+//! Each image carries its provenance in its header, since a lone image has
+//! no container or sidecar next to it: the mapping of the frame (source
+//! column and capture time span, as `--embed-mapping` writes it) and the
+//! run parameters as JSON, in `videoTranspose.*` attributes, with the
+//! software name, the frame rate and a comment naming the input in EXR's
+//! standard ones. EXR has no EXIF or XMP blocks; these attributes are what
+//! image tools reading EXR (oiiotool, exrheader, Nuke) show.
+
+use crate::sink::{FrameSink, OutputFrame};
+use crate::split;
+use std::path::{Path, PathBuf};
+
+/// Whether `path` names an OpenEXR image sequence output.
+pub fn is_exr_sequence(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("exr"))
+}
+
+/// Path of frame `index` of a sequence: a `%d` or `%0Nd` in the file name
+/// of `pattern` is replaced by the number, otherwise `_NNNNN` is added
+/// before the extension.
+pub fn frame_path(pattern: &Path, index: usize) -> PathBuf {
+    let numbered = pattern
+        .file_name()
+        .and_then(|name| name.to_str())
+        .and_then(|name| number(name, index));
+    match numbered {
+        Some(name) => pattern.with_file_name(name),
+        None => split::suffixed_path(pattern, &format!("_{:05}", index)),
+    }
+}
+
+/// `name` with its `%d` or `%0Nd` replaced by `index`, if it has one.
+fn number(name: &str, index: usize) -> Option<String> {
+    let start = name.find('%')?;
+    let rest = &name[start + 1..];
+    let end = rest.find('d')?;
+    let spec = &rest[..end];
+    if !spec.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let width = spec.trim_start_matches('0').parse().unwrap_or(0);
+    Some(format!(
+        "{}{:0width$}{}",
+        &name[..start],
+        index,
+        &rest[end + 1..],
+        width = width
+    ))
+}
+
+/// The run behind a sequence, recorded in every image.
+pub struct Provenance {
+    pub input: String,
+    /// Size of the decoded frames.
+    pub source_width: usize,
+    pub source_height: usize,
+    pub fps: (i32, i32),
+    /// `--scale` applied to the decoded frames.
+    pub scale: f64,
+    /// `--frame-step`: source frames per decoded frame kept.
+    pub frame_step: usize,
+}
+
+#[cfg(feature = "exr")]
+impl Provenance {
+    /// The run parameters as a JSON object.
+    fn parameters(&self) -> String {
+        serde_json::json!({
+            "software": software(),
+            "input": self.input,
+            "source_width": self.source_width,
+            "source_height": self.source_height,
+            "fps": format!("{}/{}", self.fps.0, self.fps.1),
+            "scale": self.scale,
+            "frame_step": self.frame_step,
+        })
+        .to_string()
+    }
+}
+
+/// A field of a frame's mapping, e.g. `x` for the source column.
+#[cfg(feature = "exr")]
+fn mapping_field<'a>(mapping: &'a str, key: &str) -> Option<&'a str> {
+    mapping.split(';').find_map(|field| {
+        let (name, value) = field.split_once('=')?;
+        (name == key).then_some(value)
+    })
+}
+
+/// Name and version of this program.
+#[cfg(feature = "exr")]
+fn software() -> String {
+    format!("video-transpose {}", env!("CARGO_PKG_VERSION"))
+}
+
+/// Writes every output frame, packed 32-bit float RGB, as one EXR file.
+pub struct ExrSequence<'a> {
+    pattern: PathBuf,
+    width: usize,
+    height: usize,
+    index: usize,
+    provenance: &'a Provenance,
+}
+
+impl<'a> ExrSequence<'a> {
+    pub fn new(
+        pattern: &Path,
+        width: usize,
+        height: usize,
+        provenance: &'a Provenance,
+    ) -> ExrSequence<'a> {
+        ExrSequence {
+            pattern: pattern.to_path_buf(),
+            width,
+            height,
+            index: 0,
+            provenance,
+        }
+    }
+}
+
+impl FrameSink for ExrSequence<'_> {
+    fn write(&mut self, frame: &OutputFrame) -> Result<(), Box<dyn std::error::Error>> {
+        let path = frame_path(&self.pattern, self.index);
+        write_rgb_f32(
+            &path,
+            frame.data,
+            (self.width, self.height),
+            self.provenance,
+            self.index,
+            frame.mapping,
+        )?;
+        self.index += 1;
+        Ok(())
+    }
+}
+
+/// Writes image `index` of a sequence, recording `provenance` and the
+/// frame's `mapping` in its header.
+#[cfg(feature = "exr")]
+fn write_rgb_f32(
+    path: &Path,
+    data: &[u8],
+    (width, height): (usize, usize),
+    provenance: &Provenance,
+    index: usize,
+    mapping: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    use exr::meta::attribute::{AttributeValue, Text};
+    use exr::prelude::*;
+
+    let sample = |offset: usize| {
+        f32::from_le_bytes([
+            data[offset],
+            data[offset + 1],
+            data[offset + 2],
+            data[offset + 3],
+        ])
+    };
+    let channels = SpecificChannels::rgb(|Vec2(x, y): Vec2<usize>| {
+        let offset = (y * width + x) * 12;
+        (sample(offset), sample(offset + 4), sample(offset + 8))
+    });
+    let mut image = Image::from_channels((width, height), channels);
+
+    // EXR text is Latin-1, anything else (e.g. in the input's path) is left out
+    let attributes = &mut image.layer_data.attributes;
+    attributes.software_name = Text::new_or_none(software());
+    attributes.comments = Text::new_or_none(format!("Transposed from {}", provenance.input));
+    let (numerator, denominator) = provenance.fps;
+    attributes.frames_per_second = u32::try_from(denominator)
+        .ok()
+        .map(|denominator| (numerator, denominator));
+    let mut custom = vec![
+        ("outputFrame", Some(AttributeValue::I32(index as i32))),
+        (
+            "mapping",
+            Text::new_or_none(mapping).map(AttributeValue::Text),
+        ),
+        (
+            "parameters",
+            Text::new_or_none(provenance.parameters()).map(AttributeValue::Text),
+        ),
+    ];
+    if let Some(column) = mapping_field(mapping, "x").and_then(|x| x.parse().ok()) {
+        custom.push(("sourceColumn", Some(AttributeValue::I32(column))));
+    }
+    if let Some(span) = mapping_field(mapping, "t").and_then(Text::new_or_none) {
+        custom.push(("timeRange", Some(AttributeValue::Text(span))));
+    }
+    for (name, value) in custom {
+        if let Some(value) = value {
+            let name = Text::new_or_panic(format!("videoTranspose.{}", name));
+            attributes.other.insert(name, value);
+        }
+    }
+    image.write().to_file(path)?;
+    Ok(())
+}
+
+#[cfg(not(feature = "exr"))]
+fn write_rgb_f32(
+    _path: &Path,
+    _data: &[u8],
+    _size: (usize, usize),
+    _provenance: &Provenance,
+    _index: usize,
+    _mapping: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    Err("EXR output needs a build with --features exr".into())
+}
@@ -0,0 +1,128 @@
+//! Shrinking the decoded frame store: `--scale` downsizes every frame as
+//! it is decoded and `--frame-step` keeps every n-th frame. When the store
+//! won't fit in memory, [`Reduction::fit`] works out how much of each is
+//! needed, which is suggested or, with `--auto-fit`, applied.
+
+/// Smallest `--scale` suggested; below this the output says little.
+const MIN_AUTO_SCALE: f64 = 0.5;
+/// `--scale` values are tried in steps of this.
+const SCALE_STEP: f64 = 0.05;
+
+/// How the decoded frames are reduced.
+#[derive(Clone, Copy, PartialEq)]
+pub struct Reduction {
+    /// Factor applied to both dimensions, at most 1.
+    pub scale: f64,
+    /// Keep one decoded frame out of this many.
+    pub step: usize,
+}
+
+impl Reduction {
+    pub fn new(scale: f64, step: usize) -> Self {
+        Reduction { scale, step }
+    }
+
+    /// Frame size after scaling `width × height`, at least 1×1.
+    pub fn dimensions(self, width: usize, height: usize) -> (usize, usize) {
+        if self.scale >= 1.0 {
+            return (width, height);
+        }
+        let scale = |size: usize| ((size as f64 * self.scale).round() as usize).max(1);
+        (scale(width), scale(height))
+    }
+
+    /// Number of frames kept out of `frames` decoded.
+    pub fn frames(self, frames: u64) -> u64 {
+        frames.div_ceil(self.step as u64)
+    }
+
+    /// Whether the `index`-th decoded frame (from 0) is kept.
+    pub fn keeps(self, index: u64) -> bool {
+        index.is_multiple_of(self.step as u64)
+    }
+
+    /// Bytes of the store for `frames` decoded frames of `width × height`
+    /// at `bytes_per_pixel`.
+    pub fn store_bytes(
+        self,
+        width: usize,
+        height: usize,
+        bytes_per_pixel: usize,
+        frames: u64,
+    ) -> u64 {
+        let (width, height) = self.dimensions(width, height);
+        (width * height * bytes_per_pixel) as u64 * self.frames(frames)
+    }
+
+    /// The reduction as command-line flags, e.g. `--scale 0.95 --frame-step 2`.
+    pub fn flags(self) -> String {
+        let mut flags = Vec::new();
+        if self.scale < 1.0 {
+            flags.push(format!("--scale {:.2}", self.scale));
+        }
+        if self.step > 1 {
+            flags.push(format!("--frame-step {}", self.step));
+        }
+        flags.join(" ")
+    }
+
+    /// Reductions, starting from this one, whose store for `frames` frames
+    /// of `width × height` fits in `budget` bytes: by scaling alone, by
+    /// frame step alone, and by doubling the frame step and scaling the rest.
+    pub fn fit(
+        self,
+        width: usize,
+        height: usize,
+        bytes_per_pixel: usize,
+        frames: u64,
+        budget: u64,
+    ) -> Fit {
+        let fits = |reduction: Reduction| {
+            reduction.store_bytes(width, height, bytes_per_pixel, frames) <= budget
+        };
+        let largest_scale = |step: usize| {
+            let steps = (self.scale / SCALE_STEP + 1e-9).floor() as usize;
+            (1..=steps)
+                .rev()
+                .map(|steps| Reduction::new(steps as f64 * SCALE_STEP, step))
+                .find(|&reduction| fits(reduction))
+        };
+        let by_step = (self.step..=frames.max(1) as usize)
+            .map(|step| Reduction::new(self.scale, step))
+            .find(|&reduction| fits(reduction));
+        Fit {
+            by_scale: largest_scale(self.step),
+            by_step,
+            combined: largest_scale(self.step * 2),
+        }
+    }
+}
+
+/// Ways to make the frame store fit, see [`Reduction::fit`].
+pub struct Fit {
+    pub by_scale: Option<Reduction>,
+    pub by_step: Option<Reduction>,
+    pub combined: Option<Reduction>,
+}
+
+impl Fit {
+    /// The suggestions as command-line flags.
+    pub fn describe(&self) -> Vec<String> {
+        [self.by_scale, self.by_step, self.combined]
+            .into_iter()
+            .flatten()
+            .map(Reduction::flags)
+            .collect()
+    }
+
+    /// The reduction `--auto-fit` applies: scaling alone while frames stay
+    /// at least half size, then scaling with every other frame, then
+    /// dropping frames alone.
+    pub fn choose(&self) -> Option<Reduction> {
+        let large_enough = |reduction: &Reduction| reduction.scale >= MIN_AUTO_SCALE;
+        self.by_scale
+            .filter(large_enough)
+            .or(self.combined.filter(large_enough))
+            .or(self.by_step)
+    }
+}
@@ -0,0 +1,168 @@
+use crate::transpose::luma;
+use ffmpeg_next::format::Pixel;
+
+/// Pixel layout of the frames kept in memory.
+#[derive(Clone, Copy, PartialEq, Default)]
+pub enum FrameFormat {
+    /// Packed RGB24, used for everything but high bit depth grayscale.
+    #[default]
+    Rgb24,
+    /// 16-bit little-endian grayscale, kept at full depth for scientific
+    /// sources and encoded losslessly with FFV1.
+    Gray16,
+    /// Packed 32-bit little-endian float RGB, for EXR sequence outputs.
+    RgbF32,
+}
+
+impl FrameFormat {
+    /// Picks the stored format for frames decoded as `input`. High bit depth
+    /// grayscale keeps its depth only if every output can store it.
+    pub fn for_input(input: Pixel, lossless_outputs: bool) -> FrameFormat {
+        if is_high_depth_gray(input) && lossless_outputs {
+            FrameFormat::Gray16
+        } else {
+            FrameFormat::Rgb24
+        }
+    }
+
+    /// Format frames are converted to when decoding. Float frames are
+    /// converted to planar GBR and interleaved afterwards.
+    pub fn pixel(self) -> Pixel {
+        match self {
+            FrameFormat::Rgb24 => Pixel::RGB24,
+            FrameFormat::Gray16 => Pixel::GRAY16LE,
+            FrameFormat::RgbF32 => Pixel::GBRPF32LE,
+        }
+    }
+
+    /// FFmpeg name of the packed layout, e.g. for `-pixel_format`.
+    pub fn name(self) -> &'static str {
+        match self {
+            FrameFormat::Rgb24 => "rgb24",
+            FrameFormat::Gray16 => "gray16le",
+            FrameFormat::RgbF32 => "rgbf32le",
+        }
+    }
+
+    pub fn bytes_per_pixel(self) -> usize {
+        match self {
+            FrameFormat::Rgb24 => 3,
+            FrameFormat::Gray16 => 2,
+            FrameFormat::RgbF32 => 12,
+        }
+    }
+
+    /// 8-bit luma of every pixel of a frame, for analysis such as heatmaps
+    /// and scene detection.
+    pub fn luma(self, frame: &[u8]) -> Vec<u8> {
+        match self {
+            FrameFormat::Rgb24 => frame.chunks_exact(3).map(luma).collect(),
+            FrameFormat::Gray16 => frame.chunks_exact(2).map(|sample| sample[1]).collect(),
+            FrameFormat::RgbF32 => frame
+                .chunks_exact(12)
+                .map(|pixel| {
+                    let rgb: Vec<u8> = pixel
+                        .chunks_exact(4)
+                        .map(|c| {
+                            let value = f32::from_le_bytes([c[0], c[1], c[2], c[3]]);
+                            (value.clamp(0.0, 1.0) * 255.0).round() as u8
+                        })
+                        .collect();
+                    luma(&rgb)
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Whether `pixel` is grayscale with more than 8 bits per sample.
+pub fn is_high_depth_gray(pixel: Pixel) -> bool {
+    matches!(
+        pixel,
+        Pixel::GRAY9LE
+            | Pixel::GRAY9BE
+            | Pixel::GRAY10LE
+            | Pixel::GRAY10BE
+            | Pixel::GRAY12LE
+            | Pixel::GRAY12BE
+            | Pixel::GRAY14LE
+            | Pixel::GRAY14BE
+            | Pixel::GRAY16LE
+            | Pixel::GRAY16BE
+    )
+}
+
+/// Decoded frames kept in memory for the transpose, along with their
+/// presentation times in seconds from the start of the stream.
+#[derive(Default)]
+pub struct FrameStore {
+    pub frames: Vec<Vec<u8>>,
+    pub times: Vec<Option<f64>>,
+    bytes: u64,
+}
+
+impl FrameStore {
+    pub fn push(&mut self, data: Vec<u8>, time: Option<f64>) {
+        self.bytes += data.len() as u64;
+        self.frames.push(data);
+        self.times.push(time);
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Total size of the stored pixel data.
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// Repeats the last frame at `interval` steps up to a frame at `time`,
+    /// standing in for frames dropped from the stream, and returns how many
+    /// were added. Gaps shorter than one and a half intervals are jitter,
+    /// not drops.
+    pub fn fill_gap(&mut self, time: f64, interval: f64) -> usize {
+        let (Some(last), Some(&Some(last_time))) = (self.frames.last(), self.times.last()) else {
+            return 0;
+        };
+        let missing = ((time - last_time) / interval).round() as i64 - 1;
+        if missing < 1 {
+            return 0;
+        }
+        let last = last.clone();
+        for step in 1..=missing {
+            self.push(last.clone(), Some(last_time + step as f64 * interval));
+        }
+        missing as usize
+    }
+
+    /// Replaces every frame with `convert` of it, e.g. to crop them all.
+    pub fn convert(&mut self, mut convert: impl FnMut(&[u8]) -> Vec<u8>) {
+        for frame in &mut self.frames {
+            *frame = convert(frame);
+        }
+        self.bytes = self.frames.iter().map(|frame| frame.len() as u64).sum();
+    }
+
+    /// Keeps only the frames `keep` is true for.
+    pub fn retain(&mut self, keep: &[bool]) {
+        let mut flags = keep.iter();
+        self.frames
+            .retain(|_| flags.next().copied().unwrap_or(true));
+        let mut flags = keep.iter();
+        self.times.retain(|_| flags.next().copied().unwrap_or(true));
+        self.bytes = self.frames.iter().map(|frame| frame.len() as u64).sum();
+    }
+
+    /// Time spanned by the decoded frames, from the first to the last timestamp.
+    pub fn time_span(&self) -> Option<(f64, f64)> {
+        let mut times = self.times.iter().flatten();
+        let first = *times.next()?;
+        let last = times.fold(first, |last, &t| t.max(last));
+        Some((first, last))
+    }
+}
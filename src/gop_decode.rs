@@ -0,0 +1,249 @@
+//! `--decode-threads`: decoding a long file on several cores. The video
+//! stream is split at keyframes into one range per thread, and each thread
+//! seeks to its range with its own demuxer and decoder. The first range is
+//! decoded on the calling thread and handed on as it goes; later ranges are
+//! buffered until the ones before them are done, so frames still arrive in
+//! order. The buffers are bounded: a range that has decoded its share of
+//! frames ahead waits for its turn, and the frame store's memory budget is
+//! reduced by what they may hold.
+
+use crate::os_path;
+use ffmpeg_next as ffmpeg;
+use ffmpeg_next::util::frame::video::Video;
+use std::os::raw::c_int;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::thread;
+
+/// Ranges shorter than this many frames aren't worth a thread of their own.
+const MIN_RANGE_FRAMES: u64 = 250;
+/// The buffers of the later ranges take at most this share of the memory
+/// budget.
+const BUFFER_SHARE: u64 = 8;
+/// Buffer size without a memory budget to go by.
+const DEFAULT_BUFFER_BYTES: u64 = 1 << 30;
+
+/// Receives decoded frames in order.
+pub type KeepFrame<'a> = dyn FnMut(Video) -> Result<(), Box<dyn std::error::Error>> + 'a;
+
+/// Frames from the keyframe with timestamp `start` (the stream's beginning
+/// if `None`) up to, but not including, the keyframe with timestamp `end`.
+#[derive(Clone, Copy)]
+pub struct GopRange {
+    start: Option<i64>,
+    end: Option<i64>,
+}
+
+impl GopRange {
+    /// Whether a frame with timestamp `pts` belongs to this range. Frames
+    /// without one can't be placed and are kept.
+    fn contains(self, pts: Option<i64>) -> bool {
+        let Some(pts) = pts else {
+            return true;
+        };
+        self.start.is_none_or(|start| pts >= start) && self.end.is_none_or(|end| pts < end)
+    }
+}
+
+/// Splits the video stream of `path` at keyframes into up to `threads`
+/// ranges of similar length. `None` if the stream is too short to be worth
+/// splitting or its keyframes have no timestamps to seek to.
+pub fn plan(
+    path: &Path,
+    stream_index: usize,
+    threads: usize,
+) -> Result<Option<Vec<GopRange>>, Box<dyn std::error::Error>> {
+    let mut ictx = os_path::open_input(path, None)?;
+    // (packet index, timestamp) of each keyframe
+    let mut keyframes = Vec::new();
+    let mut packets = 0u64;
+    for (stream, packet) in ictx.packets() {
+        if stream.index() != stream_index {
+            continue;
+        }
+        if packet.is_key() {
+            match packet.pts() {
+                Some(pts) => keyframes.push((packets, pts)),
+                None => return Ok(None),
+            }
+        }
+        packets += 1;
+    }
+
+    let threads = threads.min((packets / MIN_RANGE_FRAMES) as usize) as u64;
+    let mut starts = Vec::new();
+    for part in 1..threads {
+        let target = packets * part / threads;
+        let next = keyframes.iter().find(|(index, _)| *index >= target);
+        if let Some(&(_, pts)) = next.filter(|&&(_, pts)| starts.last() < Some(&pts)) {
+            starts.push(pts);
+        }
+    }
+    if starts.is_empty() {
+        return Ok(None);
+    }
+    let bounds: Vec<_> = std::iter::once(None)
+        .chain(starts.into_iter().map(Some))
+        .chain(std::iter::once(None))
+        .collect();
+    let ranges = bounds
+        .windows(2)
+        .map(|pair| GopRange {
+            start: pair[0],
+            end: pair[1],
+        })
+        .collect();
+    Ok(Some(ranges))
+}
+
+/// Decoded frames each range after the first of `ranges` may hold ahead of
+/// its turn, for frames of `frame_bytes` and a memory `budget`.
+pub fn buffer_frames(budget: Option<u64>, frame_bytes: u64, ranges: usize) -> usize {
+    let buffer = budget.map_or(DEFAULT_BUFFER_BYTES, |budget| budget / BUFFER_SHARE);
+    (buffer / frame_bytes.max(1) / (ranges.max(2) - 1) as u64).max(1) as usize
+}
+
+/// Decodes `ranges` of the video stream of `path` in parallel, handing the
+/// frames to `keep` in stream order. Each range after the first holds at
+/// most `buffer_frames` frames until its turn.
+pub fn decode(
+    path: &Path,
+    stream_index: usize,
+    ranges: &[GopRange],
+    buffer_frames: usize,
+    keep: &mut KeepFrame,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let Some((&first, rest)) = ranges.split_first() else {
+        return Ok(());
+    };
+    // Set when the run fails, so the other threads stop early
+    let cancel = AtomicBool::new(false);
+    thread::scope(|scope| {
+        let workers: Vec<_> = rest
+            .iter()
+            .map(|&range| {
+                let cancel = &cancel;
+                let (sender, receiver) = mpsc::sync_channel(buffer_frames);
+                let worker = scope.spawn(move || {
+                    decode_range(path, stream_index, range, cancel, &mut |frame| {
+                        // The receiver is gone once the run has failed
+                        if sender.send(frame).is_err() {
+                            cancel.store(true, Ordering::Relaxed);
+                        }
+                        Ok(())
+                    })
+                    .map_err(|error| error.to_string())
+                });
+                (worker, receiver)
+            })
+            .collect();
+
+        // Consuming the workers drops the receivers on failure, which
+        // unblocks threads waiting to hand on a frame
+        let result = (|| -> Result<(), Box<dyn std::error::Error>> {
+            decode_range(path, stream_index, first, &cancel, keep)?;
+            for (worker, receiver) in workers {
+                for frame in receiver {
+                    keep(frame)?;
+                }
+                worker.join().map_err(|_| "A decode thread panicked")??;
+            }
+            Ok(())
+        })();
+        if result.is_err() {
+            cancel.store(true, Ordering::Relaxed);
+        }
+        result
+    })
+}
+
+fn decode_range(
+    path: &Path,
+    stream_index: usize,
+    range: GopRange,
+    cancel: &AtomicBool,
+    keep: &mut KeepFrame,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut ictx = os_path::open_input(path, None)?;
+    let stream = ictx.stream(stream_index).ok_or("No video stream found")?;
+    let mut decoder = ffmpeg::codec::context::Context::from_parameters(stream.parameters())?
+        .decoder()
+        .video()?;
+    if let Some(start) = range.start {
+        seek(&mut ictx, stream_index, start)?;
+    }
+
+    let mut started = range.start.is_none();
+    let mut past_end = false;
+    for (stream, packet) in ictx.packets() {
+        if cancel.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        if stream.index() != stream_index {
+            continue;
+        }
+        let pts = packet.pts();
+        if !started {
+            // The seek may land on an earlier keyframe
+            started = packet.is_key() && pts == range.start;
+            if !started {
+                continue;
+            }
+        }
+        if let Some(end) = range.end {
+            // Packets after the next range's keyframe with earlier
+            // timestamps are frames shown before it, which belong here
+            if past_end && pts.is_some_and(|pts| pts > end) {
+                break;
+            }
+            past_end |= pts == Some(end);
+        }
+        decoder.send_packet(&packet)?;
+        receive_frames(&mut decoder, range, keep)?;
+    }
+    if !started {
+        return Err(format!(
+            "Could not seek to the keyframe at timestamp {} for parallel decoding; try \
+             --decode-threads 1",
+            range.start.unwrap_or(0)
+        )
+        .into());
+    }
+    decoder.send_eof()?;
+    receive_frames(&mut decoder, range, keep)
+}
+
+fn receive_frames(
+    decoder: &mut ffmpeg::decoder::Video,
+    range: GopRange,
+    keep: &mut KeepFrame,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut decoded = Video::empty();
+    while decoder.receive_frame(&mut decoded).is_ok() {
+        if range.contains(decoded.timestamp()) {
+            keep(std::mem::replace(&mut decoded, Video::empty()))?;
+        }
+    }
+    Ok(())
+}
+
+/// Seeks to the keyframe at or before `pts` in the stream's time base.
+fn seek(
+    ictx: &mut ffmpeg::format::context::Input,
+    stream_index: usize,
+    pts: i64,
+) -> Result<(), ffmpeg::Error> {
+    let result = unsafe {
+        ffmpeg::ffi::av_seek_frame(
+            ictx.as_mut_ptr(),
+            stream_index as c_int,
+            pts,
+            ffmpeg::ffi::AVSEEK_FLAG_BACKWARD as c_int,
+        )
+    };
+    if result < 0 {
+        return Err(ffmpeg::Error::from(result));
+    }
+    Ok(())
+}
@@ -0,0 +1,203 @@
+use crate::frame_store::FrameFormat;
+use std::ops::Range;
+
+/// Per-pixel statistic shown by `--heatmap`.
+#[derive(Clone, Copy, PartialEq)]
+pub enum HeatmapStat {
+    /// Temporal variance of each pixel's luma.
+    Variance,
+    /// Mean absolute luma difference between consecutive frames.
+    Motion,
+}
+
+impl HeatmapStat {
+    pub fn parse(text: &str) -> Result<HeatmapStat, String> {
+        match text {
+            "variance" => Ok(HeatmapStat::Variance),
+            "motion" => Ok(HeatmapStat::Motion),
+            _ => Err(format!(
+                "Invalid heatmap statistic: {} (expected variance or motion)",
+                text
+            )),
+        }
+    }
+}
+
+/// `--auto-roi`: pick the most active run of source columns to transpose.
+#[derive(Clone, Copy)]
+pub struct AutoRoi {
+    /// Number of columns to select; defaults to a quarter of the candidates.
+    pub columns: Option<usize>,
+}
+
+impl AutoRoi {
+    /// Parses `motion` or `motion:<columns>`.
+    pub fn parse(text: &str) -> Result<AutoRoi, String> {
+        let (method, columns) = match text.split_once(':') {
+            Some((method, columns)) => (method, Some(columns)),
+            None => (text, None),
+        };
+        if method != "motion" {
+            return Err(format!(
+                "Invalid --auto-roi method: {} (expected motion or motion:<columns>)",
+                method
+            ));
+        }
+        let columns = match columns {
+            Some(columns) => match columns.parse::<usize>() {
+                Ok(columns) if columns > 0 => Some(columns),
+                _ => return Err(format!("Invalid --auto-roi column count: {}", columns)),
+            },
+            None => None,
+        };
+        Ok(AutoRoi { columns })
+    }
+
+    /// Number of columns to select out of `available` candidates.
+    pub fn width(&self, available: usize) -> usize {
+        self.columns
+            .unwrap_or(available.div_ceil(4))
+            .clamp(1, available.max(1))
+    }
+}
+
+/// Activity of every pixel over the decoded frames, row-major.
+pub struct Heatmap {
+    pub width: usize,
+    pub height: usize,
+    pub values: Vec<f32>,
+}
+
+impl Heatmap {
+    /// Computes `stat` for every pixel of `frames`.
+    pub fn compute(
+        frames: &[Vec<u8>],
+        format: FrameFormat,
+        width: usize,
+        height: usize,
+        stat: HeatmapStat,
+    ) -> Heatmap {
+        let pixels = width * height;
+        let mut sum = vec![0f64; pixels];
+        let mut sum_sq = vec![0f64; pixels];
+        let mut previous: Option<Vec<u8>> = None;
+
+        for frame in frames {
+            let luma = format.luma(frame);
+            match stat {
+                HeatmapStat::Variance => {
+                    for (i, &value) in luma.iter().enumerate() {
+                        let value = f64::from(value);
+                        sum[i] += value;
+                        sum_sq[i] += value * value;
+                    }
+                }
+                HeatmapStat::Motion => {
+                    if let Some(previous) = &previous {
+                        for (i, (&a, &b)) in luma.iter().zip(previous).enumerate() {
+                            sum[i] += f64::from(a.abs_diff(b));
+                        }
+                    }
+                    previous = Some(luma);
+                }
+            }
+        }
+
+        let count = match stat {
+            HeatmapStat::Variance => frames.len(),
+            HeatmapStat::Motion => frames.len().saturating_sub(1),
+        }
+        .max(1) as f64;
+        let values = match stat {
+            HeatmapStat::Variance => sum
+                .iter()
+                .zip(&sum_sq)
+                .map(|(s, sq)| {
+                    let mean = s / count;
+                    (sq / count - mean * mean).max(0.0) as f32
+                })
+                .collect(),
+            HeatmapStat::Motion => sum.iter().map(|s| (s / count) as f32).collect(),
+        };
+        Heatmap {
+            width,
+            height,
+            values,
+        }
+    }
+
+    /// Renders the heatmap as packed RGB24 on a black-red-yellow-white ramp,
+    /// scaled so the 99th percentile is white and a few hot pixels don't wash
+    /// out the rest.
+    pub fn to_rgb(&self) -> Vec<u8> {
+        let mut sorted = self.values.clone();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        let scale = sorted
+            .get(sorted.len() * 99 / 100)
+            .copied()
+            .filter(|&max| max > 0.0)
+            .unwrap_or(1.0);
+
+        let mut rgb = Vec::with_capacity(self.values.len() * 3);
+        for &value in &self.values {
+            let t = (value / scale).clamp(0.0, 1.0) * 3.0;
+            let channel = |offset: f32| ((t - offset).clamp(0.0, 1.0) * 255.0) as u8;
+            rgb.extend_from_slice(&[channel(0.0), channel(1.0), channel(2.0)]);
+        }
+        rgb
+    }
+
+    /// Returns the most active column and row, i.e. the best candidates for
+    /// `--columns` and for studying a row in the output.
+    pub fn hottest(&self) -> (usize, usize) {
+        let columns = self.column_activity();
+        let mut rows = vec![0f64; self.height];
+        for (i, &value) in self.values.iter().enumerate() {
+            rows[i / self.width] += f64::from(value);
+        }
+        let argmax = |sums: &[f64]| {
+            sums.iter()
+                .enumerate()
+                .max_by(|a, b| a.1.total_cmp(b.1))
+                .map_or(0, |(index, _)| index)
+        };
+        (argmax(&columns), argmax(&rows))
+    }
+
+    /// Returns the run of `count` adjacent columns within `candidates` with
+    /// the highest total activity.
+    pub fn most_active_columns(&self, candidates: Range<usize>, count: usize) -> Range<usize> {
+        let activity = self.column_activity();
+        let offset = candidates.start;
+        let candidates = &activity[candidates];
+        let count = count.min(candidates.len());
+        let mut window: f64 = candidates[..count].iter().sum();
+        let (mut best, mut best_start) = (window, 0);
+        for start in 1..=candidates.len() - count {
+            window += candidates[start + count - 1] - candidates[start - 1];
+            if window > best {
+                best = window;
+                best_start = start;
+            }
+        }
+        let start = offset + best_start;
+        start..start + count
+    }
+
+    /// Mean value of each column, e.g. its temporal variance.
+    pub fn column_means(&self) -> Vec<f64> {
+        let height = self.height.max(1) as f64;
+        self.column_activity()
+            .into_iter()
+            .map(|sum| sum / height)
+            .collect()
+    }
+
+    fn column_activity(&self) -> Vec<f64> {
+        let mut columns = vec![0f64; self.width];
+        for (i, &value) in self.values.iter().enumerate() {
+            columns[i % self.width] += f64::from(value);
+        }
+        columns
+    }
+}
@@ -0,0 +1,146 @@
+//! `--index`: a random-access index of where every output pixel came from,
+//! so tools can answer "output frame 512, column 800 came from input frame
+//! 800, column 512" by lookup rather than by redoing the run's crops,
+//! frame steps, windows and blends. One index is written per output file,
+//! as `<name>.index.json` next to it (`--index sidecar`) or as a JSON
+//! attachment inside Matroska outputs (`--index embed`).
+//!
+//! The index holds an entry per output frame, naming the source column it
+//! shows (or the two it blends, with `--interpolate`), and one per column
+//! of the transposed image naming the decoded source frame and its time.
+//! Pixel `(x, y)` of output frame `f` is then source frame
+//! `columns[x].frame`, column `frames[f].column`, row `y`, all in the
+//! decoded frames' geometry; divide the column and row by `scale` for the
+//! input's. Files encoded at another size (`--also-encode`) map their
+//! pixels onto the `grid` in proportion.
+
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+
+/// Where the index goes.
+#[derive(Clone, Copy, PartialEq)]
+pub enum IndexMode {
+    /// A `<name>.index.json` file next to each output.
+    Sidecar,
+    /// An attachment in each (Matroska) output.
+    Embed,
+}
+
+impl IndexMode {
+    pub fn parse(text: &str) -> Result<IndexMode, String> {
+        match text {
+            "sidecar" => Ok(IndexMode::Sidecar),
+            "embed" => Ok(IndexMode::Embed),
+            _ => Err(format!(
+                "Invalid --index: {} (expected sidecar or embed)",
+                text
+            )),
+        }
+    }
+}
+
+/// File name of the embedded index.
+pub const ATTACHMENT_NAME: &str = "video-transpose-index.json";
+/// MIME type of the embedded index.
+pub const ATTACHMENT_MIME_TYPE: &str = "application/json";
+
+/// The contributions behind one output file.
+pub struct RunIndex<'a> {
+    pub input: &'a str,
+    /// Size of the decoded frames.
+    pub source_width: usize,
+    pub source_height: usize,
+    /// `--scale` applied to the decoded frames.
+    pub scale: f64,
+    /// `--frame-step`: source frames per decoded frame kept.
+    pub frame_step: usize,
+    /// `--temporal-smooth` radius; each column then mixes its neighbours.
+    pub temporal_smooth: usize,
+    /// `--legend` frames before the first transposed frame.
+    pub legend_frames: usize,
+    /// `--interpolate` frames blended between transposed frames.
+    pub interpolate: usize,
+    /// Output frame index (after the legend and blends) → source column.
+    pub columns: &'a [usize],
+    /// Decoded frame behind each column of the transposed image, where it
+    /// is still known (not after `--resample` or a cube cache hit).
+    pub source_frames: &'a [Option<u64>],
+    /// Seconds from the stream start of each column of the transposed image.
+    pub times: &'a [Option<f64>],
+    /// Width of the transposed image, including encoder padding.
+    pub width: usize,
+}
+
+impl RunIndex<'_> {
+    /// The index of the file at `output`, encoded at `width × height`.
+    pub fn to_json(&self, output: &str, width: usize, height: usize) -> Value {
+        let mut frames = vec![json!({"legend": true}); self.legend_frames];
+        for (i, &column) in self.columns.iter().enumerate() {
+            if let Some(&previous) = i.checked_sub(1).and_then(|i| self.columns.get(i)) {
+                for step in 1..=self.interpolate {
+                    frames.push(json!({
+                        "column": previous,
+                        "blend_column": column,
+                        "blend_weight": step as f64 / (self.interpolate + 1) as f64,
+                    }));
+                }
+            }
+            frames.push(json!({"column": column}));
+        }
+
+        // Padding columns repeat the last frame
+        let last = self.times.len().saturating_sub(1);
+        let columns: Vec<Value> = (0..self.width)
+            .map(|x| {
+                let x = x.min(last);
+                json!({
+                    "frame": self.source_frames.get(x).copied().flatten(),
+                    "time": self.times.get(x).copied().flatten(),
+                })
+            })
+            .collect();
+
+        json!({
+            "version": 1,
+            "tool": concat!("video-transpose ", env!("CARGO_PKG_VERSION")),
+            "input": self.input,
+            "output": output,
+            "source": {
+                "width": self.source_width,
+                "height": self.source_height,
+                "scale": self.scale,
+                "frame_step": self.frame_step,
+                "temporal_smooth": self.temporal_smooth,
+            },
+            "grid": {
+                "width": self.width,
+                "height": self.source_height,
+                "content_width": self.times.len(),
+            },
+            "output": {
+                "width": width,
+                "height": height,
+                "frames": frames.len(),
+            },
+            "frames": frames,
+            "columns": columns,
+        })
+    }
+}
+
+/// `<name>.index.json` for the output at `path`.
+pub fn sidecar_path(path: &Path) -> PathBuf {
+    path.with_extension("index.json")
+}
+
+/// Checks that the output at `path`, written by `muxer`, can hold an
+/// embedded index.
+pub fn check_embed(path: &Path, muxer: Option<&str>) -> Result<(), String> {
+    match muxer {
+        Some("matroska") => Ok(()),
+        _ => Err(format!(
+            "--index embed needs Matroska (.mkv) outputs, {} isn't; use --index sidecar",
+            path.display()
+        )),
+    }
+}
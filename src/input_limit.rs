@@ -0,0 +1,49 @@
+/// `--frame-limit` and `--duration-limit`: hard caps on how much of the
+/// input is decoded, so pointing the tool at a three-hour recording by
+/// mistake stops at once instead of after filling memory. Complements
+/// [`MemoryGuard`](crate::memory::MemoryGuard), which only knows bytes.
+pub struct InputLimit {
+    frames: Option<u64>,
+    seconds: Option<f64>,
+    fps: f64,
+}
+
+impl InputLimit {
+    pub fn new(frames: Option<u64>, seconds: Option<f64>, fps: f64) -> Self {
+        InputLimit {
+            frames,
+            seconds,
+            fps,
+        }
+    }
+
+    /// Checks the input's estimated frame count before decoding.
+    pub fn check_estimate(&self, frames: u64) -> Result<(), String> {
+        self.check(frames, None, "Input has about")
+    }
+
+    /// Checks a decoded frame before it is stored: `count` frames including
+    /// it, `time` its timestamp if it has one.
+    pub fn check_frame(&self, count: u64, time: Option<f64>) -> Result<(), String> {
+        self.check(count, time, "Input reached")
+    }
+
+    fn check(&self, frames: u64, time: Option<f64>, what: &str) -> Result<(), String> {
+        if let Some(limit) = self.frames.filter(|&limit| frames > limit) {
+            return Err(format!(
+                "{} {} frames, over the --frame-limit of {}. Trim the input or raise the limit.",
+                what, frames, limit
+            ));
+        }
+        let valid_fps = self.fps.is_finite() && self.fps > 0.0;
+        let seconds = time.or_else(|| valid_fps.then(|| frames as f64 / self.fps));
+        match (self.seconds, seconds) {
+            (Some(limit), Some(seconds)) if seconds > limit => Err(format!(
+                "{} {:.1} seconds of video, over the --duration-limit of {}s. Trim the input or \
+                 raise the limit.",
+                what, seconds, limit
+            )),
+            _ => Ok(()),
+        }
+    }
+}
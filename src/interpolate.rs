@@ -0,0 +1,43 @@
+use crate::frame_store::FrameFormat;
+use crate::linear_light::LinearLight;
+
+/// Linear blend of two equally sized frames: `a` at `alpha = 0`, `b` at 1,
+/// of the samples or, with `light`, of the linear light they encode.
+pub fn blend(
+    a: &[u8],
+    b: &[u8],
+    alpha: f32,
+    format: FrameFormat,
+    light: Option<&LinearLight>,
+) -> Vec<u8> {
+    if let Some(light) = light {
+        return light.blend(a, b, alpha, format);
+    }
+    let weight_b = (alpha.clamp(0.0, 1.0) * 256.0) as u32;
+    let weight_a = 256 - weight_b;
+    match format {
+        FrameFormat::Rgb24 => a
+            .iter()
+            .zip(b)
+            .map(|(&a, &b)| ((a as u32 * weight_a + b as u32 * weight_b + 128) >> 8) as u8)
+            .collect(),
+        FrameFormat::Gray16 => a
+            .chunks_exact(2)
+            .zip(b.chunks_exact(2))
+            .flat_map(|(a, b)| {
+                let a = u16::from_le_bytes([a[0], a[1]]) as u32;
+                let b = u16::from_le_bytes([b[0], b[1]]) as u32;
+                (((a * weight_a + b * weight_b + 128) >> 8) as u16).to_le_bytes()
+            })
+            .collect(),
+        FrameFormat::RgbF32 => a
+            .chunks_exact(4)
+            .zip(b.chunks_exact(4))
+            .flat_map(|(a, b)| {
+                let a = f32::from_le_bytes([a[0], a[1], a[2], a[3]]);
+                let b = f32::from_le_bytes([b[0], b[1], b[2], b[3]]);
+                (a + (b - a) * alpha.clamp(0.0, 1.0)).to_le_bytes()
+            })
+            .collect(),
+    }
+}
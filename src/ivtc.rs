@@ -0,0 +1,246 @@
+//! `--ivtc`: inverse telecine. Film transferred to 29.97 fps video with
+//! 3:2 pulldown repeats a field in two of every five frames. Those frames
+//! mix two film frames, which shows up as periodic banding once time runs
+//! along the transposed frames' x axis. `fieldmatch` rebuilds the film
+//! frames from their fields and `decimate` drops the duplicate left over,
+//! restoring the 23.976 fps original. It drops one frame in five, so it
+//! only runs with `--ivtc on`; the default `auto` just warns when it finds
+//! pulldown.
+
+use crate::cli::Options;
+use crate::source::{self, FrameSource, InputInfo, StoreFrame};
+use ffmpeg_next as ffmpeg;
+use ffmpeg_next::filter;
+use ffmpeg_next::format::Pixel;
+use ffmpeg_next::util::frame::video::Video;
+use std::fmt;
+
+/// Frames examined by `--ivtc auto`.
+const SAMPLE_FRAMES: usize = 300;
+/// Share of sampled frames with a repeated field that counts as pulldown;
+/// 3:2 pulldown repeats one in two of every five frames.
+const PULLDOWN_SHARE: std::ops::RangeInclusive<f64> = 0.3..=0.5;
+/// Frame timestamps inside the filter graph are in microseconds.
+const TIME_SCALE: f64 = 1_000_000.0;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Ivtc {
+    /// Warn about pulldown found in file inputs at NTSC rates.
+    Auto,
+    On,
+    Off,
+}
+
+impl Ivtc {
+    pub fn parse(text: &str) -> Result<Ivtc, String> {
+        match text {
+            "auto" => Ok(Ivtc::Auto),
+            "on" => Ok(Ivtc::On),
+            "off" => Ok(Ivtc::Off),
+            _ => Err(format!(
+                "Invalid --ivtc: {} (expected auto, on or off)",
+                text
+            )),
+        }
+    }
+}
+
+/// Wraps `input` in inverse telecine when `--ivtc on` asks for it,
+/// updating `info` for the lower frame rate. With `auto`, only warns if
+/// pulldown is detected.
+pub fn apply(
+    input: Box<dyn FrameSource>,
+    info: &mut InputInfo,
+    options: &Options,
+) -> Result<Box<dyn FrameSource>, Box<dyn std::error::Error>> {
+    let wanted = match options.ivtc {
+        Ivtc::Off => false,
+        Ivtc::On => true,
+        Ivtc::Auto => {
+            if detect(options, info)? {
+                status!("  Warning: pass --ivtc on to remove the pulldown before transposing");
+            }
+            false
+        }
+    };
+    if !wanted {
+        return Ok(input);
+    }
+    let source = IvtcSource {
+        inner: input,
+        width: info.width,
+        height: info.height,
+        format: info.format,
+        fps: info.fps,
+    };
+    // decimate keeps four of every five frames
+    info.fps = ffmpeg::Rational(info.fps.numerator() * 4, info.fps.denominator() * 5).reduce();
+    info.estimated_frames = info.estimated_frames.map(|frames| frames * 4 / 5);
+    status!(
+        "  Inverse telecine: fieldmatch and decimate to {:.3} fps",
+        f64::from(info.fps)
+    );
+    Ok(Box::new(source))
+}
+
+/// Looks for 3:2 pulldown in the first frames of a file input at 29.97 or
+/// 30 fps, using `idet`'s repeated field detection.
+fn detect(options: &Options, info: &InputInfo) -> Result<bool, Box<dyn std::error::Error>> {
+    let fps = f64::from(info.fps);
+    let ntsc = (fps - 30000.0 / 1001.0).abs() < 0.01 || (fps - 30.0).abs() < 0.01;
+    if !ntsc || options.raw_format.is_some() || !options.input.is_file() {
+        return Ok(false);
+    }
+
+    let (mut sample, _) = source::open(options)?;
+    let mut graph = filter_graph(info.width, info.height, info.format, info.fps, "idet")?;
+    let mut filtered = Video::empty();
+    let (mut frames, mut repeated) = (0, 0);
+    let mut index = 0;
+    let result = sample.decode_all(&mut |decoded, _| {
+        add_frame(&mut graph, decoded, index as f64 / fps)?;
+        index += 1;
+        while receive_frame(&mut graph, &mut filtered) {
+            frames += 1;
+            let field = filtered
+                .metadata()
+                .get("lavfi.idet.repeated.current_frame")
+                .map(str::to_string);
+            if field.is_some_and(|field| field != "neither") {
+                repeated += 1;
+            }
+        }
+        if frames >= SAMPLE_FRAMES {
+            return Err(Box::new(SampleDone));
+        }
+        Ok(())
+    });
+    match result {
+        Err(error) if !error.is::<SampleDone>() => return Err(error),
+        _ => {}
+    }
+    if frames < SAMPLE_FRAMES / 2 {
+        return Ok(false);
+    }
+
+    let share = repeated as f64 / frames as f64;
+    let telecined = PULLDOWN_SHARE.contains(&share);
+    if telecined {
+        status!(
+            "  3:2 pulldown detected ({:.0}% of sampled frames repeat a field)",
+            share * 100.0
+        );
+    }
+    Ok(telecined)
+}
+
+/// Stops the detection's decode once enough frames are sampled.
+#[derive(Debug)]
+struct SampleDone;
+
+impl fmt::Display for SampleDone {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("sampling done")
+    }
+}
+
+impl std::error::Error for SampleDone {}
+
+/// Decoded frames passed through `fieldmatch,decimate`.
+struct IvtcSource {
+    inner: Box<dyn FrameSource>,
+    width: usize,
+    height: usize,
+    format: Pixel,
+    fps: ffmpeg::Rational,
+}
+
+impl FrameSource for IvtcSource {
+    fn decode_all(&mut self, store: &mut StoreFrame) -> Result<(), Box<dyn std::error::Error>> {
+        let mut graph = filter_graph(
+            self.width,
+            self.height,
+            self.format,
+            self.fps,
+            "fieldmatch,decimate",
+        )?;
+        let mut filtered = Video::empty();
+        let frame_seconds = 1.0 / f64::from(self.fps);
+        let mut index = 0;
+        let mut store_filtered = |graph: &mut filter::Graph, store: &mut StoreFrame| {
+            while receive_frame(graph, &mut filtered) {
+                let time = filtered.pts().map(|pts| pts as f64 / TIME_SCALE);
+                store(&filtered, time)?;
+            }
+            Ok::<(), Box<dyn std::error::Error>>(())
+        };
+        self.inner.decode_all(&mut |decoded, time| {
+            let time = time.unwrap_or(index as f64 * frame_seconds);
+            index += 1;
+            add_frame(&mut graph, decoded, time)?;
+            store_filtered(&mut graph, store)
+        })?;
+        graph
+            .get("in")
+            .ok_or("Filter input missing")?
+            .source()
+            .flush()?;
+        store_filtered(&mut graph, store)
+    }
+
+    fn buffer_bytes(&self) -> u64 {
+        self.inner.buffer_bytes()
+    }
+}
+
+/// A filter graph running `filters` on frames of `width × height` in
+/// `format`, fed through `add_frame` and read with `receive_frame`.
+fn filter_graph(
+    width: usize,
+    height: usize,
+    format: Pixel,
+    fps: ffmpeg::Rational,
+    filters: &str,
+) -> Result<filter::Graph, ffmpeg::Error> {
+    let mut graph = filter::Graph::new();
+    let args = format!(
+        "video_size={}x{}:pix_fmt={}:time_base=1/{}:frame_rate={}/{}:pixel_aspect=1/1",
+        width,
+        height,
+        ffmpeg::ffi::AVPixelFormat::from(format) as i32,
+        TIME_SCALE as i64,
+        fps.numerator(),
+        fps.denominator()
+    );
+    let buffer = filter::find("buffer").ok_or(ffmpeg::Error::FilterNotFound)?;
+    let buffersink = filter::find("buffersink").ok_or(ffmpeg::Error::FilterNotFound)?;
+    graph.add(&buffer, "in", &args)?;
+    graph.add(&buffersink, "out", "")?;
+    graph.output("in", 0)?.input("out", 0)?.parse(filters)?;
+    graph.validate()?;
+    Ok(graph)
+}
+
+/// Feeds a frame shown at `time` seconds into the graph.
+fn add_frame(
+    graph: &mut filter::Graph,
+    frame: &Video,
+    time: f64,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut frame = frame.clone();
+    frame.set_pts(Some((time * TIME_SCALE).round() as i64));
+    graph
+        .get("in")
+        .ok_or("Filter input missing")?
+        .source()
+        .add(&frame)?;
+    Ok(())
+}
+
+/// Takes the next filtered frame, if one is ready.
+fn receive_frame(graph: &mut filter::Graph, frame: &mut Video) -> bool {
+    match graph.get("out") {
+        Some(mut sink) => sink.sink().frame(frame).is_ok(),
+        None => false,
+    }
+}
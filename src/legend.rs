@@ -0,0 +1,195 @@
+//! `--legend`: a title card at the start of the output explaining the axis
+//! mapping, so a transposed clip passed around without context still reads
+//! as what it is. Text is drawn with a built-in 5×7 pixel font, scaled up
+//! to fit the frame.
+
+use crate::cli::Options;
+use crate::frame_store::FrameFormat;
+use crate::stats;
+use ffmpeg_next as ffmpeg;
+
+const GLYPH_WIDTH: usize = 5;
+const GLYPH_HEIGHT: usize = 7;
+/// Cell size of one character, with a column and a row of spacing.
+const CELL_WIDTH: usize = GLYPH_WIDTH + 1;
+const CELL_HEIGHT: usize = GLYPH_HEIGHT + 2;
+
+/// What the legend says about a run.
+pub struct Legend<'a> {
+    pub options: &'a Options,
+    pub source_width: usize,
+    pub source_height: usize,
+    /// Top-left pixel of the frames in the decoded ones, after `--autocrop`.
+    pub origin: (usize, usize),
+    pub fps: ffmpeg::Rational,
+    /// Source frames along the output's horizontal axis.
+    pub frames: usize,
+    /// Source column of each output frame.
+    pub columns: &'a [usize],
+}
+
+impl Legend<'_> {
+    /// The legend's text, one line per entry.
+    pub fn lines(&self) -> Vec<String> {
+        let name = self
+            .options
+            .input
+            .file_name()
+            .map_or_else(|| "-".into(), |name| name.to_string_lossy());
+        let fps = f64::from(self.fps);
+        let duration = if fps.is_finite() && fps > 0.0 {
+            format!(" ({:.1}S)", self.frames as f64 / fps)
+        } else {
+            String::new()
+        };
+        let mut lines = vec![
+            "VIDEO-TRANSPOSE: X AND TIME SWAPPED".to_string(),
+            format!("SOURCE: {}", name),
+            format!(
+                "{}X{} AT {} FPS",
+                self.source_width,
+                self.source_height,
+                stats::format_rate(self.fps)
+            ),
+            String::new(),
+            format!("HORIZONTAL: TIME, {} FRAMES{}", self.frames, duration),
+            format!(
+                "VERTICAL: SOURCE ROWS {}-{}",
+                self.origin.1,
+                self.origin.1 + self.source_height.saturating_sub(1)
+            ),
+        ];
+        if let (Some(first), Some(last)) = (self.columns.first(), self.columns.last()) {
+            lines.push(format!(
+                "EACH FRAME: ONE SOURCE COLUMN, {} TO {}",
+                first + self.origin.0,
+                last + self.origin.0
+            ));
+        }
+        let mut parameters = Vec::new();
+        if self.options.boomerang {
+            parameters.push("BOOMERANG".to_string());
+        }
+        if self.options.interpolate > 0 {
+            parameters.push(format!("INTERPOLATE {}", self.options.interpolate));
+        }
+        if self.options.temporal_smooth > 0 {
+            parameters.push(format!("SMOOTH {}", self.options.temporal_smooth));
+        }
+        if !parameters.is_empty() {
+            lines.push(format!("PARAMETERS: {}", parameters.join(", ")));
+        }
+        lines
+    }
+}
+
+/// Number of legend frames shown for `seconds` at `fps`, at least one.
+pub fn frame_count(seconds: f64, fps: ffmpeg::Rational) -> usize {
+    (seconds * f64::from(fps)).round().max(1.0) as usize
+}
+
+/// Draws `lines` white on black into a packed `width × height` frame of
+/// `format`, at the largest whole scale that fits. Text that doesn't fit
+/// even unscaled is cut off at the frame's edges.
+pub fn render(lines: &[String], width: usize, height: usize, format: FrameFormat) -> Vec<u8> {
+    let columns = lines
+        .iter()
+        .map(|line| line.chars().count())
+        .max()
+        .unwrap_or(0);
+    // One cell of margin around the text
+    let scale_x = width / ((columns + 2) * CELL_WIDTH);
+    let scale_y = height / ((lines.len() + 2) * CELL_HEIGHT);
+    let scale = scale_x.min(scale_y).max(1);
+
+    let bpp = format.bytes_per_pixel();
+    let white = white_pixel(format);
+    let mut frame = vec![0u8; width * height * bpp];
+    let left = width.saturating_sub(columns * CELL_WIDTH * scale) / 2;
+    let top = height.saturating_sub(lines.len() * CELL_HEIGHT * scale) / 2;
+    for (row, line) in lines.iter().enumerate() {
+        for (column, c) in line.chars().enumerate() {
+            let glyph = glyph(c);
+            for (gy, bits) in glyph.iter().enumerate() {
+                for gx in 0..GLYPH_WIDTH {
+                    if bits & (1 << (GLYPH_WIDTH - 1 - gx)) == 0 {
+                        continue;
+                    }
+                    let x0 = left + (column * CELL_WIDTH + gx) * scale;
+                    let y0 = top + (row * CELL_HEIGHT + gy) * scale;
+                    for y in y0..(y0 + scale).min(height) {
+                        for x in x0..(x0 + scale).min(width) {
+                            let offset = (y * width + x) * bpp;
+                            frame[offset..offset + bpp].copy_from_slice(&white);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    frame
+}
+
+fn white_pixel(format: FrameFormat) -> Vec<u8> {
+    match format {
+        FrameFormat::Rgb24 => vec![255; 3],
+        FrameFormat::Gray16 => vec![255; 2],
+        FrameFormat::RgbF32 => 1.0f32.to_ne_bytes().repeat(3),
+    }
+}
+
+/// Rows of a character, top to bottom, the leftmost pixel in bit 4.
+/// Lowercase letters are drawn as capitals and anything else as `?`.
+fn glyph(c: char) -> [u8; GLYPH_HEIGHT] {
+    match c.to_ascii_uppercase() {
+        'A' => [0x0E, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'B' => [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E],
+        'C' => [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E],
+        'D' => [0x1E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x1E],
+        'E' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F],
+        'F' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10],
+        'G' => [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F],
+        'H' => [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'I' => [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        'J' => [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C],
+        'K' => [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11],
+        'L' => [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F],
+        'M' => [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11],
+        'N' => [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11],
+        'O' => [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'P' => [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10],
+        'Q' => [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D],
+        'R' => [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11],
+        'S' => [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E],
+        'T' => [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+        'U' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'V' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04],
+        'W' => [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A],
+        'X' => [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11],
+        'Y' => [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04],
+        'Z' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F],
+        '0' => [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E],
+        '1' => [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        '2' => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F],
+        '3' => [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E],
+        '4' => [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02],
+        '5' => [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E],
+        '6' => [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E],
+        '7' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+        '8' => [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E],
+        '9' => [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C],
+        ' ' => [0x00; GLYPH_HEIGHT],
+        '.' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C],
+        ',' => [0x00, 0x00, 0x00, 0x00, 0x0C, 0x04, 0x08],
+        ':' => [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00],
+        '-' => [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00],
+        '_' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1F],
+        '+' => [0x00, 0x04, 0x04, 0x1F, 0x04, 0x04, 0x00],
+        '=' => [0x00, 0x00, 0x1F, 0x00, 0x1F, 0x00, 0x00],
+        '/' => [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00],
+        '(' => [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02],
+        ')' => [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08],
+        '%' => [0x18, 0x19, 0x02, 0x04, 0x08, 0x13, 0x03],
+        _ => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04],
+    }
+}
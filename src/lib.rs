@@ -0,0 +1,91 @@
+//! Swaps the X and time axes of a video: every output frame shows one
+//! source column over time. The `video-transpose` binary is a thin wrapper
+//! around [`run`]; [`run_with_callback`] hands the transposed frames to the
+//! caller instead of encoding them, and a [`Seeker`] computes single
+//! output frames on demand for interactive viewers.
+
+#[macro_use]
+pub mod console;
+
+pub mod audio;
+pub mod autocrop;
+pub mod canvas;
+pub mod captions;
+pub mod channels;
+pub mod checksum;
+pub mod chroma;
+pub mod cli;
+pub mod cloud;
+pub mod color_range;
+pub mod compare;
+pub mod conform;
+pub mod container;
+pub mod cube_cache;
+pub mod doctor;
+pub mod encode;
+pub mod encode_queue;
+pub mod encode_stats;
+pub mod encoder_chain;
+pub mod env_options;
+pub mod exact;
+pub mod exr_output;
+pub mod fit;
+pub mod frame_store;
+pub mod gop_decode;
+pub mod heatmap;
+pub mod index;
+pub mod input_limit;
+pub mod interpolate;
+pub mod ivtc;
+pub mod legend;
+pub mod limits;
+pub mod linear_light;
+pub mod mapping;
+pub mod memory;
+pub mod metrics;
+pub mod mosaic;
+pub mod multi_track;
+pub mod muxer;
+pub mod notify;
+pub mod ops;
+pub mod order;
+pub mod os_path;
+pub mod output_file;
+pub mod output_spec;
+pub mod packaging;
+mod pipeline;
+pub mod pix_fmt;
+pub mod play;
+pub mod presets;
+pub mod probe;
+pub mod progress;
+pub mod progress_socket;
+pub mod raw_input;
+pub mod read_ahead;
+pub mod resample;
+pub mod retry;
+pub mod scaler;
+pub mod scenes;
+pub mod seek;
+pub mod self_update;
+pub mod side_by_side;
+pub mod sidecar;
+pub mod sink;
+pub mod smooth;
+pub mod sonify;
+pub mod source;
+pub mod split;
+pub mod stats;
+pub mod target;
+pub mod tempfiles;
+pub mod throttle;
+pub mod timestamps;
+pub mod transpose;
+pub mod validate;
+pub mod watermark;
+pub mod windows;
+pub mod worker;
+
+pub use pipeline::{run, run_with_callback};
+pub use seek::Seeker;
+pub use sink::{FrameCallback, FrameSink, OutputFrame};
@@ -0,0 +1,98 @@
+//! H.264 dimension and level limits, checked before decoding so an output
+//! the encoder can't open (or players can't decode) is reported up front
+//! rather than after a long decode.
+
+use std::path::Path;
+
+/// Largest width or height libx264 will open; wider outputs are split
+/// (`--split-width`).
+pub const H264_MAX_DIMENSION: usize = 16384;
+
+/// An H.264 level: the maximum macroblock rate and frame size in macroblocks.
+pub struct Level {
+    pub name: &'static str,
+    max_mbps: u64,
+    max_fs: u64,
+}
+
+const H264_LEVELS: [Level; 19] = [
+    level("1", 1_485, 99),
+    level("1.1", 3_000, 396),
+    level("1.2", 6_000, 396),
+    level("1.3", 11_880, 396),
+    level("2", 11_880, 396),
+    level("2.1", 19_800, 792),
+    level("2.2", 20_250, 1_620),
+    level("3", 40_500, 1_620),
+    level("3.1", 108_000, 3_600),
+    level("3.2", 216_000, 5_120),
+    level("4", 245_760, 8_192),
+    level("4.1", 245_760, 8_192),
+    level("4.2", 522_240, 8_704),
+    level("5", 589_824, 22_080),
+    level("5.1", 983_040, 36_864),
+    level("5.2", 2_073_600, 36_864),
+    level("6", 4_177_920, 139_264),
+    level("6.1", 8_355_840, 139_264),
+    level("6.2", 16_711_680, 139_264),
+];
+
+const fn level(name: &'static str, max_mbps: u64, max_fs: u64) -> Level {
+    Level {
+        name,
+        max_mbps,
+        max_fs,
+    }
+}
+
+impl Level {
+    fn fits(&self, width_mbs: u64, height_mbs: u64, fps: f64) -> bool {
+        let frame = width_mbs * height_mbs;
+        // Neither dimension may exceed sqrt(8 * MaxFS) macroblocks
+        let max_side = 8 * self.max_fs;
+        frame <= self.max_fs
+            && width_mbs * width_mbs <= max_side
+            && height_mbs * height_mbs <= max_side
+            && frame as f64 * fps <= self.max_mbps as f64
+    }
+}
+
+/// Returns the lowest H.264 level that can hold `width`×`height` at `fps`.
+pub fn h264_level(width: usize, height: usize, fps: f64) -> Option<&'static Level> {
+    let width_mbs = width.div_ceil(16) as u64;
+    let height_mbs = height.div_ceil(16) as u64;
+    H264_LEVELS
+        .iter()
+        .find(|level| level.fits(width_mbs, height_mbs, fps))
+}
+
+/// Checks that an output of `width`×`height` can be encoded as H.264 at all,
+/// and returns the level it needs (`None` when beyond the highest level,
+/// which encodes but won't play on most hardware decoders).
+pub fn check_h264(
+    path: &Path,
+    width: usize,
+    height: usize,
+    fps: f64,
+) -> Result<Option<&'static str>, String> {
+    if height > H264_MAX_DIMENSION {
+        return Err(format!(
+            "Output {} would be {} pixels tall, but H.264 is limited to {}. \
+             Scale it down with --also-encode <path>:scale=<s> or use a smaller input.",
+            path.display(),
+            height,
+            H264_MAX_DIMENSION
+        ));
+    }
+    if width > H264_MAX_DIMENSION {
+        return Err(format!(
+            "Output {} would be {} pixels wide, but H.264 is limited to {}. \
+             Use --split-width {} or fewer, or trim the input.",
+            path.display(),
+            width,
+            H264_MAX_DIMENSION,
+            H264_MAX_DIMENSION
+        ));
+    }
+    Ok(h264_level(width, height, fps).map(|level| level.name))
+}
@@ -0,0 +1,235 @@
+//! `--linear-light`: blending, averaging and downscaling in linear light.
+//! Decoded samples are gamma encoded, so mixing them directly darkens the
+//! mix: half way between black and white comes out at 50% of the code
+//! range, about 21% of the light. With this option the samples are
+//! converted to linear light with the input's tagged transfer function,
+//! mixed, and converted back. That applies to `--interpolate` and `--resample
+//! blend` frames, `--temporal-smooth mean`, `--mosaic` tiles and `--scale`.
+
+use crate::frame_store::FrameFormat;
+use ffmpeg_next::util::color::TransferCharacteristic;
+
+/// Steps of the linear to 8-bit table, fine enough for the darkest codes.
+const ENCODE_STEPS: usize = 1 << 16;
+
+/// Transfer functions samples can be linearized with.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Transfer {
+    /// IEC 61966-2-1, also assumed for untagged input.
+    Srgb,
+    /// The BT.709 camera curve, shared by BT.601 and BT.2020.
+    Bt709,
+    /// A pure power law.
+    Gamma(f32),
+    Linear,
+}
+
+impl Transfer {
+    /// The transfer function an input is tagged with.
+    pub fn from_tag(tag: TransferCharacteristic) -> Result<Transfer, String> {
+        match tag {
+            TransferCharacteristic::IEC61966_2_1
+            | TransferCharacteristic::Unspecified
+            | TransferCharacteristic::Reserved
+            | TransferCharacteristic::Reserved0 => Ok(Transfer::Srgb),
+            TransferCharacteristic::BT709
+            | TransferCharacteristic::SMPTE170M
+            | TransferCharacteristic::SMPTE240M
+            | TransferCharacteristic::BT1361_ECG
+            | TransferCharacteristic::BT2020_10
+            | TransferCharacteristic::BT2020_12 => Ok(Transfer::Bt709),
+            TransferCharacteristic::GAMMA22 => Ok(Transfer::Gamma(2.2)),
+            TransferCharacteristic::GAMMA28 => Ok(Transfer::Gamma(2.8)),
+            TransferCharacteristic::Linear => Ok(Transfer::Linear),
+            other => Err(format!(
+                "--linear-light doesn't support the input's {:?} transfer function",
+                other
+            )),
+        }
+    }
+
+    pub fn name(self) -> String {
+        match self {
+            Transfer::Srgb => "sRGB".to_string(),
+            Transfer::Bt709 => "BT.709".to_string(),
+            Transfer::Gamma(gamma) => format!("gamma {}", gamma),
+            Transfer::Linear => "linear".to_string(),
+        }
+    }
+
+    /// Linear light of an encoded value, both 0 to 1.
+    fn decode(self, value: f32) -> f32 {
+        let value = value.max(0.0);
+        match self {
+            Transfer::Srgb if value <= 0.04045 => value / 12.92,
+            Transfer::Srgb => ((value + 0.055) / 1.055).powf(2.4),
+            Transfer::Bt709 if value < 0.081 => value / 4.5,
+            Transfer::Bt709 => ((value + 0.099) / 1.099).powf(1.0 / 0.45),
+            Transfer::Gamma(gamma) => value.powf(gamma),
+            Transfer::Linear => value,
+        }
+    }
+
+    /// Encoded value of linear light, both 0 to 1.
+    fn encode(self, light: f32) -> f32 {
+        let light = light.max(0.0);
+        match self {
+            Transfer::Srgb if light <= 0.0031308 => light * 12.92,
+            Transfer::Srgb => 1.055 * light.powf(1.0 / 2.4) - 0.055,
+            Transfer::Bt709 if light < 0.018 => light * 4.5,
+            Transfer::Bt709 => 1.099 * light.powf(0.45) - 0.099,
+            Transfer::Gamma(gamma) => light.powf(1.0 / gamma),
+            Transfer::Linear => light,
+        }
+    }
+}
+
+/// Converts samples to and from linear light, with tables for 8-bit ones.
+pub struct LinearLight {
+    transfer: Transfer,
+    /// 8-bit code → linear light.
+    decode_table: Vec<f32>,
+    /// Linear light in `ENCODE_STEPS` steps → 8-bit code.
+    encode_table: Vec<u8>,
+}
+
+impl LinearLight {
+    pub fn new(transfer: Transfer) -> LinearLight {
+        let decode_table = (0..256)
+            .map(|code| transfer.decode(code as f32 / 255.0))
+            .collect();
+        let encode_table = (0..ENCODE_STEPS)
+            .map(|step| {
+                let light = step as f32 / (ENCODE_STEPS - 1) as f32;
+                (transfer.encode(light) * 255.0).round().clamp(0.0, 255.0) as u8
+            })
+            .collect();
+        LinearLight {
+            transfer,
+            decode_table,
+            encode_table,
+        }
+    }
+
+    pub fn transfer(&self) -> Transfer {
+        self.transfer
+    }
+
+    /// Linear light of the sample at the start of `bytes`.
+    fn sample(&self, bytes: &[u8], format: FrameFormat) -> f32 {
+        match format {
+            FrameFormat::Rgb24 => self.decode_table[bytes[0] as usize],
+            FrameFormat::Gray16 => self
+                .transfer
+                .decode(u16::from_le_bytes([bytes[0], bytes[1]]) as f32 / 65535.0),
+            FrameFormat::RgbF32 => self
+                .transfer
+                .decode(f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])),
+        }
+    }
+
+    /// Writes linear `light` as a sample to the start of `out`.
+    fn store(&self, light: f32, format: FrameFormat, out: &mut [u8]) {
+        match format {
+            FrameFormat::Rgb24 => {
+                let step = (light.clamp(0.0, 1.0) * (ENCODE_STEPS - 1) as f32).round();
+                out[0] = self.encode_table[step as usize];
+            }
+            FrameFormat::Gray16 => {
+                let value = self.transfer.encode(light) * 65535.0;
+                out[..2].copy_from_slice(&(value.round().clamp(0.0, 65535.0) as u16).to_le_bytes());
+            }
+            FrameFormat::RgbF32 => {
+                out[..4].copy_from_slice(&self.transfer.encode(light).to_le_bytes());
+            }
+        }
+    }
+
+    /// Linear blend of two equally sized frames: `a` at `alpha = 0`, `b` at 1.
+    pub fn blend(&self, a: &[u8], b: &[u8], alpha: f32, format: FrameFormat) -> Vec<u8> {
+        let alpha = alpha.clamp(0.0, 1.0);
+        let size = sample_size(format);
+        let mut blended = vec![0u8; a.len()];
+        for ((out, a), b) in blended
+            .chunks_exact_mut(size)
+            .zip(a.chunks_exact(size))
+            .zip(b.chunks_exact(size))
+        {
+            let (a, b) = (self.sample(a, format), self.sample(b, format));
+            self.store(a + (b - a) * alpha, format, out);
+        }
+        blended
+    }
+
+    /// Writes the mean of the pixel at byte `offset` of `frames` to `out`.
+    pub fn average(&self, frames: &[Vec<u8>], offset: usize, format: FrameFormat, out: &mut [u8]) {
+        let size = sample_size(format);
+        for (sample, out) in out.chunks_exact_mut(size).enumerate() {
+            let at = offset + sample * size;
+            let sum: f32 = frames
+                .iter()
+                .map(|frame| self.sample(&frame[at..], format))
+                .sum();
+            self.store(sum / frames.len() as f32, format, out);
+        }
+    }
+
+    /// Mean of 8-bit samples.
+    pub fn mean(&self, samples: &[u8]) -> u8 {
+        let sum: f32 = samples
+            .iter()
+            .map(|&sample| self.decode_table[sample as usize])
+            .sum();
+        let mut out = [0];
+        self.store(sum / samples.len() as f32, FrameFormat::Rgb24, &mut out);
+        out[0]
+    }
+
+    /// Shrinks a packed frame to `new_width × new_height`, each pixel the
+    /// mean of the source pixels it covers.
+    pub fn downscale(
+        &self,
+        data: &[u8],
+        format: FrameFormat,
+        (width, height): (usize, usize),
+        (new_width, new_height): (usize, usize),
+    ) -> Vec<u8> {
+        let bpp = format.bytes_per_pixel();
+        let size = sample_size(format);
+        let span = |index: usize, from: usize, to: usize| {
+            let start = index * from / to;
+            start..((index + 1) * from / to).max(start + 1)
+        };
+        let mut scaled = vec![0u8; new_width * new_height * bpp];
+        let mut sums = vec![0f32; bpp / size];
+        for (y, row) in scaled.chunks_exact_mut(new_width * bpp).enumerate() {
+            let rows = span(y, height, new_height);
+            for (x, pixel) in row.chunks_exact_mut(bpp).enumerate() {
+                let columns = span(x, width, new_width);
+                sums.fill(0.0);
+                for source_y in rows.clone() {
+                    let line = &data[source_y * width * bpp..(source_y + 1) * width * bpp];
+                    for source in line[columns.start * bpp..columns.end * bpp].chunks_exact(bpp) {
+                        for (sum, sample) in sums.iter_mut().zip(source.chunks_exact(size)) {
+                            *sum += self.sample(sample, format);
+                        }
+                    }
+                }
+                let count = (rows.len() * columns.len()) as f32;
+                for (sum, out) in sums.iter().zip(pixel.chunks_exact_mut(size)) {
+                    self.store(sum / count, format, out);
+                }
+            }
+        }
+        scaled
+    }
+}
+
+/// Bytes per sample of `format`.
+fn sample_size(format: FrameFormat) -> usize {
+    match format {
+        FrameFormat::Rgb24 => 1,
+        FrameFormat::Gray16 => 2,
+        FrameFormat::RgbF32 => 4,
+    }
+}
@@ -0,0 +1,63 @@
+use ffmpeg_next as ffmpeg;
+use ffmpeg_next::util::frame::side_data::Type as SideDataType;
+use ffmpeg_next::util::frame::video::Video;
+use ffmpeg_next::Dictionary;
+
+/// UUID prefixed to our user-data-unregistered SEI payloads, so readers can
+/// tell the mapping messages apart from other encoders' SEI.
+pub const MAPPING_SEI_UUID: [u8; 16] = [
+    0x7f, 0x3a, 0x52, 0x0e, 0x91, 0x4c, 0x4d, 0x2b, 0xa8, 0x61, 0x0c, 0xd4, 0x5e, 0x17, 0x93, 0xb6,
+];
+
+/// Describes which source column an output frame was built from and the
+/// capture times spanned by its columns, e.g. `x=12;t=0.000..4.967;frames=150`.
+pub fn frame_mapping(column: usize, times: &[Option<f64>]) -> String {
+    let mut known = times.iter().flatten();
+    let span = match known.next() {
+        Some(&first) => {
+            let last = known.fold(first, |last, &t| t.max(last));
+            format!("{:.6}..{:.6}", first, last)
+        }
+        None => "unknown".to_string(),
+    };
+    format!("x={};t={};frames={}", column, span, times.len())
+}
+
+/// Attaches `payload` to the frame as user-data-unregistered SEI. Encoders
+/// that support it (libx264 with `udu_sei`) write it into the bitstream.
+pub fn attach_sei(frame: &mut Video, payload: &str) {
+    let size = MAPPING_SEI_UUID.len() + payload.len();
+    if let Some(mut side_data) = frame.new_side_data(SideDataType::SEI_UNREGISTERED, size) {
+        unsafe {
+            let data = std::slice::from_raw_parts_mut((*side_data.as_mut_ptr()).data, size);
+            data[..MAPPING_SEI_UUID.len()].copy_from_slice(&MAPPING_SEI_UUID);
+            data[MAPPING_SEI_UUID.len()..].copy_from_slice(payload.as_bytes());
+        }
+    }
+}
+
+/// Stream metadata describing the axis mapping. The zero timecode makes
+/// MOV/MP4 muxers write a timecode track whose frame count equals the
+/// source column of each output frame.
+pub fn stream_metadata() -> Dictionary<'static> {
+    let mut metadata = Dictionary::new();
+    metadata.set("timecode", "00:00:00:00");
+    metadata.set(
+        "comment",
+        "video-transpose: output frame N = source column N, output column t = source frame t",
+    );
+    metadata
+}
+
+/// Muxer options needed for the timecode track to be written.
+pub fn muxer_options(format: &ffmpeg::format::Output) -> Dictionary<'static> {
+    let mut options = Dictionary::new();
+    if format
+        .name()
+        .split(',')
+        .any(|name| name == "mp4" || name == "mov")
+    {
+        options.set("write_tmcd", "1");
+    }
+    options
+}
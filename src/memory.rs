@@ -0,0 +1,144 @@
+use std::fs;
+
+/// Keeps the decoded frame store within the available RAM (or a user cap),
+/// so an oversized job fails with a useful message instead of being killed
+/// by the OOM killer halfway through.
+pub struct MemoryGuard {
+    budget: Option<u64>,
+    from_user: bool,
+}
+
+impl MemoryGuard {
+    pub fn new(max_memory: Option<u64>) -> Self {
+        match max_memory {
+            Some(limit) => MemoryGuard {
+                budget: Some(limit),
+                from_user: true,
+            },
+            None => MemoryGuard {
+                budget: available_memory(),
+                from_user: false,
+            },
+        }
+    }
+
+    pub fn budget(&self) -> Option<u64> {
+        self.budget
+    }
+
+    /// Sets `bytes` of the budget aside for something other than the frame
+    /// store.
+    pub fn reserve(&mut self, bytes: u64) {
+        self.budget = self.budget.map(|budget| budget.saturating_sub(bytes));
+    }
+
+    /// Checks the size the frame store is expected to reach before decoding.
+    pub fn check_projected(&self, projected: u64) -> Result<(), String> {
+        self.check(
+            projected,
+            "Projected frame store",
+            "Use a shorter or lower-resolution input",
+        )
+    }
+
+    /// Checks the size the frame store has actually reached while decoding.
+    pub fn check_actual(&self, actual: u64) -> Result<(), String> {
+        // Unlike a projected overrun, this one comes with no fit worked out
+        self.check(
+            actual,
+            "Frame store",
+            "Keep fewer or smaller frames with --frame-step or --scale, use a shorter or \
+             lower-resolution input",
+        )
+    }
+
+    fn check(&self, size: u64, what: &str, advice: &str) -> Result<(), String> {
+        let budget = match self.budget {
+            Some(budget) => budget,
+            None => return Ok(()),
+        };
+        if size <= budget {
+            return Ok(());
+        }
+
+        let limit = if self.from_user {
+            format!("the --max-memory limit of {}", format_bytes(budget))
+        } else {
+            format!("available memory ({})", format_bytes(budget))
+        };
+        Err(format!(
+            "{} of {} exceeds {}. {}, or pass --max-memory to set the limit explicitly.",
+            what,
+            format_bytes(size),
+            limit,
+            advice
+        ))
+    }
+}
+
+/// Returns the memory available to new allocations, if the platform reports it.
+pub fn available_memory() -> Option<u64> {
+    read_proc_kb("/proc/meminfo", "MemAvailable:")
+}
+
+/// Parses a byte count with an optional K/M/G/T suffix (powers of 1024).
+pub fn parse_size(text: &str) -> Result<u64, String> {
+    let text = text.trim();
+    let digits_end = text
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(text.len());
+    let (number, suffix) = text.split_at(digits_end);
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("Invalid size: {}", text))?;
+    let multiplier = match suffix
+        .trim()
+        .to_ascii_uppercase()
+        .trim_end_matches("IB")
+        .trim_end_matches('B')
+    {
+        "" => 1u64,
+        "K" => 1 << 10,
+        "M" => 1 << 20,
+        "G" => 1 << 30,
+        "T" => 1 << 40,
+        _ => return Err(format!("Invalid size suffix: {}", text)),
+    };
+    Ok((number * multiplier as f64) as u64)
+}
+
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+/// Returns the peak resident set size of this process, if the platform reports it.
+pub fn peak_rss() -> Option<u64> {
+    read_proc_kb("/proc/self/status", "VmHWM:")
+}
+
+/// Reads a `Key: <n> kB` line from a procfs file, in bytes.
+fn read_proc_kb(path: &str, key: &str) -> Option<u64> {
+    let contents = fs::read_to_string(path).ok()?;
+    contents
+        .lines()
+        .find_map(|line| line.strip_prefix(key))
+        .and_then(|rest| {
+            rest.trim()
+                .trim_end_matches("kB")
+                .trim()
+                .parse::<u64>()
+                .ok()
+        })
+        .map(|kb| kb * 1024)
+}
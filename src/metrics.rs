@@ -0,0 +1,257 @@
+//! Prometheus metrics for long-running modes, served at `/metrics`.
+
+use crate::memory::peak_rss;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+const DURATION_BUCKETS: &[f64] = &[1.0, 5.0, 15.0, 60.0, 300.0, 900.0, 3600.0, 14400.0];
+const FPS_BUCKETS: &[f64] = &[1.0, 5.0, 10.0, 25.0, 50.0, 100.0, 250.0, 500.0, 1000.0];
+/// How long a scrape may take to send its request or read the response,
+/// so an idle client can't hold up the others.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(5);
+
+struct Histogram {
+    bounds: &'static [f64],
+    counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn new(bounds: &'static [f64]) -> Self {
+        Histogram {
+            bounds,
+            counts: vec![0; bounds.len()],
+            sum: 0.0,
+            count: 0,
+        }
+    }
+
+    fn observe(&mut self, value: f64) {
+        for (bound, count) in self.bounds.iter().zip(&mut self.counts) {
+            if value <= *bound {
+                *count += 1;
+            }
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+
+    fn render(&self, out: &mut String, name: &str, labels: &str) {
+        let prefix = if labels.is_empty() {
+            String::new()
+        } else {
+            format!("{},", labels)
+        };
+        for (bound, count) in self.bounds.iter().zip(&self.counts) {
+            let _ = writeln!(
+                out,
+                "{}_bucket{{{}le=\"{}\"}} {}",
+                name, prefix, bound, count
+            );
+        }
+        let _ = writeln!(
+            out,
+            "{}_bucket{{{}le=\"+Inf\"}} {}",
+            name, prefix, self.count
+        );
+        let braces = |labels: &str| {
+            if labels.is_empty() {
+                String::new()
+            } else {
+                format!("{{{}}}", labels)
+            }
+        };
+        let _ = writeln!(out, "{}_sum{} {}", name, braces(labels), self.sum);
+        let _ = writeln!(out, "{}_count{} {}", name, braces(labels), self.count);
+    }
+}
+
+struct State {
+    started: u64,
+    succeeded: u64,
+    failed: u64,
+    running: u64,
+    duration: Histogram,
+    stage_fps: BTreeMap<String, Histogram>,
+    stage_frames: BTreeMap<String, u64>,
+    job_peak_rss: Option<u64>,
+}
+
+/// Job counters and per-stage throughput, shared between worker threads.
+pub struct Metrics {
+    state: Mutex<State>,
+}
+
+impl Metrics {
+    pub fn new() -> Arc<Metrics> {
+        Arc::new(Metrics {
+            state: Mutex::new(State {
+                started: 0,
+                succeeded: 0,
+                failed: 0,
+                running: 0,
+                duration: Histogram::new(DURATION_BUCKETS),
+                stage_fps: BTreeMap::new(),
+                stage_frames: BTreeMap::new(),
+                job_peak_rss: None,
+            }),
+        })
+    }
+
+    pub fn job_started(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.started += 1;
+        state.running += 1;
+    }
+
+    /// Records a finished job from its status record (see `worker`).
+    pub fn job_finished(&self, status: &Value, elapsed: Duration) {
+        let mut state = self.state.lock().unwrap();
+        state.running -= 1;
+        if status["status"] == "success" {
+            state.succeeded += 1;
+        } else {
+            state.failed += 1;
+        }
+        state.duration.observe(elapsed.as_secs_f64());
+
+        let summary = &status["summary"];
+        if let Some(stages) = summary["stages"].as_object() {
+            for (name, stage) in stages {
+                if let Some(fps) = stage["fps"].as_f64().filter(|&fps| fps > 0.0) {
+                    state
+                        .stage_fps
+                        .entry(name.clone())
+                        .or_insert_with(|| Histogram::new(FPS_BUCKETS))
+                        .observe(fps);
+                }
+                *state.stage_frames.entry(name.clone()).or_insert(0) +=
+                    stage["frames"].as_u64().unwrap_or(0);
+            }
+        }
+        if let Some(rss) = summary["peak_rss_bytes"].as_u64() {
+            state.job_peak_rss = Some(rss);
+        }
+    }
+
+    /// Renders all metrics in the Prometheus text format.
+    pub fn render(&self) -> String {
+        let state = self.state.lock().unwrap();
+        let mut out = String::new();
+
+        out.push_str("# HELP video_transpose_jobs_total Jobs by outcome.\n");
+        out.push_str("# TYPE video_transpose_jobs_total counter\n");
+        let _ = writeln!(
+            out,
+            "video_transpose_jobs_total{{status=\"started\"}} {}",
+            state.started
+        );
+        let _ = writeln!(
+            out,
+            "video_transpose_jobs_total{{status=\"success\"}} {}",
+            state.succeeded
+        );
+        let _ = writeln!(
+            out,
+            "video_transpose_jobs_total{{status=\"failure\"}} {}",
+            state.failed
+        );
+
+        out.push_str("# HELP video_transpose_jobs_running Jobs currently running.\n");
+        out.push_str("# TYPE video_transpose_jobs_running gauge\n");
+        let _ = writeln!(out, "video_transpose_jobs_running {}", state.running);
+
+        out.push_str("# HELP video_transpose_job_duration_seconds Wall-clock time per job.\n");
+        out.push_str("# TYPE video_transpose_job_duration_seconds histogram\n");
+        state
+            .duration
+            .render(&mut out, "video_transpose_job_duration_seconds", "");
+
+        out.push_str(
+            "# HELP video_transpose_stage_fps Frames per second per pipeline stage and job.\n",
+        );
+        out.push_str("# TYPE video_transpose_stage_fps histogram\n");
+        for (stage, histogram) in &state.stage_fps {
+            let labels = format!("stage=\"{}\"", stage);
+            histogram.render(&mut out, "video_transpose_stage_fps", &labels);
+        }
+
+        out.push_str(
+            "# HELP video_transpose_stage_frames_total Frames processed per pipeline stage.\n",
+        );
+        out.push_str("# TYPE video_transpose_stage_frames_total counter\n");
+        for (stage, frames) in &state.stage_frames {
+            let _ = writeln!(
+                out,
+                "video_transpose_stage_frames_total{{stage=\"{}\"}} {}",
+                stage, frames
+            );
+        }
+
+        if let Some(rss) = state.job_peak_rss {
+            out.push_str(
+                "# HELP video_transpose_job_peak_rss_bytes Peak RSS of the last finished job.\n",
+            );
+            out.push_str("# TYPE video_transpose_job_peak_rss_bytes gauge\n");
+            let _ = writeln!(out, "video_transpose_job_peak_rss_bytes {}", rss);
+        }
+        if let Some(rss) = peak_rss() {
+            out.push_str("# HELP video_transpose_peak_rss_bytes Peak RSS of this process.\n");
+            out.push_str("# TYPE video_transpose_peak_rss_bytes gauge\n");
+            let _ = writeln!(out, "video_transpose_peak_rss_bytes {}", rss);
+        }
+        out
+    }
+}
+
+/// Serves `GET /metrics` on `address` from a background thread.
+pub fn serve(address: &str, metrics: Arc<Metrics>) -> Result<(), String> {
+    let listener =
+        TcpListener::bind(address).map_err(|e| format!("Cannot listen on {}: {}", address, e))?;
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            if stream.set_read_timeout(Some(CLIENT_TIMEOUT)).is_err()
+                || stream.set_write_timeout(Some(CLIENT_TIMEOUT)).is_err()
+            {
+                continue;
+            }
+            let mut reader = BufReader::new(stream);
+            let mut request_line = String::new();
+            if reader.read_line(&mut request_line).is_err() {
+                continue;
+            }
+            // Skip the headers, the request has no body we care about
+            let mut header = String::new();
+            while reader
+                .read_line(&mut header)
+                .map(|n| n > 2)
+                .unwrap_or(false)
+            {
+                header.clear();
+            }
+
+            let path = request_line.split_whitespace().nth(1).unwrap_or("");
+            let (status, body) = if path == "/metrics" {
+                ("200 OK", metrics.render())
+            } else {
+                ("404 Not Found", "Not found\n".to_string())
+            };
+            let response = format!(
+                "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\n\
+                 Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            );
+            let _ = reader.get_mut().write_all(response.as_bytes());
+        }
+    });
+    Ok(())
+}
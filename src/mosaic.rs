@@ -0,0 +1,160 @@
+//! `--mosaic CxR`: one output showing the transposes of a grid of source
+//! regions side by side, for an overview of how the temporal behaviour
+//! varies across the frame. The source columns (all, or `--columns`) are
+//! cut into `C` regions and the rows into `R`; output frame `k` shows
+//! column `k` of every region, each tile transposed as a normal output
+//! would be. So the mosaic is no wider than a plain transpose, each tile's
+//! time axis is downsampled by `C`, averaging the frames it merges.
+
+use crate::frame_store::FrameFormat;
+use crate::linear_light::LinearLight;
+use std::ops::Range;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Mosaic {
+    pub columns: usize,
+    pub rows: usize,
+}
+
+impl Mosaic {
+    /// Parses `CxR`, e.g. `4x3`.
+    pub fn parse(text: &str) -> Result<Mosaic, String> {
+        let invalid = || format!("Invalid --mosaic: {} (expected CxR, e.g. 4x3)", text);
+        let (columns, rows) = text.split_once(['x', 'X']).ok_or_else(invalid)?;
+        match (columns.parse(), rows.parse()) {
+            (Ok(columns), Ok(rows)) if columns > 0 && rows > 0 => Ok(Mosaic { columns, rows }),
+            _ => Err(invalid()),
+        }
+    }
+
+    /// Lays the grid over the source columns `range` of frames `height`
+    /// rows high. Columns and rows left over by the division are dropped,
+    /// and tiles get an even height for the encoders.
+    pub fn grid(self, range: &Range<usize>, height: usize) -> Result<MosaicGrid, String> {
+        let region_width = range.len() / self.columns;
+        let tile_height = height / self.rows / 2 * 2;
+        if region_width == 0 || tile_height == 0 {
+            return Err(format!(
+                "--mosaic {}x{} needs frames of at least {}×{} pixels ({}×{} selected)",
+                self.columns,
+                self.rows,
+                self.columns,
+                2 * self.rows,
+                range.len(),
+                height
+            ));
+        }
+        Ok(MosaicGrid {
+            columns: self.columns,
+            rows: self.rows,
+            region_width,
+            tile_height,
+        })
+    }
+}
+
+/// A [`Mosaic`] fitted to the source frames.
+#[derive(Clone, Copy)]
+pub struct MosaicGrid {
+    columns: usize,
+    rows: usize,
+    /// Source columns per region, and so output frames.
+    pub region_width: usize,
+    tile_height: usize,
+}
+
+impl MosaicGrid {
+    /// Width of a tile showing `frames` source frames.
+    fn tile_width(&self, frames: usize) -> usize {
+        (frames / self.columns).max(1)
+    }
+
+    /// Size of the mosaic for `frames` source frames.
+    pub fn size(&self, frames: usize) -> (usize, usize) {
+        (
+            self.columns * self.tile_width(frames),
+            self.rows * self.tile_height,
+        )
+    }
+
+    /// Builds the output frame for column `x` of the first region: a packed
+    /// `out_width` wide image whose tiles show the same column of every
+    /// region over time. Columns past the mosaic repeat its last one
+    /// (encoder padding). With `light`, frames are averaged in linear light.
+    pub fn gather(
+        &self,
+        frames: &[Vec<u8>],
+        format: FrameFormat,
+        source_width: usize,
+        x: usize,
+        out_width: usize,
+        light: Option<&LinearLight>,
+    ) -> Vec<u8> {
+        let bpp = format.bytes_per_pixel();
+        let (width, height) = self.size(frames.len());
+        let tile_width = self.tile_width(frames.len());
+        // Frames averaged into each tile column
+        let spans: Vec<Range<usize>> = (0..tile_width)
+            .map(|u| {
+                let start = u * frames.len() / tile_width;
+                start..((u + 1) * frames.len() / tile_width).max(start + 1)
+            })
+            .collect();
+
+        let mut mosaic = vec![0u8; out_width * height * bpp];
+        for (y, row) in mosaic.chunks_exact_mut(out_width * bpp).enumerate() {
+            // Tile rows show the source rows they cover
+            for tile_column in 0..self.columns {
+                let source_x = x + tile_column * self.region_width;
+                let offset = (y * source_width + source_x) * bpp;
+                for (u, span) in spans.iter().enumerate() {
+                    let at = (tile_column * tile_width + u) * bpp;
+                    let (frames, out) = (&frames[span.clone()], &mut row[at..at + bpp]);
+                    match light {
+                        Some(light) => light.average(frames, offset, format, out),
+                        None => average(frames, offset, format, out),
+                    }
+                }
+            }
+            for t in width..out_width {
+                row.copy_within((width - 1) * bpp..width * bpp, t * bpp);
+            }
+        }
+        mosaic
+    }
+}
+
+/// Writes the mean of the pixel at byte `offset` of `frames` to `out`.
+fn average(frames: &[Vec<u8>], offset: usize, format: FrameFormat, out: &mut [u8]) {
+    let count = frames.len();
+    match format {
+        FrameFormat::Rgb24 => {
+            for (channel, out) in out.iter_mut().enumerate() {
+                let sum: usize = frames
+                    .iter()
+                    .map(|frame| frame[offset + channel] as usize)
+                    .sum();
+                *out = ((sum + count / 2) / count) as u8;
+            }
+        }
+        FrameFormat::Gray16 => {
+            let sum: usize = frames
+                .iter()
+                .map(|frame| u16::from_le_bytes([frame[offset], frame[offset + 1]]) as usize)
+                .sum();
+            out.copy_from_slice(&(((sum + count / 2) / count) as u16).to_le_bytes());
+        }
+        FrameFormat::RgbF32 => {
+            for (channel, out) in out.chunks_exact_mut(4).enumerate() {
+                let at = offset + channel * 4;
+                let sum: f32 = frames
+                    .iter()
+                    .map(|frame| {
+                        f32::from_le_bytes([frame[at], frame[at + 1], frame[at + 2], frame[at + 3]])
+                    })
+                    .sum();
+                out.copy_from_slice(&(sum / count as f32).to_le_bytes());
+            }
+        }
+    }
+}
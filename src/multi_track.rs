@@ -0,0 +1,108 @@
+//! `--multi-track xt,yt,original`: the transposes of the cube along both
+//! spatial axes, and the source frames themselves, as separate video
+//! streams of one Matroska file, so an archive keeps them together. The
+//! X-T track is the usual output (one frame per source column), the Y-T
+//! track has a frame per source row, each a `width × frames` image of that
+//! row over time, and the original track replays the decoded frames. The
+//! streams have different frame counts and sizes and are encoded side by
+//! side, their frames interleaved in time.
+
+use crate::frame_store::FrameFormat;
+use crate::ops::Rearrangement;
+use crate::transpose;
+use std::borrow::Cow;
+
+/// A stream of the file.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Track {
+    /// Frames along the source columns, the usual transpose.
+    Xt,
+    /// Frames along the source rows.
+    Yt,
+    /// The decoded source frames.
+    Original,
+}
+
+impl Track {
+    /// Parses a comma-separated list of `xt`, `yt` and `original`, each
+    /// at most once, in the order the streams are stored.
+    pub fn parse_list(text: &str) -> Result<Vec<Track>, String> {
+        let mut tracks = Vec::new();
+        for name in text.split(',') {
+            let track = match name.trim() {
+                "xt" => Track::Xt,
+                "yt" => Track::Yt,
+                "original" => Track::Original,
+                _ => {
+                    return Err(format!(
+                        "Invalid --multi-track track: {} (expected xt, yt or original)",
+                        name
+                    ))
+                }
+            };
+            if tracks.contains(&track) {
+                return Err(format!("--multi-track lists {} twice", name.trim()));
+            }
+            tracks.push(track);
+        }
+        Ok(tracks)
+    }
+
+    /// Title of the stream in the file.
+    pub fn title(self) -> &'static str {
+        match self {
+            Track::Xt => "X-T transpose",
+            Track::Yt => "Y-T transpose",
+            Track::Original => "Original",
+        }
+    }
+
+    /// Width, height and frame count of the track of `frames` source
+    /// frames of `width × height`, `columns` of them shown by the X-T
+    /// track, with the time axis `time_width` pixels long (padded for the
+    /// encoder).
+    pub fn size(
+        self,
+        (width, height): (usize, usize),
+        frames: usize,
+        columns: usize,
+        time_width: usize,
+    ) -> (usize, usize, usize) {
+        match self {
+            Track::Xt => (time_width, height, columns),
+            Track::Yt => (width, time_width, height),
+            Track::Original => (width, height, frames),
+        }
+    }
+
+    /// Frame `index` of the track: `columns[index]` of the source for the
+    /// X-T track, a row or a source frame for the others.
+    pub fn frame<'a>(
+        self,
+        frames: &'a [Vec<u8>],
+        format: FrameFormat,
+        (width, height): (usize, usize),
+        columns: &[usize],
+        index: usize,
+        time_width: usize,
+    ) -> Cow<'a, [u8]> {
+        match self {
+            Track::Xt => Cow::Owned(transpose::gather_column(
+                frames,
+                format,
+                width,
+                height,
+                columns[index],
+                time_width,
+            )),
+            Track::Yt => Cow::Owned(Rearrangement::YT_TRANSPOSE.gather(
+                frames,
+                format,
+                (width, height),
+                index,
+                (width, time_width),
+            )),
+            Track::Original => Cow::Borrowed(&frames[index]),
+        }
+    }
+}
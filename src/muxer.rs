@@ -0,0 +1,151 @@
+//! Writing packets of one or more streams into an output. Each stream's
+//! packets are timed in the time base of whatever produced them (the
+//! encoder, or the input stream for copied packets), while the muxer only
+//! settles the stream time bases when the header is written and may pick
+//! different ones. [`Muxer`] keeps both for every stream, so packets are
+//! rescaled and interleaved in one place whichever stream they belong to.
+
+use ffmpeg_next as ffmpeg;
+use ffmpeg_next::format::context::output::Output;
+use ffmpeg_next::format::stream::StreamMut;
+use ffmpeg_next::{Dictionary, Packet, Rational};
+use std::ptr;
+
+pub struct Muxer {
+    octx: Output,
+    /// Time base each stream's packets arrive in.
+    packet_time_bases: Vec<Rational>,
+    /// Time base of each stream in the file, once the header is written.
+    stream_time_bases: Vec<Rational>,
+}
+
+impl Muxer {
+    pub fn new(octx: Output) -> Self {
+        Muxer {
+            octx,
+            packet_time_bases: Vec::new(),
+            stream_time_bases: Vec::new(),
+        }
+    }
+
+    /// The output container format.
+    pub fn format(&self) -> ffmpeg::format::format::Output {
+        self.octx.format()
+    }
+
+    /// Whether the container wants codec headers out of band, in the stream
+    /// parameters.
+    pub fn global_header(&self) -> bool {
+        self.octx
+            .format()
+            .flags()
+            .contains(ffmpeg::format::flag::Flags::GLOBAL_HEADER)
+    }
+
+    /// Sets the container-level metadata tags, before the header is written.
+    pub fn set_metadata(&mut self, metadata: Dictionary) {
+        self.octx.set_metadata(metadata);
+    }
+
+    /// Sets the metadata tags of stream `index`, e.g. its title.
+    pub fn set_stream_metadata(&mut self, index: usize, metadata: Dictionary) {
+        if let Some(mut stream) = self.octx.stream_mut(index) {
+            stream.set_metadata(metadata);
+        }
+    }
+
+    /// Adds a stream for `codec` whose packets will be timed in
+    /// `packet_time_base`, for the caller to set its parameters.
+    pub fn add_stream(
+        &mut self,
+        codec: ffmpeg::Codec,
+        packet_time_base: Rational,
+    ) -> Result<StreamMut<'_>, ffmpeg::Error> {
+        if !self.stream_time_bases.is_empty() {
+            return Err(ffmpeg::Error::InvalidData);
+        }
+        let stream = self.octx.add_stream(codec)?;
+        self.packet_time_bases.push(packet_time_base);
+        Ok(stream)
+    }
+
+    /// Adds a file attachment (a Matroska feature), written with the header.
+    pub fn add_attachment(
+        &mut self,
+        file_name: &str,
+        mime_type: &str,
+        data: &[u8],
+    ) -> Result<(), ffmpeg::Error> {
+        if !self.stream_time_bases.is_empty() {
+            return Err(ffmpeg::Error::InvalidData);
+        }
+        let out_of_memory = ffmpeg::Error::Other {
+            errno: ffmpeg::util::error::ENOMEM,
+        };
+        let index = unsafe {
+            let stream = ffmpeg::ffi::avformat_new_stream(self.octx.as_mut_ptr(), ptr::null());
+            if stream.is_null() {
+                return Err(out_of_memory);
+            }
+            // The attachment is the stream's extradata, padded as FFmpeg expects
+            let padding = ffmpeg::ffi::AV_INPUT_BUFFER_PADDING_SIZE as usize;
+            let extradata = ffmpeg::ffi::av_mallocz(data.len() + padding) as *mut u8;
+            if extradata.is_null() {
+                return Err(out_of_memory);
+            }
+            ptr::copy_nonoverlapping(data.as_ptr(), extradata, data.len());
+            let parameters = (*stream).codecpar;
+            (*parameters).codec_type = ffmpeg::ffi::AVMediaType::AVMEDIA_TYPE_ATTACHMENT;
+            (*parameters).extradata = extradata;
+            (*parameters).extradata_size = data.len() as i32;
+            (*stream).index as usize
+        };
+        let mut metadata = Dictionary::new();
+        metadata.set("filename", file_name);
+        metadata.set("mimetype", mime_type);
+        if let Some(mut stream) = self.octx.stream_mut(index) {
+            stream.set_metadata(metadata);
+        }
+        // Attachments have no packets, the time base is never used
+        self.packet_time_bases.push(Rational(1, 1));
+        Ok(())
+    }
+
+    /// Writes the header and records the stream time bases the muxer chose,
+    /// which may differ from the ones requested.
+    pub fn write_header(&mut self, options: Dictionary) -> Result<(), ffmpeg::Error> {
+        self.octx.write_header_with(options)?;
+        self.stream_time_bases = self
+            .octx
+            .streams()
+            .map(|stream| stream.time_base())
+            .collect();
+        Ok(())
+    }
+
+    /// Time base of stream `index` in the file, once the header is written.
+    pub fn stream_time_base(&self, index: usize) -> Option<Rational> {
+        self.stream_time_bases.get(index).copied()
+    }
+
+    /// Writes a packet of stream `index`, rescaled from the stream's packet
+    /// time base to its time base in the file. PTS and DTS are kept as the
+    /// producer set them: with B-frames the DTS runs behind the PTS and
+    /// reordered packets arrive with non-monotonic PTS.
+    pub fn write(&mut self, packet: &mut Packet, index: usize) -> Result<(), ffmpeg::Error> {
+        let (Some(&from), Some(&to)) = (
+            self.packet_time_bases.get(index),
+            self.stream_time_bases.get(index),
+        ) else {
+            return Err(ffmpeg::Error::StreamNotFound);
+        };
+        packet.set_stream(index);
+        packet.rescale_ts(from, to);
+        packet.write_interleaved(&mut self.octx)
+    }
+
+    /// Writes the packets still buffered for interleaving and the trailer.
+    pub fn finish(&mut self) -> Result<(), ffmpeg::Error> {
+        self.octx.write_trailer()
+    }
+}
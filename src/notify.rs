@@ -0,0 +1,45 @@
+use serde_json::Value;
+use std::time::Duration;
+
+/// Which run outcomes trigger a `--notify-url` request.
+#[derive(Clone, Copy, PartialEq)]
+pub enum NotifyOn {
+    Failure,
+    Success,
+    Always,
+}
+
+impl NotifyOn {
+    pub fn parse(text: &str) -> Result<NotifyOn, String> {
+        match text {
+            "failure" => Ok(NotifyOn::Failure),
+            "success" => Ok(NotifyOn::Success),
+            "always" => Ok(NotifyOn::Always),
+            _ => Err(format!(
+                "Invalid --notify-on value: {} (expected failure, success or always)",
+                text
+            )),
+        }
+    }
+
+    pub fn matches(self, success: bool) -> bool {
+        match self {
+            NotifyOn::Failure => !success,
+            NotifyOn::Success => success,
+            NotifyOn::Always => true,
+        }
+    }
+}
+
+/// POSTs the run summary as JSON to `url`.
+pub fn post(url: &str, summary: &Value) -> Result<(), String> {
+    let agent = ureq::AgentBuilder::new()
+        .timeout(Duration::from_secs(30))
+        .build();
+    agent
+        .post(url)
+        .set("Content-Type", "application/json")
+        .send_string(&summary.to_string())
+        .map_err(|e| format!("Notification to {} failed: {}", url, e))?;
+    Ok(())
+}
@@ -0,0 +1,171 @@
+//! `--ops a,b,c`: a chain of axis rearrangements of the video cube, run
+//! with one decode and one encode. The tool's transpose swaps the X and T
+//! axes; chains such as `xt_transpose,reverse_time,yt_transpose` mix in
+//! the other swaps and reversals of each axis. Every operation only moves
+//! samples, so the chain composes into a single mapping from output to
+//! source coordinates before any pixel is touched, and each output frame
+//! is gathered from the decoded frames in one pass.
+
+use crate::frame_store::FrameFormat;
+
+/// An axis of the video cube.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Axis {
+    /// Frames.
+    T,
+    /// Rows.
+    Y,
+    /// Columns.
+    X,
+}
+
+impl Axis {
+    fn index(self) -> usize {
+        match self {
+            Axis::T => 0,
+            Axis::Y => 1,
+            Axis::X => 2,
+        }
+    }
+}
+
+/// Where the frames, rows and columns of the output come from.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rearrangement {
+    /// Source axis along the output's frames, rows and columns.
+    axes: [Axis; 3],
+    /// Whether each of those runs backwards through the source.
+    reversed: [bool; 3],
+}
+
+impl Rearrangement {
+    /// The cube as it is.
+    const IDENTITY: Rearrangement = Rearrangement {
+        axes: [Axis::T, Axis::Y, Axis::X],
+        reversed: [false; 3],
+    };
+
+    /// What a run without `--ops` does.
+    const TRANSPOSE: Rearrangement = Rearrangement {
+        axes: [Axis::X, Axis::Y, Axis::T],
+        reversed: [false; 3],
+    };
+
+    /// Frames along the source rows: each output frame is a row over time.
+    pub const YT_TRANSPOSE: Rearrangement = Rearrangement {
+        axes: [Axis::Y, Axis::T, Axis::X],
+        reversed: [false; 3],
+    };
+
+    /// Parses a comma-separated chain of operations, applied left to right.
+    pub fn parse(text: &str) -> Result<Rearrangement, String> {
+        text.split(',')
+            .map(|name| operation(name.trim()))
+            .try_fold(Rearrangement::IDENTITY, |chain, op| {
+                op.map(|op| chain.then(op))
+            })
+    }
+
+    /// `self` followed by `next`, as one rearrangement.
+    fn then(self, next: Rearrangement) -> Rearrangement {
+        let mut composed = Rearrangement::IDENTITY;
+        for slot in 0..3 {
+            let from = next.axes[slot].index();
+            composed.axes[slot] = self.axes[from];
+            composed.reversed[slot] = next.reversed[slot] != self.reversed[from];
+        }
+        composed
+    }
+
+    /// Whether the chain comes down to the plain X-T transpose.
+    pub fn is_transpose(&self) -> bool {
+        *self == Rearrangement::TRANSPOSE
+    }
+
+    /// Width, height and frame count of the output of `frames` source
+    /// frames of `width × height`.
+    pub fn output_size(&self, width: usize, height: usize, frames: usize) -> (usize, usize, usize) {
+        let sizes = [frames, height, width];
+        let [frames, rows, columns] = self.axes.map(|axis| sizes[axis.index()]);
+        (columns, rows, frames)
+    }
+
+    /// The mapping, for messages, e.g. `frames ← X, rows ← Y, columns ← T
+    /// (reversed)`.
+    pub fn describe(&self) -> String {
+        ["frames", "rows", "columns"]
+            .iter()
+            .zip(self.axes.iter().zip(self.reversed))
+            .map(|(slot, (axis, reversed))| {
+                let reversed = if reversed { " (reversed)" } else { "" };
+                format!("{} ← {:?}{}", slot, axis, reversed)
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Builds output frame `index`: an `out_width × out_height` packed
+    /// image gathered from `frames` of `width × height`. Rows and columns
+    /// past the end of the source repeat the last one (encoder padding).
+    pub fn gather(
+        &self,
+        frames: &[Vec<u8>],
+        format: FrameFormat,
+        (width, height): (usize, usize),
+        index: usize,
+        (out_width, out_height): (usize, usize),
+    ) -> Vec<u8> {
+        let bpp = format.bytes_per_pixel();
+        let sizes = [frames.len(), height, width];
+        let coordinate = |slot: usize, position: usize| {
+            let size = sizes[self.axes[slot].index()];
+            let position = position.min(size - 1);
+            match self.reversed[slot] {
+                true => size - 1 - position,
+                false => position,
+            }
+        };
+        let mut gathered = vec![0u8; out_width * out_height * bpp];
+        let mut source = [0; 3];
+        source[self.axes[0].index()] = coordinate(0, index);
+        for (row, pixels) in gathered.chunks_exact_mut(out_width * bpp).enumerate() {
+            source[self.axes[1].index()] = coordinate(1, row);
+            for (column, pixel) in pixels.chunks_exact_mut(bpp).enumerate() {
+                source[self.axes[2].index()] = coordinate(2, column);
+                let [t, y, x] = source;
+                let offset = (y * width + x) * bpp;
+                pixel.copy_from_slice(&frames[t][offset..offset + bpp]);
+            }
+        }
+        gathered
+    }
+}
+
+/// One operation of the chain, as a rearrangement of the cube before it.
+fn operation(name: &str) -> Result<Rearrangement, String> {
+    let swap = |axes| Rearrangement {
+        axes,
+        reversed: [false; 3],
+    };
+    let reverse = |slot: usize| {
+        let mut reversed = [false; 3];
+        reversed[slot] = true;
+        Rearrangement {
+            reversed,
+            ..Rearrangement::IDENTITY
+        }
+    };
+    match name {
+        "xt_transpose" => Ok(Rearrangement::TRANSPOSE),
+        "yt_transpose" => Ok(Rearrangement::YT_TRANSPOSE),
+        "xy_transpose" => Ok(swap([Axis::T, Axis::X, Axis::Y])),
+        "reverse_time" => Ok(reverse(0)),
+        "reverse_y" => Ok(reverse(1)),
+        "reverse_x" => Ok(reverse(2)),
+        _ => Err(format!(
+            "Invalid --ops operation: {} (expected xt_transpose, yt_transpose, xy_transpose, \
+             reverse_time, reverse_x or reverse_y)",
+            name
+        )),
+    }
+}
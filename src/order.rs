@@ -0,0 +1,149 @@
+use std::fs;
+use std::ops::Range;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Order in which source columns become output frames.
+pub enum FrameOrder {
+    Natural,
+    Reverse,
+    Shuffle,
+    /// Explicit list of source columns read from a file.
+    File(PathBuf),
+}
+
+impl FrameOrder {
+    pub fn parse(text: &str) -> Result<FrameOrder, String> {
+        match text {
+            "natural" => Ok(FrameOrder::Natural),
+            "reverse" => Ok(FrameOrder::Reverse),
+            "shuffle" => Ok(FrameOrder::Shuffle),
+            _ => match text.strip_prefix("file:") {
+                Some(path) => Ok(FrameOrder::File(PathBuf::from(path))),
+                None => Err(format!(
+                    "Invalid frame order: {} (expected natural, reverse, shuffle or file:<path>)",
+                    text
+                )),
+            },
+        }
+    }
+
+    /// Reorders `columns`, the source columns in natural output order.
+    pub fn apply(&self, mut columns: Vec<usize>, seed: u64) -> Result<Vec<usize>, String> {
+        match self {
+            FrameOrder::Natural => {}
+            FrameOrder::Reverse => columns.reverse(),
+            FrameOrder::Shuffle => shuffle(&mut columns, seed),
+            FrameOrder::File(path) => {
+                let contents = fs::read_to_string(path)
+                    .map_err(|e| format!("Cannot read frame order {}: {}", path.display(), e))?;
+                columns = parse_order_file(&contents, &columns)?;
+            }
+        }
+        Ok(columns)
+    }
+}
+
+/// Parses a list of source columns separated by whitespace or commas. Lines
+/// starting with `#` are comments. Columns may repeat or be left out.
+fn parse_order_file(contents: &str, available: &[usize]) -> Result<Vec<usize>, String> {
+    let mut order = Vec::new();
+    for line in contents.lines() {
+        let line = line.split('#').next().unwrap_or("");
+        for token in line.split(|c: char| c == ',' || c.is_whitespace()) {
+            if token.is_empty() {
+                continue;
+            }
+            let column: usize = token
+                .parse()
+                .map_err(|_| format!("Invalid column in frame order file: {}", token))?;
+            if !available.contains(&column) {
+                return Err(format!(
+                    "Column {} in frame order file is outside the source",
+                    column
+                ));
+            }
+            order.push(column);
+        }
+    }
+    if order.is_empty() {
+        return Err("Frame order file lists no columns".to_string());
+    }
+    Ok(order)
+}
+
+/// Appends the sequence in reverse, leaving out both turning points so a
+/// looping player never shows the same frame twice in a row.
+pub fn boomerang(columns: &mut Vec<usize>) {
+    if columns.len() > 2 {
+        let back: Vec<usize> = columns[1..columns.len() - 1]
+            .iter()
+            .rev()
+            .copied()
+            .collect();
+        columns.extend(back);
+    }
+}
+
+/// Half-open range of source columns to encode, written `start..end`. Either
+/// bound may be omitted (`100..`, `..500`).
+#[derive(Clone, Copy)]
+pub struct ColumnRange {
+    start: Option<usize>,
+    end: Option<usize>,
+}
+
+impl ColumnRange {
+    pub fn parse(text: &str) -> Result<ColumnRange, String> {
+        let invalid = || format!("Invalid column range: {} (expected start..end)", text);
+        let (start, end) = text.split_once("..").ok_or_else(invalid)?;
+        let bound = |s: &str| -> Result<Option<usize>, String> {
+            if s.is_empty() {
+                Ok(None)
+            } else {
+                s.parse().map(Some).map_err(|_| invalid())
+            }
+        };
+        Ok(ColumnRange {
+            start: bound(start)?,
+            end: bound(end)?,
+        })
+    }
+
+    /// Resolves the range against the source width.
+    pub fn resolve(&self, width: usize) -> Result<Range<usize>, String> {
+        let start = self.start.unwrap_or(0);
+        let end = self.end.unwrap_or(width);
+        if start >= end || end > width {
+            return Err(format!(
+                "Column range {}..{} is empty or outside the source width of {}",
+                start, end, width
+            ));
+        }
+        Ok(start..end)
+    }
+}
+
+/// Fisher-Yates shuffle driven by a xorshift generator, so a given seed
+/// always produces the same order.
+fn shuffle(columns: &mut [usize], seed: u64) {
+    let mut state = seed.max(1);
+    for i in (1..columns.len()).rev() {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        let j = (state % (i as u64 + 1)) as usize;
+        columns.swap(i, j);
+    }
+}
+
+/// Seed used with `--deterministic` when `--seed` isn't given.
+pub const DETERMINISTIC_SEED: u64 = 1;
+
+/// Seed used when `--seed` isn't given.
+pub fn default_seed() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(1)
+}
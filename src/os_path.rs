@@ -0,0 +1,213 @@
+//! Opening files with libavformat from OS paths. ffmpeg-next converts paths
+//! with `to_str().unwrap()`, which panics on non-UTF-8 names, so inputs and
+//! outputs are opened here with a conversion that handles them:
+//!
+//! - on Unix the path's bytes are passed through as they are, whatever
+//!   their encoding;
+//! - on Windows FFmpeg expects UTF-8 (it converts back to UTF-16 itself),
+//!   and paths beyond `MAX_PATH` get the `\\?\` prefix so they open without
+//!   the long-path registry setting.
+
+use ffmpeg_next as ffmpeg;
+use std::ffi::CString;
+use std::path::Path;
+use std::ptr;
+
+/// Converts `path` to the C string FFmpeg expects for a file name.
+pub fn to_cstring(path: &Path) -> Result<CString, String> {
+    let bytes = native_bytes(path)?;
+    CString::new(bytes).map_err(|_| format!("Path contains a NUL byte: {}", path.display()))
+}
+
+#[cfg(unix)]
+fn native_bytes(path: &Path) -> Result<Vec<u8>, String> {
+    use std::os::unix::ffi::OsStrExt;
+    Ok(path.as_os_str().as_bytes().to_vec())
+}
+
+#[cfg(not(unix))]
+fn native_bytes(path: &Path) -> Result<Vec<u8>, String> {
+    /// Longest path Windows opens without the extended-length prefix.
+    const MAX_PATH: usize = 260;
+
+    let text = path
+        .to_str()
+        .ok_or_else(|| format!("Path is not valid Unicode: {}", path.display()))?;
+    if text.len() >= MAX_PATH && path.is_absolute() && !text.starts_with(r"\\") {
+        return Ok(format!(r"\\?\{}", text.replace('/', r"\")).into_bytes());
+    }
+    Ok(text.as_bytes().to_vec())
+}
+
+/// Opens `path` for demuxing, probing its format unless `format` is given.
+pub fn open_input(
+    path: &Path,
+    format: Option<ffmpeg::format::format::Input>,
+) -> Result<ffmpeg::format::context::Input, Box<dyn std::error::Error>> {
+    open_input_with(path, format, ptr::null_mut(), ffmpeg::Dictionary::new())
+}
+
+/// Opens `path` like [`open_input`], with demuxer `options` and reading
+/// through `io` unless it's null. The caller keeps `io` alive until the
+/// input is closed, and frees it.
+pub fn open_input_with(
+    path: &Path,
+    format: Option<ffmpeg::format::format::Input>,
+    io: *mut ffmpeg::ffi::AVIOContext,
+    options: ffmpeg::Dictionary,
+) -> Result<ffmpeg::format::context::Input, Box<dyn std::error::Error>> {
+    let path = to_cstring(path)?;
+    unsafe {
+        let format = format.map_or(ptr::null(), |format| format.as_ptr());
+        let mut ps = ptr::null_mut();
+        if !io.is_null() {
+            ps = ffmpeg::ffi::avformat_alloc_context();
+            if ps.is_null() {
+                return Err(ffmpeg::Error::Other {
+                    errno: ffmpeg::util::error::ENOMEM,
+                }
+                .into());
+            }
+            (*ps).pb = io;
+            (*ps).flags |= ffmpeg::ffi::AVFMT_FLAG_CUSTOM_IO;
+        }
+        let mut options = options.disown();
+        let result = ffmpeg::ffi::avformat_open_input(&mut ps, path.as_ptr(), format, &mut options);
+        // Options the demuxer didn't take are left over
+        ffmpeg::ffi::av_dict_free(&mut options);
+        if result != 0 {
+            return Err(ffmpeg::Error::from(result).into());
+        }
+        match ffmpeg::ffi::avformat_find_stream_info(ps, ptr::null_mut()) {
+            e if e < 0 => {
+                ffmpeg::ffi::avformat_close_input(&mut ps);
+                Err(ffmpeg::Error::from(e).into())
+            }
+            _ => Ok(ffmpeg::format::context::Input::wrap(ps)),
+        }
+    }
+}
+
+/// Creates `path` for muxing with the muxer named `format`.
+pub fn open_output(
+    path: &Path,
+    format: &str,
+) -> Result<ffmpeg::format::context::Output, Box<dyn std::error::Error>> {
+    let path = to_cstring(path)?;
+    let format = CString::new(format)?;
+    unsafe {
+        let mut ps = ptr::null_mut();
+        match ffmpeg::ffi::avformat_alloc_output_context2(
+            &mut ps,
+            ptr::null(),
+            format.as_ptr(),
+            path.as_ptr(),
+        ) {
+            0 => {}
+            e => return Err(ffmpeg::Error::from(e).into()),
+        }
+        match ffmpeg::ffi::avio_open(&mut (*ps).pb, path.as_ptr(), ffmpeg::ffi::AVIO_FLAG_WRITE) {
+            0 => Ok(ffmpeg::format::context::Output::wrap(ps)),
+            e => {
+                ffmpeg::ffi::avformat_free_context(ps);
+                Err(ffmpeg::Error::from(e).into())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::PathBuf;
+
+    /// File names in scripts beyond Latin-1, with an emoji outside the BMP.
+    const NAMES: [&str; 3] = ["🎬 transpose.nut", "動画_テスト.nut", "видео 视频 🎞️.nut"];
+
+    /// A fresh directory for one test.
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "video-transpose-os-path-{}-{}",
+            std::process::id(),
+            name
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Writes a NUT file at `path` holding one 16×16 raw video stream.
+    fn write_clip(path: &Path) {
+        let mut output = open_output(path, "nut").unwrap();
+        let codec = ffmpeg::encoder::find(ffmpeg::codec::Id::RAWVIDEO).unwrap();
+        let mut encoder = ffmpeg::codec::context::Context::new_with_codec(codec)
+            .encoder()
+            .video()
+            .unwrap();
+        encoder.set_width(16);
+        encoder.set_height(16);
+        encoder.set_format(ffmpeg::format::Pixel::RGB24);
+        encoder.set_time_base((1, 25));
+        let encoder = encoder.open().unwrap();
+        let mut stream = output.add_stream(codec).unwrap();
+        stream.set_parameters(&encoder);
+        output.write_header().unwrap();
+        output.write_trailer().unwrap();
+    }
+
+    #[test]
+    fn to_cstring_keeps_unicode_names() {
+        for name in NAMES {
+            let path = Path::new("clips").join(name);
+            let converted = to_cstring(&path).unwrap();
+            assert_eq!(converted.to_str().unwrap(), path.to_str().unwrap());
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn to_cstring_passes_other_bytes_through() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let bytes = b"clip_\xff\xfe.mp4";
+        let converted = to_cstring(Path::new(OsStr::from_bytes(bytes))).unwrap();
+        assert_eq!(converted.as_bytes(), bytes);
+    }
+
+    #[test]
+    fn to_cstring_rejects_nul() {
+        assert!(to_cstring(Path::new("clip\0.mp4")).is_err());
+    }
+
+    #[test]
+    fn unicode_names_round_trip() {
+        ffmpeg::init().unwrap();
+        let dir = test_dir("round-trip");
+        for name in NAMES {
+            let path = dir.join(name);
+            write_clip(&path);
+            // Written under the exact name, not a mangled one
+            assert!(path.is_file(), "{} wasn't created", name);
+            let input = open_input(&path, None).unwrap();
+            let stream = input.streams().best(ffmpeg::media::Type::Video).unwrap();
+            let parameters = stream.parameters();
+            let decoder = ffmpeg::codec::context::Context::from_parameters(parameters)
+                .unwrap()
+                .decoder()
+                .video()
+                .unwrap();
+            assert_eq!((decoder.width(), decoder.height()), (16, 16));
+        }
+        let names: Vec<String> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        assert_eq!(names.len(), NAMES.len());
+        for name in NAMES {
+            assert!(names.iter().any(|written| written == name));
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
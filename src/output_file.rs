@@ -0,0 +1,146 @@
+use crate::os_path;
+use crate::packaging;
+use ffmpeg_next as ffmpeg;
+use std::ffi::CStr;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::ptr;
+
+/// An output file written under a temporary `.part` name next to its final
+/// path and renamed into place once complete, so watchers never see a
+/// half-written file. The partial file is deleted if the run fails.
+///
+/// HLS and DASH segments are staged the same way, in a `.segments.part`
+/// directory next to the playlist, and moved into place before it on
+/// commit. DASH names segments relative to its manifest, so the manifest
+/// is written in that directory too.
+pub struct PartialOutput {
+    final_path: PathBuf,
+    part_path: PathBuf,
+    /// Directory the segments are written to until committed.
+    segment_dir: Option<PathBuf>,
+    format: String,
+    committed: bool,
+}
+
+impl PartialOutput {
+    pub fn new(final_path: &Path) -> Result<Self, String> {
+        // The muxer has to come from the final name, `.part` says nothing about it
+        let format = guess_muxer(final_path).ok_or_else(|| {
+            format!(
+                "Cannot determine the output format from {}",
+                final_path.display()
+            )
+        })?;
+
+        let mut part_name = final_path.as_os_str().to_owned();
+        part_name.push(".part");
+        let mut part_path = PathBuf::from(part_name);
+        let segment_dir = packaging::is_segmented(&format).then(|| {
+            let mut dir_name = final_path.as_os_str().to_owned();
+            dir_name.push(".segments.part");
+            PathBuf::from(dir_name)
+        });
+        if let (Some(dir), Some(name), "dash") = (&segment_dir, final_path.file_name(), &*format) {
+            part_path = dir.join(name);
+        }
+
+        Ok(PartialOutput {
+            final_path: final_path.to_path_buf(),
+            part_path,
+            segment_dir,
+            format,
+            committed: false,
+        })
+    }
+
+    /// Whether the final file, or any of its segments, already exists.
+    pub fn exists(&self) -> io::Result<bool> {
+        if self.final_path.exists() {
+            return Ok(true);
+        }
+        if self.segment_dir.is_some() {
+            let segments = packaging::existing_segments(&self.format, &self.final_path)?;
+            return Ok(!segments.is_empty());
+        }
+        Ok(false)
+    }
+
+    /// Path the file will have once committed.
+    pub fn final_path(&self) -> &Path {
+        &self.final_path
+    }
+
+    /// Path the file is written to until committed.
+    pub fn partial_path(&self) -> &Path {
+        &self.part_path
+    }
+
+    /// Short name of the muxer matching the final path's extension.
+    pub fn format(&self) -> &str {
+        &self.format
+    }
+
+    /// Directory segments are written to until committed.
+    pub fn segment_dir(&self) -> Option<&Path> {
+        self.segment_dir.as_deref()
+    }
+
+    /// Opens an output context writing to the partial file.
+    pub fn open(&self) -> Result<ffmpeg::format::context::Output, Box<dyn std::error::Error>> {
+        if let Some(dir) = &self.segment_dir {
+            fs::create_dir_all(dir)?;
+        }
+        os_path::open_output(&self.part_path, &self.format)
+    }
+
+    /// Atomically moves the finished file to its final path. Segments go
+    /// first, so the playlist never names a missing one, and segments of an
+    /// earlier run the new playlist doesn't use are removed after.
+    pub fn commit(mut self) -> io::Result<()> {
+        let mut stale = Vec::new();
+        if let Some(dir) = &self.segment_dir {
+            stale = packaging::existing_segments(&self.format, &self.final_path)?;
+            for entry in fs::read_dir(dir)? {
+                let entry = entry?;
+                // The DASH manifest is staged here too, and moved last
+                if !packaging::is_segment(&self.format, &self.final_path, &entry.file_name()) {
+                    continue;
+                }
+                let segment = self.final_path.with_file_name(entry.file_name());
+                fs::rename(entry.path(), &segment)?;
+                stale.retain(|path| *path != segment);
+            }
+        }
+        fs::rename(&self.part_path, &self.final_path)?;
+        self.committed = true;
+        for path in stale {
+            let _ = fs::remove_file(path);
+        }
+        Ok(())
+    }
+}
+
+impl Drop for PartialOutput {
+    fn drop(&mut self) {
+        if !self.committed {
+            let _ = fs::remove_file(&self.part_path);
+        }
+        if let Some(dir) = &self.segment_dir {
+            let _ = fs::remove_dir_all(dir);
+        }
+    }
+}
+
+/// Asks libavformat which muxer it would pick for `path`.
+pub fn guess_muxer(path: &Path) -> Option<String> {
+    let filename = os_path::to_cstring(path).ok()?;
+    unsafe {
+        let format = ffmpeg::ffi::av_guess_format(ptr::null(), filename.as_ptr(), ptr::null());
+        if format.is_null() {
+            return None;
+        }
+        Some(CStr::from_ptr((*format).name).to_str().ok()?.to_string())
+    }
+}
@@ -0,0 +1,92 @@
+use std::path::PathBuf;
+
+/// An additional output fed from the same decode and transpose pass,
+/// written as `path[:scale=<s>][:crf=<n>]`.
+pub struct OutputSpec {
+    pub path: PathBuf,
+    pub scale: Scale,
+    pub crf: Option<u32>,
+}
+
+/// Size of an output relative to the transposed frames.
+#[derive(Clone, Copy)]
+pub enum Scale {
+    Factor(f64),
+    Size(usize, usize),
+}
+
+impl OutputSpec {
+    pub fn parse(text: &str) -> Result<OutputSpec, String> {
+        // Options are the trailing `key=value` segments, so paths may contain ':'
+        let mut segments: Vec<&str> = text.split(':').collect();
+        let mut scale = Scale::Factor(1.0);
+        let mut crf = None;
+        while segments.len() > 1 {
+            let (key, value) = match segments.last().and_then(|s| s.split_once('=')) {
+                Some(option) => option,
+                None => break,
+            };
+            match key {
+                "scale" => scale = Scale::parse(value)?,
+                "crf" => {
+                    crf = Some(
+                        value
+                            .parse()
+                            .map_err(|_| format!("Invalid crf in output spec: {}", value))?,
+                    )
+                }
+                _ => return Err(format!("Unknown output option: {}", key)),
+            }
+            segments.pop();
+        }
+
+        let path = segments.join(":");
+        if path.is_empty() {
+            return Err(format!("Missing path in output spec: {}", text));
+        }
+        Ok(OutputSpec {
+            path: PathBuf::from(path),
+            scale,
+            crf,
+        })
+    }
+}
+
+impl Scale {
+    pub fn parse(text: &str) -> Result<Scale, String> {
+        let invalid = || {
+            format!(
+                "Invalid scale: {} (expected half, quarter, a factor or WxH)",
+                text
+            )
+        };
+        match text {
+            "half" => Ok(Scale::Factor(0.5)),
+            "quarter" => Ok(Scale::Factor(0.25)),
+            _ => match text.split_once('x') {
+                Some((w, h)) => Ok(Scale::Size(
+                    w.parse().map_err(|_| invalid())?,
+                    h.parse().map_err(|_| invalid())?,
+                )),
+                None => match text.parse::<f64>() {
+                    Ok(factor) if factor > 0.0 => Ok(Scale::Factor(factor)),
+                    _ => Err(invalid()),
+                },
+            },
+        }
+    }
+
+    /// Output dimensions for a `width × height` frame. Resized outputs are
+    /// rounded down to even numbers for 4:2:0 encoding.
+    pub fn apply(&self, width: usize, height: usize) -> (usize, usize) {
+        let (w, h) = match *self {
+            Scale::Factor(1.0) => return (width, height),
+            Scale::Factor(factor) => (
+                (width as f64 * factor) as usize,
+                (height as f64 * factor) as usize,
+            ),
+            Scale::Size(w, h) => (w, h),
+        };
+        ((w & !1).max(2), (h & !1).max(2))
+    }
+}
@@ -0,0 +1,108 @@
+use ffmpeg_next::Dictionary;
+use std::ffi::OsStr;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Default target segment length for HLS/DASH outputs, in seconds.
+pub const DEFAULT_SEGMENT_SECONDS: f64 = 6.0;
+
+/// Whether the muxer writes a playlist plus separate media segments.
+pub fn is_segmented(format: &str) -> bool {
+    format == "hls" || format == "dash"
+}
+
+/// Name stem of the segments of the playlist at `final_path`: HLS writes
+/// `<stem>_00000.ts`, `<stem>_00001.ts`, ... next to it, DASH
+/// `<stem>_init-<stream>.m4s` and `<stem>_chunk-<stream>-00001.m4s`, ...
+fn segment_stem(final_path: &Path) -> String {
+    let stem = final_path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "segment".to_string());
+    format!("{}_", stem)
+}
+
+/// Whether `name` is the file name of a segment of the `format` playlist
+/// at `final_path`.
+pub fn is_segment(format: &str, final_path: &Path, name: &OsStr) -> bool {
+    let Some(rest) = name
+        .to_str()
+        .and_then(|name| name.strip_prefix(&segment_stem(final_path)))
+    else {
+        return false;
+    };
+    match format {
+        "hls" => rest
+            .strip_suffix(".ts")
+            .is_some_and(|number| !number.is_empty() && number.bytes().all(|b| b.is_ascii_digit())),
+        "dash" => rest.starts_with("init-") || rest.starts_with("chunk-"),
+        _ => false,
+    }
+}
+
+/// Segments of the `format` playlist at `final_path` already on disk, e.g.
+/// from an earlier run.
+pub fn existing_segments(format: &str, final_path: &Path) -> io::Result<Vec<PathBuf>> {
+    let dir = match final_path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(error) => return Err(error),
+    };
+    let mut segments = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        let name = entry.file_name();
+        if is_segment(format, final_path, &name) {
+            segments.push(final_path.with_file_name(name));
+        }
+    }
+    Ok(segments)
+}
+
+/// Muxer options for HLS/DASH packaging. Segment names are derived from
+/// the final playlist path, not the temporary `.part` file being written;
+/// HLS segments are written to `staging`, if given, and moved next to the
+/// playlist when it's committed. DASH segments are named relative to the
+/// manifest and land next to wherever it is written.
+pub fn muxer_options(
+    format: &str,
+    final_path: &Path,
+    staging: Option<&Path>,
+    segment_seconds: f64,
+) -> Dictionary<'static> {
+    let mut options = Dictionary::new();
+    let seconds = format!("{}", segment_seconds);
+    match format {
+        "hls" => {
+            // The playlist names segments by their file name alone
+            let name = format!("{}%05d.ts", segment_stem(final_path));
+            let segments = match staging {
+                Some(dir) => dir.join(name),
+                None => final_path.with_file_name(name),
+            };
+            options.set("hls_time", &seconds);
+            options.set("hls_playlist_type", "vod");
+            options.set("hls_segment_filename", &segments.to_string_lossy());
+        }
+        "dash" => {
+            // Named after the manifest, so outputs sharing a directory don't collide
+            let stem = segment_stem(final_path);
+            options.set("seg_duration", &seconds);
+            options.set(
+                "init_seg_name",
+                &format!("{}init-$RepresentationID$.$ext$", stem),
+            );
+            options.set(
+                "media_seg_name",
+                &format!("{}chunk-$RepresentationID$-$Number%05d$.$ext$", stem),
+            );
+        }
+        _ => {}
+    }
+    options
+}
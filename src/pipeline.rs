@@ -0,0 +1,2178 @@
+use ffmpeg_next as ffmpeg;
+use ffmpeg_next::format::Pixel;
+use ffmpeg_next::software::scaling::context::Context;
+use ffmpeg_next::util::frame::video::Video;
+use serde_json::Value;
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use crate::audio::{self, AudioDrive};
+use crate::autocrop::{self, Crop};
+use crate::captions;
+use crate::channels::Channel;
+use crate::cli::Options;
+use crate::cloud::ObjectUrl;
+use crate::conform::{self, Conform};
+use crate::cube_cache::CubeCache;
+use crate::encode::{EncodeTarget, MultiTrackWriter, VideoWriter};
+use crate::exr_output::{ExrSequence, Provenance};
+use crate::fit::Reduction;
+use crate::frame_store::{FrameFormat, FrameStore};
+use crate::heatmap::HeatmapStat;
+use crate::index::{IndexMode, RunIndex};
+use crate::input_limit::InputLimit;
+use crate::legend::Legend;
+use crate::linear_light::{LinearLight, Transfer};
+use crate::memory::{format_bytes, MemoryGuard};
+use crate::mosaic::MosaicGrid;
+use crate::multi_track::Track;
+use crate::ops::Rearrangement;
+use crate::order::FrameOrder;
+use crate::output_file::PartialOutput;
+use crate::output_spec::Scale;
+use crate::progress::Progress;
+use crate::progress_socket::ProgressSocket;
+use crate::side_by_side::{self, SideBySide};
+use crate::sidecar::Sidecar;
+use crate::sink::{CallbackSink, FrameCallback, FrameSink, NullSink, OutputFrame, PipeSink};
+use crate::sonify::{self, Sonifier};
+use crate::source::{self, SizeChange};
+use crate::split::Sequence;
+use crate::stats::RunStats;
+use crate::target::Target;
+use crate::tempfiles::TempFiles;
+use crate::throttle::{Priority, Throttle};
+use crate::watermark::Watermark;
+use crate::windows::{PastLastWindow, WindowLayout};
+
+use crate::checksum;
+use crate::cloud;
+use crate::color_range;
+use crate::console;
+use crate::container;
+use crate::encode;
+use crate::encode_stats;
+use crate::exact;
+use crate::exr_output;
+use crate::frame_store;
+use crate::heatmap;
+use crate::index;
+use crate::interpolate;
+use crate::ivtc;
+use crate::legend;
+use crate::limits;
+use crate::mapping;
+use crate::order;
+use crate::output_file;
+use crate::packaging;
+use crate::presets;
+use crate::resample;
+use crate::retry::RetryPolicy;
+use crate::scenes;
+use crate::smooth;
+use crate::split;
+use crate::stats;
+use crate::throttle;
+use crate::transpose;
+use crate::validate;
+use crate::windows;
+
+/// Runs a transpose job, returning the JSON summary of the run.
+pub fn run(options: &Options, stats: &mut RunStats) -> Result<Value, Box<dyn std::error::Error>> {
+    if options.null_sink {
+        return run_job(options, stats, Some(&mut NullSink));
+    }
+    if options.output == Path::new("-") {
+        console::reserve_stdout();
+        return run_job(options, stats, Some(&mut PipeSink::new()));
+    }
+    run_job(options, stats, None)
+}
+
+/// Runs a transpose job like [`run`], but hands each output frame to
+/// `callback` instead of writing the output files, e.g. to feed a live
+/// visualization. The output paths in `options` are ignored and the output
+/// is never split.
+pub fn run_with_callback(
+    options: &Options,
+    stats: &mut RunStats,
+    callback: &mut FrameCallback,
+) -> Result<Value, Box<dyn std::error::Error>> {
+    run_job(options, stats, Some(&mut CallbackSink(callback)))
+}
+
+/// Runs the job, writing the output files or, given `stream`, handing all
+/// frames to it instead.
+fn run_job(
+    options: &Options,
+    stats: &mut RunStats,
+    stream: Option<&mut dyn FrameSink>,
+) -> Result<Value, Box<dyn std::error::Error>> {
+    let mut socket = match &options.progress_socket {
+        Some(path) => Some(ProgressSocket::bind(path)?),
+        None => None,
+    };
+    let result = run_stages(options, stats, stream, socket.as_mut());
+    if let Some(socket) = &mut socket {
+        socket.done(&result);
+    }
+    result
+}
+
+/// The stages of [`run_job`], reporting to `--progress-socket` clients
+/// through `socket`.
+fn run_stages(
+    options: &Options,
+    stats: &mut RunStats,
+    mut stream: Option<&mut dyn FrameSink>,
+    mut socket: Option<&mut ProgressSocket>,
+) -> Result<Value, Box<dyn std::error::Error>> {
+    let input_path = &options.input;
+    let output_path = &options.output;
+
+    let temp = TempFiles::new(options.temp_dir.as_deref(), options.keep_temp);
+    if options.priority == Priority::Low {
+        throttle::lower_priority();
+    }
+    if let Some(cpus) = &options.affinity {
+        throttle::set_affinity(cpus);
+    }
+    if let Some((name, preset)) = &options.save_preset {
+        let path = presets::save(name, preset)?;
+        status!("Saved the options as preset {} in {}", name, path.display());
+    }
+
+    let run_start = Instant::now();
+
+    // A chain that comes down to the transpose runs as one
+    let ops = options.ops.filter(|ops| !ops.is_transpose());
+    status!("Loading video: {}", input_path.display());
+    match &ops {
+        Some(ops) => status!("This will rearrange the axes: {}\n", ops.describe()),
+        None => {
+            status!("This will transpose X (horizontal) and T (time) axes");
+            status!("Original: X×Y pixels, T frames → Output: T×Y pixels, X frames\n");
+        }
+    }
+
+    if let Some(expected) = &options.checksum {
+        checksum::verify(input_path, expected)?;
+    }
+
+    // Open input video
+    let (input, mut info) = source::open(options)?;
+
+    status!("Input video info:");
+    status!("  Resolution: {}×{}", info.width, info.height);
+    status!("  Frame rate: {} fps", stats::format_rate(info.fps));
+    if options.raw_format.is_some() {
+        status!("  Raw pixel format: {:?}", info.format);
+    }
+    if let Some(start) = info.start_time {
+        status!("  Start time: {:.3}s (timestamps normalized to 0)", start);
+    }
+    let mut input = ivtc::apply(input, &mut info, options)?;
+    let fps = info.fps;
+    // With --windows only the frames inside them are stored
+    let estimated_frames = match &options.windows {
+        Some(windows) => {
+            let window_frames = windows::frame_counts(windows, f64::from(fps))
+                .iter()
+                .sum::<u64>();
+            Some(
+                info.estimated_frames
+                    .map_or(window_frames, |frames| frames.min(window_frames)),
+            )
+        }
+        None => info.estimated_frames,
+    };
+
+    let frame_format = select_frame_format(options, info.format)?;
+    if frame_format != FrameFormat::Rgb24 {
+        match frame_format {
+            FrameFormat::Gray16 => {
+                status!("  Keeping 16-bit grayscale samples (lossless FFV1 output)")
+            }
+            _ => status!("  Processing 32-bit float frames (EXR sequence output)"),
+        }
+        if options.temporal_smooth > 0 {
+            return Err("--temporal-smooth needs 8-bit frames (not 16-bit or float)".into());
+        }
+        if options.split_channels.is_some() {
+            return Err("--split-channels needs 8-bit color frames (not 16-bit or float)".into());
+        }
+        if options.canvas.is_some() && frame_format == FrameFormat::RgbF32 {
+            return Err("--canvas needs 8-bit or 16-bit frames (not float)".into());
+        }
+        if options.with_original.is_some() && frame_format == FrameFormat::RgbF32 {
+            return Err("--with-original needs 8-bit or 16-bit frames (not float)".into());
+        }
+    } else if frame_store::is_high_depth_gray(info.format) {
+        status!(
+            "  Note: {:?} input is reduced to 8 bits for H.264; write .mkv or .nut outputs to keep \
+             all 16 bits with FFV1",
+            info.format
+        );
+    }
+    if options.exact {
+        exact::check_input(info.format, frame_format)?;
+        status!("  Exact mode: output pixels are input pixels re-indexed");
+    }
+    let linear_light = match options.linear_light {
+        true => {
+            let light = LinearLight::new(Transfer::from_tag(info.transfer)?);
+            status!(
+                "  Blending in linear light ({} transfer)",
+                light.transfer().name()
+            );
+            Some(light)
+        }
+        false => None,
+    };
+    let watermark = match &options.watermark {
+        Some(path) => {
+            let watermark =
+                Watermark::load(path, options.watermark_position, options.watermark_opacity)?;
+            status!("  Watermark: {} ({})", path.display(), watermark.describe());
+            Some(watermark)
+        }
+        None => None,
+    };
+    if let Some(sonify) = options.sonify {
+        status!(
+            "  Sonifying row {} ({}) into an AAC track at {} Hz",
+            sonify.row,
+            sonify.name(),
+            sonify::SAMPLE_RATE
+        );
+    }
+    // Video outputs with the muxer each one's extension picks. Streamed
+    // frames have no files to check.
+    let video_outputs: Vec<(&PathBuf, Option<String>)> = std::iter::once(&options.output)
+        .chain(options.also_encode.iter().map(|spec| &spec.path))
+        .filter(|path| stream.is_none() && !exr_output::is_exr_sequence(path))
+        .map(|path| (path, output_file::guess_muxer(path)))
+        .collect();
+    if let (Some(target), None) = (options.target, &stream) {
+        status!("  Target: {}", target.name());
+        for (path, muxer) in &video_outputs {
+            target.check_container(path, muxer.as_deref())?;
+        }
+    }
+
+    // Refuse up front if the input is longer than allowed, or the decoded
+    // frames can't possibly fit in memory
+    let limit = InputLimit::new(options.frame_limit, options.duration_limit, f64::from(fps));
+    let mut guard = MemoryGuard::new(options.max_memory);
+    // --decode-threads buffers decoded frames next to the store
+    guard.reserve(input.buffer_bytes());
+    let bytes_per_pixel = frame_format.bytes_per_pixel();
+    let mut reduction = Reduction::new(options.scale, options.frame_step);
+    if let Some(estimated) = estimated_frames {
+        limit.check_estimate(estimated)?;
+        let projected = reduction.store_bytes(info.width, info.height, bytes_per_pixel, estimated);
+        status!(
+            "  Estimated frame store: {} ({} frames)",
+            format_bytes(projected),
+            reduction.frames(estimated)
+        );
+        if let Err(error) = guard.check_projected(projected) {
+            // Work out how much smaller the frames would have to be, and
+            // shrink them that much with --auto-fit
+            let fit = match guard.budget() {
+                Some(budget) if !options.exact => {
+                    reduction.fit(info.width, info.height, bytes_per_pixel, estimated, budget)
+                }
+                _ => return Err(error.into()),
+            };
+            match fit.choose().filter(|_| options.auto_fit) {
+                Some(chosen) => {
+                    reduction = chosen;
+                    status!(
+                        "  Auto-fit: {} ({})",
+                        reduction.flags(),
+                        format_bytes(reduction.store_bytes(
+                            info.width,
+                            info.height,
+                            bytes_per_pixel,
+                            estimated
+                        ))
+                    );
+                }
+                None => {
+                    let suggestions = fit.describe();
+                    if suggestions.is_empty() {
+                        return Err(error.into());
+                    }
+                    return Err(format!(
+                        "{} It would fit with {} (or pass --auto-fit).",
+                        error,
+                        suggestions.join(", or ")
+                    )
+                    .into());
+                }
+            }
+        }
+    }
+    let (width, height) = reduction.dimensions(info.width, info.height);
+    let estimated_frames = estimated_frames.map(|frames| reduction.frames(frames));
+    if reduction.scale < 1.0 {
+        status!("  Scaling frames to {}×{}", width, height);
+    }
+    if reduction.step > 1 {
+        status!("  Keeping one of every {} decoded frames", reduction.step);
+    }
+    // Output size as far as it's known before decoding
+    let planned_size = |frames: usize| match &ops {
+        Some(ops) => {
+            let (width, height, _) = ops.output_size(width, height, frames);
+            (even_width(width), even_width(height))
+        }
+        None => (even_width(frames), height),
+    };
+    let planned_height = planned_size(estimated_frames.unwrap_or(0) as usize).1;
+    for (path, muxer) in &video_outputs {
+        container::check(path, muxer.as_deref(), options, fps, planned_height)?;
+    }
+    let matroska = video_outputs
+        .first()
+        .is_some_and(|(_, muxer)| muxer.as_deref() == Some("matroska"));
+    if options.multi_track.is_some() && !matroska {
+        return Err(format!(
+            "--multi-track writes Matroska, but {} isn't .mkv",
+            options.output.display()
+        )
+        .into());
+    }
+    if options.index == Some(IndexMode::Embed) {
+        for path in std::iter::once(&options.output)
+            .chain(options.also_encode.iter().map(|spec| &spec.path))
+        {
+            index::check_embed(path, output_file::guess_muxer(path).as_deref())?;
+        }
+    }
+    if let Some(budget) = guard.budget() {
+        status!("  Memory budget: {}", format_bytes(budget));
+    }
+
+    // Output frame i shows source column columns[i]
+    let seed = match options.seed {
+        Some(seed) => seed,
+        None if options.deterministic => order::DETERMINISTIC_SEED,
+        None => order::default_seed(),
+    };
+    let mut column_range = match (options.columns, &ops) {
+        (Some(range), _) => range.resolve(width)?,
+        // Output frames along the time axis are counted once decoded
+        (None, Some(ops)) => {
+            let estimated = estimated_frames.unwrap_or(0) as usize;
+            0..ops.output_size(width, height, estimated).2.max(1)
+        }
+        (None, None) => 0..width,
+    };
+    // --auto-roi picks where its window goes after decoding, but the number
+    // of output frames has to be known now to plan the outputs
+    let roi_candidates = column_range.clone();
+    let roi_width = options.auto_roi.map(|roi| roi.width(roi_candidates.len()));
+    if let Some(count) = roi_width {
+        column_range = roi_candidates.start..roi_candidates.start + count;
+        status!(
+            "  Auto ROI: the most active {} of columns {}..{}",
+            count,
+            roi_candidates.start,
+            roi_candidates.end
+        );
+    } else if column_range.len() != width {
+        status!(
+            "  Columns: {}..{} ({} of {})",
+            column_range.start,
+            column_range.end,
+            column_range.len(),
+            width
+        );
+    }
+    // --mosaic transposes the first region's columns, and the same columns
+    // of the others alongside
+    let mosaic = match options.mosaic {
+        Some(mosaic) => {
+            let grid = mosaic.grid(&column_range, height)?;
+            column_range = column_range.start..column_range.start + grid.region_width;
+            status!(
+                "  Mosaic: {}×{} regions of {} columns",
+                mosaic.columns,
+                mosaic.rows,
+                grid.region_width
+            );
+            Some(grid)
+        }
+        None => None,
+    };
+    let order_columns = |range: Range<usize>| -> Result<Vec<usize>, String> {
+        let selected = range.len();
+        let stepped: Vec<usize> = range
+            .skip(options.column_offset)
+            .step_by(options.column_step)
+            .collect();
+        if stepped.is_empty() {
+            return Err(format!(
+                "--column-offset {} skips all {} selected columns",
+                options.column_offset, selected
+            ));
+        }
+        let mut columns = options.frame_order.apply(stepped, seed)?;
+        if options.boomerang {
+            order::boomerang(&mut columns);
+        }
+        Ok(columns)
+    };
+    let mut columns = order_columns(column_range)?;
+    if options.column_step > 1 {
+        status!(
+            "  Column step: every {} columns from offset {}, {} output frames",
+            options.column_step,
+            options.column_offset,
+            columns.len()
+        );
+    }
+    if matches!(options.frame_order, FrameOrder::Shuffle) {
+        status!("  Shuffled frame order (seed {})", seed);
+    }
+
+    // Output frames are written as one file, or one per --split-output part
+    // and, when the output would be wider than --split-width or the codec
+    // allows, one per range of source frames
+    let mut frame_ranges = split::ranges(columns.len(), options.split_output);
+    let mxf_output = video_outputs
+        .iter()
+        .any(|(_, muxer)| muxer.as_deref().is_some_and(container::is_mxf));
+    let codec_limit = if mxf_output {
+        container::MPEG2_MAX_DIMENSION
+    } else {
+        limits::H264_MAX_DIMENSION
+    };
+    let width_limit = options.split_width.unwrap_or(codec_limit) / 2 * 2;
+    let planned_tiles = match &options.windows {
+        Some(windows) if options.windows_layout == WindowLayout::Separate => {
+            let counts: Vec<usize> = windows::frame_counts(windows, f64::from(fps))
+                .into_iter()
+                .map(|frames| reduction.frames(frames) as usize)
+                .collect();
+            windows::tiles(&windows::consecutive(&counts), width_limit)
+        }
+        // Rearranged outputs gather from all frames
+        _ if ops.is_some() => split::ranges(estimated_frames.unwrap_or(0) as usize, None),
+        _ => split::ranges(estimated_frames.unwrap_or(0) as usize, Some(width_limit)),
+    };
+    if frame_ranges.len() > 1 {
+        status!(
+            "  Splitting into {} parts of up to {} frames",
+            frame_ranges.len(),
+            options.split_output.unwrap_or(0)
+        );
+    }
+
+    // Check before decoding so a long run can't end by clobbering a previous
+    // result. Streamed frames have no files to check or split.
+    let mut parts = match stream {
+        Some(_) => vec![OutputPart {
+            frames: 0..columns.len(),
+            index: 0,
+            tile: 0,
+            destinations: Vec::new(),
+        }],
+        None => plan_parts(options, &frame_ranges, &planned_tiles, &temp)?,
+    };
+
+    // Likewise make sure the encoder can take the output size. The width is
+    // only known from the estimate, without one just the height is checked.
+    let fps_value = Some(f64::from(fps))
+        .filter(|fps| fps.is_finite())
+        .unwrap_or(0.0);
+    let (planned_width, planned_height) = match estimated_frames {
+        _ if options.canvas.is_some() => options
+            .canvas
+            .map_or((2, planned_height), |canvas| (canvas.width, canvas.height)),
+        Some(estimated) if ops.is_some() => planned_size(estimated as usize),
+        Some(_) => planned_size(planned_tiles[0].len()),
+        None => (2, planned_height),
+    };
+    let (planned_width, planned_height) = match options.with_original {
+        Some(layout) => layout.size((planned_width, planned_height), (width, height)),
+        None => (planned_width, planned_height),
+    };
+    for destination in parts[0]
+        .destinations
+        .iter()
+        .filter(|_| frame_format == FrameFormat::Rgb24)
+        .filter(|_| !options.target.is_some_and(Target::lossless))
+        .filter(|destination| !container::is_mxf(destination.file.format()))
+    {
+        let (out_width, out_height) = destination.scale.apply(planned_width, planned_height);
+        match limits::check_h264(&destination.path, out_width, out_height, fps_value)? {
+            Some(level) => {
+                if estimated_frames.is_some() {
+                    status!(
+                        "  H.264 level for {}: {}",
+                        destination.path.display(),
+                        level
+                    );
+                }
+            }
+            None => status!(
+                "  Warning: {} ({}×{} at {} fps) exceeds H.264 level 6.2 and won't play on most \
+                 hardware decoders; consider --split-width, --columns or a scaled --also-encode",
+                destination.path.display(),
+                out_width,
+                out_height,
+                stats::format_rate(fps)
+            ),
+        }
+    }
+
+    // --audio-drive: the audio is read in a pass of its own, before the
+    // long one, so an input without it fails right away
+    let envelope = match options.audio_drive {
+        Some(_) => {
+            let interval = reduction.step as f64 / f64::from(fps);
+            if !interval.is_finite() || interval <= 0.0 {
+                return Err("--audio-drive needs the input's frame rate".into());
+            }
+            status!("  Reading the audio for --audio-drive...");
+            let envelope = audio::Envelope::analyze(input_path, interval)?;
+            status!(
+                "  Audio: {:.1}s of {}, mean level {:.1} dBFS",
+                envelope.duration(),
+                envelope.codec,
+                envelope.mean_dbfs()
+            );
+            Some(envelope)
+        }
+        None => None,
+    };
+
+    // First pass: decode all frames into memory
+    status!("\n[1/2] Decoding all frames...");
+    let mut frames = FrameStore::default();
+
+    // Create scaler to RGB24 (or GRAY16LE) for easier manipulation. In
+    // linear light it only converts, and the frames are shrunk after
+    let linear_scale = linear_light.as_ref().filter(|_| reduction.scale < 1.0);
+    let (scaled_width, scaled_height) = match linear_scale {
+        Some(_) => (info.width, info.height),
+        None => (width, height),
+    };
+    let mut scaler = Context::get(
+        info.format,
+        info.width as u32,
+        info.height as u32,
+        frame_format.pixel(),
+        scaled_width as u32,
+        scaled_height as u32,
+        options.scaler.flags(),
+    )?;
+    color_range::set_ranges(&mut scaler, info.full_range, false);
+
+    let progress = Progress::new(options.no_progress, options.no_color);
+    let pb = match estimated_frames {
+        Some(estimated) => progress.bar(
+            estimated,
+            "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} frames decoded \
+             ({percent}%, ETA {eta})",
+        ),
+        None => progress.spinner("{spinner:.green} [{elapsed_precise}] {pos} frames decoded"),
+    };
+
+    // Decode all frames
+    let decode_start = Instant::now();
+    let mut throttle = Throttle::new(options.throttle);
+    let frame_interval = reduction.step as f64 / f64::from(fps);
+    let mut filled = 0;
+    let mut decoded_frames = 0;
+    let mut source_captions = Vec::new();
+    // Decoded frame behind each stored one, for --index and the cube cache
+    let mut decoded_indices = Vec::new();
+    // Index of the first frame stored for each --windows window reached
+    let mut window_starts = Vec::new();
+    let cache = options
+        .cache_cube
+        .as_ref()
+        .map(|path| CubeCache::new(path, options, &info, frame_format, reduction));
+    let cached = match &cache {
+        Some(cache) => cache.load(&guard)?,
+        None => None,
+    };
+    let cache_hit = cached.is_some();
+    if let Some(socket) = socket.as_deref_mut() {
+        socket.phase("decode", estimated_frames);
+    }
+    match cached {
+        Some(cached) => {
+            status!(
+                "  Loaded {} decoded frames from the cube cache",
+                cached.frames.len()
+            );
+            limit.check_estimate(cached.frames.len() as u64)?;
+            frames = cached.frames;
+            decoded_indices = cached.source_frames;
+        }
+        None => input
+            .decode_all(&mut |decoded, time| {
+                let index = decoded_frames;
+                decoded_frames += 1;
+                let window = match &options.windows {
+                    Some(windows) => {
+                        let at = time.unwrap_or(index as f64 / f64::from(fps));
+                        if windows::past_last(windows, at) {
+                            return Err(Box::new(PastLastWindow));
+                        }
+                        match windows::find(windows, at) {
+                            Some(window) => Some(window),
+                            None => {
+                                limit.check_frame(decoded_frames, time)?;
+                                return Ok(());
+                            }
+                        }
+                    }
+                    None => None,
+                };
+                if !reduction.keeps(index) {
+                    limit.check_frame(decoded_frames, time)?;
+                    return Ok(());
+                }
+
+                // Every stored frame must have the first frame's layout
+                let expected = scaler.input();
+                let changed = (decoded.width(), decoded.height(), decoded.format())
+                    != (expected.width, expected.height, expected.format);
+                if changed {
+                    let change = format!(
+                        "frame {} is {}×{} {:?}, the input started as {}×{} {:?}",
+                        frames.len(),
+                        decoded.width(),
+                        decoded.height(),
+                        decoded.format(),
+                        info.width,
+                        info.height,
+                        info.format
+                    );
+                    if options.on_size_change == SizeChange::Abort {
+                        return Err(format!(
+                            "Input changes size mid-stream: {}. Pass --on-size-change scale to \
+                     rescale such frames to the first size.",
+                            change
+                        )
+                        .into());
+                    }
+                    let original = (
+                        decoded.width() as usize,
+                        decoded.height() as usize,
+                        decoded.format(),
+                    ) == (info.width, info.height, info.format);
+                    if !original {
+                        status!("  Rescaling from {}", change);
+                    }
+                    scaler.cached(
+                        decoded.format(),
+                        decoded.width(),
+                        decoded.height(),
+                        frame_format.pixel(),
+                        scaled_width as u32,
+                        scaled_height as u32,
+                        options.scaler.flags(),
+                    );
+                    let full_range = color_range::is_full(decoded.format(), decoded.color_range());
+                    color_range::set_ranges(&mut scaler, full_range, false);
+                }
+                let mut rgb_frame = Video::empty();
+                scaler.run(decoded, &mut rgb_frame)?;
+
+                // Copy frame data without the line padding
+                let data = match frame_format {
+                    FrameFormat::RgbF32 => interleave_float_planes(&rgb_frame, scaled_width),
+                    _ => copy_plane(&rgb_frame, 0, scaled_width * frame_format.bytes_per_pixel()),
+                };
+                let data = match linear_scale {
+                    Some(light) => light.downscale(
+                        &data,
+                        frame_format,
+                        (scaled_width, scaled_height),
+                        (width, height),
+                    ),
+                    None => data,
+                };
+                // A window's first frame follows a gap that isn't dropped frames
+                let opens_window = window.is_some_and(|window| window >= window_starts.len());
+                if let Some(window) = window.filter(|_| opens_window) {
+                    window_starts.resize(window + 1, frames.len());
+                }
+                if let Some(time) = time.filter(|_| options.fill_dropped_frames && !opens_window) {
+                    if frame_interval.is_finite() && frame_interval > 0.0 {
+                        filled += frames.fill_gap(time, frame_interval);
+                    }
+                }
+                limit.check_frame(decoded_frames.max(frames.len() as u64 + 1), time)?;
+                guard.check_actual(frames.bytes() + data.len() as u64)?;
+                frames.push(data, time);
+                if options.keep_cc {
+                    source_captions.resize(frames.len() - 1, None);
+                    source_captions.push(captions::extract(decoded));
+                }
+                if options.index.is_some() || cache.is_some() {
+                    // Filled frames repeat the frame before the gap
+                    let previous = decoded_indices.last().copied().flatten();
+                    decoded_indices.resize(frames.len() - 1, previous);
+                    decoded_indices.push(Some(index));
+                }
+
+                pb.inc(1);
+                if let Some(socket) = socket.as_deref_mut() {
+                    socket.progress();
+                }
+                throttle.pace();
+                Ok(())
+            })
+            .or_else(windows::stopped_after_last)?,
+    }
+
+    // The estimate can be off by a few frames, end the bar at the real count
+    pb.set_length(frames.len() as u64);
+    pb.finish_with_message(format!("{} frames decoded", frames.len()));
+    if !progress.enabled() {
+        status!("  {} frames decoded", frames.len());
+    }
+    if filled > 0 {
+        status!(
+            "  Filled {} dropped frames by repeating the frame before each gap",
+            filled
+        );
+    }
+    stats.decode.elapsed = decode_start.elapsed();
+    stats.decode.frames = frames.len() as u64;
+
+    if frames.is_empty() {
+        return Err("No frames decoded".into());
+    }
+    if let (Some(cache), false) = (&cache, cache_hit) {
+        cache.save(&frames, &decoded_indices)?;
+        status!("  Decoded frames cached for later runs");
+    }
+    if let Some((first, last)) = frames.time_span() {
+        status!("  Decoded time span: {:.3}s to {:.3}s", first, last);
+    }
+    let window_ranges = options.windows.as_ref().map(|windows| {
+        let ranges = windows::frame_ranges(&window_starts, windows.len(), frames.len());
+        let counts: Vec<String> = ranges.iter().map(|range| range.len().to_string()).collect();
+        status!("  {} windows of {} frames", ranges.len(), counts.join(", "));
+        if ranges.iter().any(|range| range.is_empty()) {
+            status!("  Warning: some --windows are past the end of the input and stay empty");
+        }
+        ranges
+    });
+    if options.keep_cc {
+        match source_captions.iter().flatten().count() {
+            _ if cache_hit => status!("  Note: the cube cache holds no captions for --keep-cc"),
+            0 => status!("  Note: no closed captions found for --keep-cc"),
+            count => status!("  Keeping closed captions from {} frames", count),
+        }
+    }
+
+    // Variable frame rate input: one output column per frame interval
+    // rather than per decoded frame
+    if let Some(mode) = options.resample {
+        match resample::frame_count(&frames, frame_interval) {
+            Some(count) => {
+                let resampled_bytes = frame_store_bytes(width, height, frame_format, count as u64);
+                guard.check_projected(frames.bytes() + resampled_bytes)?;
+                let resampled = stats.transpose.time(|| {
+                    resample::resample(
+                        &frames,
+                        frame_format,
+                        frame_interval,
+                        mode,
+                        linear_light.as_ref(),
+                    )
+                });
+                if let Some(resampled) = resampled {
+                    status!(
+                        "  Resampled {} frames to {} at {} fps",
+                        frames.len(),
+                        resampled.len(),
+                        stats::format_rate(fps)
+                    );
+                    frames = resampled;
+                    // Resampled frames mix decoded ones, only their times are known
+                    decoded_indices.clear();
+                }
+            }
+            None => {
+                status!("  Warning: not resampling, the input lacks timestamps or a frame rate")
+            }
+        }
+    }
+    if let (Some(AudioDrive::Select(fraction)), Some(envelope)) = (options.audio_drive, &envelope) {
+        let levels = envelope.frame_levels(&frames.times, frame_interval);
+        frames.retain(&audio::select(&levels, fraction));
+        status!(
+            "  Audio drive: keeping the loudest {} of {} frames",
+            frames.len(),
+            levels.len()
+        );
+    }
+    let num_frames = frames.len();
+
+    // --autocrop: the bars are cut from the stored frames, which leaves
+    // fewer columns to transpose
+    let (stored_width, stored_height) = (width, height);
+    let crop = match options.autocrop {
+        true => stats
+            .transpose
+            .time(|| autocrop::detect(&frames.frames, frame_format, width, height)),
+        false => None,
+    };
+    let (width, height) = match crop {
+        Some(crop) => {
+            status!(
+                "  Autocrop: keeping {}×{} at ({}, {}) of {}×{}",
+                crop.width,
+                crop.height,
+                crop.left,
+                crop.top,
+                width,
+                height
+            );
+            stats
+                .transpose
+                .time(|| frames.convert(|frame| crop.apply(frame, frame_format, width)));
+            columns = order_columns(0..crop.width)?;
+            frame_ranges = split::ranges(columns.len(), options.split_output);
+            if stream.is_some() {
+                parts[0].frames = 0..columns.len();
+            }
+            (crop.width, crop.height)
+        }
+        None => {
+            if options.autocrop {
+                status!("  Autocrop: no black bars found");
+            }
+            (width, height)
+        }
+    };
+    // The whole stored frame without --autocrop
+    let crop_rect = crop.unwrap_or(Crop {
+        left: 0,
+        top: 0,
+        width,
+        height,
+    });
+
+    if options.temporal_smooth > 0 {
+        status!(
+            "  Smoothing over {} frames...",
+            2 * options.temporal_smooth + 1
+        );
+        stats.transpose.time(|| {
+            smooth::temporal_smooth(
+                &mut frames.frames,
+                options.temporal_smooth,
+                options.smooth_filter,
+                linear_light.as_ref(),
+            )
+        });
+    }
+
+    if let Some(count) = roi_width {
+        let motion = stats.transpose.time(|| {
+            heatmap::Heatmap::compute(
+                &frames.frames,
+                frame_format,
+                width,
+                height,
+                HeatmapStat::Motion,
+            )
+        });
+        let range = motion.most_active_columns(roi_candidates, count);
+        status!("  Auto ROI selected columns {}..{}", range.start, range.end);
+        columns = order_columns(range)?;
+    }
+    if let Some(ops) = &ops {
+        let (_, _, count) = ops.output_size(width, height, num_frames);
+        columns = order_columns(0..count)?;
+        frame_ranges = split::ranges(columns.len(), options.split_output);
+        if stream.is_some() {
+            parts[0].frames = 0..columns.len();
+        }
+    }
+
+    // --skip-static: columns that barely change over time make output
+    // frames that barely differ from a still
+    let mut skipped_static = false;
+    if let Some(threshold) = options.skip_static {
+        let variance = stats.transpose.time(|| {
+            heatmap::Heatmap::compute(
+                &frames.frames,
+                frame_format,
+                width,
+                height,
+                HeatmapStat::Variance,
+            )
+            .column_means()
+        });
+        let mut dropped: Vec<usize> = columns
+            .iter()
+            .copied()
+            .filter(|&x| variance[x] < threshold)
+            .collect();
+        dropped.sort_unstable();
+        dropped.dedup();
+        if !dropped.is_empty() {
+            let kept: Vec<usize> = columns
+                .iter()
+                .copied()
+                .filter(|&x| variance[x] >= threshold)
+                .collect();
+            if kept.is_empty() {
+                let most = variance.iter().copied().fold(0.0, f64::max);
+                return Err(format!(
+                    "--skip-static {} drops every column (the most active has variance {:.2})",
+                    threshold, most
+                )
+                .into());
+            }
+            status!(
+                "  Skipping {} static columns (variance under {}), {} output frames left",
+                dropped.len(),
+                threshold,
+                kept.len()
+            );
+            columns = kept;
+            frame_ranges = split::ranges(columns.len(), options.split_output);
+            if stream.is_some() {
+                parts[0].frames = 0..columns.len();
+            }
+            skipped_static = true;
+        }
+        if let Some(path) = &options.skip_static_log {
+            // Numbered as in the input, before --autocrop
+            let dropped: Vec<usize> = dropped.iter().map(|&x| x + crop_rect.left).collect();
+            let log = serde_json::json!({
+                "threshold": threshold,
+                "kept": columns.len(),
+                "dropped": column_runs(&dropped),
+            });
+            write_output_file(
+                path,
+                &serde_json::to_string_pretty(&log)?,
+                &temp,
+                &options.upload_retries,
+            )?;
+            status!("  Dropped columns written to: {}", path.display());
+        }
+    }
+
+    if let (Some(AudioDrive::Order), Some(envelope)) = (options.audio_drive, &envelope) {
+        let levels = envelope.frame_levels(&frames.times, frame_interval);
+        columns = audio::order(&columns, &levels)
+            .ok_or("--audio-drive order: the audio track is silent")?;
+        status!(
+            "  Audio drive: {} output frames sweep the columns by loudness",
+            columns.len()
+        );
+    }
+
+    if let Some(heatmap_path) = &options.heatmap {
+        let heatmap = stats.transpose.time(|| {
+            heatmap::Heatmap::compute(
+                &frames.frames,
+                frame_format,
+                width,
+                height,
+                options.heatmap_stat,
+            )
+        });
+        encode::write_png(heatmap_path, &heatmap.to_rgb(), width, height)?;
+        let (column, row) = heatmap.hottest();
+        status!("  Heatmap written to: {}", heatmap_path.display());
+        status!("  Most active column: {}, row: {}", column, row);
+    }
+
+    status!("\n[2/2] Transposing axes and encoding...");
+    if let Some(path) = &options.encode_stats {
+        encode_stats::create_csv(path)
+            .map_err(|e| format!("Cannot create {}: {}", path.display(), e))?;
+    }
+
+    let (output_width, output_height) = match (mosaic, &ops) {
+        (Some(grid), _) => grid.size(num_frames),
+        (None, Some(ops)) => {
+            let (width, height, _) = ops.output_size(width, height, num_frames);
+            (width, even_width(height))
+        }
+        (None, None) => (num_frames, height),
+    };
+    status!(
+        "  Output will be: {}×{} pixels, {} frames",
+        output_width,
+        output_height,
+        columns.len()
+    );
+
+    // The estimate only planned the split, tile by the real frame count
+    let tiles = if stream.is_some() || ops.is_some() {
+        split::ranges(num_frames, None)
+    } else if let (Some(ranges), WindowLayout::Separate) = (&window_ranges, options.windows_layout)
+    {
+        let filled: Vec<_> = ranges
+            .iter()
+            .filter(|range| !range.is_empty())
+            .cloned()
+            .collect();
+        windows::tiles(&filled, width_limit)
+    } else if options.align_scenes && num_frames > width_limit {
+        let cuts = stats
+            .transpose
+            .time(|| scenes::detect_cuts(&frames.frames, frame_format, options.scene_threshold));
+        status!("  Detected {} scene cuts", cuts.len());
+        split::ranges_at_cuts(num_frames, width_limit, &cuts)
+    } else {
+        split::ranges(num_frames, Some(width_limit))
+    };
+    if stream.is_none()
+        && (tiles.len() != planned_tiles.len() || ops.is_some() || crop.is_some() || skipped_static)
+    {
+        parts = plan_parts(options, &frame_ranges, &tiles, &temp)?;
+    }
+    if window_ranges.is_some() && options.windows_layout == WindowLayout::Separate {
+        status!("  Writing the windows as {} outputs", tiles.len());
+    } else if tiles.len() > 1 {
+        status!(
+            "  Splitting the width into {} outputs of up to {} source frames",
+            tiles.len(),
+            width_limit
+        );
+    }
+
+    // Create output video
+    let source = SourceInfo {
+        width,
+        height,
+        fps,
+        format: frame_format,
+        full_range: info.full_range,
+        mosaic,
+        ops,
+        linear_light,
+        watermark,
+        origin: (crop_rect.left, crop_rect.top),
+    };
+    let split = parts.len() > 1;
+    if split && options.multi_track.is_some() {
+        return Err(
+            "--multi-track writes one file, but the input is too long for one track".into(),
+        );
+    }
+    let mut saved = Vec::new();
+    let mut manifest_parts = Vec::new();
+    // Each output file of the first tile and its frames, for --conform
+    let mut conform_parts = Vec::new();
+    // Primary output file and frame count of each --split-output part, per
+    // --split-width tile
+    let mut sequences = vec![Vec::new(); tiles.len()];
+    let input_name = input_path.to_string_lossy();
+    let provenance = Provenance {
+        input: input_name.to_string(),
+        source_width: stored_width,
+        source_height: stored_height,
+        fps: (fps.numerator(), fps.denominator()),
+        scale: reduction.scale,
+        frame_step: reduction.step,
+    };
+    for part in parts {
+        let source_frames = tiles[part.tile].clone();
+        let sequence = (frame_ranges.len() > 1 && stream.is_none()).then(|| Sequence {
+            index: part.index,
+            count: frame_ranges.len(),
+            first_frame: sequences[part.tile]
+                .iter()
+                .map(|(_, frames): &(String, usize)| frames)
+                .sum(),
+        });
+        if split {
+            status!(
+                "\n  Part {}: frames {}..{}, source frames {}..{}",
+                part.destinations[0].path.display(),
+                part.frames.start,
+                part.frames.end,
+                source_frames.start,
+                source_frames.end
+            );
+        }
+        let index = options.index.map(|_| RunIndex {
+            input: &input_name,
+            source_width: width,
+            source_height: height,
+            scale: reduction.scale,
+            frame_step: reduction.step,
+            temporal_smooth: options.temporal_smooth,
+            legend_frames: options
+                .legend
+                .map_or(0, |seconds| legend::frame_count(seconds, fps)),
+            interpolate: options.interpolate,
+            columns: &columns[part.frames.clone()],
+            source_frames: decoded_indices
+                .get(source_frames.clone())
+                .unwrap_or_default(),
+            times: &frames.times[source_frames.clone()],
+            width: if options.exact {
+                source_frames.len()
+            } else {
+                even_width(source_frames.len())
+            },
+        });
+        let written = if let Some(tracks) = &options.multi_track {
+            write_multi_track(
+                &frames.frames[source_frames.clone()],
+                &source,
+                &columns[part.frames.clone()],
+                tracks,
+                &part.destinations[0],
+                options,
+                stats,
+            )?
+        } else {
+            transpose_and_save(
+                &frames.frames[source_frames.clone()],
+                &frames.times[source_frames.clone()],
+                &source,
+                &columns[part.frames.clone()],
+                Outputs {
+                    destinations: &part.destinations,
+                    stream: stream.as_deref_mut(),
+                    sequence,
+                    captions: source_captions
+                        .get(sequence.map_or(0, |sequence| sequence.first_frame)..)
+                        .unwrap_or_default(),
+                    progress_socket: socket.as_deref_mut(),
+                    index: index
+                        .as_ref()
+                        .filter(|_| options.index == Some(IndexMode::Embed)),
+                    provenance: &provenance,
+                },
+                options,
+                stats,
+            )?
+        };
+        if sequence.is_some() {
+            sequences[part.tile].push((part.destinations[0].name(), written));
+        }
+        if part.tile == 0 {
+            conform_parts.push((part.destinations[0].name(), written));
+        }
+        if split {
+            manifest_parts.push(split::Part {
+                path: part.destinations[0].name(),
+                frames: part.frames,
+                source_frames,
+            });
+        }
+        if let (Some(index), Some(IndexMode::Sidecar)) = (&index, options.index) {
+            let grid_width = index.width;
+            for destination in &part.destinations {
+                let (width, height) = destination.scale.apply(grid_width, height);
+                let index_path = index::sidecar_path(&destination.path);
+                let json = index.to_json(&destination.name(), width, height);
+                write_output_file(
+                    &index_path,
+                    &serde_json::to_string(&json)?,
+                    &temp,
+                    &options.upload_retries,
+                )?;
+                status!("  Index written to: {}", index_path.display());
+            }
+        }
+        for destination in part.destinations {
+            saved.push(destination.name());
+            destination.commit(&options.upload_retries)?;
+        }
+    }
+    if split {
+        let manifest_path = split::manifest_path(output_path);
+        let manifest = split::manifest(&output_path.to_string_lossy(), &manifest_parts, &columns);
+        write_output_file(
+            &manifest_path,
+            &serde_json::to_string_pretty(&manifest)?,
+            &temp,
+            &options.upload_retries,
+        )?;
+        status!("  Part manifest written to: {}", manifest_path.display());
+    }
+    // An ffconcat playlist per tile plays the parts back to back
+    let mut playlists = Vec::new();
+    for (tile, parts) in sequences
+        .iter()
+        .enumerate()
+        .filter(|(_, parts)| !parts.is_empty())
+    {
+        let suffix = split::suffix(None, (tiles.len() > 1).then_some(tile));
+        let playlist_path = split::playlist_path(&split::suffixed_path(output_path, &suffix));
+        write_output_file(
+            &playlist_path,
+            &split::playlist(parts, f64::from(fps)),
+            &temp,
+            &options.upload_retries,
+        )?;
+        status!("  Playlist written to: {}", playlist_path.display());
+        playlists.push(playlist_path.to_string_lossy().into_owned());
+    }
+
+    // Source columns as numbered in the input, before --autocrop
+    let recorded_columns: Vec<usize> = columns.iter().map(|&x| x + crop_rect.left).collect();
+    if let Some(sidecar_path) = &options.sidecar {
+        let sidecar = Sidecar {
+            input: &input_path.to_string_lossy(),
+            output: &output_path.to_string_lossy(),
+            source_width: stored_width,
+            source_height: stored_height,
+            crop: crop_rect,
+            fps: (fps.numerator(), fps.denominator()),
+            scale: reduction.scale,
+            frame_step: reduction.step,
+            columns: &recorded_columns,
+            times: &frames.times,
+            output_width: if options.exact {
+                num_frames
+            } else {
+                even_width(num_frames)
+            },
+            legend_frames: options
+                .legend
+                .map_or(0, |seconds| legend::frame_count(seconds, fps)),
+        };
+        sidecar.write(sidecar_path)?;
+        status!("  Sidecar written to: {}", sidecar_path.display());
+    }
+    if let Some(conform_path) = &options.conform {
+        let conform = Conform {
+            input: &input_name,
+            parts: &conform_parts,
+            fps: (fps.numerator(), fps.denominator()),
+            frame_step: reduction.step,
+            columns: &recorded_columns,
+            times: &frames.times,
+            legend_frames: options
+                .legend
+                .map_or(0, |seconds| legend::frame_count(seconds, fps)),
+        };
+        write_output_file(
+            conform_path,
+            &conform.render(conform::Format::of(conform_path)?)?,
+            &temp,
+            &options.upload_retries,
+        )?;
+        status!("  Edit list written to: {}", conform_path.display());
+    }
+
+    status!("\n✓ Video transposition complete!");
+    for path in &saved {
+        status!("  Output saved to: {}", path);
+    }
+    stats.print();
+
+    let mut summary = stats.to_json();
+    summary["input"] = input_path.to_string_lossy().into();
+    summary["output"] = output_path.to_string_lossy().into();
+    summary["outputs"] = serde_json::json!(saved);
+    summary["playlists"] = serde_json::json!(playlists);
+    summary["input_size"] = serde_json::json!([width, height, num_frames]);
+    summary["output_size"] = serde_json::json!([output_width, output_height, columns.len()]);
+    summary["total_seconds"] = run_start.elapsed().as_secs_f64().into();
+    Ok(summary)
+}
+
+/// Expected size of the frame store for a run with `options`, from the
+/// input's header without decoding it, or `None` if the frame count isn't
+/// known up front.
+pub(crate) fn estimate_memory(
+    options: &Options,
+) -> Result<Option<u64>, Box<dyn std::error::Error>> {
+    let (_, info) = source::open(options)?;
+    let frame_format = select_frame_format(options, info.format)?;
+    let reduction = Reduction::new(options.scale, options.frame_step);
+    Ok(info.estimated_frames.map(|frames| {
+        reduction.store_bytes(
+            info.width,
+            info.height,
+            frame_format.bytes_per_pixel(),
+            frames,
+        )
+    }))
+}
+
+fn frame_store_bytes(width: usize, height: usize, format: FrameFormat, frames: u64) -> u64 {
+    (width * height * format.bytes_per_pixel()) as u64 * frames
+}
+
+/// Picks the sample format the frames are stored and transposed in, from the
+/// input's pixel format and what the outputs can hold.
+pub(crate) fn select_frame_format(
+    options: &Options,
+    input: Pixel,
+) -> Result<FrameFormat, Box<dyn std::error::Error>> {
+    // High bit depth grayscale stays 16-bit when every output can hold FFV1
+    let output_paths: Vec<&PathBuf> = std::iter::once(&options.output)
+        .chain(options.also_encode.iter().map(|spec| &spec.path))
+        .collect();
+    let lossless_outputs = output_paths.iter().all(|path| {
+        output_file::guess_muxer(path).is_some_and(|muxer| encode::supports_ffv1(&muxer))
+    });
+    // EXR sequences take float frames, which no video output can
+    let exr_outputs = output_paths
+        .iter()
+        .filter(|path| exr_output::is_exr_sequence(path))
+        .count();
+    let frame_format = match exr_outputs {
+        0 => FrameFormat::for_input(input, lossless_outputs),
+        count if count == output_paths.len() => FrameFormat::RgbF32,
+        _ => return Err("EXR sequence outputs can't be mixed with video outputs".into()),
+    };
+    if exr_outputs > 0 && !cfg!(feature = "exr") {
+        return Err("EXR output needs a build with --features exr".into());
+    }
+    Ok(frame_format)
+}
+
+/// Copies one plane of a frame (the only one of a packed frame) into a
+/// tightly packed buffer of `row_bytes` per row. `data(plane)` includes the
+/// alignment padding at the end of each line, and frames may also be stored
+/// bottom-up with a negative line size, so the copy goes through
+/// `av_image_copy_plane` which handles both.
+pub(crate) fn copy_plane(frame: &Video, plane: usize, row_bytes: usize) -> Vec<u8> {
+    let rows = frame.height() as usize;
+    let mut data = vec![0u8; row_bytes * rows];
+    unsafe {
+        let raw = frame.as_ptr();
+        ffmpeg::ffi::av_image_copy_plane(
+            data.as_mut_ptr(),
+            row_bytes as i32,
+            (*raw).data[plane],
+            (*raw).linesize[plane],
+            row_bytes as i32,
+            rows as i32,
+        );
+    }
+    data
+}
+
+/// Interleaves a planar GBRPF32LE frame into packed float RGB.
+pub(crate) fn interleave_float_planes(frame: &Video, width: usize) -> Vec<u8> {
+    // Planes are stored in G, B, R order
+    let planes = [2, 0, 1].map(|plane| copy_plane(frame, plane, width * 4));
+    let mut data = Vec::with_capacity(planes[0].len() * 3);
+    for samples in (0..planes[0].len()).step_by(4) {
+        for plane in &planes {
+            data.extend_from_slice(&plane[samples..samples + 4]);
+        }
+    }
+    data
+}
+
+/// Geometry, frame rate and stored pixel format of the decoded source.
+struct SourceInfo {
+    width: usize,
+    height: usize,
+    fps: ffmpeg::Rational,
+    format: FrameFormat,
+    /// Whether the input was full range YUV, which the outputs keep.
+    full_range: bool,
+    /// `--mosaic` grid the output frames tile.
+    mosaic: Option<MosaicGrid>,
+    /// `--ops` rearrangement the output frames are gathered by.
+    ops: Option<Rearrangement>,
+    /// `--linear-light` conversions for blending.
+    linear_light: Option<LinearLight>,
+    /// `--watermark` logo composited onto the output frames.
+    watermark: Option<Watermark>,
+    /// Top-left pixel of the frames in the decoded ones, past any
+    /// `--autocrop` bars, so recorded columns and rows match the input.
+    origin: (usize, usize),
+}
+
+/// An output file and how to size and encode it.
+struct Destination {
+    file: PartialOutput,
+    path: PathBuf,
+    /// Object storage URL the finished file is uploaded to.
+    remote: Option<ObjectUrl>,
+    scale: Scale,
+    crf: Option<u32>,
+    /// Channel written as grayscale with `--split-channels`.
+    channel: Option<Channel>,
+}
+
+impl Destination {
+    /// Moves the finished file into place, uploading it for remote outputs.
+    fn commit(self, retries: &RetryPolicy) -> Result<(), Box<dyn std::error::Error>> {
+        // EXR sequences are written frame by frame under their final names
+        if exr_output::is_exr_sequence(&self.path) {
+            return Ok(());
+        }
+        let staged = self.file.final_path().to_path_buf();
+        self.file.commit()?;
+        if let Some(url) = &self.remote {
+            status!("  Uploading to {}...", url);
+            let uploaded = cloud::upload(&staged, url, retries);
+            let _ = fs::remove_file(&staged);
+            uploaded?;
+        }
+        Ok(())
+    }
+
+    /// Path of the output for messages and JSON.
+    fn name(&self) -> String {
+        self.path.to_string_lossy().into_owned()
+    }
+
+    /// Remote outputs are encoded to a staging file in the temp directory
+    /// and uploaded once complete.
+    fn new(
+        path: &Path,
+        scale: Scale,
+        crf: Option<u32>,
+        channel: Option<Channel>,
+        temp: &TempFiles,
+    ) -> Result<Destination, String> {
+        let remote = ObjectUrl::from_path(path).transpose()?;
+        let local = match &remote {
+            Some(url) => temp
+                .path(url.file_name())
+                .map_err(|e| format!("Cannot create temp directory: {}", e))?,
+            None => path.to_path_buf(),
+        };
+        let file = PartialOutput::new(&local)?;
+        if remote.is_some() && packaging::is_segmented(file.format()) {
+            return Err(format!(
+                "Cannot upload {}: HLS/DASH outputs must be written locally",
+                path.display()
+            ));
+        }
+        if remote.is_some() && exr_output::is_exr_sequence(path) {
+            return Err(format!(
+                "Cannot upload {}: EXR sequences must be written locally",
+                path.display()
+            ));
+        }
+        Ok(Destination {
+            file,
+            path: path.to_path_buf(),
+            remote,
+            scale,
+            crf,
+            channel,
+        })
+    }
+}
+
+/// One file of the output (per destination), covering a range of output
+/// frames and the source frames at index `tile` of the width split.
+struct OutputPart {
+    frames: Range<usize>,
+    /// Which of the `--split-output` frame ranges this is.
+    index: usize,
+    tile: usize,
+    destinations: Vec<Destination>,
+}
+
+/// Creates the destinations for every combination of output frame range
+/// and source frame range, numbering the files when either axis is split.
+fn plan_parts(
+    options: &Options,
+    frame_ranges: &[Range<usize>],
+    tiles: &[Range<usize>],
+    temp: &TempFiles,
+) -> Result<Vec<OutputPart>, Box<dyn std::error::Error>> {
+    let mut parts = Vec::new();
+    for (part, frames) in frame_ranges.iter().enumerate() {
+        for tile in 0..tiles.len() {
+            let suffix = split::suffix(
+                (frame_ranges.len() > 1).then_some(part),
+                (tiles.len() > 1).then_some(tile),
+            );
+            parts.push(OutputPart {
+                frames: frames.clone(),
+                index: part,
+                tile,
+                destinations: create_destinations(options, &suffix, temp)?,
+            });
+        }
+    }
+
+    // The part manifest and per-tile playlists written next to the parts
+    let mut listings = Vec::new();
+    if parts.len() > 1 {
+        listings.push(split::manifest_path(&options.output));
+    }
+    if frame_ranges.len() > 1 {
+        for tile in 0..tiles.len() {
+            let suffix = split::suffix(None, (tiles.len() > 1).then_some(tile));
+            listings.push(split::playlist_path(&split::suffixed_path(
+                &options.output,
+                &suffix,
+            )));
+        }
+    }
+    for path in listings {
+        let exists = match ObjectUrl::from_path(&path).transpose()? {
+            Some(url) => cloud::exists(&url)?,
+            None => path.exists(),
+        };
+        refuse_existing(options, &path, exists)?;
+    }
+    Ok(parts)
+}
+
+/// Creates the primary and `--also-encode` destinations, with `suffix`
+/// inserted before the extension for pieces of a split output, and refuses
+/// to replace existing files unless `--overwrite` is given.
+fn create_destinations(
+    options: &Options,
+    suffix: &str,
+    temp: &TempFiles,
+) -> Result<Vec<Destination>, Box<dyn std::error::Error>> {
+    let mut outputs = vec![(options.output.as_path(), Scale::Factor(1.0), None)];
+    for spec in &options.also_encode {
+        outputs.push((spec.path.as_path(), spec.scale, spec.crf));
+    }
+
+    // With --split-channels every output becomes one grayscale file per channel
+    let channels: Vec<Option<Channel>> = match options.split_channels {
+        Some(set) => set.channels().into_iter().map(Some).collect(),
+        None => vec![None],
+    };
+    let mut destinations = Vec::new();
+    for (path, scale, crf) in outputs {
+        for &channel in &channels {
+            let channel_suffix = channel.map_or("", Channel::suffix);
+            let path = split::suffixed_path(path, &format!("{}{}", suffix, channel_suffix));
+            destinations.push(Destination::new(&path, scale, crf, channel, temp)?);
+        }
+    }
+
+    for destination in &destinations {
+        let exists = match &destination.remote {
+            Some(url) => cloud::exists(url)?,
+            None if exr_output::is_exr_sequence(&destination.path) => {
+                exr_output::frame_path(&destination.path, 0).exists()
+            }
+            None => destination.file.exists()?,
+        };
+        refuse_existing(options, &destination.path, exists)?;
+    }
+    Ok(destinations)
+}
+
+/// Fails if the output file `path` `exists`, unless `--overwrite` is given.
+fn refuse_existing(options: &Options, path: &Path, exists: bool) -> Result<(), String> {
+    if !options.overwrite && exists {
+        return Err(format!(
+            "Output file {} already exists (use -y/--overwrite to replace it)",
+            path.display()
+        ));
+    }
+    Ok(())
+}
+
+/// Sorted columns as `[first, last]` runs of adjacent ones.
+fn column_runs(columns: &[usize]) -> Vec<[usize; 2]> {
+    let mut runs: Vec<[usize; 2]> = Vec::new();
+    for &column in columns {
+        match runs.last_mut() {
+            Some(run) if run[1] + 1 == column => run[1] = column,
+            _ => runs.push([column, column]),
+        }
+    }
+    runs
+}
+
+/// Writes a small text file next to the outputs, uploading it when `path`
+/// is an object URL. Local files are written as `.part` and renamed into
+/// place, so an interrupted run doesn't leave a truncated one behind.
+fn write_output_file(
+    path: &Path,
+    contents: &str,
+    temp: &TempFiles,
+    retries: &RetryPolicy,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match ObjectUrl::from_path(path).transpose()? {
+        Some(url) => {
+            let staged = temp.path(url.file_name())?;
+            fs::write(&staged, contents)?;
+            cloud::upload(&staged, &url, retries)?;
+        }
+        None => {
+            let mut partial = path.as_os_str().to_owned();
+            partial.push(".part");
+            fs::write(&partial, contents)?;
+            fs::rename(&partial, path)?;
+        }
+    }
+    Ok(())
+}
+
+/// H.264 requires even dimensions, so odd widths get one padding column.
+fn even_width(width: usize) -> usize {
+    width + width % 2
+}
+
+fn transpose_and_save(
+    frames: &[Vec<u8>],
+    times: &[Option<f64>],
+    source: &SourceInfo,
+    columns: &[usize],
+    outputs: Outputs<'_, '_>,
+    options: &Options,
+    stats: &mut RunStats,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    // Output dimensions: T×Y pixels, X frames
+    let (new_width_raw, new_height) = match (&source.mosaic, &source.ops) {
+        (Some(grid), _) => grid.size(frames.len()),
+        // The rows may come from an axis of odd length, which H.264 can't take
+        (None, Some(ops)) => {
+            let (width, height, _) = ops.output_size(source.width, source.height, frames.len());
+            (width, even_width(height))
+        }
+        (None, None) => (frames.len(), source.height),
+    };
+    let inserted = options.interpolate * columns.len().saturating_sub(1);
+    let legend_frames = options
+        .legend
+        .map_or(0, |seconds| legend::frame_count(seconds, source.fps));
+    let new_num_frames = legend_frames + columns.len() + inserted;
+
+    // H.264 requires even dimensions, pad if needed. Exact runs write FFV1,
+    // which doesn't, and must not add a column
+    let new_width = if options.exact {
+        new_width_raw
+    } else {
+        even_width(new_width_raw)
+    };
+
+    if new_width != new_width_raw {
+        status!(
+            "  Note: Padding width from {} to {} (H.264 requires even dimensions)",
+            new_width_raw,
+            new_width
+        );
+    }
+    // --canvas: the frames are scaled onto a canvas, which the outputs get
+    let mut fitter = match options.canvas {
+        Some(canvas) => {
+            status!(
+                "  Fitting {}×{} frames to a {}×{} canvas ({:?})",
+                new_width,
+                new_height,
+                canvas.width,
+                canvas.height,
+                canvas.fit
+            );
+            Some(canvas.fitter(new_width, new_height, source.format, options.scaler)?)
+        }
+        None => None,
+    };
+    let (fitted_width, fitted_height) = options.canvas.map_or((new_width, new_height), |canvas| {
+        (canvas.width, canvas.height)
+    });
+    // --with-original: the source frames next to the transposed ones, the
+    // size of the output frames
+    let mut side_by_side = match options.with_original {
+        Some(layout) => Some(SideBySide::new(
+            layout,
+            (source.width, source.height),
+            (fitted_width, fitted_height),
+            source.format,
+            options.scaler,
+        )?),
+        None => None,
+    };
+    let (frame_width, frame_height) = side_by_side
+        .as_ref()
+        .map_or((fitted_width, fitted_height), SideBySide::size);
+    if let Some(layout) = options.with_original {
+        status!(
+            "  Showing the original {} the transposed frames: {}×{} output frames",
+            layout.name(),
+            frame_width,
+            frame_height
+        );
+    }
+    // --sonify plays a row of the frames before they're fitted to the canvas
+    let mut sonifier = match options.sonify {
+        Some(sonify) if sonify.row >= new_height => {
+            return Err(format!(
+                "--sonify row {} is outside the {} rows of the transposed frames",
+                sonify.row, new_height
+            )
+            .into());
+        }
+        Some(sonify) => Some(Sonifier::new(sonify, source.fps)),
+        None => None,
+    };
+    if inserted > 0 {
+        status!(
+            "  Interpolating {} frames between each pair ({} total)",
+            options.interpolate,
+            new_num_frames
+        );
+    }
+
+    let progress = Progress::new(options.no_progress, options.no_color);
+    let pb = progress.bar(
+        new_num_frames as u64,
+        "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} frames",
+    );
+
+    // --validate: what each file should hold once written. Segmented
+    // outputs are a playlist of files, not one to reopen
+    let mut validations = Vec::new();
+    if options.validate {
+        for destination in outputs.destinations {
+            if !exr_output::is_exr_sequence(&destination.path)
+                && !packaging::is_segmented(destination.file.format())
+            {
+                let expected = validate::Expected::new(
+                    &destination.name(),
+                    destination.scale.apply(frame_width, frame_height),
+                    f64::from(source.fps),
+                    new_num_frames,
+                    destination.channel,
+                );
+                validations.push((destination, expected));
+            }
+        }
+    }
+
+    let mut writers: Vec<Box<dyn FrameSink + '_>> = Vec::new();
+    for destination in outputs.destinations {
+        let (width, height) = destination.scale.apply(frame_width, frame_height);
+        let target = EncodeTarget {
+            width,
+            height,
+            crf: destination.crf,
+            channel: destination.channel,
+            full_range: source.full_range,
+            sequence: outputs.sequence,
+            index: outputs.index.map(|index| {
+                index
+                    .to_json(&destination.name(), width, height)
+                    .to_string()
+            }),
+            audio: options.sonify.is_some(),
+        };
+        if !writers.is_empty() {
+            status!(
+                "  Also encoding {}: {}×{}",
+                destination.path.display(),
+                width,
+                height
+            );
+        }
+        if exr_output::is_exr_sequence(&destination.path) {
+            if (width, height) != (frame_width, frame_height) {
+                return Err(
+                    format!("Cannot scale EXR output {}", destination.path.display()).into(),
+                );
+            }
+            writers.push(Box::new(ExrSequence::new(
+                &destination.path,
+                width,
+                height,
+                outputs.provenance,
+            )));
+            continue;
+        }
+        writers.push(Box::new(VideoWriter::open(
+            &destination.file,
+            frame_width,
+            frame_height,
+            source.format,
+            &target,
+            source.fps,
+            options,
+        )?));
+    }
+    for (_, expected) in &mut validations {
+        writers.push(Box::new(expected));
+    }
+    if let Some(stream) = outputs.stream {
+        writers.push(Box::new(stream));
+    }
+    if let Some(socket) = outputs.progress_socket {
+        socket.phase("encode", Some(new_num_frames as u64));
+        writers.push(Box::new(socket));
+    }
+
+    // --exact: hash the source column each output frame should show, to
+    // check the transposed frames and, once encoded, the files
+    let mut file_checks = Vec::new();
+    if options.exact {
+        let expected = |channel: Option<Channel>| {
+            columns
+                .iter()
+                .map(|&x| {
+                    exact::column_hash(frames, source.format, source.width, new_height, x, channel)
+                })
+                .collect::<Vec<_>>()
+        };
+        writers.push(Box::new(exact::HashCheck::new(
+            "the transposed frames",
+            expected(None),
+        )));
+        for destination in outputs.destinations {
+            if !exr_output::is_exr_sequence(&destination.path) {
+                let check =
+                    exact::HashCheck::new(&destination.name(), expected(destination.channel));
+                file_checks.push((destination, check));
+            }
+        }
+    }
+
+    // Output frame n carries the captions of source frame n
+    let mut captions = outputs
+        .captions
+        .iter()
+        .map(|captions| captions.as_deref())
+        .chain(std::iter::repeat(None));
+
+    let overlay = source
+        .watermark
+        .as_ref()
+        .map(|watermark| watermark.place(frame_width, frame_height));
+
+    if legend_frames > 0 {
+        let lines = Legend {
+            options,
+            source_width: source.width,
+            source_height: source.height,
+            origin: source.origin,
+            fps: source.fps,
+            frames: new_width_raw,
+            columns,
+        }
+        .lines();
+        let mut card = legend::render(&lines, frame_width, frame_height, source.format);
+        if let Some(overlay) = &overlay {
+            overlay.apply(&mut card, source.format, frame_width);
+        }
+        for _ in 0..legend_frames {
+            let audio = sonifier.as_mut().map(|sonifier| sonifier.render(&[]));
+            let frame = OutputFrame {
+                width: frame_width,
+                height: frame_height,
+                format: source.format,
+                data: &card,
+                mapping: "legend",
+                captions: captions.next().flatten(),
+                audio: audio.as_deref(),
+            };
+            stats.encode.time(|| write_all(&mut writers, &frame))?;
+            stats.encode.frames += 1;
+            pb.inc(1);
+        }
+    }
+
+    // Process each output frame
+    let mut throttle = Throttle::new(options.throttle);
+    let mut previous: Option<(usize, Vec<u8>, Vec<f32>)> = None;
+    for (shown, &x) in columns.iter().enumerate() {
+        // Create transposed frame: new_width × new_height
+        let (transposed, row) = stats.transpose.time(|| -> Result<_, ffmpeg::Error> {
+            let mut frame = match (&source.mosaic, &source.ops) {
+                (Some(grid), _) => grid.gather(
+                    frames,
+                    source.format,
+                    source.width,
+                    x,
+                    new_width,
+                    source.linear_light.as_ref(),
+                ),
+                (None, Some(ops)) => ops.gather(
+                    frames,
+                    source.format,
+                    (source.width, source.height),
+                    x,
+                    (new_width, new_height),
+                ),
+                (None, None) => transpose::gather_column(
+                    frames,
+                    source.format,
+                    source.width,
+                    new_height,
+                    x,
+                    new_width,
+                ),
+            };
+            if options.edge_fade > 0 {
+                transpose::apply_edge_fade(
+                    &mut frame,
+                    source.format,
+                    new_width,
+                    new_height,
+                    new_width_raw,
+                    options.edge_fade,
+                );
+            }
+            let row = sonifier.as_ref().map_or_else(Vec::new, |sonifier| {
+                sonifier.row(&frame, source.format, new_width, new_width_raw)
+            });
+            if let Some(fitter) = &mut fitter {
+                frame = fitter.apply(&frame)?;
+            }
+            if let Some(side_by_side) = &mut side_by_side {
+                let original = side_by_side::retime(shown, columns.len(), frames.len());
+                frame = side_by_side.compose(&frames[original], &frame)?;
+            }
+            // Blended frames are mixed from frames that already carry it
+            if let Some(overlay) = &overlay {
+                overlay.apply(&mut frame, source.format, frame_width);
+            }
+            Ok((frame, row))
+        })?;
+        stats.transpose.frames += 1;
+
+        // Blend toward this frame from the previous one
+        if let Some((previous_x, previous_frame, previous_row)) = &previous {
+            let steps = options.interpolate;
+            for step in 1..=steps {
+                let alpha = step as f32 / (steps + 1) as f32;
+                let blended = stats.transpose.time(|| {
+                    interpolate::blend(
+                        previous_frame,
+                        &transposed,
+                        alpha,
+                        source.format,
+                        source.linear_light.as_ref(),
+                    )
+                });
+                let label = format!(
+                    "{};blend={}/{}",
+                    mapping::frame_mapping(*previous_x + source.origin.0, times),
+                    step,
+                    steps + 1
+                );
+                let audio = sonifier.as_mut().map(|sonifier| {
+                    let row: Vec<f32> = previous_row
+                        .iter()
+                        .zip(&row)
+                        .map(|(from, to)| from + (to - from) * alpha)
+                        .collect();
+                    sonifier.render(&row)
+                });
+                let frame = OutputFrame {
+                    width: frame_width,
+                    height: frame_height,
+                    format: source.format,
+                    data: &blended,
+                    mapping: &label,
+                    captions: captions.next().flatten(),
+                    audio: audio.as_deref(),
+                };
+                stats.encode.time(|| write_all(&mut writers, &frame))?;
+                stats.encode.frames += 1;
+                pb.inc(1);
+                throttle.pace();
+            }
+        }
+
+        let label = mapping::frame_mapping(x + source.origin.0, times);
+        let audio = sonifier.as_mut().map(|sonifier| sonifier.render(&row));
+        let frame = OutputFrame {
+            width: frame_width,
+            height: frame_height,
+            format: source.format,
+            data: &transposed,
+            mapping: &label,
+            captions: captions.next().flatten(),
+            audio: audio.as_deref(),
+        };
+        stats.encode.time(|| write_all(&mut writers, &frame))?;
+        stats.encode.frames += 1;
+        pb.inc(1);
+        throttle.pace();
+
+        if options.interpolate > 0 {
+            previous = Some((x, transposed, row));
+        }
+    }
+
+    // Flush encoder
+    stats
+        .encode
+        .time(|| -> Result<(), Box<dyn std::error::Error>> {
+            for writer in &mut writers {
+                writer.finish()?;
+            }
+            Ok(())
+        })?;
+    stats
+        .queues
+        .extend(writers.iter().filter_map(|writer| writer.queue_stats()));
+    pb.finish_with_message("Encoding complete");
+    if !progress.enabled() {
+        status!("  {} frames encoded", new_num_frames);
+    }
+    // Done with the expectations the writers borrowed
+    drop(writers);
+
+    for (destination, check) in &mut file_checks {
+        let (pixel, bytes_per_pixel) = match destination.channel {
+            Some(_) => (Pixel::GRAY8, 1),
+            None => (source.format.pixel(), source.format.bytes_per_pixel()),
+        };
+        exact::verify_file(
+            destination.file.partial_path(),
+            pixel,
+            bytes_per_pixel,
+            check,
+        )?;
+        check.result()?;
+    }
+    for (destination, expected) in &validations {
+        expected.verify_file(destination.file.partial_path())?;
+    }
+
+    Ok(new_num_frames)
+}
+
+/// `--multi-track`: encodes each track of `frames` as a stream of the file
+/// of `destination`, the X-T one showing `columns`. Returns the X-T track's
+/// frame count.
+fn write_multi_track(
+    frames: &[Vec<u8>],
+    source: &SourceInfo,
+    columns: &[usize],
+    tracks: &[Track],
+    destination: &Destination,
+    options: &Options,
+    stats: &mut RunStats,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    // Exact runs write FFV1, which doesn't need the time axis padded
+    let time_width = if options.exact {
+        frames.len()
+    } else {
+        even_width(frames.len())
+    };
+    let size = (source.width, source.height);
+    let sizes: Vec<_> = tracks
+        .iter()
+        .map(|track| track.size(size, frames.len(), columns.len(), time_width))
+        .collect();
+    let mut streams = Vec::new();
+    for (track, &(width, height, count)) in tracks.iter().zip(&sizes) {
+        status!(
+            "  {} track: {}×{} pixels, {} frames",
+            track.title(),
+            width,
+            height,
+            count
+        );
+        streams.push((width, height, track.title()));
+    }
+    let mut writer = MultiTrackWriter::open(
+        &destination.file,
+        &streams,
+        source.format,
+        (destination.crf, source.full_range),
+        source.fps,
+        options,
+    )?;
+
+    let total: usize = sizes.iter().map(|&(_, _, count)| count).sum();
+    let progress = Progress::new(options.no_progress, options.no_color);
+    let pb = progress.bar(
+        total as u64,
+        "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} frames",
+    );
+    // Frame n of every track in turn, so the streams advance together
+    let longest = sizes.iter().map(|&(_, _, count)| count).max().unwrap_or(0);
+    for index in 0..longest {
+        for (number, (track, &(_, _, count))) in tracks.iter().zip(&sizes).enumerate() {
+            if index >= count {
+                continue;
+            }
+            let frame = stats
+                .transpose
+                .time(|| track.frame(frames, source.format, size, columns, index, time_width));
+            stats.transpose.frames += 1;
+            stats
+                .encode
+                .time(|| writer.write(number, &frame, source.format))?;
+            stats.encode.frames += 1;
+            pb.inc(1);
+        }
+    }
+    stats.encode.time(|| writer.finish())?;
+    pb.finish_with_message("Encoding complete");
+    if !progress.enabled() {
+        status!("  {} frames encoded in {} tracks", total, tracks.len());
+    }
+    Ok(columns.len())
+}
+
+/// Where the frames of one output part go: the part's files, or a sink
+/// streaming all frames.
+struct Outputs<'a, 'c> {
+    destinations: &'a [Destination],
+    stream: Option<&'a mut (dyn FrameSink + 'c)>,
+    /// Place of the files in a `--split-output` sequence.
+    sequence: Option<Sequence>,
+    /// `--keep-cc` captions of the source frames, from the one shown with
+    /// the files' first frame.
+    captions: &'a [Option<Vec<u8>>],
+    /// `--progress-socket` clients, counting the frames as one more sink.
+    progress_socket: Option<&'a mut ProgressSocket>,
+    /// `--index embed`: the index attached to each file.
+    index: Option<&'a RunIndex<'a>>,
+    /// The run, recorded in each image of EXR sequences.
+    provenance: &'a Provenance,
+}
+
+fn write_all(
+    writers: &mut [Box<dyn FrameSink + '_>],
+    frame: &OutputFrame,
+) -> Result<(), Box<dyn std::error::Error>> {
+    for writer in writers {
+        writer.write(frame)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn copy_plane_drops_line_padding() {
+        // 33 px of RGB24 is 99 bytes a row, which FFmpeg pads to its line
+        // alignment
+        let (width, height) = (33, 4);
+        let row_bytes = width * 3;
+        let mut frame = Video::new(Pixel::RGB24, width as u32, height as u32);
+        let stride = frame.stride(0);
+        assert!(stride > row_bytes, "linesize {} isn't padded", stride);
+        let pixel = |x: usize, y: usize| (y * row_bytes + x) as u8 % 0xf0;
+        for (y, line) in frame.data_mut(0).chunks_mut(stride).enumerate() {
+            for (x, byte) in line.iter_mut().enumerate() {
+                *byte = if x < row_bytes { pixel(x, y) } else { 0xff };
+            }
+        }
+
+        let data = copy_plane(&frame, 0, row_bytes);
+        assert_eq!(data.len(), row_bytes * height);
+        assert!(!data.contains(&0xff), "padding bytes were copied");
+        for (y, row) in data.chunks(row_bytes).enumerate() {
+            for (x, &byte) in row.iter().enumerate() {
+                assert_eq!(byte, pixel(x, y), "byte {} of row {}", x, y);
+            }
+        }
+    }
+}
@@ -0,0 +1,49 @@
+use ffmpeg_next::format::Pixel;
+
+/// Pixel format of encoded color outputs, for `--pix-fmt`. 4:2:0 halves
+/// the chroma resolution along the time axis too, smearing brief color
+/// changes once time runs horizontally.
+#[derive(Clone, Copy, PartialEq)]
+pub enum PixFmt {
+    Yuv420,
+    Yuv422,
+    Yuv444,
+    /// RGB without conversion to YUV: libx264rgb for H.264, planar RGB
+    /// for FFV1.
+    Rgb,
+}
+
+impl PixFmt {
+    pub fn parse(text: &str) -> Result<PixFmt, String> {
+        match text {
+            "yuv420p" => Ok(PixFmt::Yuv420),
+            "yuv422p" => Ok(PixFmt::Yuv422),
+            "yuv444p" => Ok(PixFmt::Yuv444),
+            "rgb" => Ok(PixFmt::Rgb),
+            _ => Err(format!(
+                "Invalid --pix-fmt: {} (expected yuv420p, yuv422p, yuv444p or rgb)",
+                text
+            )),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PixFmt::Yuv420 => "yuv420p",
+            PixFmt::Yuv422 => "yuv422p",
+            PixFmt::Yuv444 => "yuv444p",
+            PixFmt::Rgb => "rgb",
+        }
+    }
+
+    /// The encoder's pixel format, for FFV1 when `lossless`, else H.264.
+    pub fn pixel(self, lossless: bool) -> Pixel {
+        match self {
+            PixFmt::Yuv420 => Pixel::YUV420P,
+            PixFmt::Yuv422 => Pixel::YUV422P,
+            PixFmt::Yuv444 => Pixel::YUV444P,
+            PixFmt::Rgb if lossless => Pixel::GBRP,
+            PixFmt::Rgb => Pixel::RGB24,
+        }
+    }
+}
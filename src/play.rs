@@ -0,0 +1,60 @@
+//! `--play`: opening the finished output in a player. mpv or ffplay is
+//! used when one is on the `PATH`, as both play anything FFmpeg writes
+//! (including the ffconcat playlists of `--split-output`); otherwise the
+//! file goes to the system's default application for it.
+
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Players tried before the system default, in order.
+const PLAYERS: [&str; 2] = ["mpv", "ffplay"];
+
+/// Starts a player for `path` without waiting for it to close.
+pub fn open(path: &Path) -> Result<(), String> {
+    let mut command = match PLAYERS.iter().find_map(|player| find_program(player)) {
+        Some(player) => {
+            let mut command = Command::new(player);
+            command.arg(path);
+            command
+        }
+        None => system_opener(path),
+    };
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("Cannot start a player for {}: {}", path.display(), e))?;
+    Ok(())
+}
+
+/// The first `name` executable on the `PATH`.
+fn find_program(name: &str) -> Option<PathBuf> {
+    let file_name = format!("{}{}", name, env::consts::EXE_SUFFIX);
+    env::split_paths(&env::var_os("PATH")?)
+        .map(|dir| dir.join(&file_name))
+        .find(|path| path.is_file())
+}
+
+#[cfg(target_os = "macos")]
+fn system_opener(path: &Path) -> Command {
+    let mut command = Command::new("open");
+    command.arg(path);
+    command
+}
+
+#[cfg(windows)]
+fn system_opener(path: &Path) -> Command {
+    // start is a cmd builtin; its first quoted argument is the window title
+    let mut command = Command::new("cmd");
+    command.args(["/C", "start", ""]).arg(path);
+    command
+}
+
+#[cfg(not(any(target_os = "macos", windows)))]
+fn system_opener(path: &Path) -> Command {
+    let mut command = Command::new("xdg-open");
+    command.arg(path);
+    command
+}
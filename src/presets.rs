@@ -0,0 +1,279 @@
+//! `--save-preset name` and `--preset name`: option sets kept under a name
+//! in `~/.config/video-transpose/presets.toml` (`$XDG_CONFIG_HOME`, or
+//! `%APPDATA%` on Windows), so a favourite combination of a dozen flags
+//! doesn't have to be retyped each run. A preset is the list of options as
+//! given on the command line, without the input and output, and `--preset`
+//! puts them back where it stands, so options after it override the
+//! preset's. The file is a small subset of TOML, one table per preset:
+//!
+//! ```toml
+//! [presets.grain]
+//! args = ["--crf", "16", "--tune", "grain"]
+//! ```
+//!
+//! The preset named `default` applies to every run, under the
+//! `VIDEO_TRANSPOSE_*` variables and the command line.
+
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::path::PathBuf;
+
+const HEADER: &str = "# video-transpose presets, written by --save-preset\n";
+
+/// The preset every run starts from.
+const DEFAULT: &str = "default";
+
+/// Replaces each `--preset <name>` in `args` with the options saved under
+/// the name, and takes out `--save-preset <name>`, returning its name.
+pub fn expand(args: &[OsString]) -> Result<(Vec<OsString>, Option<String>), String> {
+    let mut expanded = Vec::with_capacity(args.len());
+    let mut save = None;
+    let mut presets = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let text = arg.to_str().unwrap_or_default();
+        let (flag, inline_value) = match text.split_once('=') {
+            Some((flag, value)) => (flag, Some(value.to_string())),
+            None => (text, None),
+        };
+        if flag != "--preset" && flag != "--save-preset" {
+            expanded.push(arg.clone());
+            continue;
+        }
+        let name = match inline_value {
+            Some(value) => value,
+            None => iter
+                .next()
+                .and_then(|value| value.to_str())
+                .ok_or_else(|| format!("Missing value for {}", flag))?
+                .to_string(),
+        };
+        check_name(&name)?;
+        if flag == "--save-preset" {
+            save = Some(name);
+            continue;
+        }
+        if presets.is_none() {
+            presets = Some(load()?);
+        }
+        let saved = presets.as_deref().unwrap_or_default();
+        match saved.iter().find(|(saved, _)| *saved == name) {
+            Some((_, options)) => expanded.extend(options.iter().map(OsString::from)),
+            None => {
+                let names: Vec<&str> = saved.iter().map(|(name, _)| name.as_str()).collect();
+                return Err(format!(
+                    "No preset named {} in {} (saved: {})",
+                    name,
+                    path()?.display(),
+                    if names.is_empty() {
+                        "none".to_string()
+                    } else {
+                        names.join(", ")
+                    }
+                ));
+            }
+        }
+    }
+    Ok((expanded, save))
+}
+
+/// The options of the `default` preset, none if it isn't saved.
+pub fn defaults() -> Result<Vec<OsString>, String> {
+    Ok(load()?
+        .into_iter()
+        .find(|(name, _)| name == DEFAULT)
+        .map(|(_, options)| options.into_iter().map(OsString::from).collect())
+        .unwrap_or_default())
+}
+
+/// Saves `options` under `name`, replacing a preset of that name.
+pub fn save(name: &str, options: &[String]) -> Result<PathBuf, String> {
+    let path = path()?;
+    let mut presets = load()?;
+    match presets.iter_mut().find(|(saved, _)| saved == name) {
+        Some((_, saved)) => *saved = options.to_vec(),
+        None => presets.push((name.to_string(), options.to_vec())),
+    }
+    let mut text = HEADER.to_string();
+    for (name, options) in &presets {
+        let quoted: Vec<String> = options.iter().map(|option| quote(option)).collect();
+        text.push_str(&format!(
+            "\n[presets.{}]\nargs = [{}]\n",
+            name,
+            quoted.join(", ")
+        ));
+    }
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("Cannot create {}: {}", dir.display(), e))?;
+    }
+    fs::write(&path, text).map_err(|e| format!("Cannot write {}: {}", path.display(), e))?;
+    Ok(path)
+}
+
+/// Where the presets are kept.
+fn path() -> Result<PathBuf, String> {
+    let config = if cfg!(windows) {
+        env::var_os("APPDATA").map(PathBuf::from)
+    } else {
+        env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+    };
+    config
+        .map(|dir| dir.join("video-transpose").join("presets.toml"))
+        .ok_or_else(|| "Cannot find the config directory for presets (HOME isn't set)".to_string())
+}
+
+/// Names become TOML bare keys: letters, digits, `-` and `_`.
+fn check_name(name: &str) -> Result<(), String> {
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    match valid {
+        true => Ok(()),
+        false => Err(format!(
+            "Invalid preset name: {} (use letters, digits, - and _)",
+            name
+        )),
+    }
+}
+
+/// The saved presets, none if the file doesn't exist yet.
+fn load() -> Result<Vec<(String, Vec<String>)>, String> {
+    let path = path()?;
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("Cannot read {}: {}", path.display(), e)),
+    };
+    parse(&text).map_err(|e| format!("Invalid {}: {}", path.display(), e))
+}
+
+/// Reads `[presets.<name>]` tables with an `args` array of strings.
+fn parse(text: &str) -> Result<Vec<(String, Vec<String>)>, String> {
+    let mut presets: Vec<(String, Vec<String>)> = Vec::new();
+    let mut lines = text.lines().enumerate();
+    while let Some((number, line)) = lines.next() {
+        let line = line.trim();
+        let at = |problem: &str| format!("line {}: {}", number + 1, problem);
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(header) = line.strip_prefix('[') {
+            let name = header
+                .split('#')
+                .next()
+                .and_then(|header| header.trim_end().strip_suffix(']'))
+                .and_then(|header| header.trim().strip_prefix("presets."))
+                .ok_or_else(|| at("expected a [presets.<name>] table"))?;
+            check_name(name).map_err(|e| at(&e))?;
+            presets.push((name.to_string(), Vec::new()));
+            continue;
+        }
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| at("expected args = [...]"))?;
+        let Some((_, args)) = presets.last_mut() else {
+            return Err(at("args outside a [presets.<name>] table"));
+        };
+        if key.trim() != "args" {
+            return Err(at(&format!("unknown key {}", key.trim())));
+        }
+        // The array may go on over the following lines
+        let mut value = value.trim().to_string();
+        loop {
+            match parse_array(&value) {
+                Ok(Some(parsed)) => {
+                    *args = parsed;
+                    break;
+                }
+                Ok(None) => match lines.next() {
+                    Some((_, more)) => {
+                        value.push('\n');
+                        value.push_str(more);
+                    }
+                    None => return Err(at("unterminated array")),
+                },
+                Err(e) => return Err(at(&e)),
+            }
+        }
+    }
+    Ok(presets)
+}
+
+/// Parses an array of basic (`"..."`) or literal (`'...'`) strings, or
+/// returns `None` if it isn't closed yet.
+fn parse_array(text: &str) -> Result<Option<Vec<String>>, String> {
+    let mut chars = text.chars();
+    if chars.next() != Some('[') {
+        return Err("expected an array of strings".to_string());
+    }
+    let mut values = Vec::new();
+    loop {
+        match chars.next() {
+            None => return Ok(None),
+            Some(c) if c.is_whitespace() || c == ',' => {}
+            Some('#') => {
+                // A comment runs to the end of the line
+                if !chars.any(|c| c == '\n') {
+                    return Ok(None);
+                }
+            }
+            Some(']') => return Ok(Some(values)),
+            Some('\'') => {
+                let value: String = chars.by_ref().take_while(|&c| c != '\'').collect();
+                values.push(value);
+            }
+            Some('"') => {
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        None => return Ok(None),
+                        Some('"') => break,
+                        Some('\\') => value.push(match chars.next() {
+                            Some('n') => '\n',
+                            Some('t') => '\t',
+                            Some('r') => '\r',
+                            Some('"') => '"',
+                            Some('\\') => '\\',
+                            Some('u') => {
+                                let hex: String = chars.by_ref().take(4).collect();
+                                u32::from_str_radix(&hex, 16)
+                                    .ok()
+                                    .and_then(char::from_u32)
+                                    .ok_or_else(|| format!("invalid escape \\u{}", hex))?
+                            }
+                            other => {
+                                return Err(format!("invalid escape \\{}", other.unwrap_or(' ')))
+                            }
+                        }),
+                        Some(c) => value.push(c),
+                    }
+                }
+                values.push(value);
+            }
+            Some(c) => return Err(format!("unexpected {} in the array", c)),
+        }
+    }
+}
+
+/// A TOML basic string.
+fn quote(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            '\r' => quoted.push_str("\\r"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
@@ -0,0 +1,129 @@
+use crate::os_path;
+use ffmpeg_next as ffmpeg;
+use ffmpeg_next::format::stream::Disposition;
+use ffmpeg_next::media::Type;
+use std::fmt;
+use std::path::Path;
+
+/// The input opened fine but has nothing to transpose: no video stream,
+/// or just a single still image.
+pub struct UnsupportedInput {
+    pub path: String,
+    /// What the input contains instead, e.g. "audio only (mp3)".
+    pub found: String,
+}
+
+impl fmt::Display for UnsupportedInput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Unsupported input {}: found {}. video-transpose needs a video stream with \
+             more than one frame.",
+            self.path, self.found
+        )
+    }
+}
+
+// `main` returning the error prints its Debug form, so keep that readable
+impl fmt::Debug for UnsupportedInput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl std::error::Error for UnsupportedInput {}
+
+/// Picks the video stream to transpose, skipping cover art, and rejects
+/// audio-only and still-image inputs up front with a specific error.
+pub fn video_stream(
+    ictx: &ffmpeg::format::context::Input,
+    path: &Path,
+) -> Result<usize, UnsupportedInput> {
+    let unsupported = |found: String| UnsupportedInput {
+        path: path.display().to_string(),
+        found,
+    };
+
+    let demuxer = ictx.format().name().to_string();
+    let is_video = |stream: &ffmpeg::format::stream::Stream| {
+        stream.parameters().medium() == Type::Video
+            && !stream.disposition().contains(Disposition::ATTACHED_PIC)
+    };
+    let stream = match ictx.streams().best(Type::Video).filter(is_video) {
+        Some(stream) => Some(stream),
+        None => ictx.streams().find(is_video),
+    };
+
+    let stream = match stream {
+        Some(stream) => stream,
+        None => {
+            let mut kinds: Vec<String> = Vec::new();
+            for stream in ictx.streams() {
+                let parameters = stream.parameters();
+                let kind = match parameters.medium() {
+                    Type::Audio => "audio",
+                    Type::Subtitle => "subtitles",
+                    Type::Video => "cover art",
+                    Type::Data => "data",
+                    Type::Attachment => "attachments",
+                    _ => "unknown streams",
+                };
+                let description = format!("{} ({})", kind, parameters.id().name());
+                if !kinds.contains(&description) {
+                    kinds.push(description);
+                }
+            }
+            return Err(unsupported(if kinds.is_empty() {
+                format!("no streams ({})", demuxer)
+            } else {
+                format!("{} only", kinds.join(", "))
+            }));
+        }
+    };
+
+    // Image demuxers (image2, jpeg_pipe, png_pipe, ...) yield a single frame
+    // unless image2 was given a numbered sequence pattern such as `f%04d.png`
+    let image_demuxer = demuxer == "image2" || demuxer.ends_with("_pipe");
+    if (image_demuxer && !path.to_string_lossy().contains('%')) || stream.frames() == 1 {
+        return Err(unsupported(format!(
+            "a still image ({})",
+            stream.parameters().id().name()
+        )));
+    }
+    Ok(stream.index())
+}
+
+/// Estimates the number of frames in a stream from its header or duration.
+pub fn estimate_frame_count(
+    stream: &ffmpeg::format::stream::Stream,
+    container_duration: i64,
+    fps: ffmpeg::Rational,
+) -> Option<u64> {
+    if stream.frames() > 0 {
+        return Some(stream.frames() as u64);
+    }
+    if fps.numerator() <= 0 || fps.denominator() <= 0 {
+        return None;
+    }
+    let fps = f64::from(fps);
+
+    let seconds = if stream.duration() > 0 {
+        stream.duration() as f64 * f64::from(stream.time_base())
+    } else if container_duration > 0 {
+        container_duration as f64 / f64::from(ffmpeg::ffi::AV_TIME_BASE)
+    } else {
+        return None;
+    };
+    Some((seconds * fps).ceil() as u64)
+}
+
+/// Counts the stream's packets in a separate pass over the file, without
+/// decoding. Video packets carry one frame each, so this is the frame count
+/// for inputs whose header has neither a frame count nor a duration.
+pub fn count_packets(path: &Path, stream_index: usize) -> Result<u64, Box<dyn std::error::Error>> {
+    let mut ictx = os_path::open_input(path, None)?;
+    Ok(ictx
+        .packets()
+        .filter(|(stream, _)| stream.index() == stream_index)
+        .count() as u64)
+}
@@ -0,0 +1,83 @@
+use indicatif::{ProgressBar, ProgressStyle};
+use std::env;
+
+/// Builds the progress bars for a run. With `--no-progress` they are hidden
+/// so logs only get plain lines, and with `--no-color` (or `NO_COLOR` set)
+/// they are drawn without ANSI colors.
+pub struct Progress {
+    enabled: bool,
+    color: bool,
+}
+
+impl Progress {
+    pub fn new(no_progress: bool, no_color: bool) -> Self {
+        Progress {
+            enabled: !no_progress,
+            color: !no_color && env::var_os("NO_COLOR").is_none_or(|value| value.is_empty()),
+        }
+    }
+
+    /// Whether bars are drawn; callers print a summary line instead when not.
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn spinner(&self, template: &str) -> ProgressBar {
+        if !self.enabled {
+            return ProgressBar::hidden();
+        }
+        let pb = ProgressBar::new_spinner();
+        pb.set_style(
+            ProgressStyle::default_spinner()
+                .template(&self.template(template))
+                .unwrap(),
+        );
+        pb
+    }
+
+    pub fn bar(&self, len: u64, template: &str) -> ProgressBar {
+        if !self.enabled {
+            return ProgressBar::hidden();
+        }
+        let pb = ProgressBar::new(len);
+        pb.set_style(
+            ProgressStyle::default_bar()
+                .template(&self.template(template))
+                .unwrap()
+                .progress_chars("#>-"),
+        );
+        pb
+    }
+
+    /// Drops the `.style` part of `{key:width.style}` placeholders when
+    /// colors are off.
+    fn template(&self, template: &str) -> String {
+        if self.color {
+            return template.to_string();
+        }
+        let mut out = String::new();
+        let mut rest = template;
+        while let Some(open) = rest.find('{') {
+            let close = match rest[open..].find('}') {
+                Some(close) => open + close,
+                None => break,
+            };
+            out.push_str(&rest[..open]);
+            let placeholder = &rest[open + 1..close];
+            match placeholder.split_once(':') {
+                Some((key, spec)) => {
+                    let width = spec.split('.').next().unwrap_or("");
+                    if width.is_empty() {
+                        out.push_str(&format!("{{{}}}", key));
+                    } else {
+                        out.push_str(&format!("{{{}:{}}}", key, width));
+                    }
+                }
+                None => out.push_str(&format!("{{{}}}", placeholder)),
+            }
+            rest = &rest[close + 1..];
+        }
+        out.push_str(rest);
+        out
+    }
+}
@@ -0,0 +1,189 @@
+//! `--progress-socket`: progress for desktop frontends, so they don't have
+//! to parse the progress bars on stderr. The tool listens on a Unix socket
+//! and sends every connected client one JSON object per line:
+//!
+//! - `{"event":"phase","phase":"decode"|"encode","total":<frames>|null}`
+//!   when a phase starts; encoding starts once per output part
+//! - `{"event":"progress","phase":...,"frame":<n>,"total":...}` per frame
+//! - `{"event":"preview","frame":<n>,"width":<w>,"height":<h>,"bytes":<len>}`
+//!   followed by `len` bytes of a JPEG thumbnail of that output frame, at
+//!   most a few times a second
+//! - `{"event":"done","success":true|false,"error":<message>|null}` last
+//!
+//! Clients can connect at any time and receive the events from then on. A
+//! client that stops reading is dropped rather than stalling the run.
+
+use crate::encode;
+use crate::frame_store::FrameFormat;
+use crate::sink::{FrameSink, OutputFrame};
+use serde_json::{json, Value};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+#[cfg(not(unix))]
+use std::net::{TcpListener as Listener, TcpStream as Stream};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener as Listener, UnixStream as Stream};
+
+/// Minimum time between two preview thumbnails.
+const PREVIEW_INTERVAL: Duration = Duration::from_millis(500);
+/// Box the preview thumbnails are scaled to fit.
+const PREVIEW_SIZE: (usize, usize) = (320, 240);
+/// How long a write may block before the client is dropped.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+
+pub struct ProgressSocket {
+    path: PathBuf,
+    listener: Listener,
+    clients: Vec<Stream>,
+    phase: &'static str,
+    total: Option<u64>,
+    frame: u64,
+    last_preview: Option<Instant>,
+}
+
+impl ProgressSocket {
+    /// Listens on a Unix socket at `path`, replacing a stale socket left
+    /// there by an earlier run.
+    pub fn bind(path: &Path) -> Result<Self, String> {
+        let listener =
+            listen(path).map_err(|e| format!("Cannot listen on {}: {}", path.display(), e))?;
+        listener
+            .set_nonblocking(true)
+            .map_err(|e| format!("Cannot listen on {}: {}", path.display(), e))?;
+        Ok(ProgressSocket {
+            path: path.to_path_buf(),
+            listener,
+            clients: Vec::new(),
+            phase: "decode",
+            total: None,
+            frame: 0,
+            last_preview: None,
+        })
+    }
+
+    /// Starts counting frames of `phase` from zero.
+    pub fn phase(&mut self, phase: &'static str, total: Option<u64>) {
+        self.phase = phase;
+        self.total = total;
+        self.frame = 0;
+        self.last_preview = None;
+        self.send(
+            &json!({"event": "phase", "phase": phase, "total": total}),
+            &[],
+        );
+    }
+
+    /// Counts one more frame done in the current phase.
+    pub fn progress(&mut self) {
+        self.frame += 1;
+        let event = json!({
+            "event": "progress",
+            "phase": self.phase,
+            "frame": self.frame,
+            "total": self.total,
+        });
+        self.send(&event, &[]);
+    }
+
+    /// Reports how the run ended.
+    pub fn done<T>(&mut self, result: &Result<T, Box<dyn std::error::Error>>) {
+        let error = result.as_ref().err().map(|error| error.to_string());
+        self.send(
+            &json!({"event": "done", "success": error.is_none(), "error": error}),
+            &[],
+        );
+    }
+
+    /// Sends a thumbnail of `frame` if the last one is old enough. Float
+    /// frames get none.
+    fn preview(&mut self, frame: &OutputFrame) {
+        if frame.format == FrameFormat::RgbF32
+            || self
+                .last_preview
+                .is_some_and(|last| last.elapsed() < PREVIEW_INTERVAL)
+        {
+            return;
+        }
+        self.accept();
+        if self.clients.is_empty() {
+            return;
+        }
+        self.last_preview = Some(Instant::now());
+        let jpeg = match encode::jpeg_thumbnail(
+            frame.data,
+            frame.format,
+            frame.width,
+            frame.height,
+            PREVIEW_SIZE,
+        ) {
+            Ok(jpeg) => jpeg,
+            Err(_) => return,
+        };
+        let event = json!({
+            "event": "preview",
+            "frame": self.frame,
+            "width": frame.width,
+            "height": frame.height,
+            "bytes": jpeg.len(),
+        });
+        self.send(&event, &jpeg);
+    }
+
+    /// Writes `event` as a line, then `payload`, to every client, dropping
+    /// those the write fails for.
+    fn send(&mut self, event: &Value, payload: &[u8]) {
+        self.accept();
+        let mut message = event.to_string().into_bytes();
+        message.push(b'\n');
+        message.extend_from_slice(payload);
+        self.clients
+            .retain_mut(|client| client.write_all(&message).is_ok());
+    }
+
+    /// Takes the clients that connected since the last event.
+    fn accept(&mut self) {
+        while let Ok((client, _)) = self.listener.accept() {
+            let ready = client
+                .set_nonblocking(false)
+                .and_then(|()| client.set_write_timeout(Some(WRITE_TIMEOUT)));
+            if ready.is_ok() {
+                self.clients.push(client);
+            }
+        }
+    }
+}
+
+/// Counts the output frames and sends their previews, as one more sink
+/// next to the outputs.
+impl FrameSink for ProgressSocket {
+    fn write(&mut self, frame: &OutputFrame) -> Result<(), Box<dyn std::error::Error>> {
+        self.progress();
+        self.preview(frame);
+        Ok(())
+    }
+}
+
+impl Drop for ProgressSocket {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(unix)]
+fn listen(path: &Path) -> io::Result<Listener> {
+    use std::os::unix::fs::FileTypeExt;
+    if std::fs::symlink_metadata(path).is_ok_and(|metadata| metadata.file_type().is_socket()) {
+        std::fs::remove_file(path)?;
+    }
+    Listener::bind(path)
+}
+
+#[cfg(not(unix))]
+fn listen(_path: &Path) -> io::Result<Listener> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "Unix sockets are not supported on this platform",
+    ))
+}
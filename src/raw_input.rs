@@ -0,0 +1,171 @@
+use ffmpeg_next as ffmpeg;
+use ffmpeg_next::format::Pixel;
+use ffmpeg_next::util::frame::video::Video;
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::Path;
+use std::str::FromStr;
+
+/// Frame rate assumed for raw input without `--input-fps` (FFmpeg's rawvideo
+/// demuxer uses the same default).
+const DEFAULT_FPS: ffmpeg::Rational = ffmpeg::Rational(25, 1);
+
+/// Layout of the frames in a headerless raw file, e.g. a `.yuv` dump from
+/// custom capture software or a Bayer dump from a machine-vision camera.
+#[derive(Clone, Copy)]
+pub struct RawFormat {
+    pub pixel: Pixel,
+    pub width: usize,
+    pub height: usize,
+    pub fps: ffmpeg::Rational,
+}
+
+impl RawFormat {
+    /// Parses `--input-pixfmt` (an FFmpeg pixel format name such as
+    /// `yuv420p` or `bayer_rggb8`), `--input-size` (`WxH`) and
+    /// `--input-fps` (`N/D` or decimal).
+    pub fn parse(pixfmt: &str, size: &str, fps: Option<&str>) -> Result<RawFormat, String> {
+        let pixel =
+            Pixel::from_str(pixfmt).map_err(|_| format!("Unknown pixel format: {}", pixfmt))?;
+        let (width, height) = size
+            .split_once('x')
+            .and_then(|(w, h)| Some((w.parse().ok()?, h.parse().ok()?)))
+            .filter(|&(w, h)| w > 0 && h > 0)
+            .ok_or_else(|| format!("Invalid input size: {} (expected WxH)", size))?;
+        let fps = match fps {
+            Some(fps) => parse_rate(fps)?,
+            None => DEFAULT_FPS,
+        };
+        let format = RawFormat {
+            pixel,
+            width,
+            height,
+            fps,
+        };
+        if format.frame_bytes() == 0 {
+            return Err(format!(
+                "Pixel format {} can't be read as raw frames",
+                pixfmt
+            ));
+        }
+        Ok(format)
+    }
+
+    /// Size in bytes of one tightly packed frame.
+    pub fn frame_bytes(&self) -> usize {
+        let size = unsafe {
+            ffmpeg::ffi::av_image_get_buffer_size(
+                self.pixel.into(),
+                self.width as i32,
+                self.height as i32,
+                1,
+            )
+        };
+        size.max(0) as usize
+    }
+}
+
+/// Parses a frame rate given as `30000/1001`, `30` or `29.97`.
+fn parse_rate(text: &str) -> Result<ffmpeg::Rational, String> {
+    let invalid = || format!("Invalid input frame rate: {}", text);
+    let rate = match text.split_once('/') {
+        Some((num, den)) => ffmpeg::Rational(
+            num.trim().parse().map_err(|_| invalid())?,
+            den.trim().parse().map_err(|_| invalid())?,
+        ),
+        None => ffmpeg::Rational::from(text.trim().parse::<f64>().map_err(|_| invalid())?),
+    };
+    if rate.numerator() <= 0 || rate.denominator() <= 0 {
+        return Err(invalid());
+    }
+    Ok(rate)
+}
+
+/// Reads frames straight from a headerless raw file (or stdin for `-`),
+/// bypassing demuxing.
+pub struct RawReader {
+    file: Box<dyn Read>,
+    format: RawFormat,
+    buffer: Vec<u8>,
+    /// Number of whole frames in the file, unknown for stdin.
+    pub frame_count: Option<u64>,
+}
+
+impl RawReader {
+    pub fn open(path: &Path, format: RawFormat) -> Result<RawReader, String> {
+        let frame_bytes = format.frame_bytes();
+        if path == Path::new("-") {
+            return Ok(RawReader {
+                file: Box::new(BufReader::new(io::stdin())),
+                format,
+                buffer: vec![0; frame_bytes],
+                frame_count: None,
+            });
+        }
+
+        let file =
+            File::open(path).map_err(|e| format!("Cannot open {}: {}", path.display(), e))?;
+        let size = file.metadata().map(|m| m.len()).unwrap_or(0);
+        if size % frame_bytes as u64 != 0 {
+            status!(
+                "  Warning: file size is not a multiple of the {} byte frame size, \
+                 the trailing partial frame is ignored",
+                frame_bytes
+            );
+        }
+        Ok(RawReader {
+            file: Box::new(BufReader::new(file)),
+            format,
+            buffer: vec![0; frame_bytes],
+            frame_count: Some(size / frame_bytes as u64),
+        })
+    }
+
+    /// Reads the next frame, or `None` at the end of the file.
+    pub fn read_frame(&mut self) -> Result<Option<Video>, String> {
+        let mut filled = 0;
+        while filled < self.buffer.len() {
+            match self.file.read(&mut self.buffer[filled..]) {
+                Ok(0) => return Ok(None),
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.to_string()),
+            }
+        }
+
+        let RawFormat {
+            pixel,
+            width,
+            height,
+            ..
+        } = self.format;
+        let mut frame = Video::new(pixel, width as u32, height as u32);
+        unsafe {
+            // Point plane pointers into the packed buffer, then copy each
+            // plane into the frame's own (aligned) planes
+            let mut src_data = [std::ptr::null_mut::<u8>(); 4];
+            let mut src_linesize = [0i32; 4];
+            ffmpeg::ffi::av_image_fill_arrays(
+                src_data.as_mut_ptr(),
+                src_linesize.as_mut_ptr(),
+                self.buffer.as_ptr(),
+                pixel.into(),
+                width as i32,
+                height as i32,
+                1,
+            );
+            let mut src_const = src_data.map(|plane| plane as *const u8);
+            let raw = frame.as_mut_ptr();
+            ffmpeg::ffi::av_image_copy(
+                (*raw).data.as_mut_ptr(),
+                (*raw).linesize.as_mut_ptr(),
+                src_const.as_mut_ptr(),
+                src_linesize.as_ptr(),
+                pixel.into(),
+                width as i32,
+                height as i32,
+            );
+        }
+        Ok(Some(frame))
+    }
+}
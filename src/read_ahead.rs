@@ -0,0 +1,398 @@
+//! A read-ahead thread between the input and the demuxer. Reading a URL or
+//! a slow disk, every read the demuxer makes can wait on the network or
+//! the drive, and decoding waits with it. Here a thread of its own reads
+//! the input in large chunks, up to `--io-buffer-size` ahead of the
+//! demuxer, which reads from memory through a custom AVIO context. Seeks
+//! inside the buffered data just skip ahead; others are passed on to the
+//! input and restart the read-ahead from there.
+//!
+//! A read that fails is retried as `--input-retries` says: the input is
+//! reopened and, if it can seek, picked up at the byte it stopped at.
+//!
+//! Network inputs read ahead by default. Demuxers that do their own
+//! networking (RTSP) have no byte stream to read ahead; they get the size
+//! as their socket receive buffer instead.
+
+use crate::os_path;
+use crate::retry::RetryPolicy;
+use ffmpeg_next as ffmpeg;
+use std::collections::VecDeque;
+use std::ffi::CStr;
+use std::os::raw::{c_int, c_void};
+use std::path::Path;
+use std::ptr;
+use std::slice;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+
+/// Read-ahead of network inputs without `--io-buffer-size`.
+pub const DEFAULT_NETWORK_BUFFER: usize = 32 << 20;
+/// Bytes the thread reads at a time, and the size of the demuxer's buffer.
+const CHUNK_SIZE: usize = 256 << 10;
+
+/// Whether `url` is read over the network by an FFmpeg protocol.
+pub fn is_network(url: &str) -> bool {
+    match url.split_once("://") {
+        Some((scheme, _)) => !matches!(scheme, "file" | "pipe"),
+        None => false,
+    }
+}
+
+/// Whether the demuxer of `url` opens its own connections, so there's no
+/// byte stream to read ahead.
+pub fn is_demuxer_managed(url: &str) -> bool {
+    url.starts_with("rtsp://") || url.starts_with("rtsps://")
+}
+
+/// The input's AVIO context, moved to the read-ahead thread.
+struct Source(*mut ffmpeg::ffi::AVIOContext);
+
+// The context is only used by the thread it was moved to
+unsafe impl Send for Source {}
+
+impl Source {
+    fn open(url: &CStr) -> Result<Source, ffmpeg::Error> {
+        let mut context = ptr::null_mut();
+        let result = unsafe {
+            ffmpeg::ffi::avio_open2(
+                &mut context,
+                url.as_ptr(),
+                ffmpeg::ffi::AVIO_FLAG_READ,
+                ptr::null(),
+                ptr::null_mut(),
+            )
+        };
+        match result {
+            result if result < 0 => Err(ffmpeg::Error::from(result)),
+            _ => Ok(Source(context)),
+        }
+    }
+
+    /// Reopens `url` after a failed read, carrying on at `offset`. Inputs
+    /// that can't seek there fail with `ESPIPE`.
+    fn reopen_at(&mut self, url: &CStr, offset: i64) -> Result<(), ffmpeg::Error> {
+        let reopened = Source::open(url)?;
+        unsafe { ffmpeg::ffi::avio_closep(&mut self.0) };
+        self.0 = reopened.0;
+        if offset > 0
+            && unsafe { ffmpeg::ffi::avio_seek(self.0, offset, ffmpeg::ffi::SEEK_SET) } != offset
+        {
+            return Err(ffmpeg::Error::Other {
+                errno: ffmpeg::util::error::ESPIPE,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Whether retrying might get past `error`: not for inputs that aren't
+/// there, can't be read, or can't pick up where they stopped.
+fn is_transient(error: &ffmpeg::Error) -> bool {
+    !matches!(
+        error,
+        ffmpeg::Error::HttpBadRequest
+            | ffmpeg::Error::HttpUnauthorized
+            | ffmpeg::Error::HttpForbidden
+            | ffmpeg::Error::HttpNotFound
+            | ffmpeg::Error::ProtocolNotFound
+            | ffmpeg::Error::Other {
+                errno: ffmpeg::util::error::ENOENT | ffmpeg::util::error::ESPIPE
+            }
+    )
+}
+
+/// A seek handed from the demuxer to the read-ahead thread.
+enum Seek {
+    None,
+    Requested(i64),
+    /// The new position, or an FFmpeg error.
+    Done(i64),
+}
+
+struct State {
+    chunks: VecDeque<Vec<u8>>,
+    /// Bytes of the front chunk already read.
+    front_offset: usize,
+    /// Bytes in `chunks` not read yet.
+    buffered: usize,
+    /// Input offset of the next byte the demuxer reads.
+    position: i64,
+    end: bool,
+    error: Option<c_int>,
+    seek: Seek,
+    closed: bool,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    changed: Condvar,
+    capacity: usize,
+    /// Size of the input, or an FFmpeg error if it's unknown.
+    size: i64,
+}
+
+/// An input read ahead on a thread, as an AVIO context for the demuxer.
+pub struct ReadAhead {
+    shared: Arc<Shared>,
+    context: *mut ffmpeg::ffi::AVIOContext,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl ReadAhead {
+    /// Opens `path` (a file or URL) and starts reading up to `capacity`
+    /// bytes ahead, opening and reading again as `retries` allows.
+    pub fn open(
+        path: &Path,
+        capacity: usize,
+        retries: RetryPolicy,
+    ) -> Result<ReadAhead, Box<dyn std::error::Error>> {
+        let url = os_path::to_cstring(path)?;
+        let source = retries.run("Opening the input", || Source::open(&url), is_transient)?;
+        let (size, seekable) = unsafe { (ffmpeg::ffi::avio_size(source.0), (*source.0).seekable) };
+
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                chunks: VecDeque::new(),
+                front_offset: 0,
+                buffered: 0,
+                position: 0,
+                end: false,
+                error: None,
+                seek: Seek::None,
+                closed: false,
+            }),
+            changed: Condvar::new(),
+            capacity: capacity.max(CHUNK_SIZE),
+            size,
+        });
+        let thread = {
+            let shared = Arc::clone(&shared);
+            thread::spawn(move || fill(&shared, source, &url, retries))
+        };
+        let context = unsafe {
+            let buffer = ffmpeg::ffi::av_malloc(CHUNK_SIZE) as *mut u8;
+            let context = ffmpeg::ffi::avio_alloc_context(
+                buffer,
+                CHUNK_SIZE as c_int,
+                0,
+                Arc::as_ptr(&shared) as *mut c_void,
+                Some(read_packet),
+                None,
+                Some(seek),
+            );
+            // Demuxers avoid seeking inputs that can't
+            if !context.is_null() {
+                (*context).seekable = seekable;
+            }
+            context
+        };
+        let read_ahead = ReadAhead {
+            shared,
+            context,
+            thread: Some(thread),
+        };
+        if context.is_null() {
+            return Err(ffmpeg::Error::Other {
+                errno: ffmpeg::util::error::ENOMEM,
+            }
+            .into());
+        }
+        Ok(read_ahead)
+    }
+
+    /// The AVIO context to demux from; it lives as long as `self`.
+    pub fn context(&self) -> *mut ffmpeg::ffi::AVIOContext {
+        self.context
+    }
+}
+
+impl Drop for ReadAhead {
+    fn drop(&mut self) {
+        self.shared.lock().closed = true;
+        self.shared.changed.notify_all();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        if !self.context.is_null() {
+            unsafe {
+                ffmpeg::ffi::av_freep(&mut (*self.context).buffer as *mut *mut u8 as *mut c_void);
+                ffmpeg::ffi::avio_context_free(&mut self.context);
+            }
+        }
+    }
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn wait<'a>(&self, state: MutexGuard<'a, State>) -> MutexGuard<'a, State> {
+        self.changed
+            .wait(state)
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Hands the demuxer up to `out.len()` buffered bytes, waiting for the
+    /// thread if there are none yet.
+    fn read(&self, out: &mut [u8]) -> c_int {
+        let mut state = self.lock();
+        while state.chunks.is_empty() {
+            if let Some(error) = state.error {
+                return error;
+            }
+            if state.end {
+                return ffmpeg::ffi::AVERROR_EOF;
+            }
+            state = self.wait(state);
+        }
+        let copied = take(&mut state, out);
+        self.changed.notify_all();
+        copied as c_int
+    }
+
+    fn seek(&self, offset: i64, whence: c_int) -> i64 {
+        let mut state = self.lock();
+        let target = match whence & !ffmpeg::ffi::AVSEEK_FORCE {
+            ffmpeg::ffi::AVSEEK_SIZE => return self.size,
+            ffmpeg::ffi::SEEK_SET => offset,
+            ffmpeg::ffi::SEEK_CUR => state.position + offset,
+            ffmpeg::ffi::SEEK_END if self.size >= 0 => self.size + offset,
+            _ => return -i64::from(ffmpeg::util::error::EINVAL),
+        };
+        // Short hops forward, e.g. over boxes the demuxer skips, stay in
+        // the buffer
+        let skip = target - state.position;
+        if (0..=state.buffered as i64).contains(&skip) {
+            drop_bytes(&mut state, skip as usize);
+            self.changed.notify_all();
+            return target;
+        }
+        state.seek = Seek::Requested(target);
+        self.changed.notify_all();
+        loop {
+            if let Seek::Done(result) = state.seek {
+                state.seek = Seek::None;
+                return result;
+            }
+            state = self.wait(state);
+        }
+    }
+}
+
+/// Moves up to `out.len()` buffered bytes into `out`, returning how many.
+fn take(state: &mut State, out: &mut [u8]) -> usize {
+    let mut copied = 0;
+    while copied < out.len() {
+        let Some(chunk) = state.chunks.front() else {
+            break;
+        };
+        let available = &chunk[state.front_offset..];
+        let count = available.len().min(out.len() - copied);
+        out[copied..copied + count].copy_from_slice(&available[..count]);
+        copied += count;
+        state.front_offset += count;
+        if state.front_offset == chunk.len() {
+            state.chunks.pop_front();
+            state.front_offset = 0;
+        }
+    }
+    state.buffered -= copied;
+    state.position += copied as i64;
+    copied
+}
+
+/// Drops the next `count` buffered bytes.
+fn drop_bytes(state: &mut State, mut count: usize) {
+    state.buffered -= count;
+    state.position += count as i64;
+    while let Some(chunk) = state.chunks.front() {
+        let left = chunk.len() - state.front_offset;
+        if count < left {
+            state.front_offset += count;
+            break;
+        }
+        count -= left;
+        state.chunks.pop_front();
+        state.front_offset = 0;
+    }
+}
+
+/// The read-ahead thread: keeps the buffer full and carries out seeks
+/// until the reader is dropped.
+fn fill(shared: &Shared, mut source: Source, url: &CStr, retries: RetryPolicy) {
+    let mut buffer = vec![0u8; CHUNK_SIZE];
+    // Offset of `source`, where a reopened input picks up
+    let mut offset = 0;
+    let mut state = shared.lock();
+    while !state.closed {
+        if let Seek::Requested(target) = state.seek {
+            drop(state);
+            let result = unsafe { ffmpeg::ffi::avio_seek(source.0, target, ffmpeg::ffi::SEEK_SET) };
+            state = shared.lock();
+            // A failed seek leaves the input, and so the buffer, where it was
+            if result >= 0 {
+                offset = result;
+                state.chunks.clear();
+                state.front_offset = 0;
+                state.buffered = 0;
+                state.position = result;
+                state.end = false;
+                state.error = None;
+            }
+            state.seek = Seek::Done(result);
+            shared.changed.notify_all();
+            continue;
+        }
+        if state.end || state.error.is_some() || state.buffered >= shared.capacity {
+            state = shared.wait(state);
+            continue;
+        }
+        drop(state);
+        let mut broken = false;
+        let read = retries.run(
+            "Reading the input",
+            || {
+                if broken {
+                    source.reopen_at(url, offset)?;
+                    broken = false;
+                }
+                let buffer = buffer.as_mut_ptr();
+                match unsafe { ffmpeg::ffi::avio_read(source.0, buffer, CHUNK_SIZE as c_int) } {
+                    error if error < 0 && error != ffmpeg::ffi::AVERROR_EOF => {
+                        broken = true;
+                        Err(ffmpeg::Error::from(error))
+                    }
+                    read => Ok(read),
+                }
+            },
+            is_transient,
+        );
+        let read = read.unwrap_or_else(c_int::from);
+        state = shared.lock();
+        match read {
+            read if read > 0 => {
+                offset += i64::from(read);
+                state.chunks.push_back(buffer[..read as usize].to_vec());
+                state.buffered += read as usize;
+            }
+            0 | ffmpeg::ffi::AVERROR_EOF => state.end = true,
+            error => state.error = Some(error),
+        }
+        shared.changed.notify_all();
+    }
+    drop(state);
+    unsafe { ffmpeg::ffi::avio_closep(&mut source.0) };
+}
+
+unsafe extern "C" fn read_packet(opaque: *mut c_void, buf: *mut u8, buf_size: c_int) -> c_int {
+    let shared = &*(opaque as *const Shared);
+    shared.read(slice::from_raw_parts_mut(buf, buf_size.max(0) as usize))
+}
+
+unsafe extern "C" fn seek(opaque: *mut c_void, offset: i64, whence: c_int) -> i64 {
+    let shared = &*(opaque as *const Shared);
+    shared.seek(offset, whence)
+}
@@ -0,0 +1,88 @@
+use crate::frame_store::{FrameFormat, FrameStore};
+use crate::interpolate;
+use crate::linear_light::LinearLight;
+
+/// How `--resample` builds a frame for a time between two decoded frames.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Resample {
+    /// The decoded frame closest in time.
+    Nearest,
+    /// A blend of the decoded frames before and after, weighted by distance.
+    Blend,
+}
+
+impl Resample {
+    pub fn parse(text: &str) -> Result<Resample, String> {
+        match text {
+            "nearest" => Ok(Resample::Nearest),
+            "blend" => Ok(Resample::Blend),
+            _ => Err(format!(
+                "Invalid --resample mode: {} (expected nearest or blend)",
+                text
+            )),
+        }
+    }
+}
+
+/// Number of frames `resample` produces for `store`, or `None` if a frame
+/// has no timestamp to resample by or the stream has no frame rate.
+pub fn frame_count(store: &FrameStore, interval: f64) -> Option<usize> {
+    if !(interval.is_finite() && interval > 0.0) || store.times.iter().any(Option::is_none) {
+        return None;
+    }
+    let (first, last) = store.time_span()?;
+    Some(((last - first) / interval).round() as usize + 1)
+}
+
+/// Rebuilds the time axis with one frame every `interval` seconds from the
+/// first timestamp to the last, so the output's X axis is linear in time
+/// for variable frame rate and frame-dropped input, where one decoded frame
+/// per step is not.
+pub fn resample(
+    store: &FrameStore,
+    format: FrameFormat,
+    interval: f64,
+    mode: Resample,
+    light: Option<&LinearLight>,
+) -> Option<FrameStore> {
+    let count = frame_count(store, interval)?;
+    // Decoders emit frames in presentation order, but don't rely on it
+    let mut order: Vec<(f64, usize)> = store
+        .times
+        .iter()
+        .enumerate()
+        .map(|(index, time)| (time.unwrap_or_default(), index))
+        .collect();
+    order.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let first = order[0].0;
+
+    let mut resampled = FrameStore::default();
+    let mut cursor = 0;
+    for step in 0..count {
+        let time = first + step as f64 * interval;
+        while cursor + 1 < order.len() && order[cursor + 1].0 <= time {
+            cursor += 1;
+        }
+        let (before_time, before) = order[cursor];
+        let frame = match order.get(cursor + 1) {
+            Some(&(after_time, after)) if after_time > before_time => {
+                let alpha = ((time - before_time) / (after_time - before_time)) as f32;
+                match mode {
+                    Resample::Nearest if alpha < 0.5 => store.frames[before].clone(),
+                    Resample::Nearest => store.frames[after].clone(),
+                    Resample::Blend if alpha <= 0.0 => store.frames[before].clone(),
+                    Resample::Blend => interpolate::blend(
+                        &store.frames[before],
+                        &store.frames[after],
+                        alpha,
+                        format,
+                        light,
+                    ),
+                }
+            }
+            _ => store.frames[before].clone(),
+        };
+        resampled.push(frame, Some(time));
+    }
+    Some(resampled)
+}
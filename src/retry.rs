@@ -0,0 +1,106 @@
+//! `--input-retries` and `--upload-retries`: how often network reads and
+//! uploads are retried, with exponential backoff, before a run gives up.
+//! Long jobs over flaky links otherwise die on the first dropped
+//! connection. Inputs read ahead (see `read_ahead`) reconnect and resume at
+//! the byte they stopped at when the protocol can seek, as HTTP can;
+//! multipart uploads retry only the part that failed.
+
+use std::fmt;
+use std::thread;
+use std::time::Duration;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RetryPolicy {
+    /// Retries after the first failure; 0 fails right away.
+    pub retries: u32,
+    /// Wait before the first retry, doubled for each one after it.
+    pub initial_delay: Duration,
+    /// Longest wait between retries.
+    pub max_delay: Duration,
+}
+
+impl RetryPolicy {
+    /// Three retries after 1, 2 and 4 seconds.
+    pub const DEFAULT: RetryPolicy = RetryPolicy {
+        retries: 3,
+        initial_delay: Duration::from_secs(1),
+        max_delay: Duration::from_secs(30),
+    };
+
+    pub const NONE: RetryPolicy = RetryPolicy {
+        retries: 0,
+        ..RetryPolicy::DEFAULT
+    };
+
+    /// Parses `N[:initial[:max]]`, the delays in seconds, e.g. `5:2:60`.
+    pub fn parse(flag: &str, text: &str) -> Result<RetryPolicy, String> {
+        let invalid = || {
+            format!(
+                "Invalid {}: {} (expected retries[:initial delay[:max delay]], e.g. 5:2:60)",
+                flag, text
+            )
+        };
+        let mut parts = text.split(':');
+        let retries = parts
+            .next()
+            .and_then(|retries| retries.parse().ok())
+            .ok_or_else(invalid)?;
+        let mut seconds = |default: Duration| match parts.next() {
+            None => Ok(default),
+            Some(part) => match part.parse::<f64>() {
+                Ok(seconds) if seconds.is_finite() && seconds >= 0.0 => {
+                    Ok(Duration::from_secs_f64(seconds))
+                }
+                _ => Err(invalid()),
+            },
+        };
+        let initial_delay = seconds(Self::DEFAULT.initial_delay)?;
+        let max_delay = seconds(Self::DEFAULT.max_delay)?.max(initial_delay);
+        if parts.next().is_some() {
+            return Err(invalid());
+        }
+        Ok(RetryPolicy {
+            retries,
+            initial_delay,
+            max_delay,
+        })
+    }
+
+    /// Wait before retry number `retry`, counting from 1.
+    pub fn delay(&self, retry: u32) -> Duration {
+        let factor = 2u32.saturating_pow(retry.saturating_sub(1));
+        self.initial_delay
+            .saturating_mul(factor)
+            .min(self.max_delay)
+    }
+
+    /// Runs `attempt` until it succeeds, fails with an error `retryable`
+    /// rejects, or the retries run out, logging each retry as `what`.
+    pub fn run<T, E: fmt::Display>(
+        &self,
+        what: &str,
+        mut attempt: impl FnMut() -> Result<T, E>,
+        retryable: impl Fn(&E) -> bool,
+    ) -> Result<T, E> {
+        let mut retry = 0;
+        loop {
+            match attempt() {
+                Ok(value) => return Ok(value),
+                Err(err) if retry >= self.retries || !retryable(&err) => return Err(err),
+                Err(err) => {
+                    retry += 1;
+                    let delay = self.delay(retry);
+                    status!(
+                        "  {} failed ({}), retry {} of {} in {:.1}s",
+                        what,
+                        err,
+                        retry,
+                        self.retries,
+                        delay.as_secs_f64()
+                    );
+                    thread::sleep(delay);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,48 @@
+use ffmpeg_next::software::scaling::flag::Flags;
+
+/// Resampling filter for `--scaler`, used when converting decoded frames
+/// and when scaling outputs.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Scaler {
+    Bilinear,
+    Bicubic,
+    Lanczos,
+    /// Nearest neighbour: every output sample is a copy of an input one,
+    /// for transposes that must keep the data exact.
+    Point,
+}
+
+impl Scaler {
+    pub fn parse(text: &str) -> Result<Scaler, String> {
+        match text {
+            "bilinear" => Ok(Scaler::Bilinear),
+            "bicubic" => Ok(Scaler::Bicubic),
+            "lanczos" => Ok(Scaler::Lanczos),
+            "point" | "nearest" => Ok(Scaler::Point),
+            _ => Err(format!(
+                "Invalid --scaler: {} (expected bilinear, bicubic, lanczos or point)",
+                text
+            )),
+        }
+    }
+
+    /// The name `--scaler` takes.
+    pub fn name(self) -> &'static str {
+        match self {
+            Scaler::Bilinear => "bilinear",
+            Scaler::Bicubic => "bicubic",
+            Scaler::Lanczos => "lanczos",
+            Scaler::Point => "point",
+        }
+    }
+
+    /// swscale flags selecting the filter.
+    pub fn flags(self) -> Flags {
+        match self {
+            Scaler::Bilinear => Flags::BILINEAR,
+            Scaler::Bicubic => Flags::BICUBIC,
+            Scaler::Lanczos => Flags::LANCZOS,
+            Scaler::Point => Flags::POINT,
+        }
+    }
+}
@@ -0,0 +1,43 @@
+use crate::frame_store::FrameFormat;
+
+/// Luma histogram bins used to compare frames.
+const BINS: usize = 64;
+
+/// Only every `SAMPLE_STEP`th pixel is counted, plenty for a histogram.
+const SAMPLE_STEP: usize = 4;
+
+/// Default `--scene-threshold`: the fraction of the histogram that has to
+/// change between consecutive frames to count as a cut.
+pub const DEFAULT_THRESHOLD: f64 = 0.4;
+
+/// Finds scene cuts by comparing the luma histograms of consecutive frames.
+/// Returns the indices of the first frame of every new scene.
+pub fn detect_cuts(frames: &[Vec<u8>], format: FrameFormat, threshold: f64) -> Vec<usize> {
+    let mut cuts = Vec::new();
+    let mut previous: Option<[u32; BINS]> = None;
+    for (index, frame) in frames.iter().enumerate() {
+        let histogram = luma_histogram(&format.luma(frame));
+        if let Some(previous) = &previous {
+            let total: u32 = histogram.iter().sum();
+            let changed: u32 = histogram
+                .iter()
+                .zip(previous)
+                .map(|(a, b)| a.abs_diff(*b))
+                .sum();
+            // Every moved sample is counted twice, once per bin it left and entered
+            if total > 0 && f64::from(changed) / f64::from(2 * total) > threshold {
+                cuts.push(index);
+            }
+        }
+        previous = Some(histogram);
+    }
+    cuts
+}
+
+fn luma_histogram(luma: &[u8]) -> [u32; BINS] {
+    let mut histogram = [0u32; BINS];
+    for &value in luma.iter().step_by(SAMPLE_STEP) {
+        histogram[value as usize * BINS / 256] += 1;
+    }
+    histogram
+}
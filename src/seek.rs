@@ -0,0 +1,284 @@
+//! Random access to single output frames, for interactive viewers built on
+//! the library. [`Seeker::get_output_frame`] computes output frame `x` on
+//! demand instead of running the whole job. It needs column `x` of every
+//! source frame, so the input is decoded once and only a band of columns
+//! around `x` is kept of each frame. Bands stay in a decode cache, so
+//! scrubbing to nearby frames doesn't decode again; the least recently used
+//! band is dropped when the cache would outgrow `--max-memory` (1 GiB by
+//! default). Bands are sized from the frame count. Without one the first
+//! band is the single column asked for; with a wrong one the first decode
+//! only counts the frames once its band outgrows its share, and the band is
+//! decoded again.
+//!
+//! Only the decode settings apply: the input options, `--scale`,
+//! `--frame-step`, `--scaler` and `--on-size-change`. Frame orders, overlays
+//! and the rest of the output processing are left to the caller.
+
+use crate::autocrop::Crop;
+use crate::cli::Options;
+use crate::color_range;
+use crate::fit::Reduction;
+use crate::frame_store::FrameFormat;
+use crate::memory::format_bytes;
+use crate::pipeline;
+use crate::source::{self, InputInfo, SizeChange};
+use crate::transpose;
+use ffmpeg_next::software::scaling::context::Context;
+use ffmpeg_next::util::frame::video::Video;
+
+/// Decode cache size without `--max-memory`.
+const DEFAULT_CACHE_BYTES: u64 = 1 << 30;
+/// A band takes at most this share of the cache, so several stay around.
+const BANDS: u64 = 4;
+
+/// Columns `start..start + width` of every stored source frame.
+struct Band {
+    start: usize,
+    width: usize,
+    frames: Vec<Vec<u8>>,
+    /// Request that last read the band.
+    used: u64,
+}
+
+impl Band {
+    fn contains(&self, x: usize) -> bool {
+        (self.start..self.start + self.width).contains(&x)
+    }
+
+    fn bytes(&self) -> u64 {
+        self.frames.iter().map(|frame| frame.len() as u64).sum()
+    }
+}
+
+/// The output frames of one input, computed one at a time.
+pub struct Seeker<'a> {
+    options: &'a Options,
+    info: InputInfo,
+    format: FrameFormat,
+    reduction: Reduction,
+    /// Size of the stored source frames, after `--scale`.
+    width: usize,
+    height: usize,
+    /// Timestamps of the stored frames, once the input has been decoded.
+    times: Option<Vec<Option<f64>>>,
+    bands: Vec<Band>,
+    budget: u64,
+    requests: u64,
+}
+
+impl<'a> Seeker<'a> {
+    /// Opens the input of `options` to read its size, without decoding it.
+    pub fn open(options: &'a Options) -> Result<Seeker<'a>, Box<dyn std::error::Error>> {
+        let (_, info) = source::open(options)?;
+        let format = pipeline::select_frame_format(options, info.format)?;
+        let reduction = Reduction::new(options.scale, options.frame_step);
+        let (width, height) = reduction.dimensions(info.width, info.height);
+        Ok(Seeker {
+            options,
+            info,
+            format,
+            reduction,
+            width,
+            height,
+            times: None,
+            bands: Vec::new(),
+            budget: options.max_memory.unwrap_or(DEFAULT_CACHE_BYTES),
+            requests: 0,
+        })
+    }
+
+    /// Number of output frames: one per source column.
+    pub fn frame_count(&self) -> usize {
+        self.width
+    }
+
+    /// Height of the output frames. Their width is the number of stored
+    /// source frames, known once the first frame has been computed.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn format(&self) -> FrameFormat {
+        self.format
+    }
+
+    /// Timestamps of the stored source frames, the columns of every output
+    /// frame, once the first frame has been computed.
+    pub fn times(&self) -> Option<&[Option<f64>]> {
+        self.times.as_deref()
+    }
+
+    /// Output frame `x`: column `x` of every stored source frame, packed
+    /// `frames × height` pixels in [`format`](Seeker::format), with source
+    /// frame `t` in column `t`.
+    pub fn get_output_frame(&mut self, x: usize) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        if x >= self.width {
+            return Err(format!(
+                "No output frame {}: the input has {} columns",
+                x, self.width
+            )
+            .into());
+        }
+        self.requests += 1;
+        let index = match self.bands.iter().position(|band| band.contains(x)) {
+            Some(index) => index,
+            None => {
+                let band = self.decode_band(x)?;
+                self.insert(band)
+            }
+        };
+        let band = &mut self.bands[index];
+        band.used = self.requests;
+        Ok(transpose::gather_column(
+            &band.frames,
+            self.format,
+            band.width,
+            self.height,
+            x - band.start,
+            band.frames.len(),
+        ))
+    }
+
+    /// Decodes the input, keeping a band of columns around `x`.
+    fn decode_band(&mut self, x: usize) -> Result<Band, Box<dyn std::error::Error>> {
+        let (info, format, reduction) = (&self.info, self.format, self.reduction);
+        let (width, height) = (self.width, self.height);
+        let bpp = format.bytes_per_pixel();
+        let budget = self.budget;
+        // As many columns as the band's share of the cache holds, a guess
+        // until the input has been decoded once
+        let counted = self.times.as_ref().map(|times| times.len() as u64);
+        let frames = counted.unwrap_or_else(|| {
+            info.estimated_frames
+                .map_or(u64::MAX, |frames| reduction.frames(frames))
+        });
+        let column_bytes = ((height * bpp) as u64).saturating_mul(frames.max(1));
+        let columns = ((budget / BANDS / column_bytes) as usize).clamp(1, width);
+        let too_large = |column_bytes: u64| -> Box<dyn std::error::Error> {
+            format!(
+                "A column of the input takes {}, more than the {} decode cache",
+                format_bytes(column_bytes),
+                format_bytes(budget)
+            )
+            .into()
+        };
+        if counted.is_some() && column_bytes > budget {
+            return Err(too_large(column_bytes));
+        }
+        let share = budget / BANDS;
+        let frame_bytes = (columns * height * bpp) as u64;
+        let crop = Crop {
+            left: x.saturating_sub(columns / 2).min(width - columns),
+            top: 0,
+            width: columns,
+            height,
+        };
+
+        let (mut input, _) = source::open(self.options)?;
+        let flags = self.options.scaler.flags();
+        let mut scaler = Context::get(
+            info.format,
+            info.width as u32,
+            info.height as u32,
+            format.pixel(),
+            width as u32,
+            height as u32,
+            flags,
+        )?;
+        color_range::set_ranges(&mut scaler, info.full_range, false);
+        let on_size_change = self.options.on_size_change;
+        let mut decoded = 0u64;
+        let mut frames = Vec::new();
+        let mut times = Vec::new();
+        // Set once the band outgrows its share; the rest is only counted
+        let mut overflowed = false;
+        input.decode_all(&mut |frame, time| {
+            let index = decoded;
+            decoded += 1;
+            if !reduction.keeps(index) {
+                return Ok(());
+            }
+            times.push(time);
+            if overflowed {
+                return Ok(());
+            }
+            let stored = frame_bytes * times.len() as u64;
+            if stored > share && columns > 1 {
+                overflowed = true;
+                frames = Vec::new();
+                return Ok(());
+            }
+            if stored > budget {
+                return Err(too_large(stored));
+            }
+            let expected = scaler.input();
+            if (frame.width(), frame.height(), frame.format())
+                != (expected.width, expected.height, expected.format)
+            {
+                if on_size_change == SizeChange::Abort {
+                    return Err(format!(
+                        "Input changes size mid-stream at frame {}. Pass --on-size-change scale \
+                         to rescale such frames to the first size.",
+                        times.len() - 1
+                    )
+                    .into());
+                }
+                scaler.cached(
+                    frame.format(),
+                    frame.width(),
+                    frame.height(),
+                    format.pixel(),
+                    width as u32,
+                    height as u32,
+                    flags,
+                );
+                let full_range = color_range::is_full(frame.format(), frame.color_range());
+                color_range::set_ranges(&mut scaler, full_range, false);
+            }
+            let mut scaled = Video::empty();
+            scaler.run(frame, &mut scaled)?;
+            let data = match format {
+                FrameFormat::RgbF32 => pipeline::interleave_float_planes(&scaled, width),
+                _ => pipeline::copy_plane(&scaled, 0, width * bpp),
+            };
+            frames.push(crop.apply(&data, format, width));
+            Ok(())
+        })?;
+        if times.is_empty() {
+            return Err("No frames decoded".into());
+        }
+        self.times = Some(times);
+        if overflowed {
+            // The frame count is known now, so the band fits this time
+            if counted.is_some() {
+                return Err("The input decoded to more frames than before".into());
+            }
+            return self.decode_band(x);
+        }
+        Ok(Band {
+            start: crop.left,
+            width: columns,
+            frames,
+            used: self.requests,
+        })
+    }
+
+    /// Adds `band` to the cache, first dropping the least recently used
+    /// bands until it fits. Returns its place in the cache.
+    fn insert(&mut self, band: Band) -> usize {
+        let mut total = self.bands.iter().map(Band::bytes).sum::<u64>() + band.bytes();
+        while total > self.budget {
+            let Some((oldest, _)) = self
+                .bands
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, band)| band.used)
+            else {
+                break;
+            };
+            total -= self.bands.swap_remove(oldest).bytes();
+        }
+        self.bands.push(band);
+        self.bands.len() - 1
+    }
+}
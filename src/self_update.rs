@@ -0,0 +1,199 @@
+//! `video-transpose self-update`: replaces a prebuilt binary with the one
+//! of the latest GitHub release, for users without a package manager.
+//! Releases carry a binary per platform, named
+//! `video-transpose-<os>-<arch>` (`.exe` on Windows, e.g.
+//! `video-transpose-linux-x86_64`), and a `SHA256SUMS` file in sha256sum's
+//! format. The download is checked against its line there before it takes
+//! the running binary's place; `--check` only reports whether there's a
+//! newer release.
+
+use crate::cli::SelfUpdateOptions;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::cmp::Ordering;
+use std::env;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Where releases are published, unless `--repo` says otherwise.
+pub const DEFAULT_REPOSITORY: &str = "evgenykuzyakov/video-transpose";
+/// Release asset with the checksums of the others.
+const CHECKSUMS: &str = "SHA256SUMS";
+/// Largest download accepted, far above any real binary.
+const MAX_DOWNLOAD: u64 = 512 << 20;
+
+pub fn run(options: &SelfUpdateOptions) -> Result<(), Box<dyn std::error::Error>> {
+    let current = env!("CARGO_PKG_VERSION");
+    let agent = ureq::AgentBuilder::new()
+        .timeout(Duration::from_secs(60))
+        .user_agent(&format!("video-transpose/{}", current))
+        .build();
+    let url = format!(
+        "https://api.github.com/repos/{}/releases/latest",
+        options.repo
+    );
+    let body = agent
+        .get(&url)
+        .set("Accept", "application/vnd.github+json")
+        .call()
+        .map_err(|e| format!("Cannot check {} for releases: {}", options.repo, e))?
+        .into_string()?;
+    let release: Value = serde_json::from_str(&body)?;
+    let tag = release["tag_name"]
+        .as_str()
+        .ok_or("The latest release has no tag")?;
+    let latest = tag.trim_start_matches('v');
+    println!("Installed: {}, latest release: {}", current, tag);
+    if compare_versions(latest, current) != Ordering::Greater {
+        println!("video-transpose is up to date.");
+        return Ok(());
+    }
+    if options.check {
+        println!("Run video-transpose self-update to install {}.", tag);
+        return Ok(());
+    }
+
+    let name = asset_name();
+    let asset_url = |wanted: &str| -> Result<String, String> {
+        release["assets"]
+            .as_array()
+            .and_then(|assets| {
+                assets
+                    .iter()
+                    .find(|asset| asset["name"].as_str() == Some(wanted))
+            })
+            .and_then(|asset| asset["browser_download_url"].as_str())
+            .map(str::to_string)
+            .ok_or_else(|| {
+                format!(
+                    "Release {} has no {} (this platform may not have prebuilt binaries)",
+                    tag, wanted
+                )
+            })
+    };
+    let checksums = download(&agent, &asset_url(CHECKSUMS)?)?;
+    let expected = expected_digest(&String::from_utf8_lossy(&checksums), &name)
+        .ok_or_else(|| format!("{} of release {} doesn't list {}", CHECKSUMS, tag, name))?;
+    println!("Downloading {}...", name);
+    let binary = download(&agent, &asset_url(&name)?)?;
+    let actual: String = Sha256::digest(&binary)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    if actual != expected {
+        return Err(format!(
+            "Checksum mismatch for {}: expected sha256:{}, got sha256:{}. Nothing was replaced.",
+            name, expected, actual
+        )
+        .into());
+    }
+    println!("  Checksum OK (sha256:{})", actual);
+    let path = replace_binary(&binary)?;
+    println!("Updated {} to {}.", path.display(), tag);
+    Ok(())
+}
+
+/// The release asset built for this platform.
+fn asset_name() -> String {
+    format!(
+        "video-transpose-{}-{}{}",
+        env::consts::OS,
+        env::consts::ARCH,
+        env::consts::EXE_SUFFIX
+    )
+}
+
+/// Compares dotted version numbers, e.g. `0.10.1` > `0.9`.
+fn compare_versions(a: &str, b: &str) -> Ordering {
+    let numbers = |version: &str| -> Vec<u64> {
+        version
+            .split(['.', '-', '+'])
+            .map_while(|part| part.parse().ok())
+            .collect()
+    };
+    numbers(a).cmp(&numbers(b))
+}
+
+/// The digest `checksums` (in sha256sum's `<hex>  <name>` format) lists
+/// for `name`.
+fn expected_digest(checksums: &str, name: &str) -> Option<String> {
+    checksums.lines().find_map(|line| {
+        let (digest, file) = line.trim().split_once(char::is_whitespace)?;
+        // A `*` marks files hashed in binary mode
+        let file = file.trim_start().trim_start_matches('*');
+        (file == name && digest.len() == 64).then(|| digest.to_ascii_lowercase())
+    })
+}
+
+fn download(agent: &ureq::Agent, url: &str) -> Result<Vec<u8>, String> {
+    let response = agent
+        .get(url)
+        .call()
+        .map_err(|e| format!("Download of {} failed: {}", url, e))?;
+    let mut body = Vec::new();
+    response
+        .into_reader()
+        .take(MAX_DOWNLOAD)
+        .read_to_end(&mut body)
+        .map_err(|e| format!("Download of {} failed: {}", url, e))?;
+    Ok(body)
+}
+
+/// Puts `binary` in place of the running executable. The new file is
+/// written next to it as `.new` and then swapped in, so an interrupted
+/// update leaves the old binary working.
+fn replace_binary(binary: &[u8]) -> Result<PathBuf, String> {
+    let exe = env::current_exe()
+        .and_then(fs::canonicalize)
+        .map_err(|e| format!("Cannot find the running binary: {}", e))?;
+    let staged = sibling(&exe, ".new");
+    let cannot = |action: &str, path: &Path, e: std::io::Error| {
+        format!(
+            "Cannot {} {}: {} (update with the permissions of its directory, or through the \
+             package manager that installed it)",
+            action,
+            path.display(),
+            e
+        )
+    };
+    fs::write(&staged, binary).map_err(|e| cannot("write", &staged, e))?;
+    let permissions = fs::metadata(&exe)
+        .map_err(|e| cannot("read", &exe, e))?
+        .permissions();
+    let swapped = fs::set_permissions(&staged, permissions).and_then(|()| swap_in(&staged, &exe));
+    if let Err(e) = swapped {
+        let _ = fs::remove_file(&staged);
+        return Err(cannot("replace", &exe, e));
+    }
+    Ok(exe)
+}
+
+/// `path` with `suffix` appended to its file name.
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// Renames `staged` over `exe`: on Unix the rename replaces the running
+/// binary in one step, and the running process keeps the old file open.
+#[cfg(not(windows))]
+fn swap_in(staged: &Path, exe: &Path) -> std::io::Result<()> {
+    fs::rename(staged, exe)
+}
+
+/// Moves `exe` aside as `.old` and renames `staged` in its place. Windows
+/// can't delete or replace a running executable, only rename it, so the
+/// old one stays behind until the next update removes it; if the second
+/// rename fails, the old binary is moved back.
+#[cfg(windows)]
+fn swap_in(staged: &Path, exe: &Path) -> std::io::Result<()> {
+    let old = sibling(exe, ".old");
+    let _ = fs::remove_file(&old);
+    fs::rename(exe, &old)?;
+    fs::rename(staged, exe).inspect_err(|_| {
+        let _ = fs::rename(&old, exe);
+    })
+}
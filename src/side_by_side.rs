@@ -0,0 +1,156 @@
+//! `--with-original left|top`: the source plays next to the transposed
+//! frames, for presentations and for checking which moment a streak comes
+//! from. The two run at different frame counts, one output frame per
+//! source column against one source frame per frame of input, so the
+//! original is retimed to span the same length as the transpose: output
+//! frame `k` of `n` shows source frame `k × frames / n`, dropping source
+//! frames when there are more of them and repeating them when there are
+//! fewer. The original is scaled to the height of the transposed frames
+//! (`left`) or their width (`top`).
+
+use crate::frame_store::FrameFormat;
+use crate::scaler::Scaler;
+use ffmpeg_next as ffmpeg;
+use ffmpeg_next::software::scaling::context::Context;
+use ffmpeg_next::util::frame::video::Video;
+
+/// Where the original goes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Layout {
+    Left,
+    Top,
+}
+
+impl Layout {
+    pub fn parse(text: &str) -> Result<Layout, String> {
+        match text {
+            "left" => Ok(Layout::Left),
+            "top" => Ok(Layout::Top),
+            _ => Err(format!(
+                "Invalid --with-original: {} (expected left or top)",
+                text
+            )),
+        }
+    }
+
+    /// Where the original goes, for messages.
+    pub fn name(self) -> &'static str {
+        match self {
+            Layout::Left => "left of",
+            Layout::Top => "above",
+        }
+    }
+
+    /// Size of an `original` frame scaled to sit beside frames of `frame`,
+    /// even for 4:2:0 encoding.
+    fn scaled(self, frame: (usize, usize), original: (usize, usize)) -> (usize, usize) {
+        let even = |size: f64| ((size / 2.0).round() as usize * 2).max(2);
+        match self {
+            Layout::Left => (
+                even(original.0 as f64 * frame.1 as f64 / original.1 as f64),
+                frame.1,
+            ),
+            Layout::Top => (
+                frame.0,
+                even(original.1 as f64 * frame.0 as f64 / original.0 as f64),
+            ),
+        }
+    }
+
+    /// Size of the output: frames of `frame` with the `original` beside.
+    pub fn size(self, frame: (usize, usize), original: (usize, usize)) -> (usize, usize) {
+        let scaled = self.scaled(frame, original);
+        match self {
+            Layout::Left => (scaled.0 + frame.0, frame.1),
+            Layout::Top => (frame.0, scaled.1 + frame.1),
+        }
+    }
+}
+
+/// Source frame shown with output frame `index` of `count`, of `frames`.
+pub fn retime(index: usize, count: usize, frames: usize) -> usize {
+    (index * frames / count.max(1)).min(frames.saturating_sub(1))
+}
+
+/// Scales source frames and places them next to transposed ones.
+pub struct SideBySide {
+    layout: Layout,
+    context: Context,
+    format: FrameFormat,
+    original: (usize, usize),
+    scaled: (usize, usize),
+    frame: (usize, usize),
+}
+
+impl SideBySide {
+    /// Prepares placing `original` source frames beside transposed frames
+    /// of `frame`, all in `format`.
+    pub fn new(
+        layout: Layout,
+        original: (usize, usize),
+        frame: (usize, usize),
+        format: FrameFormat,
+        scaler: Scaler,
+    ) -> Result<SideBySide, ffmpeg::Error> {
+        let scaled = layout.scaled(frame, original);
+        let context = Context::get(
+            format.pixel(),
+            original.0 as u32,
+            original.1 as u32,
+            format.pixel(),
+            scaled.0 as u32,
+            scaled.1 as u32,
+            scaler.flags(),
+        )?;
+        Ok(SideBySide {
+            layout,
+            context,
+            format,
+            original,
+            scaled,
+            frame,
+        })
+    }
+
+    /// Width and height of the combined frames.
+    pub fn size(&self) -> (usize, usize) {
+        self.layout.size(self.frame, self.original)
+    }
+
+    /// The packed source frame `original` beside the packed transposed
+    /// `frame`.
+    pub fn compose(&mut self, original: &[u8], frame: &[u8]) -> Result<Vec<u8>, ffmpeg::Error> {
+        let bpp = self.format.bytes_per_pixel();
+        let input = crate::encode::packed_frame(
+            original,
+            self.format.pixel(),
+            bpp,
+            self.original.0,
+            self.original.1,
+        );
+        let mut scaled = Video::empty();
+        self.context.run(&input, &mut scaled)?;
+        let stride = scaled.stride(0);
+        let plane = scaled.data(0);
+        let scaled_row = |y: usize| &plane[y * stride..y * stride + self.scaled.0 * bpp];
+        let frame_row = |y: usize| &frame[y * self.frame.0 * bpp..(y + 1) * self.frame.0 * bpp];
+
+        let (width, height) = self.size();
+        let mut combined = Vec::with_capacity(width * height * bpp);
+        match self.layout {
+            Layout::Left => {
+                for y in 0..height {
+                    combined.extend_from_slice(scaled_row(y));
+                    combined.extend_from_slice(frame_row(y));
+                }
+            }
+            Layout::Top => {
+                for y in 0..self.scaled.1 {
+                    combined.extend_from_slice(scaled_row(y));
+                }
+                combined.extend_from_slice(frame);
+            }
+        }
+        Ok(combined)
+    }
+}
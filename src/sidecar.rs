@@ -0,0 +1,213 @@
+use crate::autocrop::Crop;
+use serde_json::{json, Value};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Everything needed to map the output back to the source: geometry, run
+/// parameters and the source column behind every output frame.
+pub struct Sidecar<'a> {
+    pub input: &'a str,
+    pub output: &'a str,
+    pub source_width: usize,
+    pub source_height: usize,
+    /// Part of the decoded frames transposed, after `--autocrop`.
+    pub crop: Crop,
+    pub fps: (i32, i32),
+    /// `--scale` applied to the decoded frames.
+    pub scale: f64,
+    /// `--frame-step`: source frames per decoded frame kept.
+    pub frame_step: usize,
+    /// Output frame index → source column.
+    pub columns: &'a [usize],
+    /// Source frame index → seconds from the stream start.
+    pub times: &'a [Option<f64>],
+    /// Output width after padding for the encoder.
+    pub output_width: usize,
+    /// `--legend` frames before the first transposed frame.
+    pub legend_frames: usize,
+}
+
+impl Sidecar<'_> {
+    /// Writes the sidecar as CSV or JSON depending on the file extension.
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let contents = match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("csv") => self.to_csv(),
+            _ => serde_json::to_string_pretty(&self.to_json())?,
+        };
+        fs::write(path, contents)
+    }
+
+    fn source_frames(&self) -> usize {
+        self.times.len()
+    }
+
+    fn padding(&self) -> usize {
+        self.output_width - self.source_frames()
+    }
+
+    /// Last source frame across the width of every output frame.
+    fn last_source_frame(&self) -> usize {
+        self.source_frames().saturating_sub(1) * self.frame_step
+    }
+
+    /// Earliest and latest source frame time. Frames may lack a timestamp
+    /// or arrive out of order, so it isn't simply the first and last.
+    fn time_span(&self) -> (Option<f64>, Option<f64>) {
+        let times = self.times.iter().flatten().copied();
+        (times.clone().reduce(f64::min), times.reduce(f64::max))
+    }
+
+    pub fn to_json(&self) -> Value {
+        let (first_time, last_time) = self.time_span();
+        let frames: Vec<Value> = self
+            .columns
+            .iter()
+            .enumerate()
+            .map(|(frame, &column)| {
+                json!({
+                    "output_frame": frame + self.legend_frames,
+                    "source_column": column,
+                    "first_source_frame": 0,
+                    "last_source_frame": self.last_source_frame(),
+                    "first_time": first_time,
+                    "last_time": last_time,
+                })
+            })
+            .collect();
+
+        json!({
+            "tool": concat!("video-transpose ", env!("CARGO_PKG_VERSION")),
+            "input": self.input,
+            "output": self.output,
+            "axis_mapping": {
+                "output_frame": "source_x",
+                "output_x": "source_t",
+                "output_y": "source_y",
+            },
+            "source": {
+                "width": self.source_width,
+                "height": self.source_height,
+                "frames": self.source_frames(),
+                "fps": [self.fps.0, self.fps.1],
+                "first_time": first_time,
+                "last_time": last_time,
+            },
+            "output": {
+                "width": self.output_width,
+                "height": self.crop.height,
+                "frames": self.columns.len(),
+                "legend_frames": self.legend_frames,
+            },
+            "crop": {
+                "x": self.crop.left,
+                "y": self.crop.top,
+                "width": self.crop.width,
+                "height": self.crop.height,
+            },
+            "frame_step": self.frame_step,
+            "scale": self.scale,
+            "padding": {
+                "columns": self.padding(),
+                "mode": "duplicate_last",
+            },
+            "frames": frames,
+            "source_times": self.times,
+        })
+    }
+
+    pub fn to_csv(&self) -> String {
+        let (first_time, last_time) = self.time_span();
+        let time = |t: Option<f64>| t.map(|t| format!("{:.6}", t)).unwrap_or_default();
+
+        let mut csv = String::new();
+        csv.push_str(&format!("# input={}\n", self.input));
+        csv.push_str(&format!("# output={}\n", self.output));
+        csv.push_str("# axis_mapping=output_frame:source_x,output_x:source_t,output_y:source_y\n");
+        csv.push_str(&format!(
+            "# source={}x{}x{} fps={}/{}\n",
+            self.source_width,
+            self.source_height,
+            self.source_frames(),
+            self.fps.0,
+            self.fps.1
+        ));
+        csv.push_str(&format!(
+            "# crop={},{},{},{} frame_step={} scale={} padding={} legend_frames={}\n",
+            self.crop.left,
+            self.crop.top,
+            self.crop.width,
+            self.crop.height,
+            self.frame_step,
+            self.scale,
+            self.padding(),
+            self.legend_frames
+        ));
+        csv.push_str(
+            "output_frame,source_column,first_source_frame,last_source_frame,first_time,last_time\n",
+        );
+        for (frame, &column) in self.columns.iter().enumerate() {
+            csv.push_str(&format!(
+                "{},{},{},{},{},{}\n",
+                frame + self.legend_frames,
+                column,
+                0,
+                self.last_source_frame(),
+                time(first_time),
+                time(last_time)
+            ));
+        }
+        csv
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sidecar(times: &[Option<f64>]) -> Sidecar<'_> {
+        Sidecar {
+            input: "in.mp4",
+            output: "out.mp4",
+            source_width: 4,
+            source_height: 2,
+            crop: Crop {
+                left: 0,
+                top: 0,
+                width: 4,
+                height: 2,
+            },
+            fps: (25, 1),
+            scale: 1.0,
+            frame_step: 2,
+            columns: &[0, 1, 2, 3],
+            times,
+            output_width: times.len() + times.len() % 2,
+            legend_frames: 0,
+        }
+    }
+
+    #[test]
+    fn time_span_skips_missing_and_reordered_times() {
+        let times = [None, Some(0.08), Some(0.0), Some(0.16), Some(0.04), None];
+        assert_eq!(sidecar(&times).time_span(), (Some(0.0), Some(0.16)));
+        assert_eq!(sidecar(&[None, None]).time_span(), (None, None));
+    }
+
+    #[test]
+    fn every_output_frame_lists_its_source_span() {
+        let times = [Some(0.08), Some(0.0), Some(0.16)];
+        let json = sidecar(&times).to_json();
+        let frames = json["frames"].as_array().unwrap();
+        assert_eq!(frames.len(), 4);
+        for (frame, entry) in frames.iter().enumerate() {
+            assert_eq!(entry["source_column"], frame);
+            assert_eq!(entry["first_source_frame"], 0);
+            assert_eq!(entry["last_source_frame"], 4);
+            assert_eq!(entry["first_time"], 0.0);
+            assert_eq!(entry["last_time"], 0.16);
+        }
+        let csv = sidecar(&times).to_csv();
+        assert!(csv.ends_with("3,3,0,4,0.000000,0.160000\n"), "{}", csv);
+    }
+}
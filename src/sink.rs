@@ -0,0 +1,117 @@
+//! Consumers of the transposed output frames. Encoded files, EXR
+//! sequences, raw frames on stdout (`-o -`), `--null-sink` and library
+//! callbacks are all [`FrameSink`]s.
+
+use crate::frame_store::FrameFormat;
+use crate::stats::QueueStats;
+use std::io::{self, BufWriter, Stdout, Write};
+
+/// One transposed output frame: packed samples of `width × height` pixels
+/// in `format`, row by row without padding.
+pub struct OutputFrame<'a> {
+    pub width: usize,
+    pub height: usize,
+    pub format: FrameFormat,
+    pub data: &'a [u8],
+    /// Source column and capture time span, as embedded by `--embed-mapping`.
+    pub mapping: &'a str,
+    /// A53 closed captions carried over with `--keep-cc`.
+    pub captions: Option<&'a [u8]>,
+    /// `--sonify` samples played while the frame is shown.
+    pub audio: Option<&'a [f32]>,
+}
+
+/// Receives every output frame in order when frames are streamed to the
+/// caller instead of encoded.
+pub type FrameCallback<'a> = dyn FnMut(&OutputFrame) -> Result<(), Box<dyn std::error::Error>> + 'a;
+
+/// Receives the output frames in order.
+pub trait FrameSink {
+    fn write(&mut self, frame: &OutputFrame) -> Result<(), Box<dyn std::error::Error>>;
+
+    /// Called once after the last frame, e.g. to flush an encoder.
+    fn finish(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+
+    /// How full the queue feeding an encoder thread got, for sinks that
+    /// have one.
+    fn queue_stats(&self) -> Option<QueueStats> {
+        None
+    }
+}
+
+impl<S: FrameSink + ?Sized> FrameSink for &mut S {
+    fn write(&mut self, frame: &OutputFrame) -> Result<(), Box<dyn std::error::Error>> {
+        (**self).write(frame)
+    }
+
+    fn finish(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        (**self).finish()
+    }
+
+    fn queue_stats(&self) -> Option<QueueStats> {
+        (**self).queue_stats()
+    }
+}
+
+/// Writes the raw frames to stdout for `-o -`, to be piped into a player or
+/// another tool.
+pub struct PipeSink {
+    out: BufWriter<Stdout>,
+    announced: bool,
+}
+
+impl PipeSink {
+    pub fn new() -> PipeSink {
+        PipeSink {
+            out: BufWriter::new(io::stdout()),
+            announced: false,
+        }
+    }
+}
+
+impl Default for PipeSink {
+    fn default() -> Self {
+        PipeSink::new()
+    }
+}
+
+impl FrameSink for PipeSink {
+    fn write(&mut self, frame: &OutputFrame) -> Result<(), Box<dyn std::error::Error>> {
+        if !self.announced {
+            eprintln!(
+                "  Writing raw {} frames of {}×{} to stdout",
+                frame.format.name(),
+                frame.width,
+                frame.height
+            );
+            self.announced = true;
+        }
+        self.out.write_all(frame.data)?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+/// Discards the frames, for `--null-sink` benchmarks of decode and transpose.
+pub struct NullSink;
+
+impl FrameSink for NullSink {
+    fn write(&mut self, _frame: &OutputFrame) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+}
+
+/// Hands the frames to a library caller's callback.
+pub struct CallbackSink<'a, 'c>(pub &'a mut FrameCallback<'c>);
+
+impl FrameSink for CallbackSink<'_, '_> {
+    fn write(&mut self, frame: &OutputFrame) -> Result<(), Box<dyn std::error::Error>> {
+        (self.0)(frame)
+    }
+}
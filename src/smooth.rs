@@ -0,0 +1,75 @@
+use crate::linear_light::LinearLight;
+
+/// Filter applied along the time axis of each pixel.
+#[derive(Clone, Copy, PartialEq)]
+pub enum SmoothFilter {
+    Mean,
+    Median,
+}
+
+impl SmoothFilter {
+    pub fn parse(text: &str) -> Result<SmoothFilter, String> {
+        match text {
+            "mean" => Ok(SmoothFilter::Mean),
+            "median" => Ok(SmoothFilter::Median),
+            _ => Err(format!(
+                "Invalid smoothing filter: {} (expected mean or median)",
+                text
+            )),
+        }
+    }
+}
+
+/// Bytes of each frame filtered together, bounding the scratch buffer to
+/// `frames × BLOCK` bytes regardless of resolution.
+const BLOCK: usize = 4096;
+
+/// Smooths every pixel channel over a window of `2 * radius + 1` frames,
+/// in place. The window is clamped at the start and end of the clip.
+///
+/// Sensor noise turns into horizontal streaks once time becomes the X axis,
+/// and filtering here is much cheaper than denoising the transposed output.
+/// With `light`, means are taken of the linear light the samples encode.
+pub fn temporal_smooth(
+    frames: &mut [Vec<u8>],
+    radius: usize,
+    filter: SmoothFilter,
+    light: Option<&LinearLight>,
+) {
+    let count = frames.len();
+    if radius == 0 || count < 2 {
+        return;
+    }
+    let frame_len = frames[0].len();
+    let mut block = vec![0u8; count * BLOCK];
+    let mut window = Vec::with_capacity(2 * radius + 1);
+
+    for start in (0..frame_len).step_by(BLOCK) {
+        let len = BLOCK.min(frame_len - start);
+
+        // Copy the block of every frame so filtering reads unmodified values
+        for (t, frame) in frames.iter().enumerate() {
+            block[t * BLOCK..t * BLOCK + len].copy_from_slice(&frame[start..start + len]);
+        }
+
+        for (t, frame) in frames.iter_mut().enumerate() {
+            let first = t.saturating_sub(radius);
+            let last = (t + radius).min(count - 1);
+            for i in 0..len {
+                window.clear();
+                window.extend((first..=last).map(|u| block[u * BLOCK + i]));
+                frame[start + i] = match (filter, light) {
+                    (SmoothFilter::Mean, Some(light)) => light.mean(&window),
+                    (SmoothFilter::Mean, None) => {
+                        let sum: u32 = window.iter().map(|&v| v as u32).sum();
+                        ((sum + window.len() as u32 / 2) / window.len() as u32) as u8
+                    }
+                    (SmoothFilter::Median, _) => {
+                        let middle = window.len() / 2;
+                        *window.select_nth_unstable(middle).1
+                    }
+                };
+            }
+        }
+    }
+}
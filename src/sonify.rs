@@ -0,0 +1,304 @@
+//! `--sonify wave:<y>|synth:<y>` (experimental): an audio track made from
+//! the pictures, muxed into each video output. Row `y` of a transposed
+//! frame is one source pixel over the whole input, so every output frame
+//! plays that pixel's history while it's shown:
+//!
+//! - `wave` loops the row as a single-cycle waveform at a fixed pitch, its
+//!   timbre changing as the sweep moves through the columns;
+//! - `synth` reads the row as a spectrum, with the start of the input at
+//!   the lowest of a bank of sine oscillators and the end at the highest,
+//!   each as loud as its stretch of the row is bright.
+//!
+//! Consecutive frames crossfade into each other over the length of a frame.
+//! The track is mono AAC at 48 kHz, exactly as long as the video.
+
+use crate::frame_store::FrameFormat;
+use crate::muxer::Muxer;
+use ffmpeg_next as ffmpeg;
+use ffmpeg_next::format::sample::{Sample, Type};
+use ffmpeg_next::util::frame::audio::Audio;
+use ffmpeg_next::{ChannelLayout, Rational};
+use std::f32::consts::TAU;
+
+/// Samples per second of the track.
+pub const SAMPLE_RATE: i32 = 48000;
+/// AAC bitrate, plenty for one channel.
+const BIT_RATE: usize = 128_000;
+/// Pitch `wave` plays the row at, in Hz.
+const WAVE_PITCH: f32 = 110.0;
+/// Most oscillators `synth` uses; longer rows are averaged down.
+const BANDS: usize = 64;
+/// Lowest and highest `synth` frequencies, in Hz: seven octaves from A1.
+const LOWEST: f32 = 55.0;
+const HIGHEST: f32 = 7040.0;
+
+/// How a row is turned into sound.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mode {
+    Wave,
+    Synth,
+}
+
+/// `--sonify`: the mode and the row of the output frames it plays.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sonify {
+    pub mode: Mode,
+    pub row: usize,
+}
+
+impl Sonify {
+    /// Parses `wave:<y>` or `synth:<y>`.
+    pub fn parse(text: &str) -> Result<Sonify, String> {
+        let invalid = || {
+            format!(
+                "Invalid --sonify: {} (expected wave:<row> or synth:<row>)",
+                text
+            )
+        };
+        let (mode, row) = text.split_once(':').ok_or_else(invalid)?;
+        let mode = match mode {
+            "wave" => Mode::Wave,
+            "synth" => Mode::Synth,
+            _ => return Err(invalid()),
+        };
+        let row = row.parse().map_err(|_| invalid())?;
+        Ok(Sonify { mode, row })
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self.mode {
+            Mode::Wave => "wave",
+            Mode::Synth => "synth",
+        }
+    }
+}
+
+/// Makes the samples of each output frame, in order.
+pub struct Sonifier {
+    sonify: Sonify,
+    fps: Rational,
+    /// Output frames rendered so far.
+    frames: i64,
+    /// What the last frame played: the waveform for `wave`, the band
+    /// levels for `synth`.
+    previous: Vec<f32>,
+    /// Phase of the wave, or of each oscillator, in cycles.
+    phases: Vec<f32>,
+}
+
+impl Sonifier {
+    pub fn new(sonify: Sonify, fps: Rational) -> Sonifier {
+        Sonifier {
+            sonify,
+            fps,
+            frames: 0,
+            previous: Vec::new(),
+            phases: Vec::new(),
+        }
+    }
+
+    /// Row `--sonify` plays of a packed frame of `format`, `width` pixels
+    /// wide, as intensities from 0 to 1: its first `length` pixels, leaving
+    /// out padding.
+    pub fn row(&self, frame: &[u8], format: FrameFormat, width: usize, length: usize) -> Vec<f32> {
+        let bpp = format.bytes_per_pixel();
+        let start = self.sonify.row * width * bpp;
+        format
+            .luma(&frame[start..start + length.min(width) * bpp])
+            .iter()
+            .map(|&luma| f32::from(luma) / 255.0)
+            .collect()
+    }
+
+    /// Samples of the next output frame, playing `row`. An empty row is
+    /// silence, e.g. for legend cards.
+    pub fn render(&mut self, row: &[f32]) -> Vec<f32> {
+        // Frame n covers samples n × rate / fps up to the next frame's
+        let rate = i64::from(SAMPLE_RATE) * i64::from(self.fps.denominator());
+        let fps = i64::from(self.fps.numerator()).max(1);
+        let start = self.frames * rate / fps;
+        self.frames += 1;
+        let count = (self.frames * rate / fps - start) as usize;
+
+        let current = match self.sonify.mode {
+            Mode::Wave => waveform(row),
+            Mode::Synth => bands(row),
+        };
+        let previous = std::mem::take(&mut self.previous);
+        let size = current.len().max(previous.len());
+        if self.phases.is_empty() {
+            self.phases = match self.sonify.mode {
+                Mode::Wave => vec![0.0],
+                // Spread out so the oscillators don't all peak together
+                Mode::Synth => (0..BANDS)
+                    .map(|band| (band as f32 * 0.618).fract())
+                    .collect(),
+            };
+        }
+        let mut samples = Vec::with_capacity(count);
+        for i in 0..count {
+            let fade = (i as f32 + 0.5) / count as f32;
+            let sample = match self.sonify.mode {
+                Mode::Wave => {
+                    let phase = &mut self.phases[0];
+                    let value =
+                        crossfade(lookup(&previous, *phase), lookup(&current, *phase), fade);
+                    *phase = (*phase + WAVE_PITCH / SAMPLE_RATE as f32).fract();
+                    value
+                }
+                Mode::Synth => {
+                    let mut sum = 0.0;
+                    for band in 0..size {
+                        let level = crossfade(
+                            previous.get(band).copied().unwrap_or(0.0),
+                            current.get(band).copied().unwrap_or(0.0),
+                            fade,
+                        );
+                        let phase = &mut self.phases[band];
+                        sum += level * (*phase * TAU).sin();
+                        *phase = (*phase + frequency(band, size) / SAMPLE_RATE as f32).fract();
+                    }
+                    // A soft clip keeps loud, bright rows from wrapping around
+                    (sum / (size.max(1) as f32).sqrt()).tanh()
+                }
+            };
+            samples.push(sample);
+        }
+        self.previous = current;
+        samples
+    }
+}
+
+/// `row` as one cycle of a waveform: without its mean, at full scale.
+fn waveform(row: &[f32]) -> Vec<f32> {
+    let mean = row.iter().sum::<f32>() / row.len().max(1) as f32;
+    let peak = row.iter().map(|&v| (v - mean).abs()).fold(0.0, f32::max);
+    if peak < 1e-3 {
+        // A flat row is silence
+        return Vec::new();
+    }
+    row.iter().map(|&v| (v - mean) / peak).collect()
+}
+
+/// `row` averaged down to at most [`BANDS`] levels.
+fn bands(row: &[f32]) -> Vec<f32> {
+    let count = row.len().min(BANDS);
+    (0..count)
+        .map(|band| {
+            let share = &row[band * row.len() / count..(band + 1) * row.len() / count];
+            share.iter().sum::<f32>() / share.len().max(1) as f32
+        })
+        .collect()
+}
+
+/// Frequency of oscillator `band` of `count`, spaced evenly in pitch.
+fn frequency(band: usize, count: usize) -> f32 {
+    let position = band as f32 / (count.max(2) - 1) as f32;
+    LOWEST * (HIGHEST / LOWEST).powf(position)
+}
+
+/// The waveform `table` at `phase` cycles, interpolated; silence if empty.
+fn lookup(table: &[f32], phase: f32) -> f32 {
+    if table.is_empty() {
+        return 0.0;
+    }
+    let position = phase * table.len() as f32;
+    let index = position as usize % table.len();
+    let next = table[(index + 1) % table.len()];
+    crossfade(table[index], next, position.fract())
+}
+
+fn crossfade(from: f32, to: f32, amount: f32) -> f32 {
+    from + (to - from) * amount
+}
+
+/// The AAC encoder of the track and its stream in a video output.
+pub struct AudioTrack {
+    encoder: ffmpeg::encoder::Audio,
+    stream_index: usize,
+    /// Samples waiting for a full encoder frame.
+    pending: Vec<f32>,
+    /// Samples sent to the encoder, the PTS of the next frame.
+    sent: i64,
+}
+
+impl AudioTrack {
+    /// Adds the track's stream to `muxer`, after the video stream and
+    /// before the header is written.
+    pub fn open(
+        muxer: &mut Muxer,
+        deterministic: bool,
+    ) -> Result<AudioTrack, Box<dyn std::error::Error>> {
+        let codec = ffmpeg::encoder::find(ffmpeg::codec::Id::AAC)
+            .ok_or("AAC encoder not found (needed for --sonify)")?;
+        let mut encoder = ffmpeg::codec::context::Context::new_with_codec(codec)
+            .encoder()
+            .audio()?;
+        encoder.set_rate(SAMPLE_RATE);
+        encoder.set_channel_layout(ChannelLayout::MONO);
+        encoder.set_format(Sample::F32(Type::Planar));
+        encoder.set_bit_rate(BIT_RATE);
+        encoder.set_time_base(Rational(1, SAMPLE_RATE));
+        if muxer.global_header() {
+            encoder.set_flags(ffmpeg::codec::flag::Flags::GLOBAL_HEADER);
+        }
+        let mut encoder_options = ffmpeg::Dictionary::new();
+        if deterministic {
+            encoder_options.set("flags", "+bitexact");
+        }
+        let encoder = encoder.open_as_with(codec, encoder_options)?;
+        let mut stream = muxer.add_stream(codec, Rational(1, SAMPLE_RATE))?;
+        stream.set_parameters(&encoder);
+        stream.set_time_base(Rational(1, SAMPLE_RATE));
+        Ok(AudioTrack {
+            stream_index: stream.index(),
+            encoder,
+            pending: Vec::new(),
+            sent: 0,
+        })
+    }
+
+    /// Encodes `samples`, holding back what doesn't fill an encoder frame.
+    pub fn send(&mut self, muxer: &mut Muxer, samples: &[f32]) -> Result<(), ffmpeg::Error> {
+        self.pending.extend_from_slice(samples);
+        let frame_size = (self.encoder.frame_size() as usize).max(1);
+        while self.pending.len() >= frame_size {
+            let rest = self.pending.split_off(frame_size);
+            let frame = std::mem::replace(&mut self.pending, rest);
+            self.encode(muxer, &frame)?;
+        }
+        Ok(())
+    }
+
+    /// Encodes the last, short frame and flushes the encoder.
+    pub fn finish(&mut self, muxer: &mut Muxer) -> Result<(), ffmpeg::Error> {
+        let rest = std::mem::take(&mut self.pending);
+        if !rest.is_empty() {
+            self.encode(muxer, &rest)?;
+        }
+        self.encoder.send_eof()?;
+        self.write_packets(muxer)
+    }
+
+    fn encode(&mut self, muxer: &mut Muxer, samples: &[f32]) -> Result<(), ffmpeg::Error> {
+        let mut frame = Audio::new(
+            Sample::F32(Type::Planar),
+            samples.len(),
+            ChannelLayout::MONO,
+        );
+        frame.set_rate(SAMPLE_RATE as u32);
+        frame.plane_mut::<f32>(0).copy_from_slice(samples);
+        frame.set_pts(Some(self.sent));
+        self.sent += samples.len() as i64;
+        self.encoder.send_frame(&frame)?;
+        self.write_packets(muxer)
+    }
+
+    fn write_packets(&mut self, muxer: &mut Muxer) -> Result<(), ffmpeg::Error> {
+        let mut packet = ffmpeg::Packet::empty();
+        while self.encoder.receive_packet(&mut packet).is_ok() {
+            muxer.write(&mut packet, self.stream_index)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,401 @@
+use crate::cli::Options;
+use crate::color_range;
+use crate::gop_decode::{self, GopRange};
+use crate::memory::{format_bytes, MemoryGuard};
+use crate::os_path;
+use crate::probe;
+use crate::raw_input::RawReader;
+use crate::read_ahead::{self, ReadAhead};
+use crate::retry::RetryPolicy;
+use crate::timestamps::TimestampNormalizer;
+use ffmpeg_next as ffmpeg;
+use ffmpeg_next::format::Pixel;
+use ffmpeg_next::util::color::TransferCharacteristic;
+use ffmpeg_next::util::frame::video::Video;
+use std::ffi::CString;
+use std::path::{Path, PathBuf};
+use std::ptr;
+
+/// Receives each decoded frame with its timestamp in seconds.
+pub type StoreFrame<'a> =
+    dyn FnMut(&Video, Option<f64>) -> Result<(), Box<dyn std::error::Error>> + 'a;
+
+/// What is known about the input before decoding.
+pub struct InputInfo {
+    pub width: usize,
+    pub height: usize,
+    /// Pixel format of the decoded frames, `yuvj*` formats normalized.
+    pub format: Pixel,
+    /// Whether YUV input uses the full (JPEG) range rather than 16-235.
+    pub full_range: bool,
+    pub fps: ffmpeg::Rational,
+    pub estimated_frames: Option<u64>,
+    /// Start time of the stream in seconds, if it doesn't start at 0.
+    pub start_time: Option<f64>,
+    /// Transfer function the input is tagged with.
+    pub transfer: TransferCharacteristic,
+}
+
+/// What to do when decoded frames stop matching the size and pixel format
+/// the input started with, as adaptive recordings do.
+#[derive(Clone, Copy, PartialEq)]
+pub enum SizeChange {
+    /// Stop with an error naming the frame.
+    Abort,
+    /// Rescale the frames to the first frame's size.
+    Scale,
+}
+
+impl SizeChange {
+    pub fn parse(text: &str) -> Result<SizeChange, String> {
+        match text {
+            "abort" => Ok(SizeChange::Abort),
+            "scale" => Ok(SizeChange::Scale),
+            _ => Err(format!(
+                "Invalid --on-size-change value: {} (expected abort or scale)",
+                text
+            )),
+        }
+    }
+}
+
+/// Where decoded frames come from. The pipeline only sees this trait, so a
+/// new kind of input is one more implementation and a branch in [`open`].
+pub trait FrameSource {
+    /// Decodes every frame, handing each to `store` with its timestamp in
+    /// seconds.
+    fn decode_all(&mut self, store: &mut StoreFrame) -> Result<(), Box<dyn std::error::Error>>;
+
+    /// Memory the source may take on top of the frame store while decoding.
+    fn buffer_bytes(&self) -> u64 {
+        0
+    }
+}
+
+/// Opens `--input`: the test pattern generator for `generate:...`, raw
+/// frames with `--raw-input`, and anything else through FFmpeg (files, URLs,
+/// image sequences, `-` for stdin, and devices with `--input-format`).
+pub fn open(
+    options: &Options,
+) -> Result<(Box<dyn FrameSource>, InputInfo), Box<dyn std::error::Error>> {
+    let generator = options
+        .input
+        .to_str()
+        .and_then(|input| input.strip_prefix("generate:"));
+    if let Some(spec) = generator {
+        let generator = Generator::parse(spec)?;
+        let info = generator.info();
+        return Ok((Box::new(generator), info));
+    }
+    if let Some(format) = options.raw_format {
+        let reader = RawReader::open(Path::new(&options.input), format)?;
+        let (pixel, full_range) = color_range::normalize(format.pixel);
+        let info = InputInfo {
+            width: format.width,
+            height: format.height,
+            format: pixel,
+            full_range,
+            fps: format.fps,
+            estimated_frames: reader.frame_count,
+            start_time: None,
+            transfer: TransferCharacteristic::Unspecified,
+        };
+        let fps = f64::from(format.fps);
+        return Ok((Box::new(RawSource { reader, fps }), info));
+    }
+    let (source, info) = DemuxedSource::open(options)?;
+    Ok((Box::new(source), info))
+}
+
+/// A container demuxed and decoded by FFmpeg.
+pub struct DemuxedSource {
+    ictx: ffmpeg::format::context::Input,
+    /// What `ictx` reads from with read-ahead; declared after it, so it's
+    /// dropped once the input is closed.
+    _read_ahead: Option<ReadAhead>,
+    decoder: ffmpeg::decoder::Video,
+    stream_index: usize,
+    clock: TimestampNormalizer,
+    path: PathBuf,
+    /// Keyframe ranges decoded in parallel with `--decode-threads`.
+    gop_ranges: Option<Vec<GopRange>>,
+    /// Frames each range after the first may hold until its turn.
+    gop_buffer_frames: usize,
+    /// Size of a decoded frame, for the buffers.
+    frame_bytes: u64,
+}
+
+impl DemuxedSource {
+    pub fn open(options: &Options) -> Result<(Self, InputInfo), Box<dyn std::error::Error>> {
+        let input_path = &options.input;
+        // FFmpeg reads stdin through its pipe protocol
+        let url = match input_path.to_str() {
+            Some("-") => Path::new("pipe:0"),
+            _ => input_path.as_path(),
+        };
+        let url_text = url.to_string_lossy();
+        let read_ahead = match options.io_buffer_size {
+            // Devices and demuxers with their own connections read as they please
+            _ if options.input_format.is_some() || read_ahead::is_demuxer_managed(&url_text) => {
+                None
+            }
+            Some(size) => Some(size),
+            None if read_ahead::is_network(&url_text) => Some(read_ahead::DEFAULT_NETWORK_BUFFER),
+            None => None,
+        };
+        let read_ahead = match read_ahead {
+            Some(size) => {
+                status!(
+                    "  Reading up to {} of the input ahead",
+                    format_bytes(size as u64)
+                );
+                // Local files and pipes fail for good
+                let retries = if read_ahead::is_network(&url_text) {
+                    options.input_retries
+                } else {
+                    RetryPolicy::NONE
+                };
+                Some(ReadAhead::open(url, size, retries)?)
+            }
+            None => None,
+        };
+        let demuxer_options = || {
+            let mut demuxer_options = ffmpeg::Dictionary::new();
+            if let Some(size) = options.io_buffer_size {
+                if read_ahead::is_demuxer_managed(&url_text) {
+                    demuxer_options.set("buffer_size", &size.to_string());
+                }
+            }
+            demuxer_options
+        };
+        let io = read_ahead
+            .as_ref()
+            .map_or(ptr::null_mut(), ReadAhead::context);
+        let ictx = match &options.input_format {
+            Some(name) => open_with_format(url, name)?,
+            // The RTSP demuxer connects on its own, so it's retried whole
+            None if read_ahead::is_demuxer_managed(&url_text) => options.input_retries.run(
+                "Opening the input",
+                || os_path::open_input_with(url, None, io, demuxer_options()),
+                |_| true,
+            )?,
+            None => os_path::open_input_with(url, None, io, demuxer_options())?,
+        };
+
+        // Find video stream and get parameters
+        let stream_index = probe::video_stream(&ictx, input_path)?;
+        let input_stream = ictx.stream(stream_index).ok_or("No video stream found")?;
+        let fps = input_stream.avg_frame_rate();
+        let clock = TimestampNormalizer::new(input_stream.start_time(), input_stream.time_base());
+
+        // Get decoder
+        let context_decoder =
+            ffmpeg::codec::context::Context::from_parameters(input_stream.parameters())?;
+        let decoder = context_decoder.decoder().video()?;
+
+        // Without a frame count in the header or a duration, count the
+        // packets. That reads the input twice, so only do it for files
+        let estimated_frames =
+            match probe::estimate_frame_count(&input_stream, ictx.duration(), fps) {
+                Some(estimated) => Some(estimated),
+                None if input_path.is_file() => probe::count_packets(input_path, stream_index)
+                    .ok()
+                    .filter(|&count| count > 0),
+                None => None,
+            };
+
+        // Parallel decoding seeks around the file, so it's only for files
+        // opened by probing
+        let gop_ranges = match options.decode_threads {
+            threads if threads > 1 && options.input_format.is_none() && input_path.is_file() => {
+                gop_decode::plan(input_path, stream_index, threads)?
+            }
+            _ => None,
+        };
+        let frame_bytes = unsafe {
+            ffmpeg::ffi::av_image_get_buffer_size(
+                decoder.format().into(),
+                decoder.width() as i32,
+                decoder.height() as i32,
+                1,
+            )
+        }
+        .max(1) as u64;
+        let gop_buffer_frames = gop_ranges.as_ref().map_or(0, |ranges| {
+            let budget = MemoryGuard::new(options.max_memory).budget();
+            gop_decode::buffer_frames(budget, frame_bytes, ranges.len())
+        });
+        if let Some(ranges) = &gop_ranges {
+            status!(
+                "  Decoding {} keyframe ranges in parallel, each buffering up to {} frames",
+                ranges.len(),
+                gop_buffer_frames
+            );
+        }
+
+        let info = InputInfo {
+            width: decoder.width() as usize,
+            height: decoder.height() as usize,
+            format: color_range::normalize(decoder.format()).0,
+            full_range: color_range::is_full(decoder.format(), decoder.color_range()),
+            fps,
+            estimated_frames,
+            start_time: clock.origin_seconds().filter(|&start| start != 0.0),
+            transfer: decoder.color_transfer_characteristic(),
+        };
+        let source = DemuxedSource {
+            ictx,
+            _read_ahead: read_ahead,
+            decoder,
+            stream_index,
+            clock,
+            path: input_path.clone(),
+            gop_ranges,
+            gop_buffer_frames,
+            frame_bytes,
+        };
+        Ok((source, info))
+    }
+
+    fn receive_frames(&mut self, store: &mut StoreFrame) -> Result<(), Box<dyn std::error::Error>> {
+        let mut decoded = Video::empty();
+        while self.decoder.receive_frame(&mut decoded).is_ok() {
+            color_range::normalize_frame(&mut decoded);
+            store(&decoded, self.clock.normalize(decoded.timestamp()))?;
+        }
+        Ok(())
+    }
+}
+
+impl FrameSource for DemuxedSource {
+    fn decode_all(&mut self, store: &mut StoreFrame) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(ranges) = &self.gop_ranges {
+            let clock = &mut self.clock;
+            let buffer = self.gop_buffer_frames;
+            return gop_decode::decode(
+                &self.path,
+                self.stream_index,
+                ranges,
+                buffer,
+                &mut |mut frame| {
+                    color_range::normalize_frame(&mut frame);
+                    store(&frame, clock.normalize(frame.timestamp()))
+                },
+            );
+        }
+        while let Some((stream, packet)) = self.ictx.packets().next() {
+            if stream.index() == self.stream_index {
+                self.decoder.send_packet(&packet)?;
+                self.receive_frames(store)?;
+            }
+        }
+
+        // Flush decoder
+        self.decoder.send_eof()?;
+        self.receive_frames(store)
+    }
+
+    fn buffer_bytes(&self) -> u64 {
+        let ranges = self.gop_ranges.as_ref().map_or(0, Vec::len);
+        (ranges.saturating_sub(1) * self.gop_buffer_frames) as u64 * self.frame_bytes
+    }
+}
+
+/// Opens `url` with a named demuxer or capture device (`v4l2`,
+/// `avfoundation`, `dshow`, `x11grab`, `lavfi`, ...) instead of probing.
+fn open_with_format(
+    url: &Path,
+    name: &str,
+) -> Result<ffmpeg::format::context::Input, Box<dyn std::error::Error>> {
+    ffmpeg::device::register_all();
+    let c_name = CString::new(name)?;
+    let format = unsafe { ffmpeg::ffi::av_find_input_format(c_name.as_ptr()) };
+    if format.is_null() {
+        return Err(format!("Unknown input format: {}", name).into());
+    }
+    let format = unsafe { ffmpeg::format::format::Input::wrap(format as *mut _) };
+    os_path::open_input(url, Some(format))
+}
+
+/// A headerless raw file read directly.
+pub struct RawSource {
+    reader: RawReader,
+    fps: f64,
+}
+
+impl FrameSource for RawSource {
+    fn decode_all(&mut self, store: &mut StoreFrame) -> Result<(), Box<dyn std::error::Error>> {
+        let mut index = 0u64;
+        while let Some(mut frame) = self.reader.read_frame()? {
+            color_range::normalize_frame(&mut frame);
+            store(&frame, Some(index as f64 / self.fps))?;
+            index += 1;
+        }
+        Ok(())
+    }
+}
+
+/// Synthetic input for `generate:WxH[:frames]`: a white square moving left
+/// to right over black at 30 fps, the same clip `test.sh` renders.
+pub struct Generator {
+    width: usize,
+    height: usize,
+    frames: u64,
+}
+
+impl Generator {
+    const FPS: i32 = 30;
+    const DEFAULT_FRAMES: u64 = 150;
+
+    pub fn parse(spec: &str) -> Result<Generator, String> {
+        let invalid = || format!("Invalid generator: {} (expected WxH[:frames])", spec);
+        let (size, frames) = match spec.split_once(':') {
+            Some((size, frames)) => (size, frames.parse().map_err(|_| invalid())?),
+            None => (spec, Self::DEFAULT_FRAMES),
+        };
+        let (width, height) = size
+            .split_once('x')
+            .and_then(|(w, h)| Some((w.parse().ok()?, h.parse().ok()?)))
+            .filter(|&(w, h): &(usize, usize)| w > 0 && h > 0 && frames > 0)
+            .ok_or_else(invalid)?;
+        Ok(Generator {
+            width,
+            height,
+            frames,
+        })
+    }
+
+    fn info(&self) -> InputInfo {
+        InputInfo {
+            width: self.width,
+            height: self.height,
+            format: Pixel::RGB24,
+            full_range: false,
+            fps: ffmpeg::Rational(Self::FPS, 1),
+            estimated_frames: Some(self.frames),
+            start_time: None,
+            transfer: TransferCharacteristic::Unspecified,
+        }
+    }
+}
+
+impl FrameSource for Generator {
+    fn decode_all(&mut self, store: &mut StoreFrame) -> Result<(), Box<dyn std::error::Error>> {
+        let side = (self.height / 5).clamp(1, self.width);
+        let top = (self.height - side) / 2;
+        let travel = (self.width - side) as u64;
+        let last = (self.frames - 1).max(1);
+        for index in 0..self.frames {
+            let left = (travel * index / last) as usize;
+            let mut frame = Video::new(Pixel::RGB24, self.width as u32, self.height as u32);
+            let stride = frame.stride(0);
+            let data = frame.data_mut(0);
+            data.fill(0);
+            for y in top..top + side {
+                data[y * stride + left * 3..y * stride + (left + side) * 3].fill(255);
+            }
+            store(&frame, Some(index as f64 / f64::from(Self::FPS)))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,149 @@
+use serde_json::json;
+use std::ffi::OsString;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+/// Inserts `suffix` (such as `_part001`) before the extension of `path`,
+/// which may also be an object URL.
+pub fn suffixed_path(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = stem(path);
+    name.push(suffix);
+    if let Some(extension) = path.extension() {
+        name.push(".");
+        name.push(extension);
+    }
+    path.with_file_name(name)
+}
+
+/// File name suffix for one piece of a split output: `_partNNN` for a range
+/// of output frames (`--split-output`), `_wNNN` for a range of source frames
+/// (`--split-width`), each only when that axis is actually split.
+pub fn suffix(part: Option<usize>, tile: Option<usize>) -> String {
+    let mut suffix = String::new();
+    if let Some(part) = part {
+        suffix.push_str(&format!("_part{:03}", part + 1));
+    }
+    if let Some(tile) = tile {
+        suffix.push_str(&format!("_w{:03}", tile + 1));
+    }
+    suffix
+}
+
+/// Path of the manifest describing the parts written for `path`.
+pub fn manifest_path(path: &Path) -> PathBuf {
+    let mut name = stem(path);
+    name.push("_parts.json");
+    path.with_file_name(name)
+}
+
+/// Path of the ffconcat playlist joining the parts written for `path`.
+pub fn playlist_path(path: &Path) -> PathBuf {
+    path.with_extension("ffconcat")
+}
+
+/// File name of `path` without its extension.
+fn stem(path: &Path) -> OsString {
+    path.file_stem().unwrap_or_default().to_os_string()
+}
+
+/// Splits `count` output frames into contiguous ranges of at most `limit`,
+/// or a single range without a limit.
+pub fn ranges(count: usize, limit: Option<usize>) -> Vec<Range<usize>> {
+    match limit {
+        Some(limit) if count > limit => (0..count)
+            .step_by(limit)
+            .map(|start| start..(start + limit).min(count))
+            .collect(),
+        _ => std::iter::once(0..count).collect(),
+    }
+}
+
+/// Like `ranges`, but ends each range at the last of `cuts` (sorted frame
+/// indices) that keeps it within `limit` and at least half as long, so the
+/// pieces start on scene changes where possible.
+pub fn ranges_at_cuts(count: usize, limit: usize, cuts: &[usize]) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut start = 0;
+    while count - start > limit {
+        let end = cuts
+            .iter()
+            .rev()
+            .find(|&&cut| cut <= start + limit && cut >= start + limit.div_ceil(2))
+            .copied()
+            .unwrap_or(start + limit);
+        ranges.push(start..end);
+        start = end;
+    }
+    ranges.push(start..count);
+    ranges
+}
+
+/// One file of a split output.
+pub struct Part {
+    pub path: String,
+    /// Output frames in the file, as indices into the column list.
+    pub frames: Range<usize>,
+    /// Source frames making up the width of every frame in the file.
+    pub source_frames: Range<usize>,
+}
+
+/// Builds the JSON manifest listing every part, its range of output frames,
+/// the source columns they show and the source frames across their width.
+pub fn manifest(output: &str, parts: &[Part], columns: &[usize]) -> serde_json::Value {
+    let parts: Vec<_> = parts
+        .iter()
+        .map(|part| {
+            json!({
+                "path": part.path,
+                "first_frame": part.frames.start,
+                "frames": part.frames.len(),
+                "source_columns": [columns[part.frames.start], columns[part.frames.end - 1]],
+                "source_frames": [part.source_frames.start, part.source_frames.end],
+            })
+        })
+        .collect();
+    json!({
+        "output": output,
+        "total_frames": columns.len(),
+        "parts": parts,
+    })
+}
+
+/// Where one file sits in a `--split-output` sequence.
+#[derive(Clone, Copy)]
+pub struct Sequence {
+    pub index: usize,
+    pub count: usize,
+    /// Output frames in the files before this one.
+    pub first_frame: usize,
+}
+
+impl Sequence {
+    /// Container metadata tags placing the file in its sequence, so players
+    /// and editors can line the parts up on one timeline.
+    pub fn metadata(&self, fps: f64) -> [(&'static str, String); 3] {
+        [
+            ("part", format!("{}/{}", self.index + 1, self.count)),
+            ("part_first_frame", self.first_frame.to_string()),
+            (
+                "part_start_time",
+                format!("{:.6}", self.first_frame as f64 / fps),
+            ),
+        ]
+    }
+}
+
+/// An ffconcat playlist playing `parts` (path and frame count) back to back
+/// at `fps`, for `ffplay` or `ffmpeg -f concat` to treat as one continuous
+/// video. Each part's exact duration is listed so nothing is probed or
+/// rounded at the joins, and paths are relative to the playlist, which sits
+/// next to the parts.
+pub fn playlist(parts: &[(String, usize)], fps: f64) -> String {
+    let mut playlist = String::from("ffconcat version 1.0\n");
+    for (path, frames) in parts {
+        let name = &path[path.rfind('/').map_or(0, |slash| slash + 1)..];
+        playlist.push_str(&format!("file '{}'\n", name.replace('\'', "'\\''")));
+        playlist.push_str(&format!("duration {:.6}\n", *frames as f64 / fps));
+    }
+    playlist
+}
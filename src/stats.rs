@@ -0,0 +1,149 @@
+use crate::memory::{format_bytes, peak_rss};
+use ffmpeg_next::Rational;
+use serde_json::{json, Value};
+use std::time::{Duration, Instant};
+
+/// Wall-clock time and frame count accumulated by one pipeline stage.
+#[derive(Default)]
+pub struct Stage {
+    pub elapsed: Duration,
+    pub frames: u64,
+}
+
+impl Stage {
+    /// Runs `f`, adding its duration to the stage.
+    pub fn time<T>(&mut self, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+        self.elapsed += start.elapsed();
+        result
+    }
+
+    pub fn fps(&self) -> f64 {
+        let seconds = self.elapsed.as_secs_f64();
+        if seconds > 0.0 {
+            self.frames as f64 / seconds
+        } else {
+            0.0
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "seconds": self.elapsed.as_secs_f64(),
+            "frames": self.frames,
+            "fps": self.fps(),
+        })
+    }
+}
+
+/// How full the queue feeding one encoder thread got over a run with
+/// `--inflight-frames`.
+#[derive(Clone, Default)]
+pub struct QueueStats {
+    pub name: String,
+    /// Most frames the queue holds.
+    pub capacity: usize,
+    pub frames: u64,
+    /// Frames queued right after each one was added, summed and at most.
+    pub total_depth: u64,
+    pub max_depth: usize,
+    /// Time spent waiting for room in a full queue.
+    pub blocked: Duration,
+}
+
+impl QueueStats {
+    /// Average number of frames queued.
+    pub fn mean_depth(&self) -> f64 {
+        if self.frames > 0 {
+            self.total_depth as f64 / self.frames as f64
+        } else {
+            0.0
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "output": self.name,
+            "capacity": self.capacity,
+            "frames": self.frames,
+            "mean_depth": self.mean_depth(),
+            "max_depth": self.max_depth,
+            "blocked_seconds": self.blocked.as_secs_f64(),
+        })
+    }
+}
+
+/// Per-stage timings collected over a run.
+#[derive(Default)]
+pub struct RunStats {
+    pub decode: Stage,
+    pub transpose: Stage,
+    pub encode: Stage,
+    /// Encoder queues, with `--inflight-frames`.
+    pub queues: Vec<QueueStats>,
+}
+
+impl RunStats {
+    pub fn print(&self) {
+        status!("\nTimings:");
+        for (name, stage) in self.stages() {
+            status!(
+                "  {:<10} {:>9.2}s  {:>8} frames  {:>9.1} fps",
+                name,
+                stage.elapsed.as_secs_f64(),
+                stage.frames,
+                stage.fps()
+            );
+        }
+        for queue in &self.queues {
+            status!(
+                "  Queue {}: {:.1} frames on average, at most {} of {}, {:.2}s waiting on the \
+                 encoder",
+                queue.name,
+                queue.mean_depth(),
+                queue.max_depth,
+                queue.capacity,
+                queue.blocked.as_secs_f64()
+            );
+        }
+        if let Some(rss) = peak_rss() {
+            status!("  Peak RSS:  {}", format_bytes(rss));
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        let mut stages = serde_json::Map::new();
+        for (name, stage) in self.stages() {
+            stages.insert(name.to_string(), stage.to_json());
+        }
+        json!({
+            "stages": stages,
+            "encode_queues": self.queues.iter().map(QueueStats::to_json).collect::<Vec<_>>(),
+            "peak_rss_bytes": peak_rss(),
+        })
+    }
+
+    fn stages(&self) -> [(&'static str, &Stage); 3] {
+        [
+            ("decode", &self.decode),
+            ("transpose", &self.transpose),
+            ("encode", &self.encode),
+        ]
+    }
+}
+
+/// Formats a rational as `num/den`, e.g. a time base of `1/30000`.
+pub fn format_rational(value: Rational) -> String {
+    format!("{}/{}", value.numerator(), value.denominator())
+}
+
+/// Formats a frame rate as `num/den (decimal)`, e.g. `30000/1001 (29.970)`.
+/// Unknown rates (a zero term) print as `num/den (unknown)` rather than NaN.
+pub fn format_rate(rate: Rational) -> String {
+    if rate.numerator() == 0 || rate.denominator() == 0 {
+        format!("{} (unknown)", format_rational(rate))
+    } else {
+        format!("{} ({:.3})", format_rational(rate), f64::from(rate))
+    }
+}
@@ -0,0 +1,117 @@
+//! `--target`: encoder settings bundled by what the output is for, so a
+//! sensible file doesn't take learning codec options. Explicit flags such as
+//! `--bframes` or an `--also-encode` CRF still take precedence.
+
+use ffmpeg_next::format::Pixel;
+use std::path::Path;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Target {
+    /// H.264 4:2:0 in MP4 with the index up front, for browsers and players.
+    Web,
+    /// Lossless FFV1 in Matroska, NUT or AVI.
+    Archive,
+    /// High quality 4:4:4 H.264 with every frame a keyframe, so any frame
+    /// can be seeked to and inspected exactly.
+    Analysis,
+    /// High quality 4:2:2 H.264 with short GOPs, for scrubbing in editors.
+    Editing,
+}
+
+impl Target {
+    pub fn parse(text: &str) -> Result<Target, String> {
+        match text {
+            "web" => Ok(Target::Web),
+            "archive" => Ok(Target::Archive),
+            "analysis" => Ok(Target::Analysis),
+            "editing" => Ok(Target::Editing),
+            _ => Err(format!(
+                "Invalid --target: {} (expected web, archive, analysis or editing)",
+                text
+            )),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Target::Web => "web",
+            Target::Archive => "archive",
+            Target::Analysis => "analysis",
+            Target::Editing => "editing",
+        }
+    }
+
+    /// Whether 8-bit color frames are encoded with FFV1 instead of H.264.
+    pub fn lossless(self) -> bool {
+        self == Target::Archive
+    }
+
+    /// Pixel format 8-bit color frames are encoded in.
+    pub fn pixel(self) -> Pixel {
+        match self {
+            Target::Web => Pixel::YUV420P,
+            Target::Archive => Pixel::GBRP,
+            Target::Analysis => Pixel::YUV444P,
+            Target::Editing => Pixel::YUV422P,
+        }
+    }
+
+    /// H.264 CRF for outputs that don't set their own.
+    pub fn crf(self) -> Option<u32> {
+        match self {
+            Target::Web => Some(23),
+            Target::Archive => None,
+            Target::Analysis => Some(12),
+            Target::Editing => Some(16),
+        }
+    }
+
+    /// Default for `--bframes`.
+    pub fn bframes(self) -> usize {
+        match self {
+            Target::Web => 2,
+            _ => 0,
+        }
+    }
+
+    /// Default for `--intra-only`.
+    pub fn intra_only(self) -> bool {
+        self == Target::Analysis
+    }
+
+    /// Keyframe interval in seconds, unless every frame is one.
+    pub fn gop_seconds(self) -> Option<f64> {
+        match self {
+            Target::Web => Some(2.0),
+            Target::Editing => Some(0.5),
+            _ => None,
+        }
+    }
+
+    /// Whether MP4 and MOV outputs move their index to the front, so
+    /// playback starts before the whole file is downloaded.
+    pub fn faststart(self) -> bool {
+        self == Target::Web
+    }
+
+    /// Checks the container of an output whose extension gives `muxer`.
+    /// Archives must be able to hold FFV1; web outputs only warn, since a
+    /// Matroska file is still fine for many players.
+    pub fn check_container(self, path: &Path, muxer: Option<&str>) -> Result<(), String> {
+        match self {
+            Target::Archive if !muxer.is_some_and(crate::encode::supports_ffv1) => Err(format!(
+                "--target archive writes lossless FFV1, which {} can't hold; use .mkv, .nut or \
+                 .avi",
+                path.display()
+            )),
+            Target::Web if !matches!(muxer, Some("mp4" | "mov")) => {
+                status!(
+                    "  Warning: --target web expects .mp4 outputs, {} may not play in browsers",
+                    path.display()
+                );
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+}
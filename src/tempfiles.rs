@@ -0,0 +1,122 @@
+use crate::memory::format_bytes;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, SystemTime};
+
+const PREFIX: &str = "video-transpose-";
+
+/// Runs started by this process, numbering their directories.
+static RUNS: AtomicUsize = AtomicUsize::new(0);
+
+/// Without `/proc` to check for a live owner, directories older than this
+/// are considered orphaned.
+const ORPHAN_AGE: Duration = Duration::from_secs(24 * 3600);
+
+/// A per-run directory for staging and scratch files, named
+/// `video-transpose-<pid>-<run>` so runs in one process (the worker's
+/// slots, the GUI) don't share it and files left behind by a crashed run
+/// can be found later by `clean-tmp`. Removed with everything in it on
+/// drop, whether the run succeeded or failed, unless `keep` is set.
+pub struct TempFiles {
+    dir: PathBuf,
+    keep: bool,
+}
+
+impl TempFiles {
+    pub fn new(base: Option<&Path>, keep: bool) -> TempFiles {
+        let base = base.map(Path::to_path_buf).unwrap_or_else(env::temp_dir);
+        let run = RUNS.fetch_add(1, Ordering::Relaxed);
+        TempFiles {
+            dir: base.join(format!("{}{}-{}", PREFIX, std::process::id(), run)),
+            keep,
+        }
+    }
+
+    /// Returns a path for the file `name` in the temp directory, creating
+    /// the directory on first use.
+    pub fn path(&self, name: &str) -> io::Result<PathBuf> {
+        fs::create_dir_all(&self.dir)?;
+        Ok(self.dir.join(name))
+    }
+}
+
+impl Drop for TempFiles {
+    fn drop(&mut self) {
+        if self.keep {
+            if self.dir.exists() {
+                status!("  Temp files kept in: {}", self.dir.display());
+            }
+        } else {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+}
+
+/// Removes temp directories whose process is no longer running. Returns
+/// the number of directories and bytes removed.
+pub fn clean_orphans(base: Option<&Path>) -> io::Result<(usize, u64)> {
+    let base = base.map(Path::to_path_buf).unwrap_or_else(env::temp_dir);
+    let has_proc = Path::new("/proc/self").exists();
+    let mut removed = (0, 0);
+
+    for entry in fs::read_dir(&base)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        // `<pid>-<run>`
+        let pid = match name
+            .to_str()
+            .and_then(|name| name.strip_prefix(PREFIX))
+            .and_then(|rest| rest.split_once('-'))
+            .filter(|(_, run)| run.parse::<usize>().is_ok())
+            .and_then(|(pid, _)| pid.parse::<u32>().ok())
+        {
+            Some(pid) => pid,
+            None => continue,
+        };
+        if !entry.file_type()?.is_dir() || pid == std::process::id() {
+            continue;
+        }
+
+        let orphaned = if has_proc {
+            !Path::new(&format!("/proc/{}", pid)).exists()
+        } else {
+            entry
+                .metadata()?
+                .modified()
+                .ok()
+                .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+                .is_some_and(|age| age > ORPHAN_AGE)
+        };
+        if !orphaned {
+            continue;
+        }
+
+        let size = dir_size(&entry.path());
+        fs::remove_dir_all(entry.path())?;
+        println!(
+            "  Removed {} ({})",
+            entry.path().display(),
+            format_bytes(size)
+        );
+        removed.0 += 1;
+        removed.1 += size;
+    }
+    Ok(removed)
+}
+
+fn dir_size(path: &Path) -> u64 {
+    let entries = match fs::read_dir(path) {
+        Ok(entries) => entries,
+        Err(_) => return 0,
+    };
+    entries
+        .flatten()
+        .map(|entry| match entry.file_type() {
+            Ok(kind) if kind.is_dir() => dir_size(&entry.path()),
+            _ => entry.metadata().map(|m| m.len()).unwrap_or(0),
+        })
+        .sum()
+}
@@ -0,0 +1,194 @@
+//! Background processing: `--priority low` (or `--nice`) lowers the
+//! scheduling priority, `--affinity` keeps the run to some CPUs,
+//! `--throttle` caps how many frames per second each stage handles, and
+//! SIGUSR1 pauses and resumes a run, so a long run doesn't starve
+//! interactive work or capture software on a workstation.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Niceness applied by `--nice`, the same default as nice(1).
+#[cfg(unix)]
+const NICE_INCREMENT: i32 = 10;
+
+/// CPUs an affinity mask can name (`CPU_SETSIZE` on Linux).
+const MAX_CPUS: usize = 1024;
+
+/// Scheduling priority of the run.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Priority {
+    /// Niceness raised by [`NICE_INCREMENT`].
+    Low,
+    Normal,
+}
+
+impl Priority {
+    pub fn parse(text: &str) -> Result<Priority, String> {
+        match text {
+            "low" => Ok(Priority::Low),
+            "normal" => Ok(Priority::Normal),
+            _ => Err(format!(
+                "Invalid --priority: {} (expected low or normal)",
+                text
+            )),
+        }
+    }
+}
+
+/// Parses a CPU list such as `0-7` or `0,2,4-6` into sorted CPU numbers.
+pub fn parse_cpu_list(text: &str) -> Result<Vec<usize>, String> {
+    let invalid = || {
+        format!(
+            "Invalid --affinity: {} (expected CPUs such as 0-7 or 0,2,4-6)",
+            text
+        )
+    };
+    let mut cpus = Vec::new();
+    for part in text.split(',') {
+        let (first, last) = part.split_once('-').unwrap_or((part, part));
+        let first: usize = first.trim().parse().map_err(|_| invalid())?;
+        let last: usize = last.trim().parse().map_err(|_| invalid())?;
+        if first > last {
+            return Err(invalid());
+        }
+        if last >= MAX_CPUS {
+            return Err(format!("--affinity CPUs must be below {}", MAX_CPUS));
+        }
+        cpus.extend(first..=last);
+    }
+    cpus.sort_unstable();
+    cpus.dedup();
+    Ok(cpus)
+}
+
+/// Toggled by SIGUSR1 once [`handle_pause_signal`] is installed.
+static PAUSED: AtomicBool = AtomicBool::new(false);
+
+/// How often a paused run checks whether it was resumed.
+const PAUSE_POLL: Duration = Duration::from_millis(100);
+
+/// Sleeps between frames to hold a stage to a maximum frame rate.
+pub struct Throttle {
+    interval: Option<Duration>,
+    next: Instant,
+}
+
+impl Throttle {
+    /// A throttle allowing `fps` frames per second, or none for `None`.
+    pub fn new(fps: Option<f64>) -> Self {
+        Throttle {
+            interval: fps.map(|fps| Duration::from_secs_f64(1.0 / fps)),
+            next: Instant::now(),
+        }
+    }
+
+    /// Called once per frame; sleeps until the frame's slot comes up, and
+    /// holds the run here while it is paused. Falling behind (a slow frame
+    /// or a pause) doesn't let later frames burst to catch up.
+    pub fn pace(&mut self) {
+        if PAUSED.load(Ordering::Relaxed) {
+            status!("  Paused, send SIGUSR1 again to resume");
+            while PAUSED.load(Ordering::Relaxed) {
+                thread::sleep(PAUSE_POLL);
+            }
+            status!("  Resumed");
+        }
+        let Some(interval) = self.interval else {
+            return;
+        };
+        let now = Instant::now();
+        if self.next > now {
+            thread::sleep(self.next - now);
+            self.next += interval;
+        } else {
+            self.next = now + interval;
+        }
+    }
+}
+
+/// Lowers the priority of the calling thread. Threads it starts afterwards,
+/// including FFmpeg's encoder threads, inherit the lower priority.
+#[cfg(unix)]
+pub fn lower_priority() {
+    // On Linux nice() applies to the calling thread only, which is why this
+    // runs before any worker threads are started. It returns the new
+    // niceness, so -1 is only an error when starting from below -10
+    match unsafe { libc::nice(NICE_INCREMENT) } {
+        -1 => status!(
+            "  Warning: could not lower the priority: {}",
+            std::io::Error::last_os_error()
+        ),
+        niceness => status!("  Running at niceness {}", niceness),
+    }
+}
+
+#[cfg(not(unix))]
+pub fn lower_priority() {
+    status!("  Warning: --nice is not supported on this platform");
+}
+
+/// Pins the calling thread to `cpus`. Like [`lower_priority`], threads it
+/// starts afterwards inherit the mask, so this runs before they start.
+#[cfg(target_os = "linux")]
+pub fn set_affinity(cpus: &[usize]) {
+    let result = unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        for &cpu in cpus {
+            libc::CPU_SET(cpu, &mut set);
+        }
+        libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set)
+    };
+    match result {
+        0 => status!("  Running on CPUs {}", format_cpu_list(cpus)),
+        _ => status!(
+            "  Warning: could not set the CPU affinity: {}",
+            std::io::Error::last_os_error()
+        ),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn set_affinity(cpus: &[usize]) {
+    status!(
+        "  Warning: --affinity {} is not supported on this platform",
+        format_cpu_list(cpus)
+    );
+}
+
+/// `0-3,6` for CPUs 0, 1, 2, 3 and 6.
+fn format_cpu_list(cpus: &[usize]) -> String {
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for &cpu in cpus {
+        match ranges.last_mut() {
+            Some((_, last)) if *last + 1 == cpu => *last = cpu,
+            _ => ranges.push((cpu, cpu)),
+        }
+    }
+    let ranges: Vec<String> = ranges
+        .iter()
+        .map(|&(first, last)| {
+            if first == last {
+                first.to_string()
+            } else {
+                format!("{}-{}", first, last)
+            }
+        })
+        .collect();
+    ranges.join(",")
+}
+
+/// Makes SIGUSR1 toggle between pausing and resuming the run. Frames in
+/// memory and open outputs are kept as they are while paused.
+#[cfg(unix)]
+pub fn handle_pause_signal() {
+    extern "C" fn toggle(_: libc::c_int) {
+        PAUSED.fetch_xor(true, Ordering::Relaxed);
+    }
+    unsafe {
+        libc::signal(libc::SIGUSR1, toggle as *const () as libc::sighandler_t);
+    }
+}
+
+#[cfg(not(unix))]
+pub fn handle_pause_signal() {}
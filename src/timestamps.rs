@@ -0,0 +1,92 @@
+use ffmpeg_next as ffmpeg;
+
+/// Maps raw stream timestamps to seconds since the start of the stream.
+///
+/// Inputs may begin at a large `start_time` (broadcast captures, trimmed
+/// segments) or at negative timestamps (edit lists), so everything derived
+/// from frame times is measured from the stream origin instead of zero.
+pub struct TimestampNormalizer {
+    origin: Option<i64>,
+    time_base: ffmpeg::Rational,
+}
+
+impl TimestampNormalizer {
+    /// `start_time` is the stream's declared start, in `time_base` units.
+    pub fn new(start_time: i64, time_base: ffmpeg::Rational) -> Self {
+        let origin = if start_time == ffmpeg::ffi::AV_NOPTS_VALUE {
+            None
+        } else {
+            Some(start_time)
+        };
+        TimestampNormalizer { origin, time_base }
+    }
+
+    /// The stream origin in seconds, once known.
+    pub fn origin_seconds(&self) -> Option<f64> {
+        self.origin.map(|origin| self.to_seconds(origin))
+    }
+
+    /// Converts a frame timestamp to seconds since the stream origin. Without
+    /// a declared start time, the first timestamp seen becomes the origin.
+    pub fn normalize(&mut self, timestamp: Option<i64>) -> Option<f64> {
+        let timestamp = timestamp?;
+        let origin = *self.origin.get_or_insert(timestamp);
+        Some(self.to_seconds(timestamp - origin))
+    }
+
+    fn to_seconds(&self, ticks: i64) -> f64 {
+        ticks as f64 * f64::from(self.time_base)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_seconds(actual: Option<f64>, expected: f64) {
+        let actual = actual.expect("no time");
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn large_start_time() {
+        // A broadcast capture starting near the 33-bit MPEG-TS wrap
+        let start = (1i64 << 33) - 90_000;
+        let time_base = ffmpeg::Rational(1, 90_000);
+        let mut normalizer = TimestampNormalizer::new(start, time_base);
+        assert_seconds(normalizer.origin_seconds(), start as f64 / 90_000.0);
+        assert_seconds(normalizer.normalize(Some(start)), 0.0);
+        assert_seconds(normalizer.normalize(Some(start + 3003)), 1001.0 / 30_000.0);
+        assert_seconds(normalizer.normalize(Some(start + 900_000)), 10.0);
+    }
+
+    #[test]
+    fn negative_first_timestamps() {
+        // An edit list starting the stream two frames before zero
+        let time_base = ffmpeg::Rational(1, 30_000);
+        let mut normalizer = TimestampNormalizer::new(-2002, time_base);
+        assert_seconds(normalizer.normalize(Some(-2002)), 0.0);
+        assert_seconds(normalizer.normalize(Some(-1001)), 1001.0 / 30_000.0);
+        assert_seconds(normalizer.normalize(Some(0)), 2002.0 / 30_000.0);
+    }
+
+    #[test]
+    fn first_timestamp_is_the_origin_without_a_start_time() {
+        let time_base = ffmpeg::Rational(1, 1000);
+        let mut normalizer = TimestampNormalizer::new(ffmpeg::ffi::AV_NOPTS_VALUE, time_base);
+        assert_eq!(normalizer.origin_seconds(), None);
+        // Frames without a timestamp don't set the origin
+        assert_eq!(normalizer.normalize(None), None);
+        assert_eq!(normalizer.origin_seconds(), None);
+        assert_seconds(normalizer.normalize(Some(-500)), 0.0);
+        assert_seconds(normalizer.origin_seconds(), -0.5);
+        assert_seconds(normalizer.normalize(Some(1500)), 2.0);
+        // The origin stays put
+        assert_seconds(normalizer.normalize(Some(-500)), 0.0);
+    }
+}
@@ -0,0 +1,88 @@
+use crate::frame_store::FrameFormat;
+
+/// Builds the output frame for source column `x`: a `out_width × height`
+/// packed image whose column `t` is column `x` of source frame `t`.
+/// Columns past the last source frame repeat it (encoder padding).
+pub fn gather_column(
+    frames: &[Vec<u8>],
+    format: FrameFormat,
+    source_width: usize,
+    height: usize,
+    x: usize,
+    out_width: usize,
+) -> Vec<u8> {
+    let bpp = format.bytes_per_pixel();
+    let mut transposed = vec![0u8; out_width * height * bpp];
+    let num_frames = frames.len();
+
+    for y in 0..height {
+        let src_offset = (y * source_width + x) * bpp;
+        let row = &mut transposed[y * out_width * bpp..(y + 1) * out_width * bpp];
+
+        for (t, frame) in frames.iter().enumerate() {
+            // Source: frame t, position (x, y)
+            // Destination: position (t, y)
+            row[t * bpp..(t + 1) * bpp].copy_from_slice(&frame[src_offset..src_offset + bpp]);
+        }
+
+        // If padded, duplicate the last column
+        for t in num_frames..out_width {
+            row.copy_within((num_frames - 1) * bpp..num_frames * bpp, t * bpp);
+        }
+    }
+    transposed
+}
+
+/// Fades the first and last `fade` columns of the `content_width` real
+/// columns toward black with a linear window, and blacks out any padding
+/// columns after them, so padded or chunked outputs have no hard seams.
+pub fn apply_edge_fade(
+    frame: &mut [u8],
+    format: FrameFormat,
+    out_width: usize,
+    height: usize,
+    content_width: usize,
+    fade: usize,
+) {
+    let weights: Vec<u32> = (0..out_width)
+        .map(|t| {
+            if t >= content_width {
+                return 0;
+            }
+            let from_edge = t.min(content_width - 1 - t) as f32 + 0.5;
+            ((from_edge / fade as f32).min(1.0) * 256.0) as u32
+        })
+        .collect();
+
+    let bpp = format.bytes_per_pixel();
+    for row in frame.chunks_exact_mut(out_width * bpp).take(height) {
+        for (pixel, &weight) in row.chunks_exact_mut(bpp).zip(&weights) {
+            if weight >= 256 {
+                continue;
+            }
+            match format {
+                FrameFormat::Rgb24 => {
+                    for channel in pixel {
+                        *channel = ((*channel as u32 * weight) >> 8) as u8;
+                    }
+                }
+                FrameFormat::Gray16 => {
+                    let sample = u16::from_le_bytes([pixel[0], pixel[1]]) as u32;
+                    pixel.copy_from_slice(&(((sample * weight) >> 8) as u16).to_le_bytes());
+                }
+                FrameFormat::RgbF32 => {
+                    for channel in pixel.chunks_exact_mut(4) {
+                        let value =
+                            f32::from_le_bytes([channel[0], channel[1], channel[2], channel[3]]);
+                        channel.copy_from_slice(&(value * weight as f32 / 256.0).to_le_bytes());
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// BT.601 luma of a packed RGB24 pixel, in integer arithmetic.
+pub fn luma(pixel: &[u8]) -> u8 {
+    ((77 * u32::from(pixel[0]) + 150 * u32::from(pixel[1]) + 29 * u32::from(pixel[2])) >> 8) as u8
+}
@@ -0,0 +1,233 @@
+//! `--validate`: reopens each output once its trailer is written and checks
+//! it plays back as written: every frame decodes, the frame count, size and
+//! duration match what was encoded, and a sample of frames looks like the
+//! frames that went in. A muxer that silently writes a broken index or
+//! drops the last GOP otherwise goes unnoticed until someone plays the
+//! file days later; a failed check fails the run and leaves no output.
+
+use crate::channels::Channel;
+use crate::os_path;
+use crate::pipeline;
+use crate::probe;
+use crate::sink::{FrameSink, OutputFrame};
+use ffmpeg_next as ffmpeg;
+use ffmpeg_next::format::Pixel;
+use ffmpeg_next::software::scaling::{context::Context, flag::Flags};
+use ffmpeg_next::util::frame::video::Video;
+use std::path::Path;
+
+/// Frames compared with what was encoded, spread evenly over the output.
+const SAMPLES: usize = 8;
+/// Cells per side of the thumbnails sampled frames are compared by, coarse
+/// enough that compression and scaling average out.
+const GRID: usize = 16;
+/// Largest mean difference (of 255) between the thumbnails of a sampled
+/// frame as encoded and as decoded; broken or misplaced frames are far off.
+const MAX_DIFFERENCE: f64 = 12.0;
+/// Frame intervals the container duration may be off by.
+const DURATION_TOLERANCE: f64 = 2.0;
+
+/// What one output should hold, recorded from the frames handed to its
+/// encoder.
+pub struct Expected {
+    name: String,
+    width: usize,
+    height: usize,
+    fps: f64,
+    /// Frames the output will hold, to pick the samples.
+    planned: usize,
+    channel: Option<Channel>,
+    frames: usize,
+    /// Thumbnails of the sampled frames, by frame number.
+    samples: Vec<(usize, Vec<u8>)>,
+}
+
+impl Expected {
+    /// Expectations for `name`, encoded at `width × height` and `fps` from
+    /// `planned` frames (of which only `channel`, if given).
+    pub fn new(
+        name: &str,
+        (width, height): (usize, usize),
+        fps: f64,
+        planned: usize,
+        channel: Option<Channel>,
+    ) -> Expected {
+        Expected {
+            name: name.to_string(),
+            width,
+            height,
+            fps,
+            planned,
+            channel,
+            frames: 0,
+            samples: Vec::new(),
+        }
+    }
+
+    /// Whether frame `frame` is one of the `SAMPLES` spread evenly over
+    /// the output, first and last frame included.
+    fn is_sampled(&self, frame: usize) -> bool {
+        match self.planned {
+            planned if planned <= SAMPLES => true,
+            planned => (0..SAMPLES).any(|sample| sample * (planned - 1) / (SAMPLES - 1) == frame),
+        }
+    }
+
+    /// Decodes the file at `path` and fails unless it matches.
+    pub fn verify_file(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let fail = |problem: String| format!("--validate failed for {}: {}", self.name, problem);
+        let mut ictx = os_path::open_input(path, None)
+            .map_err(|e| fail(format!("the file can't be reopened ({})", e)))?;
+        let stream_index = probe::video_stream(&ictx, path)?;
+        let parameters = ictx
+            .stream(stream_index)
+            .ok_or_else(|| fail("no video stream".to_string()))?
+            .parameters();
+        let duration = ictx.duration();
+        let mut decoder = ffmpeg::codec::context::Context::from_parameters(parameters)?
+            .decoder()
+            .video()?;
+        if (decoder.width() as usize, decoder.height() as usize) != (self.width, self.height) {
+            return Err(fail(format!(
+                "frames are {}×{}, expected {}×{}",
+                decoder.width(),
+                decoder.height(),
+                self.width,
+                self.height
+            ))
+            .into());
+        }
+
+        let mut decoded_frames = 0;
+        let mut differences = Vec::new();
+        let mut scaler = None;
+        let mut receive = |decoder: &mut ffmpeg::decoder::Video| -> Result<(), ffmpeg::Error> {
+            let mut decoded = Video::empty();
+            while decoder.receive_frame(&mut decoded).is_ok() {
+                let frame = decoded_frames;
+                decoded_frames += 1;
+                let Some((_, expected)) = self.samples.iter().find(|(n, _)| *n == frame) else {
+                    continue;
+                };
+                if scaler.is_none() {
+                    scaler = Some(Context::get(
+                        decoded.format(),
+                        decoded.width(),
+                        decoded.height(),
+                        Pixel::GRAY8,
+                        decoded.width(),
+                        decoded.height(),
+                        Flags::BILINEAR,
+                    )?);
+                }
+                let mut gray = Video::empty();
+                if let Some(scaler) = &mut scaler {
+                    scaler.run(&decoded, &mut gray)?;
+                }
+                let luma = pipeline::copy_plane(&gray, 0, gray.width() as usize);
+                let actual = thumbnail(&luma, self.width, self.height);
+                differences.push((frame, difference(expected, &actual)));
+            }
+            Ok(())
+        };
+        for (stream, packet) in ictx.packets() {
+            if stream.index() == stream_index {
+                decoder
+                    .send_packet(&packet)
+                    .map_err(|e| fail(format!("packet doesn't decode ({})", e)))?;
+                receive(&mut decoder)?;
+            }
+        }
+        decoder.send_eof()?;
+        receive(&mut decoder)?;
+
+        if decoded_frames != self.frames {
+            return Err(fail(format!(
+                "{} frames decode, expected {}",
+                decoded_frames, self.frames
+            ))
+            .into());
+        }
+        let expected_seconds = self.frames as f64 / self.fps;
+        let seconds = duration as f64 / f64::from(ffmpeg::ffi::AV_TIME_BASE);
+        if duration <= 0 {
+            return Err(fail("the container has no duration".to_string()).into());
+        }
+        if (seconds - expected_seconds).abs() > DURATION_TOLERANCE / self.fps {
+            return Err(fail(format!(
+                "the container says {:.3}s, expected {:.3}s",
+                seconds, expected_seconds
+            ))
+            .into());
+        }
+        if let Some(&(frame, worst)) = differences
+            .iter()
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .filter(|(_, worst)| *worst > MAX_DIFFERENCE)
+        {
+            return Err(fail(format!(
+                "frame {} doesn't look like the frame encoded (mean difference {:.1} of 255)",
+                frame, worst
+            ))
+            .into());
+        }
+        status!(
+            "  Validated {}: {} frames of {}×{}, {:.3}s, {} sampled frames match",
+            self.name,
+            decoded_frames,
+            self.width,
+            self.height,
+            seconds,
+            differences.len()
+        );
+        Ok(())
+    }
+}
+
+/// Counts the frames handed to the output and keeps thumbnails of the
+/// sampled ones.
+impl FrameSink for Expected {
+    fn write(&mut self, frame: &OutputFrame) -> Result<(), Box<dyn std::error::Error>> {
+        if self.is_sampled(self.frames) {
+            let luma = match self.channel {
+                Some(channel) => channel.extract(frame.data),
+                None => frame.format.luma(frame.data),
+            };
+            self.samples
+                .push((self.frames, thumbnail(&luma, frame.width, frame.height)));
+        }
+        self.frames += 1;
+        Ok(())
+    }
+}
+
+/// Mean luma of each cell of a `GRID × GRID` grid over the frame.
+fn thumbnail(luma: &[u8], width: usize, height: usize) -> Vec<u8> {
+    let cell = |index: usize, size: usize| index * size / GRID..((index + 1) * size / GRID).max(1);
+    let mut cells = Vec::with_capacity(GRID * GRID);
+    for cy in 0..GRID {
+        for cx in 0..GRID {
+            let (xs, ys) = (cell(cx, width), cell(cy, height));
+            let mut sum = 0u64;
+            let mut count = 0u64;
+            for y in ys.start..ys.end.min(height) {
+                for x in xs.start..xs.end.min(width) {
+                    sum += u64::from(luma[y * width + x]);
+                    count += 1;
+                }
+            }
+            cells.push((sum / count.max(1)) as u8);
+        }
+    }
+    cells
+}
+
+/// Mean absolute difference of two thumbnails.
+fn difference(a: &[u8], b: &[u8]) -> f64 {
+    let total: u64 = a
+        .iter()
+        .zip(b)
+        .map(|(&a, &b)| u64::from(a.abs_diff(b)))
+        .sum();
+    total as f64 / a.len().max(1) as f64
+}
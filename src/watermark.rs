@@ -0,0 +1,218 @@
+//! `--watermark logo.png`: a logo composited onto every output frame before
+//! it's encoded, so transposed clips go out branded without a second encode
+//! generation. The image keeps its own transparency, scaled by `--opacity`,
+//! and sits in a corner (or the center) chosen with `--position`, shrunk to
+//! fit frames smaller than the logo.
+
+use crate::frame_store::FrameFormat;
+use crate::os_path;
+use crate::pipeline;
+use crate::probe;
+use ffmpeg_next as ffmpeg;
+use ffmpeg_next::format::Pixel;
+use ffmpeg_next::software::scaling::{context::Context, flag::Flags};
+use ffmpeg_next::util::frame::video::Video;
+use std::path::Path;
+
+/// Where on the frame the logo goes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Position {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+    Center,
+}
+
+impl Position {
+    pub fn parse(text: &str) -> Result<Position, String> {
+        match text {
+            "tl" => Ok(Position::TopLeft),
+            "tr" => Ok(Position::TopRight),
+            "bl" => Ok(Position::BottomLeft),
+            "br" => Ok(Position::BottomRight),
+            "center" => Ok(Position::Center),
+            _ => Err(format!(
+                "Invalid --position: {} (expected tl, tr, bl, br or center)",
+                text
+            )),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Position::TopLeft => "top left",
+            Position::TopRight => "top right",
+            Position::BottomLeft => "bottom left",
+            Position::BottomRight => "bottom right",
+            Position::Center => "center",
+        }
+    }
+}
+
+/// A decoded logo, as packed RGBA.
+pub struct Watermark {
+    rgba: Vec<u8>,
+    width: usize,
+    height: usize,
+    position: Position,
+    opacity: f32,
+}
+
+/// The logo fitted and placed on frames of one size.
+pub struct Overlay<'a> {
+    watermark: &'a Watermark,
+    /// Logo pixel each column and row of the placed logo samples.
+    columns: Vec<usize>,
+    rows: Vec<usize>,
+    left: usize,
+    top: usize,
+}
+
+impl Watermark {
+    /// Decodes the first frame of the image at `path` (PNG, or anything
+    /// else FFmpeg reads).
+    pub fn load(
+        path: &Path,
+        position: Position,
+        opacity: f64,
+    ) -> Result<Watermark, Box<dyn std::error::Error>> {
+        let cannot = |e: &dyn std::fmt::Display| {
+            format!("Cannot read the watermark {}: {}", path.display(), e)
+        };
+        let mut ictx = os_path::open_input(path, None).map_err(|e| cannot(&e))?;
+        let stream_index = probe::video_stream(&ictx, path)?;
+        let parameters = ictx
+            .stream(stream_index)
+            .ok_or_else(|| cannot(&"no image"))?
+            .parameters();
+        let mut decoder = ffmpeg::codec::context::Context::from_parameters(parameters)?
+            .decoder()
+            .video()?;
+        let mut decoded = Video::empty();
+        for (stream, packet) in ictx.packets() {
+            if stream.index() == stream_index {
+                decoder.send_packet(&packet).map_err(|e| cannot(&e))?;
+                if decoder.receive_frame(&mut decoded).is_ok() {
+                    break;
+                }
+            }
+        }
+        if decoded.width() == 0 {
+            decoder.send_eof()?;
+            decoder
+                .receive_frame(&mut decoded)
+                .map_err(|_| cannot(&"no frame decodes"))?;
+        }
+
+        let (width, height) = (decoded.width() as usize, decoded.height() as usize);
+        let mut rgba = Video::empty();
+        Context::get(
+            decoded.format(),
+            decoded.width(),
+            decoded.height(),
+            Pixel::RGBA,
+            decoded.width(),
+            decoded.height(),
+            Flags::POINT,
+        )?
+        .run(&decoded, &mut rgba)?;
+        Ok(Watermark {
+            rgba: pipeline::copy_plane(&rgba, 0, width * 4),
+            width,
+            height,
+            position,
+            opacity: opacity as f32,
+        })
+    }
+
+    /// For status messages, e.g. `200×80, bottom right, opacity 0.5`.
+    pub fn describe(&self) -> String {
+        format!(
+            "{}×{}, {}, opacity {}",
+            self.width,
+            self.height,
+            self.position.name(),
+            self.opacity
+        )
+    }
+
+    /// Places the logo on `width × height` frames, a margin of 1/40 of the
+    /// frame's smaller side from the edges, shrunk to fit inside them.
+    pub fn place(&self, width: usize, height: usize) -> Overlay<'_> {
+        let margin = width.min(height) / 40;
+        let room = (
+            width.saturating_sub(2 * margin).max(1),
+            height.saturating_sub(2 * margin).max(1),
+        );
+        let scale = (room.0 as f64 / self.width as f64)
+            .min(room.1 as f64 / self.height as f64)
+            .min(1.0);
+        let placed_width = ((self.width as f64 * scale).round() as usize).clamp(1, room.0);
+        let placed_height = ((self.height as f64 * scale).round() as usize).clamp(1, room.1);
+        let samples = |placed: usize, size: usize| -> Vec<usize> {
+            (0..placed).map(|i| i * size / placed).collect()
+        };
+        let (left, top) = match self.position {
+            Position::TopLeft => (margin, margin),
+            Position::TopRight => (width - margin - placed_width, margin),
+            Position::BottomLeft => (margin, height - margin - placed_height),
+            Position::BottomRight => (
+                width - margin - placed_width,
+                height - margin - placed_height,
+            ),
+            Position::Center => ((width - placed_width) / 2, (height - placed_height) / 2),
+        };
+        Overlay {
+            watermark: self,
+            columns: samples(placed_width, self.width),
+            rows: samples(placed_height, self.height),
+            left,
+            top,
+        }
+    }
+}
+
+impl Overlay<'_> {
+    /// Composites the logo onto a packed frame of `format`, `width` pixels
+    /// wide.
+    pub fn apply(&self, frame: &mut [u8], format: FrameFormat, width: usize) {
+        let bpp = format.bytes_per_pixel();
+        let logo = self.watermark;
+        for (row, &logo_y) in self.rows.iter().enumerate() {
+            for (column, &logo_x) in self.columns.iter().enumerate() {
+                let source = (logo_y * logo.width + logo_x) * 4;
+                let [r, g, b, a] = [0, 1, 2, 3].map(|i| logo.rgba[source + i]);
+                let alpha = f32::from(a) / 255.0 * logo.opacity;
+                if alpha <= 0.0 {
+                    continue;
+                }
+                let offset = ((self.top + row) * width + self.left + column) * bpp;
+                let pixel = &mut frame[offset..offset + bpp];
+                let mix = |under: f32, over: f32| under + (over - under) * alpha;
+                match format {
+                    FrameFormat::Rgb24 => {
+                        for (sample, over) in pixel.iter_mut().zip([r, g, b]) {
+                            *sample = mix(f32::from(*sample), f32::from(over)).round() as u8;
+                        }
+                    }
+                    FrameFormat::Gray16 => {
+                        let luma =
+                            0.299 * f32::from(r) + 0.587 * f32::from(g) + 0.114 * f32::from(b);
+                        let under = f32::from(u16::from_le_bytes([pixel[0], pixel[1]]));
+                        let value = mix(under, luma * 257.0).round() as u16;
+                        pixel.copy_from_slice(&value.to_le_bytes());
+                    }
+                    FrameFormat::RgbF32 => {
+                        for (sample, over) in pixel.chunks_exact_mut(4).zip([r, g, b]) {
+                            let under =
+                                f32::from_le_bytes([sample[0], sample[1], sample[2], sample[3]]);
+                            let value = mix(under, f32::from(over) / 255.0);
+                            sample.copy_from_slice(&value.to_le_bytes());
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
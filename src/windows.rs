@@ -0,0 +1,161 @@
+//! `--windows`: transposing chosen stretches of the input only, e.g.
+//! `0-5s,30-35s,60-65s`. Only frames inside a window are stored, so the
+//! windows end up side by side along the output's time axis, in one frame,
+//! for comparing different periods kymograph style; or, with
+//! `--windows-layout separate`, each in a file of its own. Decoding stops
+//! after the last window.
+
+use crate::split;
+use std::fmt;
+use std::ops::Range;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TimeWindow {
+    /// Seconds, inclusive.
+    pub start: f64,
+    /// Seconds, exclusive.
+    pub end: f64,
+}
+
+impl TimeWindow {
+    /// Frames the window spans at `fps`.
+    fn frames(self, fps: f64) -> u64 {
+        ((self.end - self.start) * fps).ceil() as u64
+    }
+}
+
+/// Parses a comma separated list of `start-end` windows in seconds, each
+/// number optionally followed by `s`. Windows must be in order and must not
+/// overlap.
+pub fn parse(text: &str) -> Result<Vec<TimeWindow>, String> {
+    let invalid = || {
+        format!(
+            "Invalid --windows: {} (expected start-end seconds, e.g. 0-5s,30-35s)",
+            text
+        )
+    };
+    let seconds = |value: &str| {
+        value
+            .trim()
+            .trim_end_matches('s')
+            .parse::<f64>()
+            .ok()
+            .filter(|seconds| seconds.is_finite() && *seconds >= 0.0)
+    };
+    let mut windows: Vec<TimeWindow> = Vec::new();
+    for window in text.split(',') {
+        let (start, end) = window.split_once('-').ok_or_else(invalid)?;
+        let window = match (seconds(start), seconds(end)) {
+            (Some(start), Some(end)) if end > start => TimeWindow { start, end },
+            _ => return Err(invalid()),
+        };
+        if windows.last().is_some_and(|last| window.start < last.end) {
+            return Err(format!(
+                "Invalid --windows: {} (windows must be in order and not overlap)",
+                text
+            ));
+        }
+        windows.push(window);
+    }
+    Ok(windows)
+}
+
+/// Index of the window showing `time`.
+pub fn find(windows: &[TimeWindow], time: f64) -> Option<usize> {
+    windows
+        .iter()
+        .position(|window| (window.start..window.end).contains(&time))
+}
+
+/// Whether `time` is after the last window.
+pub fn past_last(windows: &[TimeWindow], time: f64) -> bool {
+    windows.last().is_some_and(|last| time >= last.end)
+}
+
+/// Frames each window is expected to hold at `fps`.
+pub fn frame_counts(windows: &[TimeWindow], fps: f64) -> Vec<u64> {
+    windows.iter().map(|window| window.frames(fps)).collect()
+}
+
+/// Stored frame ranges of the windows, from the index of the first frame
+/// stored for each window that got any. Windows past the end of the input
+/// come out empty.
+pub fn frame_ranges(starts: &[usize], windows: usize, frames: usize) -> Vec<Range<usize>> {
+    (0..windows)
+        .map(|window| {
+            let start = starts.get(window).copied().unwrap_or(frames);
+            let end = starts.get(window + 1).copied().unwrap_or(frames);
+            start..end
+        })
+        .collect()
+}
+
+/// How `--windows` are laid out in the output.
+#[derive(Clone, Copy, PartialEq)]
+pub enum WindowLayout {
+    /// Side by side in one output.
+    Stack,
+    /// One output per window, as `<name>_wNNN`.
+    Separate,
+}
+
+impl WindowLayout {
+    pub fn parse(text: &str) -> Result<WindowLayout, String> {
+        match text {
+            "stack" => Ok(WindowLayout::Stack),
+            "separate" => Ok(WindowLayout::Separate),
+            _ => Err(format!(
+                "Invalid --windows-layout: {} (expected stack or separate)",
+                text
+            )),
+        }
+    }
+}
+
+/// Stops decoding once the frames are past the last window.
+#[derive(Debug)]
+pub struct PastLastWindow;
+
+impl fmt::Display for PastLastWindow {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("past the last --windows window")
+    }
+}
+
+impl std::error::Error for PastLastWindow {}
+
+/// Ranges of `counts` frames each, back to back.
+pub fn consecutive(counts: &[usize]) -> Vec<Range<usize>> {
+    let mut start = 0;
+    counts
+        .iter()
+        .map(|&count| {
+            start += count;
+            start - count..start
+        })
+        .collect()
+}
+
+/// Output pieces for `--windows-layout separate`: each window on its own,
+/// split further where it is wider than `limit`.
+pub fn tiles(windows: &[Range<usize>], limit: usize) -> Vec<Range<usize>> {
+    windows
+        .iter()
+        .flat_map(|window| {
+            split::ranges(window.len(), Some(limit))
+                .into_iter()
+                .map(move |tile| window.start + tile.start..window.start + tile.end)
+        })
+        .collect()
+}
+
+/// Treats a decode stopped by [`PastLastWindow`] as finished.
+pub fn stopped_after_last(
+    error: Box<dyn std::error::Error>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if error.is::<PastLastWindow>() {
+        Ok(())
+    } else {
+        Err(error)
+    }
+}
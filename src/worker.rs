@@ -0,0 +1,386 @@
+//! `video-transpose worker`: pulls job specs from a Redis list and runs them.
+//!
+//! A job is a JSON object `{"id": "...", "args": ["in.mp4", "out.mp4", ...]}`
+//! pushed onto the queue list (`RPUSH <list> <json>`). Each job runs as a
+//! child process of this binary with the given arguments, and its status
+//! (`running`, then `success` or `failure` with the run summary) is stored in
+//! the `<list>:status` hash under the job id.
+//!
+//! Jobs only start together while their projected frame stores fit the
+//! worker's memory budget; a job that doesn't fit waits for running ones to
+//! finish.
+
+use crate::cli::{Options, WorkerOptions};
+use crate::memory::{format_bytes, MemoryGuard};
+use crate::metrics::{self, Metrics};
+use crate::pipeline;
+use crate::tempfiles::TempFiles;
+use serde_json::{json, Value};
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::process::Command;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const DEFAULT_LIST: &str = "video-transpose:jobs";
+
+/// Where to find the queue: `redis://[:password@]host[:port][/list]`.
+#[derive(Clone)]
+pub struct QueueUrl {
+    address: String,
+    password: Option<String>,
+    list: String,
+}
+
+impl QueueUrl {
+    pub fn parse(url: &str) -> Result<QueueUrl, String> {
+        let rest = match url.split_once("://") {
+            Some(("redis", rest)) => rest,
+            Some((scheme, _)) => {
+                return Err(format!(
+                    "Unsupported queue: {} (only redis:// queues are supported, not {}://)",
+                    url, scheme
+                ))
+            }
+            None => return Err(format!("Invalid queue URL: {}", url)),
+        };
+        let (authority, list) = match rest.split_once('/') {
+            Some((authority, list)) if !list.is_empty() => (authority, list),
+            Some((authority, _)) => (authority, DEFAULT_LIST),
+            None => (rest, DEFAULT_LIST),
+        };
+        let (password, host) = match authority.rsplit_once('@') {
+            Some((credentials, host)) => {
+                let password = credentials.rsplit(':').next().unwrap_or(credentials);
+                (Some(password.to_string()), host)
+            }
+            None => (None, authority),
+        };
+        if host.is_empty() {
+            return Err(format!("Invalid queue URL: {}", url));
+        }
+        let address = if host.contains(':') {
+            host.to_string()
+        } else {
+            format!("{}:6379", host)
+        };
+        Ok(QueueUrl {
+            address,
+            password,
+            list: list.to_string(),
+        })
+    }
+}
+
+/// Runs the worker until killed, with one job-pulling thread per slot.
+pub fn run(options: &WorkerOptions) -> Result<(), Box<dyn std::error::Error>> {
+    let exe = env::current_exe()?;
+    println!(
+        "Worker pulling jobs from {} on {} ({} at a time)",
+        options.queue.list, options.queue.address, options.concurrency
+    );
+
+    let metrics = Metrics::new();
+    let temp = Arc::new(TempFiles::new(None, false));
+    let admission = Arc::new(Admission::new(
+        MemoryGuard::new(options.max_memory).budget(),
+    ));
+    if let Some(budget) = admission.budget {
+        println!(
+            "Memory budget for concurrent jobs: {}",
+            format_bytes(budget)
+        );
+    }
+    if let Some(address) = &options.metrics_addr {
+        metrics::serve(address, metrics.clone())?;
+        println!("Serving metrics on http://{}/metrics", address);
+    }
+
+    let handles: Vec<_> = (0..options.concurrency)
+        .map(|slot| {
+            let queue = options.queue.clone();
+            let exe = exe.clone();
+            let metrics = metrics.clone();
+            let temp = temp.clone();
+            let admission = admission.clone();
+            thread::spawn(move || loop {
+                if let Err(err) = serve(&queue, &exe, slot, &metrics, &temp, &admission) {
+                    eprintln!("[worker {}] {}; reconnecting in 5s", slot, err);
+                    thread::sleep(Duration::from_secs(5));
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        let _ = handle.join();
+    }
+    Ok(())
+}
+
+/// Pulls and runs jobs until the connection fails.
+fn serve(
+    queue: &QueueUrl,
+    exe: &std::path::Path,
+    slot: usize,
+    metrics: &Metrics,
+    temp: &TempFiles,
+    admission: &Admission,
+) -> Result<(), String> {
+    let mut redis = Redis::connect(queue)?;
+    let status_key = format!("{}:status", queue.list);
+    loop {
+        let spec = match redis.command(&["BLPOP", &queue.list, "0"])? {
+            Reply::Array(mut items) if items.len() == 2 => match items.pop() {
+                Some(Reply::Bulk(spec)) => spec,
+                _ => continue,
+            },
+            _ => continue,
+        };
+
+        let job: Value = match serde_json::from_str(&spec) {
+            Ok(job) => job,
+            Err(err) => {
+                eprintln!(
+                    "[worker {}] Ignoring malformed job {:?}: {}",
+                    slot, spec, err
+                );
+                continue;
+            }
+        };
+        let id = match &job["id"] {
+            Value::String(id) => id.clone(),
+            Value::Null => {
+                eprintln!("[worker {}] Ignoring job without an id: {}", slot, spec);
+                continue;
+            }
+            other => other.to_string(),
+        };
+        let args: Vec<String> = match job["args"].as_array() {
+            Some(args) => args
+                .iter()
+                .map(|arg| {
+                    arg.as_str()
+                        .map(String::from)
+                        .unwrap_or_else(|| arg.to_string())
+                })
+                .collect(),
+            None => {
+                let status = json!({ "status": "failure", "error": "Job has no args array" });
+                redis.command(&["HSET", &status_key, &id, &status.to_string()])?;
+                continue;
+            }
+        };
+
+        let estimate = estimate_memory(exe, &args);
+        let _reservation = admission.reserve(estimate, || {
+            println!(
+                "[worker {}] Job {} needs {}, waiting for memory",
+                slot,
+                id,
+                format_bytes(estimate)
+            )
+        });
+
+        println!("[worker {}] Starting job {}", slot, id);
+        let running = json!({ "status": "running" });
+        redis.command(&["HSET", &status_key, &id, &running.to_string()])?;
+
+        metrics.job_started();
+        let started = Instant::now();
+        let status = run_job(exe, &id, &args, temp);
+        metrics.job_finished(&status, started.elapsed());
+        println!("[worker {}] Job {}: {}", slot, id, status["status"]);
+        redis.command(&["HSET", &status_key, &id, &status.to_string()])?;
+    }
+}
+
+/// Projected frame store of a job from its input's header, 0 if it can't be
+/// told up front (the job's own check reports a bad input when it runs).
+fn estimate_memory(exe: &std::path::Path, args: &[String]) -> u64 {
+    let argv: Vec<OsString> = std::iter::once(exe.as_os_str().to_owned())
+        .chain(args.iter().map(OsString::from))
+        .collect();
+    Options::parse_layered(&argv)
+        .ok()
+        .and_then(|options| pipeline::estimate_memory(&options).ok().flatten())
+        .unwrap_or(0)
+}
+
+/// Shares the memory budget between the jobs running at the same time.
+struct Admission {
+    budget: Option<u64>,
+    reserved: Mutex<u64>,
+    released: Condvar,
+}
+
+impl Admission {
+    fn new(budget: Option<u64>) -> Self {
+        Admission {
+            budget,
+            reserved: Mutex::new(0),
+            released: Condvar::new(),
+        }
+    }
+
+    /// Blocks until `bytes` fit next to the running jobs' reservations,
+    /// calling `on_wait` once if it has to wait. A job is always admitted
+    /// when nothing else is running, even if it alone exceeds the budget.
+    fn reserve(&self, bytes: u64, on_wait: impl FnOnce()) -> Reservation<'_> {
+        let mut reserved = self.reserved.lock().unwrap();
+        if let Some(budget) = self.budget {
+            let fits = |reserved: u64| reserved == 0 || reserved + bytes <= budget;
+            if !fits(*reserved) {
+                on_wait();
+                reserved = self
+                    .released
+                    .wait_while(reserved, |reserved| !fits(*reserved))
+                    .unwrap();
+            }
+        }
+        *reserved += bytes;
+        Reservation {
+            admission: self,
+            bytes,
+        }
+    }
+}
+
+/// Memory held by a running job, given back when it's dropped.
+struct Reservation<'a> {
+    admission: &'a Admission,
+    bytes: u64,
+}
+
+impl Drop for Reservation<'_> {
+    fn drop(&mut self) {
+        *self.admission.reserved.lock().unwrap() -= self.bytes;
+        self.admission.released.notify_all();
+    }
+}
+
+/// Runs one job as a child process and returns its status record.
+fn run_job(exe: &std::path::Path, id: &str, args: &[String], temp: &TempFiles) -> Value {
+    let name = format!(
+        "{}.json",
+        id.replace(|c: char| !c.is_ascii_alphanumeric(), "_")
+    );
+    let summary_path = match temp.path(&name) {
+        Ok(path) => path,
+        Err(err) => return json!({ "status": "failure", "error": err.to_string() }),
+    };
+    let output = Command::new(exe)
+        .args(args)
+        .arg("--summary-json")
+        .arg(&summary_path)
+        .output();
+    let summary = fs::read_to_string(&summary_path)
+        .ok()
+        .and_then(|text| serde_json::from_str::<Value>(&text).ok());
+    let _ = fs::remove_file(&summary_path);
+
+    match output {
+        Ok(output) if output.status.success() => json!({
+            "status": "success",
+            "summary": summary,
+        }),
+        Ok(output) => {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let lines: Vec<&str> = stderr.lines().collect();
+            json!({
+                "status": "failure",
+                "error": lines[lines.len().saturating_sub(5)..].join("\n"),
+                "exit_code": output.status.code(),
+            })
+        }
+        Err(err) => json!({ "status": "failure", "error": err.to_string() }),
+    }
+}
+
+/// A Redis protocol (RESP) reply.
+enum Reply {
+    Status,
+    Integer,
+    Bulk(String),
+    Nil,
+    Array(Vec<Reply>),
+}
+
+/// Just enough of a Redis client for BLPOP and HSET.
+struct Redis {
+    reader: BufReader<TcpStream>,
+}
+
+impl Redis {
+    fn connect(queue: &QueueUrl) -> Result<Redis, String> {
+        let stream = TcpStream::connect(&queue.address)
+            .map_err(|e| format!("Cannot connect to {}: {}", queue.address, e))?;
+        let mut redis = Redis {
+            reader: BufReader::new(stream),
+        };
+        if let Some(password) = &queue.password {
+            redis.command(&["AUTH", password])?;
+        }
+        Ok(redis)
+    }
+
+    fn command(&mut self, args: &[&str]) -> Result<Reply, String> {
+        let mut request = format!("*{}\r\n", args.len());
+        for arg in args {
+            request.push_str(&format!("${}\r\n{}\r\n", arg.len(), arg));
+        }
+        self.reader
+            .get_mut()
+            .write_all(request.as_bytes())
+            .map_err(|e| e.to_string())?;
+        self.read_reply()
+    }
+
+    fn read_reply(&mut self) -> Result<Reply, String> {
+        let mut line = String::new();
+        if self
+            .reader
+            .read_line(&mut line)
+            .map_err(|e| e.to_string())?
+            == 0
+        {
+            return Err("Redis closed the connection".to_string());
+        }
+        let line = line.trim_end_matches("\r\n");
+        let (kind, rest) = line.split_at(1.min(line.len()));
+        let count = || {
+            rest.parse::<i64>()
+                .map_err(|_| format!("Malformed Redis reply: {}", line))
+        };
+        match kind {
+            "+" => Ok(Reply::Status),
+            ":" => Ok(Reply::Integer),
+            "-" => Err(format!("Redis error: {}", rest)),
+            "$" => {
+                let len = count()?;
+                if len < 0 {
+                    return Ok(Reply::Nil);
+                }
+                let mut data = vec![0u8; len as usize + 2];
+                std::io::Read::read_exact(&mut self.reader, &mut data)
+                    .map_err(|e| e.to_string())?;
+                data.truncate(len as usize);
+                Ok(Reply::Bulk(String::from_utf8_lossy(&data).into_owned()))
+            }
+            "*" => {
+                let len = count()?;
+                if len < 0 {
+                    return Ok(Reply::Nil);
+                }
+                (0..len)
+                    .map(|_| self.read_reply())
+                    .collect::<Result<_, _>>()
+                    .map(Reply::Array)
+            }
+            _ => Err(format!("Malformed Redis reply: {}", line)),
+        }
+    }
+}
@@ -24,7 +24,7 @@ cargo build --release
 if [ $? -eq 0 ]; then
     echo ""
     echo "Running transposition..."
-    ./target/release/video-transpose res/test_input.mp4 res/test_output.mp4
+    ./target/release/video-transpose -y res/test_input.mp4 res/test_output.mp4
 
     echo ""
     echo "Done! Compare the videos:"